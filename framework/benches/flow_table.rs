@@ -0,0 +1,123 @@
+//! Compares `FlowTable` to the `HashMap` with FNV hashing that NFs used to keep per-flow state in, at a million flows
+//! so neither fits in the caches. Run with `cargo +nightly bench --bench flow_table`.
+#![feature(test)]
+extern crate e2d2;
+extern crate fnv;
+extern crate test;
+use e2d2::state::FlowTable;
+use e2d2::utils::Flow;
+use fnv::FnvHashMap;
+use test::{black_box, Bencher};
+
+const FLOWS: u32 = 1 << 20;
+/// Operations per iteration, so the loop overhead does not dominate.
+const OPS: u32 = 1024;
+
+fn flow(i: u32) -> Flow {
+    Flow {
+        src_ip: 0x0a00_0000_u32.wrapping_add(i),
+        dst_ip: 0xc0a8_0001,
+        src_port: (i % 60_000) as u16 + 1024,
+        dst_port: 443,
+        proto: 6,
+    }
+}
+
+/// Visits `0..FLOWS` in a fixed pseudo-random order, so consecutive lookups touch unrelated cache lines.
+fn scatter(i: u32) -> u32 {
+    i.wrapping_mul(0x9e37_79b1) & (FLOWS - 1)
+}
+
+fn flow_table() -> FlowTable<u64> {
+    // Twice the flows, so few of them are evicted.
+    let mut table = FlowTable::new(2 * FLOWS as usize, None);
+    for i in 0..FLOWS {
+        table.insert(flow(i), u64::from(i));
+    }
+    table
+}
+
+fn hash_map() -> FnvHashMap<Flow, u64> {
+    let mut map = FnvHashMap::default();
+    for i in 0..FLOWS {
+        map.insert(flow(i), u64::from(i));
+    }
+    map
+}
+
+#[bench]
+fn flow_table_lookup(b: &mut Bencher) {
+    let mut table = flow_table();
+    let mut n = 0;
+    b.iter(|| {
+        for _ in 0..OPS {
+            n += 1;
+            black_box(table.get(&flow(scatter(n))));
+        }
+    });
+}
+
+#[bench]
+fn hash_map_lookup(b: &mut Bencher) {
+    let map = hash_map();
+    let mut n = 0;
+    b.iter(|| {
+        for _ in 0..OPS {
+            n += 1;
+            black_box(map.get(&flow(scatter(n))));
+        }
+    });
+}
+
+/// The reply direction of a connection, which a `HashMap` needs a second lookup (or a second entry) for.
+#[bench]
+fn flow_table_lookup_reverse(b: &mut Bencher) {
+    let mut table = flow_table();
+    let mut n = 0;
+    b.iter(|| {
+        for _ in 0..OPS {
+            n += 1;
+            black_box(table.get(&flow(scatter(n)).reverse_flow()));
+        }
+    });
+}
+
+#[bench]
+fn hash_map_lookup_reverse(b: &mut Bencher) {
+    let map = hash_map();
+    let mut n = 0;
+    b.iter(|| {
+        for _ in 0..OPS {
+            n += 1;
+            let flow = flow(scatter(n)).reverse_flow();
+            black_box(map.get(&flow).or_else(|| map.get(&flow.reverse_flow())));
+        }
+    });
+}
+
+/// New flows replacing old ones, with the number of flows staying put.
+#[bench]
+fn flow_table_churn(b: &mut Bencher) {
+    let mut table = flow_table();
+    let mut n = FLOWS;
+    b.iter(|| {
+        for _ in 0..OPS {
+            table.remove(&flow(n - FLOWS));
+            table.insert(flow(n), u64::from(n));
+            n += 1;
+        }
+    });
+}
+
+#[bench]
+fn hash_map_churn(b: &mut Bencher) {
+    let mut map = hash_map();
+    let mut n = FLOWS;
+    b.iter(|| {
+        for _ in 0..OPS {
+            map.remove(&flow(n - FLOWS));
+            map.insert(flow(n), u64::from(n));
+            n += 1;
+        }
+    });
+}
@@ -45,6 +45,10 @@ fn write_external_link(libs: &[String]) {
 /// Cargo runs main in this file to get some additional settings (e.g., LD_LIBRARY_PATH). It reads the printed output
 /// looking for certain variables, see [here](http://doc.crates.io/build-script.html) for documentation.
 fn main() {
+    // With heap backed mbufs nothing links against DPDK or libzcsi, and the mbuf layout is written by hand.
+    if env::var("CARGO_FEATURE_HEAP_MBUF").is_ok() {
+        return;
+    }
     // Get the directory where we are building.
     let cargo_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
     let dpdk_build = Path::new(&cargo_dir)
@@ -15,6 +15,10 @@ error_chain! {
             description("Failed to initialize port")
             display("Failed to initialize port: {}", port)
         }
+        FailedToSetMtu(port: u8, mtu: u16) {
+            description("Failed to set the MTU of port")
+            display("Failed to set the MTU of port {} to {}", port, mtu)
+        }
         BadQueue {
             description("Invalid queue request")
             display("Invalid queue request")
@@ -31,6 +35,10 @@ error_chain! {
             description("Bad vdev specification")
             display("Bad vdev specification: {}", vdev)
         }
+        BadPcap(path: String, reason: String) {
+            description("Bad pcap file")
+            display("Bad pcap file {}: {}", path, reason)
+        }
         BadTxQueue(port: u8, queue: i32) {
             description("Bad TX queue")
             display("Bad TX queue {} for port {}", queue, port)
@@ -73,6 +81,50 @@ error_chain! {
             description("No scheduler running on core")
             display("No scheduler running on core {}", core)
         }
+
+        NoSuchTask(task: usize) {
+            description("No such task on scheduler")
+            display("No task with handle {} on scheduler", task)
+        }
+
+        BadExperimentConfig(path: String, reason: String) {
+            description("Bad PVN experiment configuration")
+            display("Bad PVN experiment configuration {}: {}", path, reason)
+        }
+
+        NoExperimentConfig {
+            description("No PVN experiment configuration")
+            display("No PVN experiment configuration, set pvn_config or pass --pvn_config")
+        }
+
+        BadMetricName(name: String) {
+            description("Bad metric name")
+            display("Bad metric name {}", name)
+        }
+
+        BadControlRequest(request: String) {
+            description("Bad control request")
+            display("Bad control request {:?}", request)
+        }
+
+        NoSuchPipeline(name: String) {
+            description("No pipeline registered under this name")
+            display("No pipeline registered as {}", name)
+        }
+        HooksNotSupported {
+            description("Scheduler cannot run pipeline hooks")
+            display("Scheduler cannot run pipeline hooks")
+        }
+
+        DontFragment(len: usize, mtu: usize) {
+            description("Packet exceeds the MTU and must not be fragmented")
+            display("Packet of {} bytes exceeds MTU {} and must not be fragmented", len, mtu)
+        }
+
+        BadMtu(mtu: usize) {
+            description("MTU too small to fragment packet")
+            display("MTU {} too small to fragment packet", mtu)
+        }
     }
 
     foreign_links {
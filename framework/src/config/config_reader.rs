@@ -1,5 +1,6 @@
 use super::{NetbricksConfiguration, PortConfiguration};
 use crate::common::*;
+use crate::scheduler::IdleBackoff;
 use std::fs::File;
 use std::io::Read;
 use toml::{self, Value};
@@ -16,10 +17,16 @@ pub const DEFAULT_PRIMARY_CORE: i32 = 0;
 pub const DEFAULT_NAME: &str = "zcsi";
 /// Default duration.
 pub const DEFAULT_DURATION: Option<u64> = None;
+/// Default time `NetBricksContext::stop` lets pipelines drain, in milliseconds.
+pub const DEFAULT_DRAIN_TIMEOUT_MS: u64 = 5000;
 /// Default number of RXD.
 pub const NUM_RXD: i32 = 128;
 /// Default number of TXD.
 pub const NUM_TXD: i32 = 128;
+/// Default MTU of ports, in bytes of IP packet.
+pub const DEFAULT_MTU: u16 = 1500;
+/// Smallest MTU IPv4 allows (RFC 791).
+const MIN_MTU: i64 = 68;
 
 /// Read a TOML stub and figure out the port.
 fn read_port(value: &Value) -> Result<PortConfiguration> {
@@ -67,6 +74,20 @@ fn read_port(value: &Value) -> Result<PortConfiguration> {
             v => return Err(ErrorKind::ConfigurationError(format!("Could not parse csum spec {:?}", v)).into()),
         };
 
+        let rx_interrupts = match port_def.get("rx_interrupts") {
+            Some(&Value::Boolean(l)) => l,
+            None => false,
+            v => {
+                return Err(ErrorKind::ConfigurationError(format!("Could not parse rx_interrupts spec {:?}", v)).into())
+            }
+        };
+
+        let mtu = match port_def.get("mtu") {
+            Some(&Value::Integer(mtu)) if (MIN_MTU..=65535).contains(&mtu) => mtu as u16,
+            None => DEFAULT_MTU,
+            v => return Err(ErrorKind::ConfigurationError(format!("Could not parse mtu spec {:?}", v)).into()),
+        };
+
         let symmetric_queue = port_def.contains_key("cores");
         if symmetric_queue && (port_def.contains_key("rx_cores") || port_def.contains_key("tx_cores")) {
             println!("cores specified along with rx_cores and/or tx_cores for port {}", name);
@@ -125,6 +146,8 @@ fn read_port(value: &Value) -> Result<PortConfiguration> {
             loopback,
             csum,
             tso,
+            rx_interrupts,
+            mtu,
         })
     } else {
         Err(ErrorKind::ConfigurationError(String::from("Could not understand port spec")).into())
@@ -259,6 +282,91 @@ pub fn read_configuration_from_str(configuration: &str, filename: &str) -> Resul
         }
     };
 
+    let idle_backoff = match toml.get("idle_backoff") {
+        Some(&Value::Table(ref backoff)) => Some(read_idle_backoff(backoff)?),
+        Some(&Value::Boolean(true)) => Some(IdleBackoff::default()),
+        Some(&Value::Boolean(false)) | None => None,
+        v => {
+            return Err(ErrorKind::ConfigurationError(format!(
+                "Could not parse idle_backoff spec (should be boolean or table) {:?}",
+                v
+            ))
+            .into())
+        }
+    };
+
+    let pvn_config = match toml.get("pvn_config") {
+        Some(&Value::String(ref path)) => Some(path.clone()),
+        None => None,
+        v => {
+            return Err(ErrorKind::ConfigurationError(format!(
+                "Could not parse pvn_config spec (should be string) {:?}",
+                v
+            ))
+            .into())
+        }
+    };
+
+    let stats_report = match toml.get("stats_report") {
+        Some(&Value::String(ref path)) => Some(path.clone()),
+        None => None,
+        v => {
+            return Err(ErrorKind::ConfigurationError(format!(
+                "Could not parse stats_report spec (should be string) {:?}",
+                v
+            ))
+            .into())
+        }
+    };
+
+    let metrics_address = match toml.get("metrics_address") {
+        Some(&Value::String(ref address)) => Some(address.clone()),
+        None => None,
+        v => {
+            return Err(ErrorKind::ConfigurationError(format!(
+                "Could not parse metrics_address spec (should be string) {:?}",
+                v
+            ))
+            .into())
+        }
+    };
+
+    let control_address = match toml.get("control_address") {
+        Some(&Value::String(ref address)) => Some(address.clone()),
+        None => None,
+        v => {
+            return Err(ErrorKind::ConfigurationError(format!(
+                "Could not parse control_address spec (should be string) {:?}",
+                v
+            ))
+            .into())
+        }
+    };
+
+    let drain_timeout_ms = match toml.get("drain_timeout_ms") {
+        Some(&Value::Integer(t)) if t >= 0 => t as u64,
+        None => DEFAULT_DRAIN_TIMEOUT_MS,
+        v => {
+            return Err(ErrorKind::ConfigurationError(format!(
+                "Could not parse drain_timeout_ms spec (should be non-negative integer) {:?}",
+                v
+            ))
+            .into())
+        }
+    };
+
+    let shutdown_signals = match toml.get("shutdown_signals") {
+        Some(&Value::Boolean(s)) => s,
+        None => true,
+        v => {
+            return Err(ErrorKind::ConfigurationError(format!(
+                "Could not parse shutdown_signals spec (should be boolean) {:?}",
+                v
+            ))
+            .into())
+        }
+    };
+
     Ok(NetbricksConfiguration {
         name,
         primary_core: master_lcore,
@@ -270,6 +378,41 @@ pub fn read_configuration_from_str(configuration: &str, filename: &str) -> Resul
         ports,
         dpdk_args: None,
         duration,
+        idle_backoff,
+        pvn_config,
+        stats_report,
+        metrics_address,
+        control_address,
+        drain_timeout_ms,
+        shutdown_signals,
+    })
+}
+
+/// Read an `idle_backoff` table, using the `IdleBackoff` defaults for missing keys.
+fn read_idle_backoff(backoff: &toml::value::Table) -> Result<IdleBackoff> {
+    let default = IdleBackoff::default();
+    let read_u64 = |key: &str, default: u64| match backoff.get(key) {
+        Some(&Value::Integer(v)) if v >= 0 => Ok(v as u64),
+        None => Ok(default),
+        v => Err(ErrorKind::ConfigurationError(format!(
+            "Could not parse idle_backoff {} {:?}",
+            key, v
+        ))),
+    };
+    let rx_interrupts = match backoff.get("rx_interrupts") {
+        Some(&Value::Boolean(l)) => l,
+        None => default.rx_interrupts,
+        v => {
+            return Err(
+                ErrorKind::ConfigurationError(format!("Could not parse idle_backoff rx_interrupts {:?}", v)).into(),
+            )
+        }
+    };
+    Ok(IdleBackoff {
+        pause_after: read_u64("pause_after", default.pause_after)?,
+        sleep_after: read_u64("sleep_after", default.sleep_after)?,
+        sleep_us: read_u64("sleep_us", default.sleep_us)?,
+        rx_interrupts,
     })
 }
 
@@ -20,6 +20,37 @@ pub fn basic_opts() -> Options {
     opts.optopt("f", "configuration", "Configuration file", "path");
     opts.optmulti("", "dpdk_args", "DPDK arguments", "DPDK arguments");
     opts.optopt("d", "duration", "Run duration", "duration");
+    opts.optflag("", "idle_backoff", "pause and then sleep when all pipelines are idle");
+    opts.optopt("", "pvn_config", "PVN experiment configuration file", "path");
+    opts.optopt(
+        "",
+        "stats_report",
+        "write statistics to this file (.json or .csv) on shutdown",
+        "path",
+    );
+    opts.optopt(
+        "",
+        "metrics_address",
+        "serve metrics over HTTP at this address",
+        "address:port",
+    );
+    opts.optopt(
+        "",
+        "control_address",
+        "accept runtime control requests at this address",
+        "address:port",
+    );
+    opts.optopt(
+        "",
+        "drain_timeout_ms",
+        "how long to let pipelines drain on shutdown",
+        "milliseconds",
+    );
+    opts.optflag(
+        "",
+        "no_shutdown_signals",
+        "let SIGINT and SIGTERM kill the process right away",
+    );
 
     opts
 }
@@ -86,10 +117,81 @@ pub fn read_matches(matches: &Matches, opts: &Options) -> NetbricksConfiguration
     };
 
     let configuration = if matches.opt_present("duration") {
-        let _duration = matches.opt_strs("d");
+        NetbricksConfiguration {
+            duration: Some(
+                matches
+                    .opt_str("duration")
+                    .unwrap()
+                    .parse()
+                    .expect("Could not parse duration"),
+            ),
+            ..configuration
+        }
+    } else {
+        configuration
+    };
+
+    let configuration = if matches.opt_present("idle_backoff") {
+        NetbricksConfiguration {
+            idle_backoff: Some(configuration.idle_backoff.unwrap_or_default()),
+            ..configuration
+        }
+    } else {
+        configuration
+    };
+
+    let configuration = if matches.opt_present("pvn_config") {
+        NetbricksConfiguration {
+            pvn_config: matches.opt_str("pvn_config"),
+            ..configuration
+        }
+    } else {
+        configuration
+    };
+
+    let configuration = if matches.opt_present("stats_report") {
+        NetbricksConfiguration {
+            stats_report: matches.opt_str("stats_report"),
+            ..configuration
+        }
+    } else {
+        configuration
+    };
+
+    let configuration = if matches.opt_present("metrics_address") {
+        NetbricksConfiguration {
+            metrics_address: matches.opt_str("metrics_address"),
+            ..configuration
+        }
+    } else {
+        configuration
+    };
+
+    let configuration = if matches.opt_present("control_address") {
+        NetbricksConfiguration {
+            control_address: matches.opt_str("control_address"),
+            ..configuration
+        }
+    } else {
+        configuration
+    };
+
+    let configuration = if matches.opt_present("drain_timeout_ms") {
+        NetbricksConfiguration {
+            drain_timeout_ms: matches
+                .opt_str("drain_timeout_ms")
+                .unwrap()
+                .parse()
+                .expect("Could not parse drain timeout"),
+            ..configuration
+        }
+    } else {
+        configuration
+    };
 
+    let configuration = if matches.opt_present("no_shutdown_signals") {
         NetbricksConfiguration {
-            duration: None,
+            shutdown_signals: false,
             ..configuration
         }
     } else {
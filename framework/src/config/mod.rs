@@ -2,6 +2,7 @@
 
 pub use self::config_reader::*;
 pub use self::flag_reader::*;
+use crate::scheduler::IdleBackoff;
 use std::fmt;
 mod config_reader;
 mod flag_reader;
@@ -31,9 +32,24 @@ pub struct NetbricksConfiguration {
     pub cache_size: u32,
     /// Custom DPDK arguments.
     pub dpdk_args: Option<String>,
-    /// Duration for running netbricks network function
-    /// (esp. useful for testing); used if not setting from cli-args
+    /// How long to run, in seconds: `NetBricksContext::wait_for_shutdown` asks for the system to stop once this much
+    /// time passed since `execute`.
     pub duration: Option<i64>,
+    /// Let idle schedulers back off instead of busy polling, see `IdleBackoff`. Off by default.
+    pub idle_backoff: Option<IdleBackoff>,
+    /// PVN experiment configuration file (JSON or TOML), see `PvnExperimentConfig`.
+    pub pvn_config: Option<String>,
+    /// File to write a `StatsReport` to when the system shuts down, as CSV if it ends in `.csv` and JSON otherwise.
+    pub stats_report: Option<String>,
+    /// Address (e.g., `127.0.0.1:9100`) to serve Prometheus-style metrics at, see
+    /// `NetBricksContext::start_metrics_server`.
+    pub metrics_address: Option<String>,
+    /// Address to accept runtime control requests at, see `NetBricksContext::start_control_server`.
+    pub control_address: Option<String>,
+    /// How long `NetBricksContext::stop` lets pipelines drain the packets already received, in milliseconds.
+    pub drain_timeout_ms: u64,
+    /// Turn SIGINT and SIGTERM into shutdown requests, see `NetBricksContext::wait_for_shutdown`. On by default.
+    pub shutdown_signals: bool,
 }
 
 /// Create an empty `NetbricksConfiguration`, useful when initializing through arguments.
@@ -50,6 +66,13 @@ impl Default for NetbricksConfiguration {
             ports: vec![],
             dpdk_args: None,
             duration: None,
+            idle_backoff: None,
+            pvn_config: None,
+            stats_report: None,
+            metrics_address: None,
+            control_address: None,
+            drain_timeout_ms: DEFAULT_DRAIN_TIMEOUT_MS,
+            shutdown_signals: true,
         }
     }
 }
@@ -84,6 +107,25 @@ impl fmt::Display for NetbricksConfiguration {
         if let Some(ref arg) = self.duration {
             writeln!(f, "Duration: {}", arg)?
         };
+        if let Some(ref backoff) = self.idle_backoff {
+            writeln!(f, "Idle backoff: {:?}", backoff)?
+        };
+        if let Some(ref path) = self.pvn_config {
+            writeln!(f, "PVN experiment configuration: {}", path)?
+        };
+        if let Some(ref path) = self.stats_report {
+            writeln!(f, "Statistics report: {}", path)?
+        };
+        if let Some(ref address) = self.metrics_address {
+            writeln!(f, "Metrics: http://{}/metrics", address)?
+        };
+        if let Some(ref address) = self.control_address {
+            writeln!(f, "Control: {}", address)?
+        };
+        writeln!(f, "Drain timeout: {}ms", self.drain_timeout_ms)?;
+        if !self.shutdown_signals {
+            writeln!(f, "Not handling shutdown signals")?
+        };
         write!(f, "")
     }
 }
@@ -96,6 +138,7 @@ pub struct PortConfiguration {
     ///    dpdk:<PMD Descriptor>: PMD driver with arguments
     ///    bess:<port_name>: BESS RingVport with name.
     ///    ovs:<port_id>: OVS ring with ID.
+    ///    pcap:<options>: Replay from and record to capture files, see `PcapSpec` for the options.
     pub name: String,
     /// Core on which receive node for a given queue lives.
     pub rx_queues: Vec<i32>,
@@ -111,6 +154,10 @@ pub struct PortConfiguration {
     pub tso: bool,
     /// TCP checksum option.
     pub csum: bool,
+    /// Set up RX interrupts, which idle schedulers can wait on when `IdleBackoff::rx_interrupts` is set.
+    pub rx_interrupts: bool,
+    /// Largest IP packet sent, see `Batch::fragment`.
+    pub mtu: u16,
 }
 
 impl Default for PortConfiguration {
@@ -124,6 +171,8 @@ impl Default for PortConfiguration {
             loopback: false,
             tso: false,
             csum: false,
+            rx_interrupts: false,
+            mtu: DEFAULT_MTU,
         }
     }
 }
@@ -155,7 +204,7 @@ impl fmt::Display for PortConfiguration {
         let tx_queue_str = tx_queues_str_vec.join(" ");
         write!(
             f,
-            "Port {} RXQ_Count: {} RX_Queues: [ {} ] TXQ_Count: {} TX_Queues: {} RXD: {} TXD: {} Loopback {}",
+            "Port {} RXQ_Count: {} RX_Queues: [ {} ] TXQ_Count: {} TX_Queues: {} RXD: {} TXD: {} Loopback {} MTU {}",
             self.name,
             self.rx_queues.len(),
             rx_queue_str,
@@ -163,7 +212,8 @@ impl fmt::Display for PortConfiguration {
             tx_queue_str,
             self.rxd,
             self.txd,
-            self.loopback
+            self.loopback,
+            self.mtu
         )
     }
 }
@@ -0,0 +1,429 @@
+//! Prometheus-style metrics over HTTP.
+//!
+//! `MetricsAgent` is a `TcpControlAgent` answering `GET /metrics` with the current value of every metric in the
+//! Prometheus text exposition format, so Prometheus (or curl) can watch a run while it is going. Metrics come from
+//! sources registered with `add_metrics_source`, which write them into a `MetricsWriter` on every request, and from
+//! counters NFs register with `register_counter`. `NetBricksContext::start_metrics_server` registers the ports,
+//! mempools and schedulers of a context and serves them from a thread of its own.
+use super::tcp::{TcpControlAgent, TcpControlServer};
+use super::IOScheduler;
+use crate::common::*;
+use crate::interface::dpdk::mempool_usage;
+use crate::interface::PmdPort;
+use crate::scheduler::SchedulerStats;
+use crate::stats::PortStats;
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::io::{ErrorKind as IoErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Control server answering metrics requests.
+pub type MetricsServer = TcpControlServer<MetricsAgent>;
+
+/// Requests larger than this are dropped.
+const MAX_REQUEST: usize = 8192;
+
+/// Kind of a metric family.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetricKind {
+    /// A value that only goes up.
+    Counter,
+    /// A value that goes up and down.
+    Gauge,
+}
+
+impl MetricKind {
+    fn name(self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Family {
+    help: String,
+    kind: MetricKind,
+    samples: Vec<(String, String)>,
+}
+
+/// Collects samples, grouped by metric family, and renders them in the Prometheus text format.
+#[derive(Debug, Default)]
+pub struct MetricsWriter {
+    families: BTreeMap<String, Family>,
+}
+
+impl MetricsWriter {
+    /// Create an empty writer.
+    pub fn new() -> MetricsWriter {
+        Default::default()
+    }
+
+    /// Add a sample of a counter. `help` is only used the first time a family is seen.
+    pub fn counter(&mut self, name: &str, help: &str, labels: &[(&str, &str)], value: u64) {
+        self.sample(name, help, MetricKind::Counter, labels, value.to_string());
+    }
+
+    /// Add a sample of a gauge. `help` is only used the first time a family is seen.
+    pub fn gauge(&mut self, name: &str, help: &str, labels: &[(&str, &str)], value: f64) {
+        let value = if value.is_nan() {
+            String::from("NaN")
+        } else if value.is_infinite() {
+            String::from(if value > 0.0 { "+Inf" } else { "-Inf" })
+        } else {
+            value.to_string()
+        };
+        self.sample(name, help, MetricKind::Gauge, labels, value);
+    }
+
+    fn sample(&mut self, name: &str, help: &str, kind: MetricKind, labels: &[(&str, &str)], value: String) {
+        debug_assert!(valid_name(name), "bad metric name {}", name);
+        let family = self.families.entry(name.to_string()).or_insert_with(|| Family {
+            help: help.to_string(),
+            kind,
+            samples: vec![],
+        });
+        let mut rendered = String::new();
+        for (i, (label, value)) in labels.iter().enumerate() {
+            let sep = if i == 0 { '{' } else { ',' };
+            write!(rendered, "{}{}=\"{}\"", sep, label, escape(value, true)).unwrap();
+        }
+        if !labels.is_empty() {
+            rendered.push('}');
+        }
+        family.samples.push((rendered, value));
+    }
+
+    /// Render all samples, families sorted by name.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (name, family) in &self.families {
+            writeln!(out, "# HELP {} {}", name, escape(&family.help, false)).unwrap();
+            writeln!(out, "# TYPE {} {}", name, family.kind.name()).unwrap();
+            for (labels, value) in &family.samples {
+                writeln!(out, "{}{} {}", name, labels, value).unwrap();
+            }
+        }
+        out
+    }
+}
+
+/// Escape backslashes and newlines, and double quotes in label values.
+fn escape(s: &str, quotes: bool) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '"' if quotes => escaped.push_str("\\\""),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Whether `name` is a valid Prometheus metric name.
+fn valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == ':' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+/// A counter NFs can bump from any core. Clones share the count.
+#[derive(Clone, Debug, Default)]
+pub struct Counter {
+    value: Arc<AtomicU64>,
+}
+
+impl Counter {
+    /// Add one.
+    #[inline]
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    /// Add `n`.
+    #[inline]
+    pub fn add(&self, n: u64) {
+        self.value.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Current count.
+    #[inline]
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// Handle of a source registered with `add_metrics_source`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MetricsSourceHandle(usize);
+
+type MetricsSource = Box<dyn Fn(&mut MetricsWriter) + Send + Sync>;
+
+lazy_static! {
+    static ref COUNTERS: RwLock<BTreeMap<String, (String, Counter)>> = RwLock::new(BTreeMap::new());
+    static ref SOURCES: RwLock<(usize, Vec<(usize, MetricsSource)>)> = RwLock::new((0, vec![]));
+}
+
+/// Register a counter shown on the metrics page as `name`. Registering a name again returns the existing counter, so
+/// pipelines on every core can share one.
+pub fn register_counter(name: &str, help: &str) -> Result<Counter> {
+    if !valid_name(name) {
+        return Err(ErrorKind::BadMetricName(name.to_string()).into());
+    }
+    let mut counters = COUNTERS.write().unwrap();
+    let entry = counters
+        .entry(name.to_string())
+        .or_insert_with(|| (help.to_string(), Counter::default()));
+    Ok(entry.1.clone())
+}
+
+/// Add a function writing metrics each time the page is requested. It must not add or remove sources itself.
+pub fn add_metrics_source<F>(source: F) -> MetricsSourceHandle
+where
+    F: Fn(&mut MetricsWriter) + Send + Sync + 'static,
+{
+    let mut sources = SOURCES.write().unwrap();
+    sources.0 += 1;
+    let handle = sources.0;
+    sources.1.push((handle, Box::new(source)));
+    MetricsSourceHandle(handle)
+}
+
+/// Remove a source added with `add_metrics_source`.
+pub fn remove_metrics_source(handle: MetricsSourceHandle) {
+    SOURCES.write().unwrap().1.retain(|&(h, _)| h != handle.0);
+}
+
+/// Render the metrics page: everything written by the registered sources, and the registered counters.
+pub fn render_metrics() -> String {
+    let mut writer = MetricsWriter::new();
+    for (_, source) in &SOURCES.read().unwrap().1 {
+        source(&mut writer);
+    }
+    for (name, (help, counter)) in COUNTERS.read().unwrap().iter() {
+        writer.counter(name, help, &[], counter.get());
+    }
+    writer.render()
+}
+
+/// Write packet counters of ports, see `stats::PortStats`.
+pub fn write_port_stats(writer: &mut MetricsWriter, ports: &[PortStats]) {
+    for stats in ports {
+        let queue = stats.queue.map(|q| q.to_string()).unwrap_or_default();
+        let labels = [("port", &stats.port[..]), ("queue", &queue[..])];
+        writer.counter(
+            "netbricks_port_rx_packets_total",
+            "Packets received.",
+            &labels,
+            stats.rx,
+        );
+        writer.counter("netbricks_port_tx_packets_total", "Packets sent.", &labels, stats.tx);
+    }
+}
+
+/// Write the drop counters of a PMD port: packets each TX queue could not hand to the NIC, and the drops the NIC
+/// counted on receive (if the driver reports them).
+pub fn write_pmd_drops(writer: &mut MetricsWriter, name: &str, port: &PmdPort) {
+    for queue in 0..port.txqs() {
+        let queue_label = queue.to_string();
+        writer.counter(
+            "netbricks_port_tx_dropped_total",
+            "Packets the NIC did not accept for sending.",
+            &[("port", name), ("queue", &queue_label)],
+            port.tx_drops(queue) as u64,
+        );
+    }
+    if let Some((missed, nombuf)) = port.rx_drops() {
+        writer.counter(
+            "netbricks_port_rx_missed_total",
+            "Packets dropped by the NIC because the RX rings were full.",
+            &[("port", name)],
+            missed,
+        );
+        writer.counter(
+            "netbricks_port_rx_nombuf_total",
+            "Packets the NIC could not receive for want of mbufs.",
+            &[("port", name)],
+            nombuf,
+        );
+    }
+}
+
+/// Write the number of mbufs in use and available in each packet mempool.
+pub fn write_mempool_usage(writer: &mut MetricsWriter) {
+    for (pool, (in_use, avail)) in mempool_usage().into_iter().enumerate() {
+        let pool = pool.to_string();
+        writer.gauge(
+            "netbricks_mempool_mbufs_in_use",
+            "Mbufs currently allocated from the mempool.",
+            &[("pool", &pool)],
+            f64::from(in_use),
+        );
+        writer.gauge(
+            "netbricks_mempool_mbufs_available",
+            "Mbufs left in the mempool.",
+            &[("pool", &pool)],
+            f64::from(avail),
+        );
+    }
+}
+
+/// Write the statistics of the scheduler on `core` and each of its tasks.
+pub fn write_scheduler_stats(writer: &mut MetricsWriter, core: i32, stats: &SchedulerStats) {
+    let core = core.to_string();
+    for task in &stats.tasks {
+        let id = task.task.to_string();
+        let pipeline = task.pipeline.map(|p| p.to_string()).unwrap_or_default();
+        let labels = [("core", &core[..]), ("task", &id[..]), ("pipeline", &pipeline[..])];
+        writer.counter(
+            "netbricks_task_cycles_total",
+            "Cycles spent running the task.",
+            &labels,
+            task.cycles,
+        );
+        writer.counter(
+            "netbricks_task_invocations_total",
+            "Times the task was run.",
+            &labels,
+            task.invocations,
+        );
+        writer.counter(
+            "netbricks_task_packets_total",
+            "Packets processed by the task.",
+            &labels,
+            task.packets,
+        );
+        writer.counter(
+            "netbricks_task_idle_total",
+            "Runs of the task that processed no packets.",
+            &labels,
+            task.idle,
+        );
+        writer.gauge(
+            "netbricks_task_paused",
+            "Whether the task is paused.",
+            &labels,
+            if task.paused { 1.0 } else { 0.0 },
+        );
+    }
+    writer.counter(
+        "netbricks_scheduler_sleep_cycles_total",
+        "Cycles the scheduler spent backing off while idle.",
+        &[("core", &core)],
+        stats.sleep_cycles,
+    );
+    writer.counter(
+        "netbricks_scheduler_sleeps_total",
+        "Times the scheduler backed off while idle.",
+        &[("core", &core)],
+        stats.sleeps,
+    );
+}
+
+/// Answer a request whose header is `request`.
+fn respond(request: &[u8]) -> Vec<u8> {
+    let request = String::from_utf8_lossy(request);
+    let mut parts = request.lines().next().unwrap_or("").split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let path = path.split('?').next().unwrap_or("");
+    let (status, body) = match (method, path) {
+        ("GET", "/metrics") => ("200 OK", render_metrics()),
+        ("GET", _) => ("404 Not Found", String::from("Metrics are at /metrics\n")),
+        _ => ("405 Method Not Allowed", String::from("Only GET is supported\n")),
+    };
+    let mut response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+    )
+    .into_bytes();
+    response.extend_from_slice(body.as_bytes());
+    response
+}
+
+/// Serves one HTTP connection: reads a request, writes the response and closes the connection.
+#[derive(Debug)]
+pub struct MetricsAgent {
+    stream: TcpStream,
+    scheduler: IOScheduler,
+    request: Vec<u8>,
+    response: Vec<u8>,
+    written: usize,
+}
+
+impl MetricsAgent {
+    /// Write as much of the response as the socket takes. Returns false once the connection should be closed.
+    fn write_response(&mut self) -> bool {
+        while self.written < self.response.len() {
+            match self.stream.write(&self.response[self.written..]) {
+                Ok(0) => return false,
+                Ok(n) => self.written += n,
+                Err(ref e) if e.kind() == IoErrorKind::WouldBlock => {
+                    self.scheduler.schedule_write();
+                    return true;
+                }
+                Err(ref e) if e.kind() == IoErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+        false
+    }
+}
+
+impl TcpControlAgent for MetricsAgent {
+    fn new(_address: SocketAddr, stream: TcpStream, scheduler: IOScheduler) -> MetricsAgent {
+        scheduler.schedule_read();
+        MetricsAgent {
+            stream,
+            scheduler,
+            request: vec![],
+            response: vec![],
+            written: 0,
+        }
+    }
+
+    fn handle_read_ready(&mut self) -> bool {
+        let mut buf = [0; 1024];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return false,
+                Ok(n) => {
+                    self.request.extend_from_slice(&buf[..n]);
+                    if self.request.len() > MAX_REQUEST {
+                        return false;
+                    }
+                }
+                Err(ref e) if e.kind() == IoErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == IoErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+        match self.request.windows(4).position(|w| w == b"\r\n\r\n") {
+            Some(end) if self.response.is_empty() => {
+                self.response = respond(&self.request[..end]);
+                self.write_response()
+            }
+            Some(_) => true,
+            None => {
+                self.scheduler.schedule_read();
+                true
+            }
+        }
+    }
+
+    fn handle_write_ready(&mut self) -> bool {
+        self.write_response()
+    }
+
+    fn handle_hup(&mut self) -> bool {
+        false
+    }
+}
@@ -5,6 +5,8 @@ pub use self::epoll::*;
 #[cfg(target_os = "linux")]
 #[path = "linux/epoll.rs"]
 mod epoll;
+pub mod metrics;
+pub mod protocol;
 // #[cfg(feature = "sctp")]
 // pub mod sctp;
 pub mod tcp;
@@ -0,0 +1,254 @@
+//! Runtime control protocol.
+//!
+//! A client sends one request per line and gets one JSON object per line back: `{"ok":true,"result":...}` or
+//! `{"ok":false,"error":"..."}`. Requests are:
+//!
+//! ```text
+//! ports                       list ports and their queues
+//! cores                       list active cores and the queues they poll
+//! pipelines                   list pipelines running on each core, and those that can be added by name
+//! stats                       port counters, latency summary and per-task scheduler statistics
+//! add <name> <core>           install the pipeline registered as <name> on <core>
+//! remove <pipeline> [<core>]  remove a pipeline from <core> (default all cores)
+//! drain [<core>]              stop RX, wait for pipelines to go idle, then pause them (answers right away)
+//! resume [<core>]             resume pipelines paused by drain, and RX
+//! shutdown                    stop the system, writing the end-of-run statistics
+//! help                        list requests
+//! ```
+//!
+//! `ControlAgent` implements the protocol as a `TcpControlAgent`, handing requests to the handler set with
+//! `set_control_handler`; `NetBricksContext::start_control_server` sets one acting on the context.
+use super::tcp::{TcpControlAgent, TcpControlServer};
+use super::IOScheduler;
+use crate::common::*;
+use serde_json::{json, Value};
+use std::fmt;
+use std::io::{ErrorKind as IoErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, RwLock};
+
+/// Control server speaking the runtime control protocol.
+pub type ControlServer = TcpControlServer<ControlAgent>;
+
+/// Lines longer than this close the connection.
+const MAX_LINE: usize = 4096;
+
+/// Usage shown by `help`.
+pub const CONTROL_USAGE: &str = "ports | cores | pipelines | stats | add <name> <core> | remove <pipeline> [<core>] | \
+                                 drain [<core>] | resume [<core>] | shutdown | help";
+
+/// A request of the runtime control protocol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ControlRequest {
+    /// List ports.
+    Ports,
+    /// List active cores.
+    Cores,
+    /// List running and registered pipelines.
+    Pipelines,
+    /// Dump statistics.
+    Stats,
+    /// Install a registered pipeline on a core.
+    Add {
+        /// Name the pipeline was registered under.
+        name: String,
+        /// Core to install it on.
+        core: i32,
+    },
+    /// Remove a pipeline.
+    Remove {
+        /// Pipeline handle.
+        pipeline: usize,
+        /// Core to remove it from, all cores if `None`.
+        core: Option<i32>,
+    },
+    /// Stop RX and let pipelines go idle, then pause them.
+    Drain {
+        /// Core to drain, all cores if `None`.
+        core: Option<i32>,
+    },
+    /// Resume paused pipelines and RX.
+    Resume {
+        /// Core to resume, all cores if `None`.
+        core: Option<i32>,
+    },
+    /// Shut the system down.
+    Shutdown,
+    /// List requests.
+    Help,
+}
+
+impl ControlRequest {
+    /// Parse one request line.
+    pub fn parse(line: &str) -> Result<ControlRequest> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let request = match words[..] {
+            ["ports"] => Some(ControlRequest::Ports),
+            ["cores"] => Some(ControlRequest::Cores),
+            ["pipelines"] => Some(ControlRequest::Pipelines),
+            ["stats"] => Some(ControlRequest::Stats),
+            ["add", name, core] => core.parse().ok().map(|core| ControlRequest::Add {
+                name: name.to_string(),
+                core,
+            }),
+            ["remove", pipeline] => pipeline
+                .parse()
+                .ok()
+                .map(|pipeline| ControlRequest::Remove { pipeline, core: None }),
+            ["remove", pipeline, core] => match (pipeline.parse(), core.parse()) {
+                (Ok(pipeline), Ok(core)) => Some(ControlRequest::Remove {
+                    pipeline,
+                    core: Some(core),
+                }),
+                _ => None,
+            },
+            ["drain"] => Some(ControlRequest::Drain { core: None }),
+            ["drain", core] => core.parse().ok().map(|core| ControlRequest::Drain { core: Some(core) }),
+            ["resume"] => Some(ControlRequest::Resume { core: None }),
+            ["resume", core] => core
+                .parse()
+                .ok()
+                .map(|core| ControlRequest::Resume { core: Some(core) }),
+            ["shutdown"] => Some(ControlRequest::Shutdown),
+            ["help"] => Some(ControlRequest::Help),
+            _ => None,
+        };
+        request.ok_or_else(|| ErrorKind::BadControlRequest(line.trim().to_string()).into())
+    }
+}
+
+impl fmt::Display for ControlRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let optional = |core: &Option<i32>| core.map(|c| format!(" {}", c)).unwrap_or_default();
+        match *self {
+            ControlRequest::Ports => write!(f, "ports"),
+            ControlRequest::Cores => write!(f, "cores"),
+            ControlRequest::Pipelines => write!(f, "pipelines"),
+            ControlRequest::Stats => write!(f, "stats"),
+            ControlRequest::Add { ref name, core } => write!(f, "add {} {}", name, core),
+            ControlRequest::Remove { pipeline, ref core } => write!(f, "remove {}{}", pipeline, optional(core)),
+            ControlRequest::Drain { ref core } => write!(f, "drain{}", optional(core)),
+            ControlRequest::Resume { ref core } => write!(f, "resume{}", optional(core)),
+            ControlRequest::Shutdown => write!(f, "shutdown"),
+            ControlRequest::Help => write!(f, "help"),
+        }
+    }
+}
+
+/// Answers requests with a result or an error message.
+pub type ControlHandler = Arc<dyn Fn(&ControlRequest) -> std::result::Result<Value, String> + Send + Sync>;
+
+lazy_static! {
+    static ref HANDLER: RwLock<Option<ControlHandler>> = RwLock::new(None);
+}
+
+/// Set the handler `ControlAgent`s hand requests to, replacing any previous one.
+pub fn set_control_handler(handler: ControlHandler) {
+    *HANDLER.write().unwrap() = Some(handler);
+}
+
+/// Remove the handler; requests fail until a new one is set.
+pub fn clear_control_handler() {
+    *HANDLER.write().unwrap() = None;
+}
+
+/// Answer one request line with a response line (without the newline).
+pub fn handle_control_line(line: &str) -> String {
+    let result = match ControlRequest::parse(line) {
+        Ok(ControlRequest::Help) => Ok(Value::from(CONTROL_USAGE)),
+        Ok(request) => match *HANDLER.read().unwrap() {
+            Some(ref handler) => handler(&request),
+            None => Err(String::from("no system attached")),
+        },
+        Err(e) => Err(format!("{}, expected {}", e, CONTROL_USAGE)),
+    };
+    match result {
+        Ok(result) => json!({ "ok": true, "result": result }).to_string(),
+        Err(error) => json!({ "ok": false, "error": error }).to_string(),
+    }
+}
+
+/// Serves one control connection: answers each request line in turn until the client hangs up.
+#[derive(Debug)]
+pub struct ControlAgent {
+    stream: TcpStream,
+    scheduler: IOScheduler,
+    input: Vec<u8>,
+    output: Vec<u8>,
+}
+
+impl ControlAgent {
+    /// Answer complete lines, then write out responses. Returns false once the connection should be closed.
+    fn process(&mut self) -> bool {
+        while let Some(end) = self.input.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.input.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            self.output.extend_from_slice(handle_control_line(&line).as_bytes());
+            self.output.push(b'\n');
+        }
+        if self.input.len() > MAX_LINE {
+            return false;
+        }
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => return false,
+                Ok(n) => {
+                    self.output.drain(..n);
+                }
+                Err(ref e) if e.kind() == IoErrorKind::WouldBlock => {
+                    self.scheduler.schedule_write();
+                    return true;
+                }
+                Err(ref e) if e.kind() == IoErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+        self.scheduler.schedule_read();
+        true
+    }
+}
+
+impl TcpControlAgent for ControlAgent {
+    fn new(_address: SocketAddr, stream: TcpStream, scheduler: IOScheduler) -> ControlAgent {
+        scheduler.schedule_read();
+        ControlAgent {
+            stream,
+            scheduler,
+            input: vec![],
+            output: vec![],
+        }
+    }
+
+    fn handle_read_ready(&mut self) -> bool {
+        let mut buf = [0; 1024];
+        let mut closed = false;
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => {
+                    // Still answer what came before the client closed its end.
+                    if !self.input.is_empty() && !self.input.ends_with(b"\n") {
+                        self.input.push(b'\n');
+                    }
+                    closed = true;
+                    break;
+                }
+                Ok(n) => self.input.extend_from_slice(&buf[..n]),
+                Err(ref e) if e.kind() == IoErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == IoErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+        self.process() && !closed
+    }
+
+    fn handle_write_ready(&mut self) -> bool {
+        self.process()
+    }
+
+    fn handle_hup(&mut self) -> bool {
+        false
+    }
+}
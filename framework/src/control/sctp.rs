@@ -36,8 +36,9 @@ pub struct SctpControlServer<T: SctpControlAgent> {
 }
 
 impl<T: SctpControlAgent> Executable for SctpControlServer<T> {
-    fn execute(&mut self) {
+    fn execute(&mut self) -> usize {
         self.schedule();
+        0
     }
 
     #[inline]
@@ -1,5 +1,6 @@
 //! TCP connection.
 use super::{Available, IOScheduler, PollHandle, PollScheduler, Token, HUP, READ, WRITE};
+use crate::common::*;
 use crate::scheduler::Executable;
 use fnv::FnvHasher;
 use net2::TcpBuilder;
@@ -36,8 +37,9 @@ pub struct TcpControlServer<T: TcpControlAgent> {
 }
 
 impl<T: TcpControlAgent> Executable for TcpControlServer<T> {
-    fn execute(&mut self) {
+    fn execute(&mut self) -> usize {
         self.schedule();
+        0
     }
 
     #[inline]
@@ -49,21 +51,25 @@ impl<T: TcpControlAgent> Executable for TcpControlServer<T> {
 impl<T: TcpControlAgent> TcpControlServer<T> {
     /// Initialize TCP control server.
     pub fn new(address: SocketAddr) -> TcpControlServer<T> {
+        TcpControlServer::bind(address).unwrap()
+    }
+
+    /// Initialize TCP control server, returning an error if it cannot listen on `address`.
+    pub fn bind(address: SocketAddr) -> Result<TcpControlServer<T>> {
         let socket = match address {
             SocketAddr::V4(_) => TcpBuilder::new_v4(),
             SocketAddr::V6(_) => TcpBuilder::new_v6(),
-        }
-        .unwrap();
-        let _ = socket.reuse_address(true).unwrap();
+        }?;
+        socket.reuse_address(true)?;
         // FIXME: Change 1024 to a parameter
-        let listener = socket.bind(address).unwrap().listen(1024).unwrap();
-        listener.set_nonblocking(true).unwrap();
+        let listener = socket.bind(address)?.listen(1024)?;
+        listener.set_nonblocking(true)?;
         let scheduler = PollScheduler::new();
         let listener_token = 0;
         let handle = scheduler.new_poll_handle();
         handle.new_io_port(&listener, listener_token);
         handle.schedule_read(&listener, listener_token);
-        TcpControlServer {
+        Ok(TcpControlServer {
             listener,
             scheduler,
             handle,
@@ -71,7 +77,12 @@ impl<T: TcpControlAgent> TcpControlServer<T> {
             listener_token,
             phantom_t: PhantomData,
             connections: HashMap::with_capacity_and_hasher(32, Default::default()),
-        }
+        })
+    }
+
+    /// Address the server listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener.local_addr().unwrap()
     }
 
     /// Initialize schedule for TCP control server.
@@ -0,0 +1,244 @@
+use super::{EndOffset, Follows};
+use crate::headers::{MacHeader, UdpHeader};
+use byteorder::{BigEndian, ByteOrder};
+use std::default::Default;
+use std::fmt;
+use std::iter::FusedIterator;
+use std::slice;
+
+/// UDP port assigned to GENEVE (RFC 8926).
+pub const GENEVE_PORT: u16 = 6081;
+/// Protocol type of Ethernet frames carried by GENEVE, Transparent Ethernet Bridging.
+pub const ETHERTYPE_TEB: u16 = 0x6558;
+
+const HDR_SIZE: usize = 8;
+/// Option data is given in 4 byte words, in 5 bits.
+const MAX_OPTION_DATA: usize = 31 * 4;
+
+/// A GENEVE header (RFC 8926): a network identifier (VNI), the protocol type of the payload and variable length
+/// options, see `GeneveHeader::options`.
+#[derive(Default)]
+#[repr(C, packed)]
+pub struct GeneveHeader {
+    version_opt_len: u8,
+    flags: u8,
+    protocol: u16,
+    vni_reserved: u32,
+}
+
+impl fmt::Display for GeneveHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "geneve vni: {} proto: 0x{:04x} options: {} oam: {} critical: {}",
+            self.vni(),
+            self.protocol(),
+            self.options_len(),
+            self.oam(),
+            self.critical()
+        )
+    }
+}
+
+impl EndOffset for GeneveHeader {
+    type PreviousHeader = UdpHeader;
+
+    #[inline]
+    fn offset(&self) -> usize {
+        HDR_SIZE + self.options_len()
+    }
+
+    #[inline]
+    fn size() -> usize {
+        HDR_SIZE
+    }
+
+    #[inline]
+    fn payload_size(&self, hint: usize) -> usize {
+        hint - self.offset()
+    }
+
+    /// The UDP destination port must be the GENEVE port, and the version 0.
+    #[inline]
+    fn check_correct(&self, prev: &UdpHeader) -> bool {
+        prev.dst_port() == GENEVE_PORT && self.version() == 0
+    }
+}
+
+impl Follows<GeneveHeader> for MacHeader {
+    /// Only Ethernet frames follow GENEVE headers of the Transparent Ethernet Bridging protocol type.
+    #[inline]
+    fn check_correct_after(&self, prev: &GeneveHeader, _available: usize) -> bool {
+        prev.protocol() == ETHERTYPE_TEB
+    }
+}
+
+impl GeneveHeader {
+    /// Initialize a GENEVE header carrying Ethernet frames, without options.
+    #[inline]
+    pub fn new() -> GeneveHeader {
+        let mut header: GeneveHeader = Default::default();
+        header.set_protocol(ETHERTYPE_TEB);
+        header
+    }
+
+    /// Get the version, 0 is the only one defined.
+    #[inline]
+    pub fn version(&self) -> u8 {
+        self.version_opt_len >> 6
+    }
+
+    /// Get the length of the options in bytes.
+    #[inline]
+    pub fn options_len(&self) -> usize {
+        (self.version_opt_len & 0x3f) as usize * 4
+    }
+
+    /// Set the length of the options in bytes, a multiple of 4 up to 252. The options must follow the header.
+    #[inline]
+    pub fn set_options_len(&mut self, len: usize) {
+        self.version_opt_len = (self.version_opt_len & 0xc0) | ((len / 4) as u8 & 0x3f)
+    }
+
+    /// Returns true for control messages (the O bit).
+    #[inline]
+    pub fn oam(&self) -> bool {
+        self.flags & 0x80 != 0
+    }
+
+    /// Set the O bit.
+    #[inline]
+    pub fn set_oam(&mut self, oam: bool) {
+        self.flags = (self.flags & !0x80) | if oam { 0x80 } else { 0 }
+    }
+
+    /// Returns true if critical options are present (the C bit).
+    #[inline]
+    pub fn critical(&self) -> bool {
+        self.flags & 0x40 != 0
+    }
+
+    /// Set the C bit.
+    #[inline]
+    pub fn set_critical(&mut self, critical: bool) {
+        self.flags = (self.flags & !0x40) | if critical { 0x40 } else { 0 }
+    }
+
+    /// Get the EtherType of the payload.
+    #[inline]
+    pub fn protocol(&self) -> u16 {
+        u16::from_be(self.protocol)
+    }
+
+    /// Set the EtherType of the payload.
+    #[inline]
+    pub fn set_protocol(&mut self, protocol: u16) {
+        self.protocol = u16::to_be(protocol)
+    }
+
+    /// Get the virtual network identifier.
+    #[inline]
+    pub fn vni(&self) -> u32 {
+        u32::from_be(self.vni_reserved) >> 8
+    }
+
+    /// Set the virtual network identifier.
+    #[inline]
+    pub fn set_vni(&mut self, vni: u32) {
+        self.vni_reserved = u32::to_be((vni & 0x00ff_ffff) << 8)
+    }
+
+    /// The options, following the fixed 8 bytes of the header.
+    #[inline]
+    pub fn option_bytes(&self) -> &[u8] {
+        unsafe {
+            let options = (self as *const GeneveHeader as *const u8).add(HDR_SIZE);
+            slice::from_raw_parts(options, self.options_len())
+        }
+    }
+
+    /// Iterate over the options.
+    #[inline]
+    pub fn options(&self) -> GeneveOptions<'_> {
+        GeneveOptions::new(self.option_bytes())
+    }
+}
+
+/// A GENEVE option.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GeneveOption<'a> {
+    /// Namespace of the option type.
+    pub class: u16,
+    /// Type, its high bit marks critical options that receivers must understand.
+    pub option_type: u8,
+    /// Option data, a multiple of 4 bytes.
+    pub data: &'a [u8],
+}
+
+impl<'a> GeneveOption<'a> {
+    /// Returns true if receivers that do not understand the option must drop the packet.
+    #[inline]
+    pub fn is_critical(&self) -> bool {
+        self.option_type & 0x80 != 0
+    }
+
+    /// Bytes of the option on the wire, its data padded to a multiple of 4 bytes.
+    #[inline]
+    pub fn encoded_len(&self) -> usize {
+        4 + ((self.data.len() + 3) & !3)
+    }
+
+    /// Append the option to `bytes`, padding its data with zeros to a multiple of 4 bytes.
+    ///
+    /// # Panics
+    ///
+    /// If the data is longer than the 124 bytes an option can carry.
+    pub fn write(&self, bytes: &mut Vec<u8>) {
+        assert!(self.data.len() <= MAX_OPTION_DATA, "GENEVE option data too long");
+        let words = (self.encoded_len() - 4) / 4;
+        bytes.extend_from_slice(&self.class.to_be_bytes());
+        bytes.push(self.option_type);
+        bytes.push(words as u8);
+        bytes.extend_from_slice(self.data);
+        bytes.resize(bytes.len() + words * 4 - self.data.len(), 0);
+    }
+}
+
+/// Iterator over the options of a GENEVE header, see `GeneveHeader::options`. Iteration stops at the first option
+/// running past the end of the options.
+#[derive(Clone, Debug)]
+pub struct GeneveOptions<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> GeneveOptions<'a> {
+    /// Iterate over the options in `bytes`, i.e., the part of a GENEVE header after its fixed 8 bytes.
+    pub fn new(bytes: &'a [u8]) -> GeneveOptions<'a> {
+        GeneveOptions { bytes }
+    }
+}
+
+impl<'a> Iterator for GeneveOptions<'a> {
+    type Item = GeneveOption<'a>;
+
+    fn next(&mut self) -> Option<GeneveOption<'a>> {
+        if self.bytes.len() < 4 {
+            self.bytes = &[];
+            return None;
+        }
+        let len = 4 + (self.bytes[3] & 0x1f) as usize * 4;
+        if len > self.bytes.len() {
+            self.bytes = &[];
+            return None;
+        }
+        let option = GeneveOption {
+            class: BigEndian::read_u16(&self.bytes[..2]),
+            option_type: self.bytes[2],
+            data: &self.bytes[4..len],
+        };
+        self.bytes = &self.bytes[len..];
+        Some(option)
+    }
+}
+
+impl<'a> FusedIterator for GeneveOptions<'a> {}
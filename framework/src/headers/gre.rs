@@ -0,0 +1,225 @@
+use super::{EndOffset, Follows};
+use crate::headers::IpHeader;
+use byteorder::{BigEndian, ByteOrder};
+use std::default::Default;
+use std::fmt;
+use std::slice;
+
+/// IP protocol number of GRE.
+pub const IPPROTO_GRE: u8 = 47;
+
+const HDR_SIZE: usize = 4;
+const FLAG_CHECKSUM: u16 = 0x8000;
+/// Routing present, RFC 1701 only, RFC 2784 receivers drop such packets.
+const FLAG_ROUTING: u16 = 0x4000;
+const FLAG_KEY: u16 = 0x2000;
+const FLAG_SEQUENCE: u16 = 0x1000;
+const VERSION_MASK: u16 = 0x0007;
+
+/// A GRE header (RFC 2784), with the key and sequence number extensions (RFC 2890). The optional checksum, key and
+/// sequence number follow the fixed 4 bytes, in that order, each present if its flag is set.
+#[derive(Default)]
+#[repr(C, packed)]
+pub struct GreHeader {
+    flags_version: u16,
+    protocol: u16,
+}
+
+impl fmt::Display for GreHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "gre proto: 0x{:04x} version: {}", self.protocol(), self.version())?;
+        if let Some(key) = self.key() {
+            write!(f, " key: {}", key)?;
+        }
+        if let Some(seq) = self.sequence() {
+            write!(f, " seq: {}", seq)?;
+        }
+        Ok(())
+    }
+}
+
+impl EndOffset for GreHeader {
+    type PreviousHeader = IpHeader;
+
+    #[inline]
+    fn offset(&self) -> usize {
+        HDR_SIZE
+            + 4 * (self.checksum_present() as usize + self.key_present() as usize + self.sequence_present() as usize)
+    }
+
+    #[inline]
+    fn size() -> usize {
+        HDR_SIZE
+    }
+
+    #[inline]
+    fn payload_size(&self, hint: usize) -> usize {
+        hint - self.offset()
+    }
+
+    #[inline]
+    fn check_correct(&self, _: &IpHeader) -> bool {
+        self.version() == 0 && self.flags() & FLAG_ROUTING == 0
+    }
+}
+
+impl Follows<GreHeader> for IpHeader {
+    #[inline]
+    fn check_correct_after(&self, _prev: &GreHeader, _available: usize) -> bool {
+        self.check_valid()
+    }
+}
+
+impl GreHeader {
+    /// Initialize a GRE header carrying IPv4, without checksum, key or sequence number.
+    #[inline]
+    pub fn new() -> GreHeader {
+        let mut header: GreHeader = Default::default();
+        header.set_protocol(0x0800);
+        header
+    }
+
+    #[inline]
+    fn flags(&self) -> u16 {
+        u16::from_be(self.flags_version)
+    }
+
+    #[inline]
+    fn set_flag(&mut self, flag: u16, set: bool) {
+        let flags = if set { self.flags() | flag } else { self.flags() & !flag };
+        self.flags_version = u16::to_be(flags)
+    }
+
+    /// Get the version, 0 is the only one defined for GRE.
+    #[inline]
+    pub fn version(&self) -> u8 {
+        (self.flags() & VERSION_MASK) as u8
+    }
+
+    /// Returns true if a checksum follows the header.
+    #[inline]
+    pub fn checksum_present(&self) -> bool {
+        self.flags() & FLAG_CHECKSUM != 0
+    }
+
+    /// Returns true if a key follows the header.
+    #[inline]
+    pub fn key_present(&self) -> bool {
+        self.flags() & FLAG_KEY != 0
+    }
+
+    /// Returns true if a sequence number follows the header.
+    #[inline]
+    pub fn sequence_present(&self) -> bool {
+        self.flags() & FLAG_SEQUENCE != 0
+    }
+
+    /// Set whether a checksum follows the header. This changes the header length, the bytes must be there.
+    #[inline]
+    pub fn set_checksum_present(&mut self, present: bool) {
+        self.set_flag(FLAG_CHECKSUM, present)
+    }
+
+    /// Set whether a key follows the header. This changes the header length, the bytes must be there.
+    #[inline]
+    pub fn set_key_present(&mut self, present: bool) {
+        self.set_flag(FLAG_KEY, present)
+    }
+
+    /// Set whether a sequence number follows the header. This changes the header length, the bytes must be there.
+    #[inline]
+    pub fn set_sequence_present(&mut self, present: bool) {
+        self.set_flag(FLAG_SEQUENCE, present)
+    }
+
+    /// Get the EtherType of the payload.
+    #[inline]
+    pub fn protocol(&self) -> u16 {
+        u16::from_be(self.protocol)
+    }
+
+    /// Set the EtherType of the payload.
+    #[inline]
+    pub fn set_protocol(&mut self, protocol: u16) {
+        self.protocol = u16::to_be(protocol)
+    }
+
+    /// The bytes following the fixed 4 bytes of the header, up to its end.
+    #[inline]
+    fn fields(&self) -> &[u8] {
+        unsafe {
+            let fields = (self as *const GreHeader as *const u8).add(HDR_SIZE);
+            slice::from_raw_parts(fields, self.offset() - HDR_SIZE)
+        }
+    }
+
+    #[inline]
+    fn fields_mut(&mut self) -> &mut [u8] {
+        unsafe {
+            let fields = (self as *mut GreHeader as *mut u8).add(HDR_SIZE);
+            slice::from_raw_parts_mut(fields, self.offset() - HDR_SIZE)
+        }
+    }
+
+    /// Offset of the key among the fields.
+    #[inline]
+    fn key_field(&self) -> usize {
+        4 * self.checksum_present() as usize
+    }
+
+    /// Offset of the sequence number among the fields.
+    #[inline]
+    fn sequence_field(&self) -> usize {
+        self.key_field() + 4 * self.key_present() as usize
+    }
+
+    /// Get the checksum, if present.
+    #[inline]
+    pub fn checksum(&self) -> Option<u16> {
+        if self.checksum_present() {
+            Some(BigEndian::read_u16(&self.fields()[..2]))
+        } else {
+            None
+        }
+    }
+
+    /// Get the key, if present.
+    #[inline]
+    pub fn key(&self) -> Option<u32> {
+        if self.key_present() {
+            let at = self.key_field();
+            Some(BigEndian::read_u32(&self.fields()[at..at + 4]))
+        } else {
+            None
+        }
+    }
+
+    /// Set the key, if present.
+    #[inline]
+    pub fn set_key(&mut self, key: u32) {
+        if self.key_present() {
+            let at = self.key_field();
+            BigEndian::write_u32(&mut self.fields_mut()[at..at + 4], key)
+        }
+    }
+
+    /// Get the sequence number, if present.
+    #[inline]
+    pub fn sequence(&self) -> Option<u32> {
+        if self.sequence_present() {
+            let at = self.sequence_field();
+            Some(BigEndian::read_u32(&self.fields()[at..at + 4]))
+        } else {
+            None
+        }
+    }
+
+    /// Set the sequence number, if present.
+    #[inline]
+    pub fn set_sequence(&mut self, seq: u32) {
+        if self.sequence_present() {
+            let at = self.sequence_field();
+            BigEndian::write_u32(&mut self.fields_mut()[at..at + 4], seq)
+        }
+    }
+}
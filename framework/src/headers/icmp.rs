@@ -0,0 +1,316 @@
+use super::{EndOffset, Follows};
+use crate::headers::{IpHeader, Ipv6Header};
+use crate::interface::Packet;
+use crate::utils::{checksum, checksum_adjust};
+use byteorder::{BigEndian, ByteOrder};
+use std::cmp::min;
+use std::default::Default;
+use std::fmt;
+use std::mem::size_of;
+use std::slice;
+
+/// ICMP echo reply.
+pub const ICMP_ECHO_REPLY: u8 = 0;
+/// ICMP destination unreachable.
+pub const ICMP_DEST_UNREACHABLE: u8 = 3;
+/// ICMP redirect.
+pub const ICMP_REDIRECT: u8 = 5;
+/// ICMP echo request.
+pub const ICMP_ECHO_REQUEST: u8 = 8;
+/// ICMP time exceeded.
+pub const ICMP_TIME_EXCEEDED: u8 = 11;
+/// ICMP parameter problem.
+pub const ICMP_PARAMETER_PROBLEM: u8 = 12;
+
+/// ICMPv6 destination unreachable.
+pub const ICMPV6_DEST_UNREACHABLE: u8 = 1;
+/// ICMPv6 packet too big.
+pub const ICMPV6_PACKET_TOO_BIG: u8 = 2;
+/// ICMPv6 time exceeded.
+pub const ICMPV6_TIME_EXCEEDED: u8 = 3;
+/// ICMPv6 parameter problem.
+pub const ICMPV6_PARAMETER_PROBLEM: u8 = 4;
+/// ICMPv6 echo request.
+pub const ICMPV6_ECHO_REQUEST: u8 = 128;
+/// ICMPv6 echo reply.
+pub const ICMPV6_ECHO_REPLY: u8 = 129;
+
+const HDR_SIZE: usize = 8;
+/// ICMP errors quote the offending IP header and this many bytes of its payload (RFC 792).
+const QUOTED_PAYLOAD: usize = 8;
+/// Largest IPv4 header.
+const MAX_IP_HDR_SIZE: usize = 60;
+/// TTL used for generated messages.
+const DEFAULT_TTL: u8 = 64;
+
+/// ICMP header, also used for ICMPv6 which shares the same layout.
+#[derive(Default)]
+#[repr(C, packed)]
+pub struct IcmpHeader {
+    msg_type: u8,
+    code: u8,
+    csum: u16,
+    rest: u32,
+}
+
+impl fmt::Display for IcmpHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "icmp type: {} code: {} csum: {} rest: 0x{:08x}",
+            self.msg_type(),
+            self.code(),
+            self.checksum(),
+            self.rest_of_header()
+        )
+    }
+}
+
+impl EndOffset for IcmpHeader {
+    type PreviousHeader = IpHeader;
+
+    #[inline]
+    fn offset(&self) -> usize {
+        HDR_SIZE
+    }
+
+    #[inline]
+    fn size() -> usize {
+        HDR_SIZE
+    }
+
+    #[inline]
+    fn payload_size(&self, hint: usize) -> usize {
+        hint - self.offset()
+    }
+
+    #[inline]
+    fn check_correct(&self, prev: &IpHeader) -> bool {
+        prev.protocol() == 1
+    }
+}
+
+impl Follows<Ipv6Header> for IcmpHeader {
+    /// The upper layer protocol of `prev`, past any extension headers, must be ICMPv6.
+    #[inline]
+    fn check_correct_after(&self, prev: &Ipv6Header, available: usize) -> bool {
+        if cfg!(feature = "performance") {
+            // Extension headers are not skipped, see `Ipv6Header::offset_within`.
+            return prev.next_header() == 58;
+        }
+        let offset = self as *const IcmpHeader as usize - prev as *const Ipv6Header as usize;
+        prev.upper_layer(offset + available) == Some((58, offset))
+    }
+}
+
+impl IcmpHeader {
+    /// Initialize a default ICMP header.
+    #[inline]
+    pub fn new() -> IcmpHeader {
+        Default::default()
+    }
+
+    /// Get the message type.
+    #[inline]
+    pub fn msg_type(&self) -> u8 {
+        self.msg_type
+    }
+
+    /// Set the message type.
+    #[inline]
+    pub fn set_msg_type(&mut self, msg_type: u8) {
+        self.msg_type = msg_type
+    }
+
+    /// Get the code.
+    #[inline]
+    pub fn code(&self) -> u8 {
+        self.code
+    }
+
+    /// Set the code.
+    #[inline]
+    pub fn set_code(&mut self, code: u8) {
+        self.code = code
+    }
+
+    /// Get the checksum.
+    #[inline]
+    pub fn checksum(&self) -> u16 {
+        u16::from_be(self.csum)
+    }
+
+    /// Set the checksum.
+    #[inline]
+    pub fn set_checksum(&mut self, csum: u16) {
+        self.csum = u16::to_be(csum)
+    }
+
+    /// Get the type specific second word of the header, e.g., the next-hop MTU of a packet too big message.
+    #[inline]
+    pub fn rest_of_header(&self) -> u32 {
+        u32::from_be(self.rest)
+    }
+
+    /// Set the type specific second word of the header.
+    #[inline]
+    pub fn set_rest_of_header(&mut self, rest: u32) {
+        self.rest = u32::to_be(rest)
+    }
+
+    /// Get the identifier of an echo request or reply.
+    #[inline]
+    pub fn identifier(&self) -> u16 {
+        (self.rest_of_header() >> 16) as u16
+    }
+
+    /// Set the identifier of an echo request or reply.
+    #[inline]
+    pub fn set_identifier(&mut self, id: u16) {
+        let rest = (self.rest_of_header() & 0xffff) | ((id as u32) << 16);
+        self.set_rest_of_header(rest)
+    }
+
+    /// Get the sequence number of an echo request or reply.
+    #[inline]
+    pub fn sequence(&self) -> u16 {
+        (self.rest_of_header() & 0xffff) as u16
+    }
+
+    /// Set the sequence number of an echo request or reply.
+    #[inline]
+    pub fn set_sequence(&mut self, seq: u16) {
+        let rest = (self.rest_of_header() & !0xffff) | seq as u32;
+        self.set_rest_of_header(rest)
+    }
+
+    /// Returns true for error messages (destination unreachable, time exceeded, etc.), which must never trigger another
+    /// ICMP error.
+    #[inline]
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self.msg_type,
+            ICMP_DEST_UNREACHABLE | ICMP_REDIRECT | ICMP_TIME_EXCEEDED | ICMP_PARAMETER_PROBLEM
+        )
+    }
+
+    /// Change the type, adjusting the checksum incrementally (RFC 1624) so the rest of the message need not be read.
+    #[inline]
+    pub fn replace_msg_type(&mut self, msg_type: u8) {
+        let old = (self.msg_type as u16) << 8 | self.code as u16;
+        let new = (msg_type as u16) << 8 | self.code as u16;
+        let csum = checksum_adjust(self.checksum(), old, new);
+        self.set_checksum(csum);
+        self.msg_type = msg_type;
+    }
+}
+
+impl<M: Sized + Send> Packet<IpHeader, M> {
+    /// If this packet is an ICMP echo request, turn it into the matching echo reply in place by swapping the addresses
+    /// and changing the ICMP type. Returns false, leaving the packet untouched, for anything else.
+    pub fn write_echo_reply(&mut self) -> bool {
+        if self.get_header().protocol() != 1
+            || self.payload_size() < HDR_SIZE
+            || self.get_payload()[0] != ICMP_ECHO_REQUEST
+        {
+            return false;
+        }
+        {
+            let ip = self.get_mut_header();
+            let (src, dst) = (ip.src(), ip.dst());
+            ip.set_src(dst);
+            ip.set_dst(src);
+            ip.set_ttl(DEFAULT_TTL);
+            ip.update_checksum();
+        }
+        let icmp = self.get_mut_payload().as_mut_ptr() as *mut IcmpHeader;
+        unsafe { (*icmp).replace_msg_type(ICMP_ECHO_REPLY) };
+        true
+    }
+
+    /// Rewrite this packet in place into an ICMP error (e.g., time exceeded) sent from `src` back to the original sender.
+    /// The message quotes the original IP header and the first 8 bytes of its payload; anything beyond is trimmed.
+    /// Returns false, leaving the packet untouched, for non-initial fragments, for ICMP errors (which must not trigger
+    /// further errors, RFC 1812) and when the mbuf has no room for the message.
+    pub fn write_icmp_error(&mut self, src: u32, msg_type: u8, code: u8) -> bool {
+        let (ihl_bytes, orig_src) = {
+            let ip = self.get_header();
+            if ip.fragment_offset() != 0 {
+                return false;
+            }
+            (ip.ihl() as usize * 4, ip.src())
+        };
+        let payload_size = self.payload_size();
+        if self.get_header().protocol() == 1 && payload_size >= HDR_SIZE {
+            let icmp = self.get_payload().as_ptr() as *const IcmpHeader;
+            if unsafe { (*icmp).is_error() } {
+                return false;
+            }
+        }
+
+        let quoted_len = ihl_bytes + min(payload_size, QUOTED_PAYLOAD);
+        let mut quoted = [0u8; MAX_IP_HDR_SIZE + QUOTED_PAYLOAD];
+        {
+            let hdr = self.get_header() as *const IpHeader as *const u8;
+            let bytes = unsafe { slice::from_raw_parts(hdr, quoted_len) };
+            quoted[..quoted_len].copy_from_slice(bytes);
+        }
+
+        let current = ihl_bytes + payload_size;
+        let needed = size_of::<IpHeader>() + HDR_SIZE + quoted_len;
+        let resized = if needed > current {
+            self.add_to_payload_tail(needed - current)
+        } else {
+            self.remove_from_payload_tail(current - needed)
+        };
+        if resized.is_err() {
+            return false;
+        }
+
+        let mut ip = IpHeader::new();
+        ip.set_version(4);
+        ip.set_ihl(5);
+        ip.set_length(needed as u16);
+        ip.set_ttl(DEFAULT_TTL);
+        ip.set_protocol(1);
+        ip.set_src(src);
+        ip.set_dst(orig_src);
+        ip.update_checksum();
+        self.replace_header(&ip);
+
+        let message = self.get_mut_payload();
+        message[0] = msg_type;
+        message[1] = code;
+        for b in &mut message[2..HDR_SIZE] {
+            *b = 0;
+        }
+        message[HDR_SIZE..HDR_SIZE + quoted_len].copy_from_slice(&quoted[..quoted_len]);
+        let csum = checksum(&message[..HDR_SIZE + quoted_len]);
+        BigEndian::write_u16(&mut message[2..4], csum);
+        true
+    }
+}
+
+impl<M: Sized + Send> Packet<Ipv6Header, M> {
+    /// If this packet is an ICMPv6 echo request, turn it into the matching echo reply in place by swapping the
+    /// addresses and changing the ICMPv6 type. Returns false, leaving the packet untouched, for anything else.
+    pub fn write_echo_reply(&mut self) -> bool {
+        if self.get_header().protocol(self.header_available()) != Some(58)
+            || self.payload_size() < HDR_SIZE
+            || self.get_payload()[0] != ICMPV6_ECHO_REQUEST
+        {
+            return false;
+        }
+        {
+            // The pseudo-header sum does not change when the addresses are swapped.
+            let ip = self.get_mut_header();
+            let (src, dst) = (ip.src(), ip.dst());
+            ip.set_src(dst);
+            ip.set_dst(src);
+            ip.set_hop_limit(DEFAULT_TTL);
+        }
+        let icmp = self.get_mut_payload().as_mut_ptr() as *mut IcmpHeader;
+        unsafe { (*icmp).replace_msg_type(ICMPV6_ECHO_REPLY) };
+        true
+    }
+}
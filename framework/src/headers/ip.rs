@@ -1,13 +1,27 @@
-use super::EndOffset;
+use super::{EndOffset, PseudoHeader};
+use crate::common::*;
 use crate::headers::MacHeader;
-use crate::utils::Flow;
+use crate::interface::{new_packet, packet_from_mbuf_no_increment, Packet};
+use crate::utils::{
+    checksum, checksum_adjust, ipv4_pseudo_header_sum, ones_complement_add, ones_complement_fold, ChecksumMode, Flow,
+};
 use byteorder::{BigEndian, ByteOrder};
+use std::cmp::min;
 use std::convert::From;
 use std::default::Default;
 use std::fmt;
 use std::net::Ipv4Addr;
 use std::slice;
 
+/// EtherType announcing an IPv4 packet.
+pub(crate) const ETHERTYPE_IPV4: u16 = 0x0800;
+/// More fragments flag, in `IpHeader::flags`.
+pub(crate) const MORE_FRAGMENTS: u8 = 0x1;
+/// Don't fragment flag, in `IpHeader::flags`.
+const DONT_FRAGMENT: u8 = 0x2;
+/// Largest header, options included.
+const MAX_HEADER_SIZE: usize = 60;
+
 /// IP header using SSE
 #[derive(Default)]
 #[repr(C, packed)]
@@ -60,20 +74,62 @@ impl EndOffset for IpHeader {
         (self.length() as usize) - self.offset()
     }
 
+    /// The Ethernet header must announce IPv4, a VLAN tagged frame does not. With the `verify_checksum` feature, also
+    /// verifies the header checksum.
+    #[inline]
+    fn check_correct(&self, prev: &MacHeader) -> bool {
+        prev.etype() == ETHERTYPE_IPV4 && self.check_valid()
+    }
+}
+
+impl PseudoHeader for IpHeader {
+    #[inline]
+    fn ip_version(&self) -> u8 {
+        4
+    }
+
+    #[inline]
+    fn upper_layer_protocol(&self, _available: usize) -> Option<u8> {
+        Some(self.protocol())
+    }
+
+    #[inline]
+    fn upper_layer_offset(&self, _available: usize) -> usize {
+        self.ihl() as usize * 4
+    }
+
+    #[inline]
+    fn upper_layer_len(&self, _available: usize) -> usize {
+        (self.length() as usize).saturating_sub(self.ihl() as usize * 4)
+    }
+
     #[inline]
-    fn check_correct(&self, _prev: &MacHeader) -> bool {
-        true
+    fn pseudo_header_sum(&self, protocol: u8, len: usize) -> u32 {
+        ipv4_pseudo_header_sum(self.src(), self.dst(), protocol, len as u16)
+    }
+
+    #[inline]
+    fn update_header_checksum(&mut self, mode: ChecksumMode) {
+        match mode {
+            ChecksumMode::Software => self.update_checksum(),
+            ChecksumMode::Offload => self.set_csum(0),
+        }
+    }
+
+    #[inline]
+    fn header_checksum_valid(&self) -> bool {
+        self.checksum_valid()
     }
 }
 
 impl IpHeader {
-    /// Flow of the IP header.
+    /// Flow of the IP header. None for non-first fragments, which carry no ports.
     #[inline]
     pub fn flow(&self) -> Option<Flow> {
         let protocol = self.protocol();
         let src_ip = self.src();
         let dst_ip = self.dst();
-        if (protocol == 6 || protocol == 17) && self.payload_size(0) >= 4 {
+        if (protocol == 6 || protocol == 17) && self.fragment_offset() == 0 && self.payload_size(0) >= 4 {
             unsafe {
                 let self_as_u8 = (self as *const IpHeader) as *const u8;
                 let port_as_u8 = self_as_u8.add(self.offset());
@@ -157,7 +213,7 @@ impl IpHeader {
     #[inline]
     pub fn csum(&self) -> u16 {
         let ttlpcsum = self.ttl_to_csum;
-        ((ttlpcsum & 0xffff_0000) >> 16) as u16
+        u16::from_be(((ttlpcsum & 0xffff_0000) >> 16) as u16)
     }
 
     /// Set the checksum.
@@ -168,6 +224,53 @@ impl IpHeader {
         self.ttl_to_csum = blanked | ((u16::to_be(csum) as u32) << 16);
     }
 
+    #[inline]
+    pub(crate) fn header_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts((self as *const IpHeader) as *const u8, self.ihl() as usize * 4) }
+    }
+
+    /// Compute the header checksum, options included, without modifying the header.
+    #[inline]
+    pub fn compute_checksum(&self) -> u16 {
+        let sum = ones_complement_add(0, self.header_bytes());
+        !ones_complement_fold(sum + (!self.csum()) as u32)
+    }
+
+    /// Recompute and store the header checksum.
+    #[inline]
+    pub fn update_checksum(&mut self) {
+        let csum = self.compute_checksum();
+        self.set_csum(csum);
+    }
+
+    /// The checks of `check_correct`, which do not depend on the header before this one: with the `verify_checksum`
+    /// feature, the header checksum.
+    #[inline]
+    pub(crate) fn check_valid(&self) -> bool {
+        !cfg!(feature = "verify_checksum") || self.checksum_valid()
+    }
+
+    /// Returns true if the header checksum is correct.
+    #[inline]
+    pub fn checksum_valid(&self) -> bool {
+        checksum(self.header_bytes()) == 0
+    }
+
+    /// Decrement the TTL, adjusting the checksum incrementally (RFC 1624). Returns the new TTL; a TTL of 0 is left
+    /// alone.
+    #[inline]
+    pub fn decrement_ttl(&mut self) -> u8 {
+        let ttl = self.ttl();
+        if ttl == 0 {
+            return 0;
+        }
+        let proto = self.protocol() as u16;
+        let csum = checksum_adjust(self.csum(), (ttl as u16) << 8 | proto, ((ttl - 1) as u16) << 8 | proto);
+        self.set_ttl(ttl - 1);
+        self.set_csum(csum);
+        ttl - 1
+    }
+
     /// Get the ID flag.
     #[inline]
     pub fn id(&self) -> u16 {
@@ -200,9 +303,8 @@ impl IpHeader {
     /// Get the fragment offset.
     #[inline]
     pub fn fragment_offset(&self) -> u16 {
-        let id_flag_fragment = self.id_to_foffset;
-        let flag_fragment = (id_flag_fragment & 0xffff) as u16;
-        u16::from_be((flag_fragment & !0xe) >> 3)
+        let id_flag_fragment = u32::from_be(self.id_to_foffset);
+        (id_flag_fragment & 0x1fff) as u16
     }
 
     /// Set the fragment offset.
@@ -273,3 +375,138 @@ impl IpHeader {
         self.version_to_len = (self.version_to_len & !0xffff_0000) | ((u16::to_be(len) as u32) << 16);
     }
 }
+
+/// The options to repeat in every fragment but the first: those with the copied flag set (RFC 791), padded to a
+/// multiple of 4 bytes.
+fn copied_options(options: &[u8]) -> Vec<u8> {
+    let mut copied = Vec::with_capacity(options.len());
+    let mut at = 0;
+    while at < options.len() {
+        match options[at] {
+            // End of option list.
+            0 => break,
+            // No operation.
+            1 => at += 1,
+            kind => {
+                let len = match options.get(at + 1) {
+                    Some(&len) if len >= 2 && at + len as usize <= options.len() => len as usize,
+                    _ => break,
+                };
+                if kind & 0x80 != 0 {
+                    copied.extend_from_slice(&options[at..at + len]);
+                }
+                at += len;
+            }
+        }
+    }
+    while copied.len() & 3 != 0 {
+        copied.push(0);
+    }
+    copied
+}
+
+fn free_packets<M: Sized + Send>(packets: Vec<Packet<IpHeader, M>>) {
+    for packet in packets {
+        packet.free_packet();
+    }
+}
+
+impl<M: Sized + Send> Packet<IpHeader, M> {
+    /// Split this packet into fragments of at most `mtu` bytes of IP packet (RFC 791). This packet is trimmed into the
+    /// first fragment, the others are returned in order, in new packets with the same bytes before the IP header, the
+    /// same metadata and the same timestamp. Packets that fit are left alone and nothing is returned. Fails, leaving the
+    /// packet untouched, for malformed headers, when the don't fragment flag is set, when `mtu` is smaller than the
+    /// header and 8 bytes of data, and when packets cannot be allocated. A chained packet (e.g., a reassembled datagram) is
+    /// fragmented whole, its first fragment stays in its own mbuf and the chained mbufs are freed.
+    pub fn fragment(&mut self, mtu: usize) -> Result<Vec<Packet<IpHeader, M>>> {
+        let chained: Vec<u8>;
+        let payload = if self.is_chained() {
+            chained = self.payload_segments().flatten().copied().collect();
+            &chained[..]
+        } else {
+            self.get_payload()
+        };
+        let ip = self.get_header();
+        let ihl = ip.ihl() as usize * 4;
+        // Bytes past the IP length are link layer padding.
+        let total = min(ip.length() as usize, self.payload_offset() + payload.len());
+        if total <= mtu {
+            return Ok(Vec::new());
+        }
+        if ihl < 20 || total <= ihl {
+            return Err(ErrorKind::BadOffset(ihl).into());
+        }
+        let flags = ip.flags();
+        if flags & DONT_FRAGMENT != 0 {
+            return Err(ErrorKind::DontFragment(total, mtu).into());
+        }
+        // All fragments but the last carry a multiple of 8 bytes, so each needs room for at least 8.
+        if mtu < ihl + 8 {
+            return Err(ErrorKind::BadMtu(mtu).into());
+        }
+        // The first fragment is what is left of this packet, so it cannot be longer than its mbuf holds.
+        let current = self.payload_offset() + self.payload_size();
+        let first_len = min(mtu - ihl, current.saturating_sub(ihl)) & !7;
+        if first_len == 0 {
+            return Err(ErrorKind::BadOffset(current).into());
+        }
+        let mut header = [0u8; MAX_HEADER_SIZE];
+        header[..ihl].copy_from_slice(ip.header_bytes());
+        let options = copied_options(&header[20..ihl]);
+        let later_ihl = 20 + options.len();
+        let later_len = (mtu - later_ihl) & !7;
+        let offset = ip.fragment_offset() as usize * 8;
+
+        let prefix = self.offset();
+        let link = unsafe { slice::from_raw_parts((ip as *const IpHeader as *const u8).sub(prefix), prefix) };
+        let data = &payload[..total - ihl];
+        let mut fragments = Vec::with_capacity(1 + (data.len() - first_len) / later_len);
+        let mut start = first_len;
+        while start < data.len() {
+            let len = min(later_len, data.len() - start);
+            let mut packet = match new_packet() {
+                Some(packet) => packet,
+                None => {
+                    free_packets(fragments);
+                    return Err(ErrorKind::FailedAllocation.into());
+                }
+            };
+            if packet.add_to_payload_tail(prefix + later_ihl + len).is_err() {
+                packet.free_packet();
+                free_packets(fragments);
+                return Err(ErrorKind::FailedAllocation.into());
+            }
+            {
+                let bytes = packet.get_mut_payload();
+                bytes[..prefix].copy_from_slice(link);
+                bytes[prefix..prefix + 20].copy_from_slice(&header[..20]);
+                bytes[prefix + 20..prefix + later_ihl].copy_from_slice(&options);
+                bytes[prefix + later_ihl..].copy_from_slice(&data[start..start + len]);
+            }
+            let mut fragment = unsafe { packet_from_mbuf_no_increment::<IpHeader>(packet.get_mbuf(), prefix) }
+                .reinterpret_metadata::<M>();
+            {
+                let ip = fragment.get_mut_header();
+                let more = start + len < data.len() || flags & MORE_FRAGMENTS != 0;
+                ip.set_ihl((later_ihl / 4) as u8);
+                ip.set_length((later_ihl + len) as u16);
+                ip.set_flags(if more { flags | MORE_FRAGMENTS } else { flags });
+                ip.set_fragment_offset(((offset + start) / 8) as u16);
+                ip.update_checksum();
+            }
+            // Cannot fail, this packet's metadata has the same type.
+            let _ = fragment.write_metadata(self.read_metadata());
+            fragment.write_timestamp(self.read_timestamp());
+            fragments.push(fragment);
+            start += len;
+        }
+
+        self.free_chain();
+        self.remove_from_payload_tail(current - ihl - first_len)?;
+        let ip = self.get_mut_header();
+        ip.set_length((ihl + first_len) as u16);
+        ip.set_flags(flags | MORE_FRAGMENTS);
+        ip.update_checksum();
+        Ok(fragments)
+    }
+}
@@ -0,0 +1,330 @@
+use super::{EndOffset, PseudoHeader};
+use crate::headers::MacHeader;
+use crate::utils::{ipv6_pseudo_header_sum, Ipv6Flow};
+use byteorder::{BigEndian, ByteOrder};
+use std::default::Default;
+use std::fmt;
+use std::net::Ipv6Addr;
+use std::slice;
+
+/// EtherType announcing an IPv6 packet.
+pub(crate) const ETHERTYPE_IPV6: u16 = 0x86dd;
+/// Size of the fixed IPv6 header.
+const HDR_SIZE: usize = 40;
+
+/// Hop-by-hop options extension header.
+pub const IPV6_HOP_BY_HOP: u8 = 0;
+/// Routing extension header.
+pub const IPV6_ROUTING: u8 = 43;
+/// Fragment extension header.
+pub const IPV6_FRAGMENT: u8 = 44;
+/// Authentication header.
+pub const IPV6_AUTH: u8 = 51;
+/// No next header.
+pub const IPV6_NO_NEXT_HEADER: u8 = 59;
+/// Destination options extension header.
+pub const IPV6_DEST_OPTS: u8 = 60;
+
+/// Walk the extension headers of the IPv6 packet in `bytes`, which must start at the fixed IPv6 header. Returns the
+/// upper layer protocol and its offset from the start of `bytes`, or `None` if the packet is truncated or carries no
+/// upper layer header. For fragments other than the first, the fragment header is returned since the upper layer
+/// header is not present.
+#[inline]
+pub fn ipv6_upper_layer(bytes: &[u8]) -> Option<(u8, usize)> {
+    ipv6_walk(bytes).map(|(proto, offset, _)| (proto, offset))
+}
+
+/// Like `ipv6_upper_layer`, also returning whether a fragment header was found on the way.
+#[inline]
+pub(crate) fn ipv6_walk(bytes: &[u8]) -> Option<(u8, usize, bool)> {
+    if bytes.len() < HDR_SIZE {
+        return None;
+    }
+    let mut next = bytes[6];
+    let mut offset = HDR_SIZE;
+    let mut fragmented = false;
+    loop {
+        match next {
+            IPV6_HOP_BY_HOP | IPV6_ROUTING | IPV6_DEST_OPTS => {
+                if bytes.len() < offset + 2 {
+                    return None;
+                }
+                next = bytes[offset];
+                offset += (bytes[offset + 1] as usize + 1) * 8;
+            }
+            IPV6_AUTH => {
+                if bytes.len() < offset + 2 {
+                    return None;
+                }
+                next = bytes[offset];
+                offset += (bytes[offset + 1] as usize + 2) * 4;
+            }
+            IPV6_FRAGMENT => {
+                if bytes.len() < offset + 8 {
+                    return None;
+                }
+                if BigEndian::read_u16(&bytes[offset + 2..offset + 4]) >> 3 != 0 {
+                    return Some((IPV6_FRAGMENT, offset, true));
+                }
+                fragmented = true;
+                next = bytes[offset];
+                offset += 8;
+            }
+            IPV6_NO_NEXT_HEADER => return None,
+            proto => {
+                return if offset <= bytes.len() {
+                    Some((proto, offset, fragmented))
+                } else {
+                    None
+                };
+            }
+        }
+    }
+}
+
+/// IPv6 header.
+#[derive(Default)]
+#[repr(C, packed)]
+pub struct Ipv6Header {
+    version_to_flow: u32,
+    payload_len: u16,
+    next_header: u8,
+    hop_limit: u8,
+    src_ip: u128,
+    dst_ip: u128,
+}
+
+impl fmt::Display for Ipv6Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let src = Ipv6Addr::from(self.src());
+        let dst = Ipv6Addr::from(self.dst());
+        write!(
+            f,
+            "{} > {} version: {} tc: {} flow label: {} len: {} next header: {} hop limit: {}",
+            src,
+            dst,
+            self.version(),
+            self.traffic_class(),
+            self.flow_label(),
+            self.payload_len(),
+            self.next_header(),
+            self.hop_limit()
+        )
+    }
+}
+
+impl EndOffset for Ipv6Header {
+    type PreviousHeader = MacHeader;
+
+    /// Only the fixed header is known to be there, see `offset_within`.
+    #[inline]
+    fn offset(&self) -> usize {
+        HDR_SIZE
+    }
+
+    /// The offset includes any extension headers within `available`, so the next header parsed is the upper layer
+    /// header.
+    #[inline]
+    fn offset_within(&self, available: usize) -> usize {
+        if cfg!(feature = "performance") {
+            HDR_SIZE
+        } else {
+            self.upper_layer_offset(available)
+        }
+    }
+
+    #[inline]
+    fn size() -> usize {
+        HDR_SIZE
+    }
+
+    /// Extension headers within the `hint` bytes of packet are not payload, as `offset_within` skips them.
+    #[inline]
+    fn payload_size(&self, hint: usize) -> usize {
+        (self.payload_len() as usize + HDR_SIZE).saturating_sub(self.offset_within(hint))
+    }
+
+    #[inline]
+    fn check_correct(&self, prev: &MacHeader) -> bool {
+        prev.etype() == ETHERTYPE_IPV6 && self.version() == 6
+    }
+}
+
+impl PseudoHeader for Ipv6Header {
+    #[inline]
+    fn ip_version(&self) -> u8 {
+        6
+    }
+
+    #[inline]
+    fn upper_layer_protocol(&self, available: usize) -> Option<u8> {
+        self.protocol(available)
+    }
+
+    #[inline]
+    fn upper_layer_offset(&self, available: usize) -> usize {
+        self.upper_layer(available).map_or(HDR_SIZE, |(_, offset)| offset)
+    }
+
+    #[inline]
+    fn upper_layer_len(&self, available: usize) -> usize {
+        match self.upper_layer(available) {
+            Some((_, offset)) => (self.payload_len() as usize + HDR_SIZE).saturating_sub(offset),
+            None => 0,
+        }
+    }
+
+    #[inline]
+    fn pseudo_header_sum(&self, protocol: u8, len: usize) -> u32 {
+        ipv6_pseudo_header_sum(self.src(), self.dst(), protocol, len as u32)
+    }
+}
+
+impl Ipv6Header {
+    /// Initialize a default IPv6 header.
+    #[inline]
+    pub fn new() -> Ipv6Header {
+        let mut hdr: Ipv6Header = Default::default();
+        hdr.set_version(6);
+        hdr
+    }
+
+    /// The packet from this header on, as far as its payload length says, but no further than the `available` bytes
+    /// actually there.
+    #[inline]
+    fn as_bytes(&self, available: usize) -> &[u8] {
+        let len = (HDR_SIZE + self.payload_len() as usize).min(available.max(HDR_SIZE));
+        unsafe { slice::from_raw_parts((self as *const Ipv6Header) as *const u8, len) }
+    }
+
+    /// Upper layer protocol and its offset from the start of this header, skipping extension headers. Only the first
+    /// `available` bytes from the start of this header, e.g., `Packet::payload_offset() + Packet::payload_size()`, are
+    /// looked at.
+    #[inline]
+    pub fn upper_layer(&self, available: usize) -> Option<(u8, usize)> {
+        ipv6_upper_layer(self.as_bytes(available))
+    }
+
+    /// Upper layer protocol number, skipping extension headers within `available` bytes.
+    #[inline]
+    pub fn protocol(&self, available: usize) -> Option<u8> {
+        self.upper_layer(available).map(|(proto, _)| proto)
+    }
+
+    /// Flow of the IPv6 header, if it carries TCP or UDP within `available` bytes.
+    #[inline]
+    pub fn flow(&self, available: usize) -> Option<Ipv6Flow> {
+        let (proto, offset) = self.upper_layer(available)?;
+        let bytes = self.as_bytes(available);
+        if (proto == 6 || proto == 17) && bytes.len() >= offset + 4 {
+            Some(Ipv6Flow {
+                src_ip: self.src(),
+                dst_ip: self.dst(),
+                src_port: BigEndian::read_u16(&bytes[offset..offset + 2]),
+                dst_port: BigEndian::read_u16(&bytes[offset + 2..offset + 4]),
+                proto,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Get the version.
+    #[inline]
+    pub fn version(&self) -> u8 {
+        (u32::from_be(self.version_to_flow) >> 28) as u8
+    }
+
+    /// Set the version.
+    #[inline]
+    pub fn set_version(&mut self, version: u8) {
+        let v = u32::from_be(self.version_to_flow);
+        self.version_to_flow = u32::to_be((v & !0xf000_0000) | (((version & 0xf) as u32) << 28));
+    }
+
+    /// Get the traffic class.
+    #[inline]
+    pub fn traffic_class(&self) -> u8 {
+        ((u32::from_be(self.version_to_flow) >> 20) & 0xff) as u8
+    }
+
+    /// Set the traffic class.
+    #[inline]
+    pub fn set_traffic_class(&mut self, tc: u8) {
+        let v = u32::from_be(self.version_to_flow);
+        self.version_to_flow = u32::to_be((v & !0x0ff0_0000) | ((tc as u32) << 20));
+    }
+
+    /// Get the flow label.
+    #[inline]
+    pub fn flow_label(&self) -> u32 {
+        u32::from_be(self.version_to_flow) & 0x000f_ffff
+    }
+
+    /// Set the flow label.
+    #[inline]
+    pub fn set_flow_label(&mut self, label: u32) {
+        let v = u32::from_be(self.version_to_flow);
+        self.version_to_flow = u32::to_be((v & !0x000f_ffff) | (label & 0x000f_ffff));
+    }
+
+    /// Get the payload length, which includes extension headers.
+    #[inline]
+    pub fn payload_len(&self) -> u16 {
+        u16::from_be(self.payload_len)
+    }
+
+    /// Set the payload length.
+    #[inline]
+    pub fn set_payload_len(&mut self, len: u16) {
+        self.payload_len = u16::to_be(len)
+    }
+
+    /// Get the next header field of the fixed header.
+    #[inline]
+    pub fn next_header(&self) -> u8 {
+        self.next_header
+    }
+
+    /// Set the next header field of the fixed header.
+    #[inline]
+    pub fn set_next_header(&mut self, next_header: u8) {
+        self.next_header = next_header
+    }
+
+    /// Get the hop limit.
+    #[inline]
+    pub fn hop_limit(&self) -> u8 {
+        self.hop_limit
+    }
+
+    /// Set the hop limit.
+    #[inline]
+    pub fn set_hop_limit(&mut self, hop_limit: u8) {
+        self.hop_limit = hop_limit
+    }
+
+    /// Get the src IP address.
+    #[inline]
+    pub fn src(&self) -> u128 {
+        u128::from_be(self.src_ip)
+    }
+
+    /// Set the src IP address.
+    #[inline]
+    pub fn set_src(&mut self, src: u128) {
+        self.src_ip = u128::to_be(src)
+    }
+
+    /// Get the dst IP address.
+    #[inline]
+    pub fn dst(&self) -> u128 {
+        u128::from_be(self.dst_ip)
+    }
+
+    /// Set the dst IP address.
+    #[inline]
+    pub fn set_dst(&mut self, dst: u128) {
+        self.dst_ip = u128::to_be(dst)
+    }
+}
@@ -0,0 +1,170 @@
+use super::{EndOffset, Follows};
+use crate::headers::{header_bytes, GreHeader, IpHeader, IPPROTO_GRE};
+use crate::interface::Packet;
+use crate::utils::checksum;
+use byteorder::{BigEndian, ByteOrder};
+
+/// IP protocol number of IPv4 in IPv4 (RFC 2003).
+pub const IPPROTO_IPIP: u8 = 4;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const IP_SIZE: usize = 20;
+
+/// An IPv4 header follows another in IP-in-IP tunnels.
+impl Follows<IpHeader> for IpHeader {
+    #[inline]
+    fn check_correct_after(&self, _prev: &IpHeader, _available: usize) -> bool {
+        self.check_valid()
+    }
+}
+
+/// The outer IPv4 header, and GRE header for GRE tunnels, that `Batch::encap_ip` puts in front of IPv4 packets. The
+/// outer header's length, checksum and identification are filled in per packet, its DSCP and ECN are copied from the
+/// inner header.
+#[derive(Clone, Debug)]
+pub struct IpTunnelTemplate {
+    /// Address of the local tunnel endpoint.
+    pub src_ip: u32,
+    /// Address of the remote tunnel endpoint.
+    pub dst_ip: u32,
+    /// TTL of the outer header.
+    pub ttl: u8,
+    /// True for GRE, false for IP-in-IP.
+    pub gre: bool,
+    /// GRE key, e.g., identifying the tenant. Ignored for IP-in-IP.
+    pub key: Option<u32>,
+    id: u16,
+}
+
+impl IpTunnelTemplate {
+    /// Create a template for IP-in-IP tunneling from `src_ip` to `dst_ip`, with a TTL of 64.
+    pub fn ipip(src_ip: u32, dst_ip: u32) -> IpTunnelTemplate {
+        IpTunnelTemplate {
+            src_ip,
+            dst_ip,
+            ttl: 64,
+            gre: false,
+            key: None,
+            id: 0,
+        }
+    }
+
+    /// Create a template for GRE tunneling from `src_ip` to `dst_ip`, with `key` if given and a TTL of 64.
+    pub fn gre(src_ip: u32, dst_ip: u32, key: Option<u32>) -> IpTunnelTemplate {
+        IpTunnelTemplate {
+            gre: true,
+            key,
+            ..IpTunnelTemplate::ipip(src_ip, dst_ip)
+        }
+    }
+
+    /// IP protocol number of the tunnel.
+    pub fn protocol(&self) -> u8 {
+        if self.gre {
+            IPPROTO_GRE
+        } else {
+            IPPROTO_IPIP
+        }
+    }
+
+    /// Put the outer headers in front of the IPv4 packet in `packet`, after the headers before it, which move into the
+    /// mbuf's headroom, and return the packet at the outer IP header. Padding after the inner packet is removed. `None`
+    /// is returned when the headroom is too small, the packet should be dropped then.
+    pub fn encap<M: Sized + Send>(&mut self, mut packet: Packet<IpHeader, M>) -> Option<Packet<IpHeader, M>> {
+        let (inner_len, dscp, ecn) = {
+            let inner = packet.get_header();
+            (inner.length() as usize, inner.dscp(), inner.ecn())
+        };
+        let len = header_bytes(&packet).len();
+        if len > inner_len && inner_len >= IP_SIZE {
+            packet.remove_from_payload_tail(len - inner_len).unwrap();
+        }
+        let len = header_bytes(&packet).len();
+
+        let mut gre = GreHeader::new();
+        gre.set_key_present(self.gre && self.key.is_some());
+        let gre_len = if self.gre { gre.offset() } else { 0 };
+        let mut ip = IpHeader::new();
+        ip.set_version(4);
+        ip.set_ihl(5);
+        ip.set_dscp(dscp);
+        ip.set_ecn(ecn);
+        ip.set_length((IP_SIZE + gre_len + len) as u16);
+        ip.set_id(self.id);
+        ip.set_ttl(self.ttl);
+        ip.set_protocol(self.protocol());
+        ip.set_src(self.src_ip);
+        ip.set_dst(self.dst_ip);
+        ip.update_checksum();
+        self.id = self.id.wrapping_add(1);
+
+        if self.gre {
+            let mut packet = packet.prepend_header(&gre)?;
+            if let Some(key) = self.key {
+                packet.get_mut_header().set_key(key);
+            }
+            packet.prepend_header(&ip)
+        } else {
+            packet.prepend_header(&ip)
+        }
+    }
+}
+
+/// Length of the outer headers, length of the inner packet and GRE key of the IP-in-IP or GRE packet `packet`, from
+/// its outer IPv4 header on.
+fn ip_tunnel_headers(packet: &[u8]) -> Option<(usize, usize, Option<u32>)> {
+    if packet.len() < IP_SIZE * 2 {
+        return None;
+    }
+    let ihl = (packet[0] & 0xf) as usize * 4;
+    let ip_len = BigEndian::read_u16(&packet[2..4]) as usize;
+    // Fragmented tunnel packets need reassembling first.
+    if packet[0] >> 4 != 4
+        || ihl < IP_SIZE
+        || BigEndian::read_u16(&packet[6..8]) & 0x3fff != 0
+        || ip_len > packet.len()
+        || ip_len < ihl + IP_SIZE
+    {
+        return None;
+    }
+    let (tunnel_len, key) = match packet[9] {
+        IPPROTO_IPIP => (0, None),
+        IPPROTO_GRE => {
+            let ip = unsafe { &*(packet.as_ptr() as *const IpHeader) };
+            let gre = unsafe { &*(packet[ihl..].as_ptr() as *const GreHeader) };
+            if !gre.check_correct(ip) || gre.protocol() != ETHERTYPE_IPV4 || ip_len < ihl + gre.offset() + IP_SIZE {
+                return None;
+            }
+            if gre.checksum_present() && checksum(&packet[ihl..ip_len]) != 0 {
+                return None;
+            }
+            (gre.offset(), gre.key())
+        }
+        _ => return None,
+    };
+    let outer = ihl + tunnel_len;
+    if packet[outer] >> 4 != 4 {
+        return None;
+    }
+    Some((outer, ip_len - outer, key))
+}
+
+impl<M: Sized + Send> Packet<IpHeader, M> {
+    /// If this packet carries an IPv4 packet in IP-in-IP or GRE, strip the outer headers, and any padding after the
+    /// outer packet, and return the inner packet with its GRE key, if any. The headers before this one move up to the
+    /// inner packet. Anything else, fragments of tunneled packets and GRE packets with a bad checksum included, is
+    /// given back unchanged.
+    pub fn decap_ip(mut self) -> Result<(Packet<IpHeader, M>, Option<u32>), Packet<IpHeader, M>> {
+        let len = header_bytes(&self).len();
+        let (outer, inner, key) = match ip_tunnel_headers(header_bytes(&self)) {
+            Some(headers) => headers,
+            None => return Err(self),
+        };
+        self.remove_from_payload_tail(len - outer - inner)
+            .expect("Padding is within the packet");
+        let packet = self
+            .remove_headers::<IpHeader>(outer)
+            .expect("Outer headers are within the packet");
+        Ok((packet, key))
+    }
+}
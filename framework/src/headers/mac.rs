@@ -89,22 +89,13 @@ impl fmt::Display for MacHeader {
 }
 
 const HDR_SIZE: usize = 14;
-const HDR_SIZE_802_1Q: usize = HDR_SIZE + 4;
-const HDR_SIZE_802_1AD: usize = HDR_SIZE_802_1Q + 4;
 
 impl EndOffset for MacHeader {
     type PreviousHeader = NullHeader;
+    /// VLAN tags are not part of the MAC header, they are parsed as `VlanHeader`s following it.
     #[inline]
     fn offset(&self) -> usize {
-        if cfg!(feature = "performance") {
-            HDR_SIZE
-        } else {
-            match self.etype {
-                0x8100 => HDR_SIZE_802_1Q,
-                0x9100 => HDR_SIZE_802_1AD,
-                _ => HDR_SIZE,
-            }
-        }
+        HDR_SIZE
     }
     #[inline]
     fn size() -> usize {
@@ -129,7 +120,7 @@ impl MacHeader {
         Default::default()
     }
 
-    /// Get the etype from MAC header.
+    /// Get the etype from MAC header. For tagged frames this is the TPID of the outermost VLAN tag.
     #[inline]
     pub fn etype(&self) -> u16 {
         u16::from_be(self.etype)
@@ -1,14 +1,36 @@
 //! Headers.
+use crate::utils::{ones_complement_add, ones_complement_fold, ChecksumMode};
+use byteorder::{BigEndian, ByteOrder};
+use std::slice;
+
+pub use self::geneve::*;
+pub use self::gre::*;
+pub use self::icmp::*;
 pub use self::ip::*;
+pub use self::ip6::*;
+pub use self::ip_tunnel::*;
 pub use self::mac::*;
 pub use self::null_header::*;
 pub use self::tcp::*;
+pub use self::tcp_options::*;
+pub use self::tunnel::*;
 pub use self::udp::*;
+pub use self::vlan::*;
+pub use self::vxlan::*;
+mod geneve;
+mod gre;
+mod icmp;
 mod ip;
+mod ip6;
+mod ip_tunnel;
 mod mac;
 mod null_header;
 mod tcp;
+mod tcp_options;
+mod tunnel;
 mod udp;
+mod vlan;
+mod vxlan;
 
 /// A trait implemented by all headers, used for reading them from a mbuf.
 pub trait EndOffset: Send {
@@ -19,6 +41,13 @@ pub trait EndOffset: Send {
     /// of the mbuf.
     fn offset(&self) -> usize;
 
+    /// Like `offset`, for a header followed by `available` bytes of packet (itself included). Headers whose length
+    /// depends on what follows them, e.g., IPv6 with extension headers, must not read past those bytes.
+    #[inline]
+    fn offset_within(&self, _available: usize) -> usize {
+        self.offset()
+    }
+
     /// Returns the size of this header in bytes.
     fn size() -> usize;
 
@@ -28,4 +57,114 @@ pub trait EndOffset: Send {
 
     /// Check if correct.
     fn check_correct(&self, prev: &Self::PreviousHeader) -> bool;
+
+    /// Like `check_correct`, for a header followed by `available` bytes of packet (itself included). Headers whose
+    /// checks cover what follows them, e.g., TCP and UDP checksums, must not read past those bytes.
+    #[inline]
+    fn check_correct_within(&self, prev: &Self::PreviousHeader, _available: usize) -> bool {
+        self.check_correct(prev)
+    }
+}
+
+/// A header that can be parsed directly after header `P`. Every header follows its `PreviousHeader`; headers that may
+/// also appear after other headers (e.g., `IpHeader` after a `VlanHeader`) implement this for each of them. Deparsing
+/// always returns to `PreviousHeader`.
+pub trait Follows<P: EndOffset>: EndOffset {
+    /// Check if correct after `prev` with `available` bytes of packet from this header on, see
+    /// `EndOffset::check_correct_within`. Headers with checks of their own implement this for every header they follow.
+    #[inline]
+    fn check_correct_after(&self, _prev: &P, _available: usize) -> bool {
+        true
+    }
+}
+
+impl<T: EndOffset> Follows<T::PreviousHeader> for T {
+    #[inline]
+    fn check_correct_after(&self, prev: &T::PreviousHeader, available: usize) -> bool {
+        self.check_correct_within(prev, available)
+    }
+}
+
+/// A network layer header whose addresses are covered by the checksum of the upper layer (TCP, UDP or ICMPv6) message
+/// it carries. `available` is the number of bytes of the packet from the start of the header on, extension headers are
+/// only looked for within them.
+pub trait PseudoHeader: EndOffset {
+    /// IP version, 4 or 6.
+    fn ip_version(&self) -> u8;
+
+    /// Upper layer protocol number, skipping options and extension headers.
+    fn upper_layer_protocol(&self, available: usize) -> Option<u8>;
+
+    /// Offset of the upper layer message from the start of this header, i.e., past options and extension headers.
+    fn upper_layer_offset(&self, available: usize) -> usize;
+
+    /// Length of the upper layer message in bytes, as given by the header's length fields.
+    fn upper_layer_len(&self, available: usize) -> usize;
+
+    /// Partial one's complement sum of the pseudo-header for an upper layer message of `protocol` and `len` bytes.
+    fn pseudo_header_sum(&self, protocol: u8, len: usize) -> u32;
+
+    /// Fill in the header's own checksum, if it has one. With `ChecksumMode::Offload` the checksum is zeroed for the
+    /// NIC to compute.
+    #[inline]
+    fn update_header_checksum(&mut self, _mode: ChecksumMode) {}
+
+    /// Returns true if the header's own checksum, if it has one, is correct.
+    #[inline]
+    fn header_checksum_valid(&self) -> bool {
+        true
+    }
+}
+
+/// Length of the upper layer message at `segment`, which follows `ip` in the same packet and has `available` bytes of
+/// packet from its start on. `None` if the message, as given by the header's length fields, runs past those bytes.
+#[inline]
+unsafe fn upper_layer_len_within<P: PseudoHeader>(ip: &P, segment: *const u8, available: usize) -> Option<usize> {
+    let ip_available = segment.offset_from(ip as *const P as *const u8) as usize + available;
+    let len = ip.upper_layer_len(ip_available);
+    if len <= available {
+        Some(len)
+    } else {
+        None
+    }
+}
+
+/// Checksum of the upper layer message at `segment`, which carries its current checksum at byte `csum_offset`, as it
+/// should be with the pseudo-header of `ip`. The current checksum is subtracted out, so it need not be zeroed first.
+/// A message running past the `available` bytes of packet from its start on is summed only as far as those go.
+#[inline]
+pub(crate) unsafe fn upper_layer_checksum<P: PseudoHeader>(
+    ip: &P,
+    protocol: u8,
+    segment: *const u8,
+    csum_offset: usize,
+    available: usize,
+) -> u16 {
+    let len = upper_layer_len_within(ip, segment, available).unwrap_or(available);
+    let bytes = slice::from_raw_parts(segment, len);
+    let current = if len >= csum_offset + 2 {
+        BigEndian::read_u16(&bytes[csum_offset..csum_offset + 2])
+    } else {
+        0
+    };
+    let sum = ones_complement_add(ip.pseudo_header_sum(protocol, len), bytes);
+    !ones_complement_fold(sum + (!current) as u32)
+}
+
+/// Returns true if the upper layer message at `segment` has a correct checksum given the pseudo-header of `ip`. A
+/// message running past the `available` bytes of packet from its start on is never correct.
+#[inline]
+pub(crate) unsafe fn upper_layer_checksum_valid<P: PseudoHeader>(
+    ip: &P,
+    protocol: u8,
+    segment: *const u8,
+    available: usize,
+) -> bool {
+    match upper_layer_len_within(ip, segment, available) {
+        Some(len) => {
+            let bytes = slice::from_raw_parts(segment, len);
+            ones_complement_fold(ones_complement_add(ip.pseudo_header_sum(protocol, len), bytes)) == 0xffff
+        }
+        None => false,
+    }
 }
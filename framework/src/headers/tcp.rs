@@ -1,7 +1,10 @@
-use super::EndOffset;
-use crate::headers::IpHeader;
+use super::{upper_layer_checksum, upper_layer_checksum_valid, EndOffset, Follows, PseudoHeader};
+use crate::headers::{IpHeader, Ipv6Header, TcpOption, TcpOptions, TCP_OPT_NOP};
+use crate::utils::{checksum_adjust, ChecksumMode};
+use byteorder::{BigEndian, ByteOrder};
 use std::default::Default;
 use std::fmt;
+use std::slice;
 
 /// TCP header.
 #[derive(Default)]
@@ -27,6 +30,11 @@ const RST: u8 = 0b0000_0100;
 const SYN: u8 = 0b0000_0010;
 const FIN: u8 = 0b0000_0001;
 
+/// Size of the fixed part of the header, options follow it.
+const FIXED_SIZE: usize = 20;
+/// Largest size of the options, as limited by the data offset.
+const MAX_OPTIONS: usize = 40;
+
 macro_rules! write_or_return {
     ($dst: expr, $($arg:tt)*) => {
         {
@@ -81,12 +89,21 @@ impl EndOffset for TcpHeader {
         frame_size - self.offset()
     }
 
+    /// The checksum is verified by `check_correct_within`, which knows how much of the segment the packet holds.
     #[inline]
     fn check_correct(&self, _prev: &IpHeader) -> bool {
         true
     }
+
+    /// With the `verify_checksum` feature, also verifies the checksum.
+    #[inline]
+    fn check_correct_within(&self, prev: &IpHeader, available: usize) -> bool {
+        !cfg!(feature = "verify_checksum") || self.checksum_valid(prev, available)
+    }
 }
 
+impl Follows<Ipv6Header> for TcpHeader {}
+
 impl TcpHeader {
     /// Initialize a default TCP header.
     #[inline]
@@ -369,11 +386,31 @@ impl TcpHeader {
     }
 
     /// Set the checksum.
-    // FIXME: Validate checksum and update checksum
     pub fn set_checksum(&mut self, csum: u16) {
         self.csum = u16::to_be(csum)
     }
 
+    /// Compute the checksum of this segment, whose length and pseudo-header are taken from `ip`, without modifying it.
+    /// `available` is the number of bytes of packet from the start of the segment on.
+    #[inline]
+    pub fn compute_checksum<P: PseudoHeader>(&self, ip: &P, available: usize) -> u16 {
+        unsafe { upper_layer_checksum(ip, 6, (self as *const TcpHeader) as *const u8, 16, available) }
+    }
+
+    /// Recompute and store the checksum of this segment.
+    #[inline]
+    pub fn update_checksum<P: PseudoHeader>(&mut self, ip: &P, available: usize) {
+        let csum = self.compute_checksum(ip, available);
+        self.set_checksum(csum);
+    }
+
+    /// Returns true if the checksum of this segment is correct. A segment longer than the `available` bytes of packet
+    /// from its start on is not.
+    #[inline]
+    pub fn checksum_valid<P: PseudoHeader>(&self, ip: &P, available: usize) -> bool {
+        unsafe { upper_layer_checksum_valid(ip, 6, (self as *const TcpHeader) as *const u8, available) }
+    }
+
     /// Get the urgent pointer.
     pub fn urgent(&self) -> u16 {
         u16::from_be(self.urgent)
@@ -383,4 +420,114 @@ impl TcpHeader {
     pub fn set_urgent(&mut self, urgent: u16) {
         self.urgent = u16::to_be(urgent);
     }
+
+    /// The options, i.e., the bytes between the fixed header and the data offset, padding included. Like the rest of
+    /// the header they must be within the packet, which parsing only checks for the fixed part.
+    #[inline]
+    pub fn option_bytes(&self) -> &[u8] {
+        let len = self.offset().saturating_sub(FIXED_SIZE);
+        unsafe { slice::from_raw_parts((self as *const TcpHeader as *const u8).add(FIXED_SIZE), len) }
+    }
+
+    #[inline]
+    fn option_bytes_mut(&mut self) -> &mut [u8] {
+        let len = self.offset().saturating_sub(FIXED_SIZE);
+        unsafe { slice::from_raw_parts_mut((self as *mut TcpHeader as *mut u8).add(FIXED_SIZE), len) }
+    }
+
+    /// Iterate over the options.
+    #[inline]
+    pub fn options(&self) -> TcpOptions<'_> {
+        TcpOptions::new(self.option_bytes())
+    }
+
+    /// The maximum segment size option, found on SYN segments.
+    pub fn mss(&self) -> Option<u16> {
+        self.options().find_map(|option| match option {
+            TcpOption::Mss(mss) => Some(mss),
+            _ => None,
+        })
+    }
+
+    /// The window scale option, found on SYN segments.
+    pub fn window_scale(&self) -> Option<u8> {
+        self.options().find_map(|option| match option {
+            TcpOption::WindowScale(shift) => Some(shift),
+            _ => None,
+        })
+    }
+
+    /// Whether the SACK permitted option is present, found on SYN segments.
+    pub fn sack_permitted(&self) -> bool {
+        self.options().any(|option| option == TcpOption::SackPermitted)
+    }
+
+    /// The timestamps option, as (value, echo reply).
+    pub fn timestamps(&self) -> Option<(u32, u32)> {
+        self.options().find_map(|option| match option {
+            TcpOption::Timestamps { value, echo } => Some((value, echo)),
+            _ => None,
+        })
+    }
+
+    /// Lower the maximum segment size option to `mss` if it is larger, adjusting the checksum as `mode` says (see
+    /// `rewrite_options`). Returns whether the option was changed; segments without the option are left alone.
+    pub fn clamp_mss(&mut self, mss: u16, mode: ChecksumMode) -> bool {
+        let mut options = self.options();
+        while let Some((offset, _, option)) = options.next_with_span() {
+            if let TcpOption::Mss(current) = option {
+                if current <= mss {
+                    return false;
+                }
+                self.rewrite_options(mode, |bytes| {
+                    BigEndian::write_u16(&mut bytes[offset + 2..offset + 4], mss)
+                });
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Strip the options for which `strip` returns true, adjusting the checksum as `mode` says (see `rewrite_options`).
+    /// Stripped options are overwritten with NOPs, so the segment keeps its length. Returns the number of options
+    /// stripped.
+    pub fn strip_options<F: FnMut(&TcpOption<'_>) -> bool>(&mut self, mode: ChecksumMode, mut strip: F) -> usize {
+        let mut old = [0u8; MAX_OPTIONS];
+        let len = self.option_bytes().len().min(MAX_OPTIONS);
+        old[..len].copy_from_slice(&self.option_bytes()[..len]);
+        let mut stripped = 0;
+        self.rewrite_options(mode, |bytes| {
+            let mut options = TcpOptions::new(&old[..len]);
+            while let Some((offset, size, option)) = options.next_with_span() {
+                if strip(&option) {
+                    stripped += 1;
+                    for b in &mut bytes[offset..offset + size] {
+                        *b = TCP_OPT_NOP;
+                    }
+                }
+            }
+        });
+        stripped
+    }
+
+    /// Change the options with `rewrite`. With `ChecksumMode::Software` the checksum is then adjusted incrementally
+    /// (RFC 1624) for the 16-bit words that changed. With `ChecksumMode::Offload` it holds the pseudo-header seed (see
+    /// `Packet::update_checksums`), which does not cover the options, so it is left alone.
+    fn rewrite_options<F: FnOnce(&mut [u8])>(&mut self, mode: ChecksumMode, rewrite: F) {
+        let mut old = [0u8; MAX_OPTIONS];
+        let len = self.option_bytes().len().min(MAX_OPTIONS);
+        old[..len].copy_from_slice(&self.option_bytes()[..len]);
+        rewrite(&mut self.option_bytes_mut()[..len]);
+        if mode == ChecksumMode::Offload {
+            return;
+        }
+        // Options start at an even offset and are a multiple of 4 bytes long, so their words are words of the segment.
+        let mut csum = self.checksum();
+        for (before, after) in old[..len].chunks_exact(2).zip(self.option_bytes().chunks_exact(2)) {
+            if before != after {
+                csum = checksum_adjust(csum, BigEndian::read_u16(before), BigEndian::read_u16(after));
+            }
+        }
+        self.set_checksum(csum);
+    }
 }
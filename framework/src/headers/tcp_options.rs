@@ -0,0 +1,146 @@
+use byteorder::{BigEndian, ByteOrder};
+use std::iter::FusedIterator;
+
+/// End of option list.
+pub const TCP_OPT_EOL: u8 = 0;
+/// No operation, used for padding.
+pub const TCP_OPT_NOP: u8 = 1;
+/// Maximum segment size (RFC 793).
+pub const TCP_OPT_MSS: u8 = 2;
+/// Window scale (RFC 7323).
+pub const TCP_OPT_WINDOW_SCALE: u8 = 3;
+/// SACK permitted (RFC 2018).
+pub const TCP_OPT_SACK_PERMITTED: u8 = 4;
+/// Selective acknowledgment blocks (RFC 2018).
+pub const TCP_OPT_SACK: u8 = 5;
+/// Timestamps (RFC 7323).
+pub const TCP_OPT_TIMESTAMPS: u8 = 8;
+
+/// A TCP option. Options of a known kind but with the wrong length are returned as `Unknown`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TcpOption<'a> {
+    /// Maximum segment size the sender can receive.
+    Mss(u16),
+    /// Shift count applied to the sender's window field.
+    WindowScale(u8),
+    /// The sender accepts selective acknowledgments.
+    SackPermitted,
+    /// Blocks of data received out of order.
+    Sack(SackBlocks<'a>),
+    /// Timestamp value and echo reply.
+    Timestamps {
+        /// Sender's timestamp clock.
+        value: u32,
+        /// Latest timestamp value received from the other side.
+        echo: u32,
+    },
+    /// Any other option, with its data (without the kind and length bytes).
+    Unknown {
+        /// Option kind.
+        kind: u8,
+        /// Option data.
+        data: &'a [u8],
+    },
+}
+
+impl<'a> TcpOption<'a> {
+    /// Option kind, as in the `TCP_OPT_*` constants.
+    pub fn kind(&self) -> u8 {
+        match *self {
+            TcpOption::Mss(_) => TCP_OPT_MSS,
+            TcpOption::WindowScale(_) => TCP_OPT_WINDOW_SCALE,
+            TcpOption::SackPermitted => TCP_OPT_SACK_PERMITTED,
+            TcpOption::Sack(_) => TCP_OPT_SACK,
+            TcpOption::Timestamps { .. } => TCP_OPT_TIMESTAMPS,
+            TcpOption::Unknown { kind, .. } => kind,
+        }
+    }
+
+    fn parse(kind: u8, data: &'a [u8]) -> TcpOption<'a> {
+        match (kind, data.len()) {
+            (TCP_OPT_MSS, 2) => TcpOption::Mss(BigEndian::read_u16(data)),
+            (TCP_OPT_WINDOW_SCALE, 1) => TcpOption::WindowScale(data[0]),
+            (TCP_OPT_SACK_PERMITTED, 0) => TcpOption::SackPermitted,
+            (TCP_OPT_SACK, len) if len > 0 && len % 8 == 0 => TcpOption::Sack(SackBlocks { data }),
+            (TCP_OPT_TIMESTAMPS, 8) => TcpOption::Timestamps {
+                value: BigEndian::read_u32(&data[..4]),
+                echo: BigEndian::read_u32(&data[4..]),
+            },
+            _ => TcpOption::Unknown { kind, data },
+        }
+    }
+}
+
+/// The blocks of a SACK option, as (left edge, right edge) sequence number pairs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SackBlocks<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for SackBlocks<'a> {
+    type Item = (u32, u32);
+
+    fn next(&mut self) -> Option<(u32, u32)> {
+        if self.data.len() < 8 {
+            return None;
+        }
+        let block = (
+            BigEndian::read_u32(&self.data[..4]),
+            BigEndian::read_u32(&self.data[4..8]),
+        );
+        self.data = &self.data[8..];
+        Some(block)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.data.len() / 8, Some(self.data.len() / 8))
+    }
+}
+
+impl<'a> ExactSizeIterator for SackBlocks<'a> {}
+
+/// Iterator over the options of a TCP header, see `TcpHeader::options`. Padding is skipped, and iteration stops at the
+/// end of the option list or at the first malformed option.
+#[derive(Clone, Debug)]
+pub struct TcpOptions<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> TcpOptions<'a> {
+    /// Iterate over the options in `bytes`, i.e., the part of a TCP header after its fixed 20 bytes.
+    pub fn new(bytes: &'a [u8]) -> TcpOptions<'a> {
+        TcpOptions { bytes, offset: 0 }
+    }
+
+    /// Next option, with its offset and length in bytes (including the kind and length bytes).
+    pub(crate) fn next_with_span(&mut self) -> Option<(usize, usize, TcpOption<'a>)> {
+        while let Some(&kind) = self.bytes.get(self.offset) {
+            match kind {
+                TCP_OPT_EOL => break,
+                TCP_OPT_NOP => self.offset += 1,
+                _ => {
+                    let start = self.offset;
+                    let len = match self.bytes.get(start + 1) {
+                        Some(&len) if len >= 2 && start + len as usize <= self.bytes.len() => len as usize,
+                        _ => break,
+                    };
+                    self.offset += len;
+                    return Some((start, len, TcpOption::parse(kind, &self.bytes[start + 2..start + len])));
+                }
+            }
+        }
+        self.offset = self.bytes.len();
+        None
+    }
+}
+
+impl<'a> Iterator for TcpOptions<'a> {
+    type Item = TcpOption<'a>;
+
+    fn next(&mut self) -> Option<TcpOption<'a>> {
+        self.next_with_span().map(|(_, _, option)| option)
+    }
+}
+
+impl<'a> FusedIterator for TcpOptions<'a> {}
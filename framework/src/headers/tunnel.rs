@@ -0,0 +1,126 @@
+use super::EndOffset;
+use crate::headers::{GeneveHeader, MacHeader, UdpHeader, VxlanHeader, ETHERTYPE_TEB, GENEVE_PORT, VXLAN_PORT};
+use crate::interface::Packet;
+use crate::utils::{flow_hash, ipv4_extract_flow, ipv6_extract_flow, ipv6_flow_hash, Flow};
+use byteorder::{BigEndian, ByteOrder};
+use fnv::FnvHasher;
+use std::cmp::min;
+use std::hash::Hasher;
+use std::slice;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const MAC_SIZE: usize = 14;
+const UDP_SIZE: usize = 8;
+/// Tunneled packets take their UDP source port from the dynamic range (RFC 7348), the low bits hold the entropy.
+const ENTROPY_PORT_BASE: u16 = 0xc000;
+const ENTROPY_PORT_MASK: u16 = 0x3fff;
+
+/// The bytes of `packet` from its current header to the end of the data, e.g., the Ethernet frame of a packet at its
+/// MAC header.
+#[inline]
+pub(crate) fn header_bytes<T: EndOffset, M: Sized + Send>(packet: &Packet<T, M>) -> &[u8] {
+    let len = packet.payload_offset() + packet.payload_size();
+    unsafe { slice::from_raw_parts(packet.get_header() as *const T as *const u8, len) }
+}
+
+/// Flow of the IPv4 packet carried by the Ethernet frame `frame`, `None` for other frames and non-first fragments.
+pub fn frame_flow(frame: &[u8]) -> Option<Flow> {
+    if frame.len() < MAC_SIZE + 20 || BigEndian::read_u16(&frame[12..14]) != ETHERTYPE_IPV4 {
+        return None;
+    }
+    let ip = &frame[MAC_SIZE..];
+    let ihl = (ip[0] & 0xf) as usize * 4;
+    if ip[0] >> 4 != 4 || ihl < 20 || ip.len() < ihl + 4 {
+        return None;
+    }
+    ipv4_extract_flow(ip)
+}
+
+/// UDP source port of a tunneled packet carrying the Ethernet frame `frame`: a hash of the inner flow, or of the MAC
+/// header for frames without one, in the dynamic port range. Routers balancing tunnel traffic over paths by outer flow
+/// thus keep each inner flow on one path while spreading the inner flows (RFC 7348).
+pub fn tunnel_source_port(frame: &[u8]) -> u16 {
+    let ipv6 = frame.len() > MAC_SIZE
+        && BigEndian::read_u16(&frame[12..14]) == ETHERTYPE_IPV6
+        && ipv6_extract_flow(&frame[MAC_SIZE..]).is_some();
+    let hash = if let Some(flow) = frame_flow(frame) {
+        flow_hash(&flow)
+    } else if ipv6 {
+        ipv6_flow_hash(&frame[MAC_SIZE..], 0)
+    } else {
+        let mut hasher = FnvHasher::default();
+        hasher.write(&frame[..min(MAC_SIZE, frame.len())]);
+        hasher.finish() as usize
+    };
+    ENTROPY_PORT_BASE | (hash as u16 & ENTROPY_PORT_MASK)
+}
+
+/// Length of the outer headers, length of the inner frame and network identifier of the VXLAN or GENEVE over IPv4
+/// frame `frame`.
+fn tunnel_headers(frame: &[u8]) -> Option<(usize, usize, u32)> {
+    if frame.len() < MAC_SIZE + 20 + UDP_SIZE + 8 || BigEndian::read_u16(&frame[12..14]) != ETHERTYPE_IPV4 {
+        return None;
+    }
+    let ip = &frame[MAC_SIZE..];
+    let ihl = (ip[0] & 0xf) as usize * 4;
+    let ip_len = BigEndian::read_u16(&ip[2..4]) as usize;
+    // Fragmented tunnel packets need reassembling first.
+    if ip[0] >> 4 != 4
+        || ihl < 20
+        || ip[9] != 17
+        || BigEndian::read_u16(&ip[6..8]) & 0x3fff != 0
+        || ip_len > ip.len()
+        || ip_len < ihl + UDP_SIZE + 8
+    {
+        return None;
+    }
+    let udp_bytes = &ip[ihl..ip_len];
+    let udp = unsafe { &*(udp_bytes.as_ptr() as *const UdpHeader) };
+    let udp_len = udp.length() as usize;
+    if udp_len < UDP_SIZE + 8 || udp_len > udp_bytes.len() {
+        return None;
+    }
+    let tunnel = &udp_bytes[UDP_SIZE..udp_len];
+    let (tunnel_len, vni) = match udp.dst_port() {
+        VXLAN_PORT => {
+            let vxlan = unsafe { &*(tunnel.as_ptr() as *const VxlanHeader) };
+            if !vxlan.check_correct(udp) {
+                return None;
+            }
+            (vxlan.offset(), vxlan.vni())
+        }
+        GENEVE_PORT => {
+            let geneve = unsafe { &*(tunnel.as_ptr() as *const GeneveHeader) };
+            if !geneve.check_correct(udp) || geneve.protocol() != ETHERTYPE_TEB {
+                return None;
+            }
+            (geneve.offset(), geneve.vni())
+        }
+        _ => return None,
+    };
+    if tunnel.len() < tunnel_len + MAC_SIZE {
+        return None;
+    }
+    Some((MAC_SIZE + ihl + UDP_SIZE + tunnel_len, tunnel.len() - tunnel_len, vni))
+}
+
+impl<M: Sized + Send> Packet<MacHeader, M> {
+    /// If this frame carries an Ethernet frame in VXLAN or GENEVE over IPv4, strip the outer headers, and any padding
+    /// after the inner frame, and return the inner frame with its network identifier. Anything else, fragments of
+    /// tunneled packets included, is given back unchanged.
+    pub fn decap(mut self) -> Result<(Packet<MacHeader, M>, u32), Packet<MacHeader, M>> {
+        let len = self.payload_offset() + self.payload_size();
+        let (outer, inner, vni) = match tunnel_headers(header_bytes(&self)) {
+            Some(headers) => headers,
+            None => return Err(self),
+        };
+        self.remove_from_payload_tail(len - outer - inner)
+            .expect("Padding is within the packet");
+        let offset = self.offset();
+        let packet = self
+            .remove_from_head(offset + outer)
+            .expect("Outer headers are within the packet");
+        Ok((packet.parse_header::<MacHeader>(), vni))
+    }
+}
@@ -1,5 +1,5 @@
-use super::EndOffset;
-use crate::headers::IpHeader;
+use super::{upper_layer_checksum, upper_layer_checksum_valid, EndOffset, Follows, PseudoHeader};
+use crate::headers::{IpHeader, Ipv6Header};
 use std::default::Default;
 use std::fmt;
 
@@ -43,12 +43,21 @@ impl EndOffset for UdpHeader {
         self.length() as usize - self.offset()
     }
 
+    /// The checksum is verified by `check_correct_within`, which knows how much of the datagram the packet holds.
     #[inline]
     fn check_correct(&self, _prev: &IpHeader) -> bool {
         true
     }
+
+    /// With the `verify_checksum` feature, also verifies the checksum.
+    #[inline]
+    fn check_correct_within(&self, prev: &IpHeader, available: usize) -> bool {
+        !cfg!(feature = "verify_checksum") || self.checksum_valid(prev, available)
+    }
 }
 
+impl Follows<Ipv6Header> for UdpHeader {}
+
 impl UdpHeader {
     /// Initialize a default UDP header.
     #[inline]
@@ -103,4 +112,30 @@ impl UdpHeader {
     pub fn set_checksum(&mut self, csum: u16) {
         self.csum = u16::to_be(csum);
     }
+
+    /// Compute the checksum of this datagram, whose length and pseudo-header are taken from `ip`, without modifying
+    /// it. `available` is the number of bytes of packet from the start of the datagram on. A computed checksum of 0 is
+    /// sent as 0xffff, since 0 means no checksum.
+    #[inline]
+    pub fn compute_checksum<P: PseudoHeader>(&self, ip: &P, available: usize) -> u16 {
+        match unsafe { upper_layer_checksum(ip, 17, (self as *const UdpHeader) as *const u8, 6, available) } {
+            0 => 0xffff,
+            csum => csum,
+        }
+    }
+
+    /// Recompute and store the checksum of this datagram.
+    #[inline]
+    pub fn update_checksum<P: PseudoHeader>(&mut self, ip: &P, available: usize) {
+        let csum = self.compute_checksum(ip, available);
+        self.set_checksum(csum);
+    }
+
+    /// Returns true if the checksum of this datagram is correct or, as IPv4 allows, absent (0). A datagram longer than
+    /// the `available` bytes of packet from its start on is not.
+    #[inline]
+    pub fn checksum_valid<P: PseudoHeader>(&self, ip: &P, available: usize) -> bool {
+        self.checksum() == 0
+            || unsafe { upper_layer_checksum_valid(ip, 17, (self as *const UdpHeader) as *const u8, available) }
+    }
 }
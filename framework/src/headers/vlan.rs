@@ -0,0 +1,192 @@
+use super::{EndOffset, Follows};
+use crate::headers::{IpHeader, Ipv6Header, MacHeader, ETHERTYPE_IPV4, ETHERTYPE_IPV6};
+use crate::interface::Packet;
+use byteorder::{BigEndian, ByteOrder};
+use std::default::Default;
+use std::fmt;
+
+/// EtherType (TPID) of an 802.1Q customer tag.
+pub const ETHERTYPE_VLAN: u16 = 0x8100;
+/// EtherType (TPID) of an 802.1ad service tag, the outer tag of a QinQ frame.
+pub const ETHERTYPE_QINQ: u16 = 0x88a8;
+/// Pre-standard EtherType still used by some switches for QinQ outer tags.
+pub const ETHERTYPE_QINQ_OLD: u16 = 0x9100;
+
+const HDR_SIZE: usize = 4;
+
+/// Returns true if `etype` announces a VLAN tag.
+#[inline]
+pub fn is_vlan_tpid(etype: u16) -> bool {
+    etype == ETHERTYPE_VLAN || etype == ETHERTYPE_QINQ || etype == ETHERTYPE_QINQ_OLD
+}
+
+/// An 802.1Q VLAN tag. The tag's TPID is the EtherType of the header it follows, so this header holds the TCI and the
+/// EtherType of what comes next. QinQ frames are parsed as one `VlanHeader` following another.
+#[derive(Default)]
+#[repr(C, packed)]
+pub struct VlanHeader {
+    tci: u16,
+    etype: u16,
+}
+
+impl fmt::Display for VlanHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "vlan {} pcp: {} dei: {} 0x{:04x}",
+            self.vid(),
+            self.pcp(),
+            self.dei(),
+            self.etype()
+        )
+    }
+}
+
+impl EndOffset for VlanHeader {
+    type PreviousHeader = MacHeader;
+
+    #[inline]
+    fn offset(&self) -> usize {
+        HDR_SIZE
+    }
+
+    #[inline]
+    fn size() -> usize {
+        HDR_SIZE
+    }
+
+    #[inline]
+    fn payload_size(&self, hint: usize) -> usize {
+        hint - self.offset()
+    }
+
+    #[inline]
+    fn check_correct(&self, prev: &MacHeader) -> bool {
+        is_vlan_tpid(prev.etype())
+    }
+}
+
+impl Follows<VlanHeader> for VlanHeader {
+    #[inline]
+    fn check_correct_after(&self, prev: &VlanHeader, _available: usize) -> bool {
+        is_vlan_tpid(prev.etype())
+    }
+}
+
+impl Follows<VlanHeader> for IpHeader {
+    #[inline]
+    fn check_correct_after(&self, prev: &VlanHeader, _available: usize) -> bool {
+        prev.etype() == ETHERTYPE_IPV4 && self.check_valid()
+    }
+}
+
+impl Follows<VlanHeader> for Ipv6Header {
+    #[inline]
+    fn check_correct_after(&self, prev: &VlanHeader, _available: usize) -> bool {
+        prev.etype() == ETHERTYPE_IPV6 && self.version() == 6
+    }
+}
+
+impl VlanHeader {
+    /// Initialize a default VLAN tag.
+    #[inline]
+    pub fn new() -> VlanHeader {
+        Default::default()
+    }
+
+    /// Get the tag control information (PCP, DEI and VLAN ID).
+    #[inline]
+    pub fn tci(&self) -> u16 {
+        u16::from_be(self.tci)
+    }
+
+    /// Set the tag control information.
+    #[inline]
+    pub fn set_tci(&mut self, tci: u16) {
+        self.tci = u16::to_be(tci)
+    }
+
+    /// Get the priority code point.
+    #[inline]
+    pub fn pcp(&self) -> u8 {
+        (self.tci() >> 13) as u8
+    }
+
+    /// Set the priority code point.
+    #[inline]
+    pub fn set_pcp(&mut self, pcp: u8) {
+        let tci = (self.tci() & 0x1fff) | (((pcp & 0x7) as u16) << 13);
+        self.set_tci(tci)
+    }
+
+    /// Get the drop eligible indicator.
+    #[inline]
+    pub fn dei(&self) -> bool {
+        self.tci() & 0x1000 != 0
+    }
+
+    /// Set the drop eligible indicator.
+    #[inline]
+    pub fn set_dei(&mut self, dei: bool) {
+        let tci = (self.tci() & !0x1000) | if dei { 0x1000 } else { 0 };
+        self.set_tci(tci)
+    }
+
+    /// Get the VLAN ID.
+    #[inline]
+    pub fn vid(&self) -> u16 {
+        self.tci() & 0x0fff
+    }
+
+    /// Set the VLAN ID.
+    #[inline]
+    pub fn set_vid(&mut self, vid: u16) {
+        let tci = (self.tci() & !0x0fff) | (vid & 0x0fff);
+        self.set_tci(tci)
+    }
+
+    /// Get the EtherType of the header following this tag.
+    #[inline]
+    pub fn etype(&self) -> u16 {
+        u16::from_be(self.etype)
+    }
+
+    /// Set the EtherType of the header following this tag.
+    #[inline]
+    pub fn set_etype(&mut self, etype: u16) {
+        self.etype = u16::to_be(etype)
+    }
+}
+
+impl<M: Sized + Send> Packet<MacHeader, M> {
+    /// Insert a VLAN tag with the given TCI right after the MAC header. An untagged frame gets an 802.1Q tag; if the
+    /// frame is already tagged the new tag becomes the 802.1ad outer tag. As with `push_header`, `None` is returned
+    /// when the mbuf has no room for the tag.
+    pub fn push_vlan(mut self, tci: u16) -> Option<Packet<VlanHeader, M>> {
+        let etype = self.get_header().etype();
+        let tpid = if is_vlan_tpid(etype) {
+            ETHERTYPE_QINQ
+        } else {
+            ETHERTYPE_VLAN
+        };
+        let mut tag = VlanHeader::new();
+        tag.set_tci(tci);
+        tag.set_etype(etype);
+        self.get_mut_header().set_etype(tpid);
+        self.push_header(&tag)
+    }
+
+    /// Remove the outermost VLAN tag, returning its TCI, or `None` if the frame is not tagged.
+    pub fn pop_vlan(&mut self) -> Option<u16> {
+        if !is_vlan_tpid(self.get_header().etype()) || self.payload_size() < HDR_SIZE {
+            return None;
+        }
+        let (tci, etype) = {
+            let tag = self.get_payload();
+            (BigEndian::read_u16(&tag[0..2]), BigEndian::read_u16(&tag[2..4]))
+        };
+        self.get_mut_header().set_etype(etype);
+        self.remove_from_payload_head(HDR_SIZE).ok()?;
+        Some(tci)
+    }
+}
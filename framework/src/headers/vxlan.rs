@@ -0,0 +1,185 @@
+use super::{EndOffset, Follows};
+use crate::headers::{header_bytes, tunnel_source_port, IpHeader, MacAddress, MacHeader, UdpHeader};
+use crate::interface::Packet;
+use std::default::Default;
+use std::fmt;
+use std::ptr;
+
+/// UDP port assigned to VXLAN (RFC 7348).
+pub const VXLAN_PORT: u16 = 4789;
+/// Flag announcing a valid VNI, the only flag VXLAN defines.
+const VXLAN_FLAG_VNI: u8 = 0x08;
+
+const HDR_SIZE: usize = 8;
+/// Offsets of the outer headers written by `VxlanTemplate::encap`.
+const IP_OFFSET: usize = 14;
+const UDP_OFFSET: usize = IP_OFFSET + 20;
+const VXLAN_OFFSET: usize = UDP_OFFSET + 8;
+/// Bytes of outer MAC, IPv4, UDP and VXLAN headers added by `VxlanTemplate::encap`.
+pub const VXLAN_ENCAP_SIZE: usize = VXLAN_OFFSET + HDR_SIZE;
+
+/// A VXLAN header (RFC 7348), carrying a 24-bit VXLAN network identifier (VNI). An Ethernet frame follows it.
+#[derive(Default)]
+#[repr(C, packed)]
+pub struct VxlanHeader {
+    flags: u8,
+    reserved: [u8; 3],
+    vni_reserved: u32,
+}
+
+impl fmt::Display for VxlanHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "vxlan vni: {} flags: 0x{:02x}", self.vni(), self.flags())
+    }
+}
+
+impl EndOffset for VxlanHeader {
+    type PreviousHeader = UdpHeader;
+
+    #[inline]
+    fn offset(&self) -> usize {
+        HDR_SIZE
+    }
+
+    #[inline]
+    fn size() -> usize {
+        HDR_SIZE
+    }
+
+    #[inline]
+    fn payload_size(&self, hint: usize) -> usize {
+        hint - self.offset()
+    }
+
+    /// The UDP destination port must be the VXLAN port, and the VNI must be valid.
+    #[inline]
+    fn check_correct(&self, prev: &UdpHeader) -> bool {
+        prev.dst_port() == VXLAN_PORT && self.flags() & VXLAN_FLAG_VNI != 0
+    }
+}
+
+impl Follows<VxlanHeader> for MacHeader {
+    /// The Ethernet frame is only there under a valid VNI.
+    #[inline]
+    fn check_correct_after(&self, prev: &VxlanHeader, _available: usize) -> bool {
+        prev.flags() & VXLAN_FLAG_VNI != 0
+    }
+}
+
+impl VxlanHeader {
+    /// Initialize a VXLAN header with VNI 0.
+    #[inline]
+    pub fn new() -> VxlanHeader {
+        VxlanHeader {
+            flags: VXLAN_FLAG_VNI,
+            ..Default::default()
+        }
+    }
+
+    /// Get the flags.
+    #[inline]
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Set the flags.
+    #[inline]
+    pub fn set_flags(&mut self, flags: u8) {
+        self.flags = flags
+    }
+
+    /// Get the VXLAN network identifier.
+    #[inline]
+    pub fn vni(&self) -> u32 {
+        u32::from_be(self.vni_reserved) >> 8
+    }
+
+    /// Set the VXLAN network identifier, and the flag announcing it.
+    #[inline]
+    pub fn set_vni(&mut self, vni: u32) {
+        self.vni_reserved = u32::to_be((vni & 0x00ff_ffff) << 8);
+        self.flags |= VXLAN_FLAG_VNI;
+    }
+}
+
+/// The outer headers `Batch::encap_vxlan` puts in front of Ethernet frames: MAC, IPv4 and UDP headers between two
+/// tunnel endpoints, and a VXLAN header with the VNI. Lengths, checksums, the IP identification and the UDP source
+/// port are filled in per packet, the source port from a hash of the inner flow so that routers spreading traffic
+/// over paths keep the inner flows apart (RFC 7348).
+#[derive(Clone)]
+pub struct VxlanTemplate {
+    /// Source MAC address.
+    pub src_mac: MacAddress,
+    /// Destination MAC address, e.g., of the next hop towards the remote endpoint.
+    pub dst_mac: MacAddress,
+    /// Address of the local tunnel endpoint.
+    pub src_ip: u32,
+    /// Address of the remote tunnel endpoint.
+    pub dst_ip: u32,
+    /// TTL of the outer IPv4 header.
+    pub ttl: u8,
+    /// UDP destination port.
+    pub dst_port: u16,
+    /// VXLAN network identifier.
+    pub vni: u32,
+    id: u16,
+}
+
+impl VxlanTemplate {
+    /// Create a template for tunneling frames from `src_ip` to `dst_ip` on VNI `vni`, with a TTL of 64 and the VXLAN
+    /// port.
+    pub fn new(src_mac: MacAddress, dst_mac: MacAddress, src_ip: u32, dst_ip: u32, vni: u32) -> VxlanTemplate {
+        VxlanTemplate {
+            src_mac,
+            dst_mac,
+            src_ip,
+            dst_ip,
+            ttl: 64,
+            dst_port: VXLAN_PORT,
+            vni,
+            id: 0,
+        }
+    }
+
+    /// Put the outer headers in front of the Ethernet frame in `packet`, in the mbuf's headroom, and return the packet
+    /// starting at the outer MAC header. Anything in front of the inner MAC header is tunneled with it. `None` is
+    /// returned when the headroom is too small.
+    pub fn encap<M: Sized + Send>(&mut self, packet: Packet<MacHeader, M>) -> Option<Packet<MacHeader, M>> {
+        let frame_len = packet.data_len();
+        let src_port = tunnel_source_port(header_bytes(&packet));
+        let mut packet = packet.add_to_head(VXLAN_ENCAP_SIZE)?;
+
+        let mut mac = MacHeader::new();
+        mac.src = self.src_mac.clone();
+        mac.dst = self.dst_mac.clone();
+        mac.set_etype(0x0800);
+        let mut ip = IpHeader::new();
+        ip.set_version(4);
+        ip.set_ihl(5);
+        ip.set_length((VXLAN_ENCAP_SIZE - IP_OFFSET + frame_len) as u16);
+        ip.set_id(self.id);
+        ip.set_ttl(self.ttl);
+        ip.set_protocol(17);
+        ip.set_src(self.src_ip);
+        ip.set_dst(self.dst_ip);
+        ip.update_checksum();
+        let mut udp = UdpHeader::new();
+        udp.set_src_port(src_port);
+        udp.set_dst_port(self.dst_port);
+        udp.set_length((VXLAN_ENCAP_SIZE - UDP_OFFSET + frame_len) as u16);
+        // IPv4 tunnels send no UDP checksum, the inner frame has its own.
+        udp.set_checksum(0);
+        let mut vxlan = VxlanHeader::new();
+        vxlan.set_vni(self.vni);
+        self.id = self.id.wrapping_add(1);
+
+        let bytes = packet.get_mut_payload();
+        unsafe {
+            ptr::copy_nonoverlapping(&mac, bytes.as_mut_ptr() as *mut MacHeader, 1);
+            ptr::copy_nonoverlapping(&ip, bytes[IP_OFFSET..].as_mut_ptr() as *mut IpHeader, 1);
+            ptr::copy_nonoverlapping(&udp, bytes[UDP_OFFSET..].as_mut_ptr() as *mut UdpHeader, 1);
+            ptr::copy_nonoverlapping(&vxlan, bytes[VXLAN_OFFSET..].as_mut_ptr() as *mut VxlanHeader, 1);
+        }
+        Some(packet.parse_header::<MacHeader>())
+    }
+}
@@ -2,8 +2,10 @@
 
 use super::METADATA_SLOTS;
 use crate::config::{NetbricksConfiguration, DEFAULT_CACHE_SIZE, DEFAULT_POOL_SIZE};
+#[cfg(not(feature = "heap_mbuf"))]
 use crate::native::libnuma;
 use crate::native::zcsi;
+use crate::utils::{set_latency_core, set_tsc_hz};
 use std::cell::Cell;
 use std::ffi::CString;
 
@@ -26,6 +28,7 @@ fn init_system_wl_with_mempool(name: &str, core: i32, pci: &[String], pool_size:
         if ret != 0 {
             panic!("Could not initialize the system errno {}", ret)
         }
+        set_tsc_hz(zcsi::rte_get_tsc_hz());
     }
 }
 
@@ -44,6 +47,7 @@ pub fn init_system_secondary(name: &str, core: i32) {
         if ret != 0 {
             panic!("Could not initialize secondary process errno {}", ret)
         }
+        set_tsc_hz(zcsi::rte_get_tsc_hz());
     }
     set_numa_domain();
 }
@@ -70,7 +74,9 @@ pub fn init_system(config: &NetbricksConfiguration) {
 }
 
 thread_local!(static NUMA_DOMAIN: Cell<i32> = Cell::new(-1));
+thread_local!(static CORE: Cell<i32> = Cell::new(-1));
 
+#[cfg(not(feature = "heap_mbuf"))]
 fn set_numa_domain() {
     let domain = unsafe {
         if libnuma::numa_available() == -1 {
@@ -85,9 +91,17 @@ fn set_numa_domain() {
     NUMA_DOMAIN.with(|f| f.set(domain))
 }
 
+/// Heap backed mbufs are not NUMA aware.
+#[cfg(feature = "heap_mbuf")]
+fn set_numa_domain() {
+    NUMA_DOMAIN.with(|f| f.set(-1))
+}
+
 /// Affinitize a pthread to a core and assign a DPDK thread ID.
 pub fn init_thread(tid: i32, core: i32) {
     let numa = unsafe { zcsi::init_thread(tid, core) };
+    CORE.with(|f| f.set(core));
+    set_latency_core(core);
     NUMA_DOMAIN.with(|f| {
         f.set(numa);
     });
@@ -103,3 +117,21 @@ pub fn init_thread(tid: i32, core: i32) {
 pub fn get_domain() -> i32 {
     NUMA_DOMAIN.with(|f| f.get())
 }
+
+/// Get the core this thread was bound to by `init_thread`, or -1.
+#[inline]
+pub fn get_core() -> i32 {
+    CORE.with(|f| f.get())
+}
+
+/// Number of mbufs in use and available in each packet mempool (one per core or per NUMA node).
+pub fn mempool_usage() -> Vec<(u32, u32)> {
+    let mut pools = vec![];
+    loop {
+        let (mut in_use, mut avail) = (0, 0);
+        if unsafe { zcsi::mempool_usage(pools.len() as i32, &mut in_use, &mut avail) } != 0 {
+            return pools;
+        }
+        pools.push((in_use, avail));
+    }
+}
@@ -7,6 +7,7 @@ mod port;
 
 use crate::common::*;
 use crate::native::zcsi::MBuf;
+use crate::utils::ChecksumMode;
 
 /// Generic trait for objects that can receive packets.
 pub trait PacketRx: Send {
@@ -18,6 +19,18 @@ pub trait PacketRx: Send {
 pub trait PacketTx: Send {
     /// Send.
     fn send(&self, pkts: &mut [*mut MBuf]) -> Result<u32>;
+
+    /// How checksums of packets sent here should be filled in, see `Packet::update_checksums`. Only ports configured
+    /// with checksum offload return `ChecksumMode::Offload`.
+    fn checksum_mode(&self) -> ChecksumMode {
+        ChecksumMode::Software
+    }
+
+    /// Largest IP packet this port sends, `None` if it has no limit. Packets are not checked against it on send, use
+    /// `Batch::fragment` to enforce it.
+    fn mtu(&self) -> Option<u16> {
+        None
+    }
 }
 
 /// Generic trait for objects that can send and receive packets.
@@ -1,6 +1,7 @@
 use crate::common::*;
-use crate::headers::{EndOffset, NullHeader};
+use crate::headers::{EndOffset, Follows, NullHeader, PseudoHeader, TcpHeader, UdpHeader};
 use crate::native::zcsi::*;
+use crate::utils::{ones_complement_fold, ChecksumMode};
 use std::marker::PhantomData;
 use std::mem::size_of;
 use std::ptr;
@@ -144,10 +145,16 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
 
     #[inline]
     #[cfg(not(feature = "packet_offset"))]
-    fn offset(&self) -> usize {
+    pub(crate) fn offset(&self) -> usize {
         self.offset
     }
 
+    #[inline]
+    #[cfg(not(feature = "packet_offset"))]
+    fn set_header(&mut self, header: *mut u8) {
+        self.header = header as *mut T;
+    }
+
     // ----------------- Using packet offsets -------------------------------------------------------------
     #[inline]
     #[cfg(feature = "packet_offset")]
@@ -163,10 +170,17 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
 
     #[inline]
     #[cfg(feature = "packet_offset")]
-    fn offset(&self) -> usize {
+    pub(crate) fn offset(&self) -> usize {
         self.read_offset()
     }
 
+    #[inline]
+    #[cfg(feature = "packet_offset")]
+    fn set_header(&mut self, header: *mut u8) {
+        let offset = self.offset();
+        self.update_ptrs(header, offset);
+    }
+
     // -----------------Common code ------------------------------------------------------------------------
     #[inline]
     fn read_stack_depth(&self) -> usize {
@@ -263,7 +277,13 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
     /// Return the offset of the payload relative to the header.
     #[inline]
     pub fn payload_offset(&self) -> usize {
-        unsafe { (*self.header()).offset() }
+        unsafe { (*self.header()).offset_within(self.header_available()) }
+    }
+
+    /// Bytes of the packet from the start of the header on.
+    #[inline]
+    pub(crate) fn header_available(&self) -> usize {
+        self.data_len().saturating_sub(self.offset())
     }
 
     #[inline]
@@ -319,6 +339,19 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
         }
     }
 
+    /// Read the ingress timestamp (TSC) written by `Batch::timestamp`, 0 if the packet has none. It is kept in the
+    /// mbuf's own timestamp field, not in the metadata slots.
+    #[inline]
+    pub fn read_timestamp(&self) -> u64 {
+        unsafe { (*self.mbuf).timestamp }
+    }
+
+    /// Write the ingress timestamp (TSC). Writing 0 clears it.
+    #[inline]
+    pub fn write_timestamp(&mut self, tsc: u64) {
+        unsafe { (*self.mbuf).timestamp = tsc }
+    }
+
     /// Reinterpret metadata.
     #[inline]
     pub fn reinterpret_metadata<M2: Sized + Send>(mut self) -> Packet<T, M2> {
@@ -329,7 +362,7 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
 
     /// When constructing a packet, take a packet as input and add a header.
     #[inline]
-    pub fn push_header<T2: EndOffset<PreviousHeader = T>>(mut self, header: &T2) -> Option<Packet<T2, M>> {
+    pub fn push_header<T2: Follows<T>>(mut self, header: &T2) -> Option<Packet<T2, M>> {
         unsafe {
             let len = self.data_len();
             let size = header.offset();
@@ -343,7 +376,7 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
                     let final_dst = self.payload();
                     let move_loc = final_dst.add(size);
                     let to_move = len - offset;
-                    ptr::copy_nonoverlapping(final_dst, move_loc, to_move);
+                    ptr::copy(final_dst, move_loc, to_move);
                     final_dst as *mut T2
                 } else {
                     self.payload() as *mut T2
@@ -356,14 +389,20 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
         }
     }
 
-    /// Remove data at the top of the payload, useful when removing headers.
+    /// Remove data at the top of the payload, useful when removing headers. Everything in front of the payload is
+    /// moved down over the removed bytes.
     #[inline]
     pub fn remove_from_payload_head(&mut self, size: usize) -> Result<()> {
+        if size > self.payload_size() {
+            return Err(ErrorKind::BadOffset(size).into());
+        }
         unsafe {
             let src = self.data_base();
             let dst = src.add(size);
-            ptr::copy_nonoverlapping(src, dst, size);
+            ptr::copy(src, dst, self.offset() + self.payload_offset());
             (*self.mbuf).remove_data_beginning(size);
+            let header = self.header_u8().add(size);
+            self.set_header(header);
             Ok(())
         }
     }
@@ -384,6 +423,76 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
         }
     }
 
+    /// Grow the packet by `size` bytes at the front, in the mbuf's headroom, and return it reset to its new start, e.g.,
+    /// to write outer tunnel headers. `None` is returned when the headroom is too small.
+    #[inline]
+    pub fn add_to_head(mut self, size: usize) -> Option<Packet<NullHeader, M>> {
+        unsafe {
+            if (*self.mbuf).add_data_beginning(size) < size {
+                return None;
+            }
+            let header = self.data_base() as *mut NullHeader;
+            Some(create_packet(self.get_mbuf_ref(), header, 0))
+        }
+    }
+
+    /// Remove the first `size` bytes of the packet and return it reset to its new start, e.g., to strip outer tunnel
+    /// headers. `None` is returned when the packet is shorter.
+    #[inline]
+    pub fn remove_from_head(mut self, size: usize) -> Option<Packet<NullHeader, M>> {
+        if size > self.data_len() {
+            return None;
+        }
+        unsafe {
+            (*self.mbuf).remove_data_beginning(size);
+            let header = self.data_base() as *mut NullHeader;
+            Some(create_packet(self.get_mbuf_ref(), header, 0))
+        }
+    }
+
+    /// Insert `header` right in front of this header, moving the headers before it into the mbuf's headroom, and return
+    /// the packet at the inserted header, e.g., to put an outer IP header in front of an IP packet. Room is made for
+    /// `header.offset()` bytes, of which `T2::size()` are copied from `header`. `None` is returned when the headroom is
+    /// too small.
+    #[inline]
+    pub fn prepend_header<T2: EndOffset>(mut self, header: &T2) -> Option<Packet<T2, M>>
+    where
+        T: Follows<T2>,
+    {
+        unsafe {
+            let size = header.offset();
+            let before = self.offset();
+            if (*self.mbuf).add_data_beginning(size) < size {
+                return None;
+            }
+            let base = self.data_base();
+            ptr::copy(base.add(size), base, before);
+            let dst = base.add(before) as *mut T2;
+            ptr::copy_nonoverlapping(header as *const T2, dst, 1);
+            Some(create_packet(self.get_mbuf_ref(), dst, before))
+        }
+    }
+
+    /// Remove the `size` bytes starting at this header, moving the headers before it up, and return the packet at the
+    /// header that followed them, e.g., to strip an outer IP header. `None` is returned when the packet is shorter.
+    #[inline]
+    pub fn remove_headers<T2: EndOffset<PreviousHeader = T::PreviousHeader>>(
+        mut self,
+        size: usize,
+    ) -> Option<Packet<T2, M>> {
+        let before = self.offset();
+        if before + size + T2::size() > self.data_len() {
+            return None;
+        }
+        unsafe {
+            let base = self.data_base();
+            ptr::copy(base, base.add(size), before);
+            (*self.mbuf).remove_data_beginning(size);
+            let header = self.data_base().add(before) as *mut T2;
+            Some(create_packet(self.get_mbuf_ref(), header, before))
+        }
+    }
+
     /// Remove *size* off from the payload tail.
     #[inline]
     pub fn remove_from_payload_tail(&mut self, size: usize) -> Result<()> {
@@ -422,7 +531,7 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
 
     /// Parse header.
     #[inline]
-    pub fn parse_header<T2: EndOffset<PreviousHeader = T>>(mut self) -> Packet<T2, M> {
+    pub fn parse_header<T2: Follows<T>>(mut self) -> Packet<T2, M> {
         unsafe {
             assert! {self.payload_size() >= T2::size()}
             let hdr = self.payload() as *mut T2;
@@ -431,9 +540,16 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
         }
     }
 
+    /// Returns true if the payload holds a complete `T2` header that passes `Follows::check_correct_after`.
+    #[inline]
+    pub fn payload_header_correct<T2: Follows<T>>(&self) -> bool {
+        self.payload_size() >= T2::size()
+            && unsafe { (*(self.payload() as *const T2)).check_correct_after(self.get_header(), self.payload_size()) }
+    }
+
     /// Parse header and record.
     #[inline]
-    pub fn parse_header_and_record<T2: EndOffset<PreviousHeader = T>>(mut self) -> Packet<T2, M> {
+    pub fn parse_header_and_record<T2: Follows<T>>(mut self) -> Packet<T2, M> {
         unsafe {
             assert! {self.payload_size() >= T2::size()}
             let hdr = self.payload() as *mut T2;
@@ -521,6 +637,88 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
         }
     }
 
+    /// Whether mbufs are chained to this packet's, holding data past its payload (see `write_chained`).
+    #[inline]
+    pub fn is_chained(&self) -> bool {
+        unsafe { !(*self.mbuf).next.is_null() }
+    }
+
+    /// The payload followed by the data of the mbufs chained to this packet's, one slice per mbuf.
+    pub fn payload_segments(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        let mut next = unsafe { (*self.mbuf).next };
+        std::iter::once(self.get_payload()).chain(std::iter::from_fn(move || unsafe {
+            if next.is_null() {
+                return None;
+            }
+            let segment = &*next;
+            next = segment.next;
+            Some(slice::from_raw_parts(
+                segment.data_address(0) as *const u8,
+                segment.data_len(),
+            ))
+        }))
+    }
+
+    /// Replace the bytes from the header on by `bytes`. What does not fit into this packet's mbuf goes into new mbufs
+    /// chained to it: the payload is the part in this packet's mbuf and `payload_segments` has the rest. Pcap ports
+    /// send chained packets whole, PMD ports only when started with TSO, which enables multi-segment transmit (use
+    /// `Packet::fragment` otherwise). Fails, leaving the packet untouched, when mbufs cannot be allocated.
+    pub fn write_chained(&mut self, bytes: &[u8]) -> Result<()> {
+        let offset = self.offset();
+        let room = unsafe { (*self.mbuf).data_len() + (*self.mbuf).pkt_tailroom() } - offset;
+        let head = bytes.len().min(room);
+        let mut segments = Vec::new();
+        let mut rest = &bytes[head..];
+        while !rest.is_empty() {
+            let segment = unsafe { mbuf_alloc() };
+            if segment.is_null() {
+                for segment in segments {
+                    unsafe { mbuf_free(segment) };
+                }
+                return Err(ErrorKind::FailedAllocation.into());
+            }
+            unsafe {
+                let len = rest.len().min((*segment).pkt_tailroom());
+                (*segment).add_data_end(len);
+                ptr::copy_nonoverlapping(rest.as_ptr(), (*segment).data_address(0), len);
+                rest = &rest[len..];
+            }
+            segments.push(segment);
+        }
+
+        self.free_chain();
+        unsafe {
+            let mbuf = &mut *self.mbuf;
+            let current = mbuf.data_len() - offset;
+            if head > current {
+                mbuf.add_data_end(head - current);
+            } else {
+                mbuf.remove_data_end(current - head);
+            }
+            ptr::copy_nonoverlapping(bytes.as_ptr(), self.header_u8(), head);
+            for pair in segments.windows(2) {
+                (*pair[0]).next = pair[1];
+            }
+            mbuf.next = segments.first().copied().unwrap_or(ptr::null_mut());
+            mbuf.nb_segs = 1 + segments.len() as u16;
+            mbuf.pkt_len = (offset + bytes.len()) as u32;
+        }
+        Ok(())
+    }
+
+    /// Free the mbufs chained to this packet's, if any, so it only holds its payload.
+    pub(crate) fn free_chain(&mut self) {
+        unsafe {
+            let mbuf = &mut *self.mbuf;
+            if !mbuf.next.is_null() {
+                mbuf_free(mbuf.next);
+                mbuf.next = ptr::null_mut();
+                mbuf.nb_segs = 1;
+                mbuf.pkt_len = u32::from(mbuf.data_len);
+            }
+        }
+    }
+
     /// Increase payload size by *increase_by*.
     #[inline]
     pub fn increase_payload_size(&mut self, increase_by: usize) -> usize {
@@ -580,3 +778,89 @@ impl<T: EndOffset, M: Sized + Send> Packet<T, M> {
         mbuf
     }
 }
+
+impl<T: PseudoHeader, M: Sized + Send> Packet<T, M> {
+    /// Location of the TCP or UDP segment carried by this packet, as its protocol and offset from the header. `None` for
+    /// other protocols and for segments that do not fit in the mbuf.
+    #[inline]
+    fn l4_segment(&self) -> Option<(u8, usize)> {
+        let hdr = self.get_header();
+        let available = self.header_available();
+        let proto = hdr.upper_layer_protocol(available)?;
+        let l4_offset = hdr.upper_layer_offset(available);
+        let min_len = match proto {
+            6 => TcpHeader::size(),
+            17 => UdpHeader::size(),
+            _ => return None,
+        };
+        let len = hdr.upper_layer_len(available);
+        if len >= min_len && self.offset() + l4_offset + len <= self.data_len() {
+            Some((proto, l4_offset))
+        } else {
+            None
+        }
+    }
+
+    /// Fill in the IPv4 header checksum and the checksum of the TCP or UDP segment this packet carries. With
+    /// `ChecksumMode::Software` they are computed here. With `ChecksumMode::Offload` the IPv4 checksum is zeroed, the
+    /// TCP/UDP checksum is seeded with the pseudo-header sum and the mbuf is flagged for the NIC to finish the job; the
+    /// port must have been configured with checksum offload (`PortConfiguration.csum`).
+    pub fn update_checksums(&mut self, mode: ChecksumMode) {
+        self.get_mut_header().update_header_checksum(mode);
+        let (proto, l4_offset) = match self.l4_segment() {
+            Some(segment) => segment,
+            None => {
+                if mode == ChecksumMode::Offload {
+                    self.request_checksum_offload(self.payload_offset(), 0);
+                }
+                return;
+            }
+        };
+        unsafe {
+            let hdr = &*self.header();
+            let segment = self.header_u8().add(l4_offset);
+            let available = self.header_available() - l4_offset;
+            match (mode, proto) {
+                (ChecksumMode::Software, 6) => (*(segment as *mut TcpHeader)).update_checksum(hdr, available),
+                (ChecksumMode::Software, _) => (*(segment as *mut UdpHeader)).update_checksum(hdr, available),
+                (ChecksumMode::Offload, _) => {
+                    let len = hdr.upper_layer_len(self.header_available());
+                    let sum = ones_complement_fold(hdr.pseudo_header_sum(proto, len));
+                    if proto == 6 {
+                        (*(segment as *mut TcpHeader)).set_checksum(sum);
+                    } else {
+                        (*(segment as *mut UdpHeader)).set_checksum(sum);
+                    }
+                    self.request_checksum_offload(l4_offset, proto);
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn request_checksum_offload(&mut self, l3_len: usize, l4_proto: u8) {
+        let ipv6 = (self.get_header().ip_version() == 6) as i32;
+        unsafe { mbuf_csum_offload(self.mbuf, self.offset() as u16, l3_len as u16, ipv6, l4_proto) }
+    }
+
+    /// Returns true if the IPv4 header checksum (for IPv4 packets) and the TCP or UDP checksum (if the packet carries a
+    /// complete TCP or UDP segment) are correct.
+    pub fn checksums_valid(&self) -> bool {
+        let hdr = self.get_header();
+        if !hdr.header_checksum_valid() {
+            return false;
+        }
+        match self.l4_segment() {
+            Some((proto, l4_offset)) => unsafe {
+                let segment = self.header_u8().add(l4_offset);
+                let available = self.header_available() - l4_offset;
+                if proto == 6 {
+                    (*(segment as *const TcpHeader)).checksum_valid(hdr, available)
+                } else {
+                    (*(segment as *const UdpHeader)).checksum_valid(hdr, available)
+                }
+            },
+            None => true,
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub use self::pcap_port::*;
 pub use self::phy_port::*;
 pub use self::virt_port::*;
 
@@ -6,8 +7,10 @@ use crate::allocators::*;
 use crate::common::*;
 use crate::interface::{PacketRx, PacketTx};
 use crate::native::zcsi::MBuf;
+use crate::utils::ChecksumMode;
 use std::sync::atomic::AtomicUsize;
 
+mod pcap_port;
 mod phy_port;
 mod virt_port;
 
@@ -37,4 +40,14 @@ impl<T: PacketTx> PacketTx for CacheAligned<T> {
     fn send(&self, pkts: &mut [*mut MBuf]) -> Result<u32> {
         T::send(&*self, pkts)
     }
+
+    #[inline]
+    fn checksum_mode(&self) -> ChecksumMode {
+        T::checksum_mode(&*self)
+    }
+
+    #[inline]
+    fn mtu(&self) -> Option<u16> {
+        T::mtu(&*self)
+    }
 }
@@ -0,0 +1,717 @@
+use super::super::{PacketRx, PacketTx};
+use super::PortStats;
+use crate::allocators::*;
+use crate::common::*;
+use crate::native::zcsi::*;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Magic number for classic libpcap files with microsecond timestamps.
+const PCAP_MAGIC_USEC: u32 = 0xa1b2_c3d4;
+/// Magic number for classic libpcap files with nanosecond timestamps.
+const PCAP_MAGIC_NSEC: u32 = 0xa1b2_3c4d;
+/// Block type of a pcapng section header block.
+const PCAPNG_SHB: u32 = 0x0a0d_0d0a;
+/// Block type of a pcapng interface description block.
+const PCAPNG_IDB: u32 = 0x0000_0001;
+/// Block type of a pcapng simple packet block.
+const PCAPNG_SPB: u32 = 0x0000_0003;
+/// Block type of a pcapng enhanced packet block.
+const PCAPNG_EPB: u32 = 0x0000_0006;
+/// Byte order magic found in the pcapng section header.
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+/// Interface option carrying the timestamp resolution.
+const PCAPNG_OPT_IF_TSRESOL: u16 = 9;
+/// Ethernet link type.
+const LINKTYPE_ETHERNET: u32 = 1;
+/// Snap length written into output captures, also the largest frame read from classic captures.
+const PCAP_SNAPLEN: u32 = 65535;
+/// Largest pcapng block we read, as in libpcap.
+const PCAPNG_MAX_BLOCK: usize = 16 * 1024 * 1024;
+
+/// A single frame read from a capture. Timestamps are kept in nanoseconds.
+#[derive(Debug)]
+struct PcapRecord {
+    ts_ns: u64,
+    data: Vec<u8>,
+}
+
+/// The two on-disk formats we understand.
+#[derive(Debug)]
+enum PcapFormat {
+    /// Classic libpcap, `ts_scale` converts the sub-second field to nanoseconds. Records longer than `snaplen` are
+    /// rejected.
+    Classic {
+        big_endian: bool,
+        ts_scale: u64,
+        snaplen: usize,
+    },
+    /// pcapng, with the timestamp resolution (in ns per tick, or the power of 2 divisor) for each interface.
+    Ng {
+        big_endian: bool,
+        if_tsresol: Vec<TsResolution>,
+    },
+}
+
+/// Timestamp resolution of a pcapng interface.
+#[derive(Debug, Clone, Copy)]
+enum TsResolution {
+    /// Ticks are 10^-x seconds.
+    Decimal(u32),
+    /// Ticks are 2^-x seconds.
+    Binary(u32),
+}
+
+impl TsResolution {
+    fn to_ns(self, ticks: u64) -> u64 {
+        match self {
+            TsResolution::Decimal(exp) if exp <= 9 => ticks.saturating_mul(10u64.pow(9 - exp)),
+            TsResolution::Decimal(exp) => ticks / 10u64.pow((exp - 9).min(19)),
+            TsResolution::Binary(exp) => ((ticks as u128 * 1_000_000_000) >> exp.min(127)) as u64,
+        }
+    }
+}
+
+#[inline]
+fn read_u16(big_endian: bool, buf: &[u8]) -> u16 {
+    if big_endian {
+        BigEndian::read_u16(buf)
+    } else {
+        LittleEndian::read_u16(buf)
+    }
+}
+
+#[inline]
+fn read_u32(big_endian: bool, buf: &[u8]) -> u32 {
+    if big_endian {
+        BigEndian::read_u32(buf)
+    } else {
+        LittleEndian::read_u32(buf)
+    }
+}
+
+fn bad_pcap(path: &str, reason: &str) -> Error {
+    ErrorKind::BadPcap(String::from(path), String::from(reason)).into()
+}
+
+/// Reads frames out of a libpcap or pcapng file.
+#[derive(Debug)]
+struct PcapReader {
+    path: String,
+    file: BufReader<File>,
+    format: PcapFormat,
+}
+
+impl PcapReader {
+    fn open(path: &str) -> Result<PcapReader> {
+        let file = BufReader::new(File::open(path)?);
+        let mut reader = PcapReader {
+            path: String::from(path),
+            file,
+            format: PcapFormat::Classic {
+                big_endian: false,
+                ts_scale: 1000,
+                snaplen: PCAP_SNAPLEN as usize,
+            },
+        };
+        reader.read_file_header()?;
+        Ok(reader)
+    }
+
+    /// Go back to the beginning of the capture.
+    fn rewind(&mut self) -> Result<()> {
+        self.file.seek(SeekFrom::Start(0))?;
+        self.read_file_header()
+    }
+
+    fn read_file_header(&mut self) -> Result<()> {
+        let mut magic = [0u8; 4];
+        self.file.read_exact(&mut magic)?;
+        if LittleEndian::read_u32(&magic) == PCAPNG_SHB {
+            self.format = PcapFormat::Ng {
+                big_endian: false,
+                if_tsresol: vec![],
+            };
+            // The section header is parsed as a regular block so it also sets the byte order.
+            self.file.seek(SeekFrom::Start(0))?;
+            return Ok(());
+        }
+        let (big_endian, ts_scale) = match (LittleEndian::read_u32(&magic), BigEndian::read_u32(&magic)) {
+            (PCAP_MAGIC_USEC, _) => (false, 1000),
+            (PCAP_MAGIC_NSEC, _) => (false, 1),
+            (_, PCAP_MAGIC_USEC) => (true, 1000),
+            (_, PCAP_MAGIC_NSEC) => (true, 1),
+            _ => return Err(bad_pcap(&self.path, "unknown magic number")),
+        };
+        // Skip version, thiszone, sigfigs; check the link type.
+        let mut rest = [0u8; 20];
+        self.file.read_exact(&mut rest)?;
+        if read_u32(big_endian, &rest[16..20]) != LINKTYPE_ETHERNET {
+            return Err(bad_pcap(&self.path, "only ethernet captures are supported"));
+        }
+        // Records are allocated before reading them, so do not trust a snap length beyond what we write ourselves.
+        let snaplen = match read_u32(big_endian, &rest[12..16]) {
+            0 => PCAP_SNAPLEN,
+            snaplen => snaplen.min(PCAP_SNAPLEN),
+        };
+        self.format = PcapFormat::Classic {
+            big_endian,
+            ts_scale,
+            snaplen: snaplen as usize,
+        };
+        Ok(())
+    }
+
+    /// Read the next frame, returning `None` at the end of the file.
+    fn next_record(&mut self) -> Result<Option<PcapRecord>> {
+        match self.format {
+            PcapFormat::Classic {
+                big_endian,
+                ts_scale,
+                snaplen,
+            } => self.next_classic_record(big_endian, ts_scale, snaplen),
+            PcapFormat::Ng { .. } => self.next_ng_record(),
+        }
+    }
+
+    fn next_classic_record(&mut self, big_endian: bool, ts_scale: u64, snaplen: usize) -> Result<Option<PcapRecord>> {
+        let mut hdr = [0u8; 16];
+        if !self.read_or_eof(&mut hdr)? {
+            return Ok(None);
+        }
+        let ts_sec = read_u32(big_endian, &hdr[0..4]) as u64;
+        let ts_frac = read_u32(big_endian, &hdr[4..8]) as u64;
+        let incl_len = read_u32(big_endian, &hdr[8..12]) as usize;
+        if incl_len > snaplen {
+            return Err(bad_pcap(&self.path, "record longer than the snap length"));
+        }
+        let mut data = vec![0u8; incl_len];
+        self.file.read_exact(&mut data)?;
+        Ok(Some(PcapRecord {
+            ts_ns: ts_sec * 1_000_000_000 + ts_frac * ts_scale,
+            data,
+        }))
+    }
+
+    fn next_ng_record(&mut self) -> Result<Option<PcapRecord>> {
+        loop {
+            let mut hdr = [0u8; 8];
+            if !self.read_or_eof(&mut hdr)? {
+                return Ok(None);
+            }
+            let block_type = LittleEndian::read_u32(&hdr[0..4]);
+            if block_type == PCAPNG_SHB {
+                // Block type is a palindrome, the byte order magic tells us how to read the rest.
+                let mut bom = [0u8; 4];
+                self.file.read_exact(&mut bom)?;
+                let big_endian = match LittleEndian::read_u32(&bom) {
+                    PCAPNG_BYTE_ORDER_MAGIC => false,
+                    _ if BigEndian::read_u32(&bom) == PCAPNG_BYTE_ORDER_MAGIC => true,
+                    _ => return Err(bad_pcap(&self.path, "bad pcapng byte order magic")),
+                };
+                let total_len = read_u32(big_endian, &hdr[4..8]) as usize;
+                if !(16..=PCAPNG_MAX_BLOCK).contains(&total_len) {
+                    return Err(bad_pcap(&self.path, "bad section header length"));
+                }
+                let mut body = vec![0u8; total_len - 12];
+                self.file.read_exact(&mut body)?;
+                self.format = PcapFormat::Ng {
+                    big_endian,
+                    if_tsresol: vec![],
+                };
+                continue;
+            }
+
+            let big_endian = match self.format {
+                PcapFormat::Ng { big_endian, .. } => big_endian,
+                _ => unreachable!(),
+            };
+            let block_type = read_u32(big_endian, &hdr[0..4]);
+            let total_len = read_u32(big_endian, &hdr[4..8]) as usize;
+            if total_len < 12 || total_len % 4 != 0 || total_len > PCAPNG_MAX_BLOCK {
+                return Err(bad_pcap(&self.path, "bad pcapng block length"));
+            }
+            // Body plus the trailing copy of the length.
+            let mut body = vec![0u8; total_len - 8];
+            self.file.read_exact(&mut body)?;
+            let body = &body[..total_len - 12];
+
+            match block_type {
+                PCAPNG_IDB => {
+                    if body.len() < 8 {
+                        return Err(bad_pcap(&self.path, "truncated interface description"));
+                    }
+                    if read_u16(big_endian, &body[0..2]) as u32 != LINKTYPE_ETHERNET {
+                        return Err(bad_pcap(&self.path, "only ethernet captures are supported"));
+                    }
+                    let resolution = Self::if_tsresol(big_endian, &body[8..]);
+                    if let PcapFormat::Ng { ref mut if_tsresol, .. } = self.format {
+                        if_tsresol.push(resolution);
+                    }
+                }
+                PCAPNG_EPB => {
+                    if body.len() < 20 {
+                        return Err(bad_pcap(&self.path, "truncated enhanced packet block"));
+                    }
+                    let iface = read_u32(big_endian, &body[0..4]) as usize;
+                    let ticks =
+                        ((read_u32(big_endian, &body[4..8]) as u64) << 32) | read_u32(big_endian, &body[8..12]) as u64;
+                    let cap_len = read_u32(big_endian, &body[12..16]) as usize;
+                    if 20 + cap_len > body.len() {
+                        return Err(bad_pcap(&self.path, "enhanced packet block overruns block"));
+                    }
+                    let resolution = match self.format {
+                        PcapFormat::Ng { ref if_tsresol, .. } => {
+                            if_tsresol.get(iface).cloned().unwrap_or(TsResolution::Decimal(6))
+                        }
+                        _ => unreachable!(),
+                    };
+                    return Ok(Some(PcapRecord {
+                        ts_ns: resolution.to_ns(ticks),
+                        data: body[20..20 + cap_len].to_vec(),
+                    }));
+                }
+                PCAPNG_SPB => {
+                    if body.len() < 4 {
+                        return Err(bad_pcap(&self.path, "truncated simple packet block"));
+                    }
+                    let orig_len = read_u32(big_endian, &body[0..4]) as usize;
+                    let cap_len = orig_len.min(body.len() - 4);
+                    // Simple packets carry no timestamp, so they are replayed back to back.
+                    return Ok(Some(PcapRecord {
+                        ts_ns: 0,
+                        data: body[4..4 + cap_len].to_vec(),
+                    }));
+                }
+                // Name resolution, statistics and custom blocks are skipped.
+                _ => {}
+            }
+        }
+    }
+
+    /// Walk the IDB options looking for `if_tsresol`, defaulting to microseconds.
+    fn if_tsresol(big_endian: bool, mut options: &[u8]) -> TsResolution {
+        while options.len() >= 4 {
+            let code = read_u16(big_endian, &options[0..2]);
+            let len = read_u16(big_endian, &options[2..4]) as usize;
+            if code == 0 || options.len() < 4 + len {
+                break;
+            }
+            if code == PCAPNG_OPT_IF_TSRESOL && len >= 1 {
+                let v = options[4];
+                return if v & 0x80 == 0 {
+                    TsResolution::Decimal(v as u32)
+                } else {
+                    TsResolution::Binary((v & 0x7f) as u32)
+                };
+            }
+            let padded = (len + 3) & !3;
+            options = &options[(4 + padded).min(options.len())..];
+        }
+        TsResolution::Decimal(6)
+    }
+
+    /// Fill `buf` completely, returning false if the file ended before anything was read.
+    fn read_or_eof(&mut self, buf: &mut [u8]) -> Result<bool> {
+        let mut read = 0;
+        while read < buf.len() {
+            match self.file.read(&mut buf[read..])? {
+                0 if read == 0 => return Ok(false),
+                0 => return Err(bad_pcap(&self.path, "truncated record")),
+                n => read += n,
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Writes frames to a classic (microsecond) libpcap file.
+#[derive(Debug)]
+struct PcapWriter {
+    file: BufWriter<File>,
+}
+
+impl PcapWriter {
+    fn create(path: &str) -> Result<PcapWriter> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let mut hdr = [0u8; 24];
+        LittleEndian::write_u32(&mut hdr[0..4], PCAP_MAGIC_USEC);
+        LittleEndian::write_u16(&mut hdr[4..6], 2);
+        LittleEndian::write_u16(&mut hdr[6..8], 4);
+        LittleEndian::write_u32(&mut hdr[16..20], PCAP_SNAPLEN);
+        LittleEndian::write_u32(&mut hdr[20..24], LINKTYPE_ETHERNET);
+        file.write_all(&hdr)?;
+        Ok(PcapWriter { file })
+    }
+
+    fn write_frame(&mut self, data: &[u8]) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut hdr = [0u8; 16];
+        LittleEndian::write_u32(&mut hdr[0..4], now.as_secs() as u32);
+        LittleEndian::write_u32(&mut hdr[4..8], now.subsec_micros());
+        LittleEndian::write_u32(&mut hdr[8..12], data.len() as u32);
+        LittleEndian::write_u32(&mut hdr[12..16], data.len() as u32);
+        self.file.write_all(&hdr)?;
+        self.file.write_all(data)?;
+        Ok(())
+    }
+
+    /// Write the frames in `pkts` and flush them to the file. Frames in chained mbufs are gathered first.
+    fn write_frames(&mut self, pkts: &[*mut MBuf]) -> Result<()> {
+        let mut gathered = Vec::new();
+        for &mbuf in pkts {
+            let data = unsafe { std::slice::from_raw_parts((*mbuf).data_address(0) as *const u8, (*mbuf).data_len()) };
+            if unsafe { (*mbuf).next.is_null() } {
+                self.write_frame(data)?;
+                continue;
+            }
+            gathered.clear();
+            gathered.extend_from_slice(data);
+            let mut segment = unsafe { (*mbuf).next };
+            while !segment.is_null() {
+                unsafe {
+                    let data =
+                        std::slice::from_raw_parts((*segment).data_address(0) as *const u8, (*segment).data_len());
+                    gathered.extend_from_slice(data);
+                    segment = (*segment).next;
+                }
+            }
+            self.write_frame(&gathered)?;
+        }
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Parsed form of a `pcap:` port name.
+///
+/// The name is a comma separated list of `key=value` pairs:
+/// -   `rx_pcap=<file>`: capture (libpcap or pcapng) replayed on receive.
+/// -   `tx_pcap=<file>`: capture that sent packets are written to. Without it sent packets are dropped.
+/// -   `loop=<n>`: replay the input `n` times, `0` (the default is 1) loops forever.
+/// -   `pace=<factor>`: pace replay using the capture timestamps, sped up by `factor`. Without it packets are
+///     replayed as fast as they can be received.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PcapSpec {
+    /// Input capture.
+    pub rx_pcap: Option<String>,
+    /// Output capture.
+    pub tx_pcap: Option<String>,
+    /// Number of times to replay the input, 0 loops forever.
+    pub loops: u64,
+    /// Replay speedup relative to the capture timestamps, `None` disables pacing.
+    pub pace: Option<f64>,
+}
+
+impl PcapSpec {
+    /// Parse the part of a port name following `pcap:`.
+    pub fn parse(spec: &str) -> Result<PcapSpec> {
+        let mut parsed = PcapSpec {
+            rx_pcap: None,
+            tx_pcap: None,
+            loops: 1,
+            pace: None,
+        };
+        for part in spec.split(',').filter(|p| !p.is_empty()) {
+            let kv: Vec<_> = part.splitn(2, '=').collect();
+            match (kv[0], kv.get(1)) {
+                ("rx_pcap", Some(v)) => parsed.rx_pcap = Some(String::from(*v)),
+                ("tx_pcap", Some(v)) => parsed.tx_pcap = Some(String::from(*v)),
+                ("loop", Some(v)) => {
+                    parsed.loops = v
+                        .parse()
+                        .map_err(|_| ErrorKind::BadVdev(format!("pcap:{} (bad loop count {})", spec, v)))?
+                }
+                ("pace", Some(v)) => match v.parse::<f64>() {
+                    Ok(f) if f > 0.0 => parsed.pace = Some(f),
+                    _ => return Err(ErrorKind::BadVdev(format!("pcap:{} (bad pace factor {})", spec, v)).into()),
+                },
+                _ => return Err(ErrorKind::BadVdev(format!("pcap:{} (unknown option {})", spec, part)).into()),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// Replay state shared by the queues of a pcap port.
+#[derive(Debug)]
+struct PcapReplay {
+    reader: PcapReader,
+    /// Replays completed so far.
+    loops_done: u64,
+    /// Set once the input has been replayed `loops` times.
+    exhausted: bool,
+    /// A frame read from the capture that is not due yet.
+    pending: Option<PcapRecord>,
+    /// Timestamp of the first frame in the capture.
+    first_ts: Option<u64>,
+    /// Timestamp of the last frame, used to lay successive loops end to end.
+    last_ts: u64,
+    /// Offset added to capture timestamps for the current loop.
+    loop_offset: u64,
+    /// Wall clock time replay started.
+    started: Option<Instant>,
+    /// An error reading the capture, held back until the frames received before it were handed out.
+    error: Option<Error>,
+}
+
+/// A port backed by capture files rather than a NIC. Received packets are read out of a libpcap or pcapng file into
+/// freshly allocated mbufs, sent packets are appended to an output capture (or dropped). Send and receive should be
+/// called on a `PcapQueue`.
+#[derive(Debug)]
+pub struct PcapPort {
+    spec: PcapSpec,
+    replay: Option<Mutex<PcapReplay>>,
+    writer: Option<Mutex<PcapWriter>>,
+    stats_rx: Arc<CacheAligned<PortStats>>,
+    stats_tx: Arc<CacheAligned<PortStats>>,
+    /// Frames that did not fit in an mbuf and were truncated.
+    truncated: Arc<CacheAligned<PortStats>>,
+    /// Frames that could not be written to the output capture.
+    tx_errors: Arc<CacheAligned<PortStats>>,
+    rx_stopped: AtomicBool,
+}
+
+/// A queue on a `PcapPort`. All queues of a port share the same input and output captures.
+#[derive(Clone, Debug)]
+pub struct PcapQueue {
+    /// Port for the queue.
+    pub port: Arc<PcapPort>,
+    queue: i32,
+}
+
+impl fmt::Display for PcapQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "pcap queue {} rx: {} tx: {}",
+            self.queue,
+            self.port.spec.rx_pcap.as_ref().map_or("-", |s| &s[..]),
+            self.port.spec.tx_pcap.as_ref().map_or("-", |s| &s[..])
+        )
+    }
+}
+
+impl PcapPort {
+    /// Create a port from the part of its name following `pcap:`.
+    pub fn new(spec: &str) -> Result<Arc<PcapPort>> {
+        PcapPort::new_with_spec(PcapSpec::parse(spec)?)
+    }
+
+    /// Create a port from a parsed spec.
+    pub fn new_with_spec(spec: PcapSpec) -> Result<Arc<PcapPort>> {
+        let replay = match spec.rx_pcap {
+            Some(ref path) => Some(Mutex::new(PcapReplay {
+                reader: PcapReader::open(path)?,
+                loops_done: 0,
+                exhausted: false,
+                pending: None,
+                first_ts: None,
+                last_ts: 0,
+                loop_offset: 0,
+                started: None,
+                error: None,
+            })),
+            None => None,
+        };
+        let writer = match spec.tx_pcap {
+            Some(ref path) => Some(Mutex::new(PcapWriter::create(path)?)),
+            None => None,
+        };
+        Ok(Arc::new(PcapPort {
+            spec,
+            replay,
+            writer,
+            stats_rx: Arc::new(PortStats::new()),
+            stats_tx: Arc::new(PortStats::new()),
+            truncated: Arc::new(PortStats::new()),
+            tx_errors: Arc::new(PortStats::new()),
+            rx_stopped: AtomicBool::new(false),
+        }))
+    }
+
+    /// Create a queue on this port.
+    pub fn new_pcap_queue(port: &Arc<PcapPort>, queue: i32) -> Result<CacheAligned<PcapQueue>> {
+        Ok(CacheAligned::allocate(PcapPort::queue(port, queue)))
+    }
+
+    /// A queue on this port, as used by a `PortQueue` on a port configured with a `pcap:` name.
+    pub(crate) fn queue(port: &Arc<PcapPort>, queue: i32) -> PcapQueue {
+        PcapQueue {
+            port: port.clone(),
+            queue,
+        }
+    }
+
+    /// Get stats for the port, as (received, sent).
+    pub fn stats(&self) -> (usize, usize) {
+        (
+            self.stats_rx.stats.load(Ordering::Relaxed),
+            self.stats_tx.stats.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Number of received frames that were larger than an mbuf and had to be truncated.
+    pub fn truncated(&self) -> usize {
+        self.truncated.stats.load(Ordering::Relaxed)
+    }
+
+    /// Number of sent frames that were dropped because writing the output capture failed.
+    pub fn tx_errors(&self) -> usize {
+        self.tx_errors.stats.load(Ordering::Relaxed)
+    }
+
+    /// True once the input capture has been replayed the requested number of times.
+    pub fn rx_done(&self) -> bool {
+        match self.replay {
+            Some(ref replay) => {
+                let replay = replay.lock().unwrap();
+                replay.exhausted && replay.pending.is_none()
+            }
+            None => true,
+        }
+    }
+
+    /// Stop replaying the input capture; its queues return no more packets.
+    pub fn stop_rx(&self) {
+        self.rx_stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// Resume replaying the input capture after `stop_rx`.
+    pub fn start_rx(&self) {
+        self.rx_stopped.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether `stop_rx` was called.
+    pub fn rx_stopped(&self) -> bool {
+        self.rx_stopped.load(Ordering::Relaxed)
+    }
+
+    /// Fetch the next frame that is due according to the pacing configuration.
+    fn next_due(&self, replay: &mut PcapReplay) -> Result<Option<PcapRecord>> {
+        if replay.pending.is_none() {
+            if replay.exhausted {
+                return Ok(None);
+            }
+            let record = match replay.reader.next_record()? {
+                Some(r) => Some(r),
+                None => {
+                    replay.loops_done += 1;
+                    if (self.spec.loops != 0 && replay.loops_done >= self.spec.loops) || replay.first_ts.is_none() {
+                        replay.exhausted = true;
+                        None
+                    } else {
+                        // Lay the next loop immediately after the last frame of this one.
+                        let first = replay.first_ts.unwrap_or(0);
+                        replay.loop_offset += replay.last_ts.saturating_sub(first) + 1;
+                        replay.reader.rewind()?;
+                        replay.reader.next_record()?
+                    }
+                }
+            };
+            replay.pending = record.map(|mut r| {
+                replay.last_ts = r.ts_ns;
+                if replay.first_ts.is_none() {
+                    replay.first_ts = Some(r.ts_ns);
+                }
+                r.ts_ns = r.ts_ns.saturating_sub(replay.first_ts.unwrap()) + replay.loop_offset;
+                r
+            });
+        }
+
+        if let (Some(pace), Some(ref record)) = (self.spec.pace, &replay.pending) {
+            let started = *replay.started.get_or_insert_with(Instant::now);
+            let elapsed = started.elapsed().as_nanos() as f64 * pace;
+            if (record.ts_ns as f64) > elapsed {
+                return Ok(None);
+            }
+        }
+        Ok(replay.pending.take())
+    }
+
+    fn recv(&self, pkts: &mut [*mut MBuf]) -> Result<u32> {
+        let replay = match self.replay {
+            Some(ref replay) if !self.rx_stopped() => replay,
+            _ => return Ok(0),
+        };
+        let mut replay = replay.lock().unwrap();
+        if let Some(e) = replay.error.take() {
+            return Err(e);
+        }
+        let mut received = 0;
+        while received < pkts.len() {
+            let record = match self.next_due(&mut replay) {
+                Ok(Some(r)) => r,
+                Ok(None) => break,
+                Err(e) if received == 0 => return Err(e),
+                Err(e) => {
+                    replay.error = Some(e);
+                    break;
+                }
+            };
+            if record.data.is_empty() {
+                continue;
+            }
+            let mbuf = unsafe { mbuf_alloc() };
+            if mbuf.is_null() {
+                // Put the frame back and try again on the next call.
+                replay.pending = Some(record);
+                break;
+            }
+            unsafe {
+                // Frames longer than the mbuf keep only their head.
+                let len = record.data.len().min((*mbuf).pkt_tailroom());
+                if len < record.data.len() {
+                    self.truncated.stats.fetch_add(1, Ordering::Relaxed);
+                }
+                (*mbuf).add_data_end(len);
+                ptr::copy_nonoverlapping(record.data.as_ptr(), (*mbuf).data_address(0), len);
+            }
+            pkts[received] = mbuf;
+            received += 1;
+        }
+        self.stats_rx.stats.fetch_add(received, Ordering::Relaxed);
+        Ok(received as u32)
+    }
+
+    fn send(&self, pkts: &mut [*mut MBuf]) -> Result<u32> {
+        let len = pkts.len();
+        let written = match self.writer {
+            Some(ref writer) => writer.lock().unwrap().write_frames(pkts),
+            None => Ok(()),
+        };
+        // The packets are consumed whether or not they made it to the file, so a write error is only counted.
+        unsafe {
+            mbuf_free_bulk(pkts.as_mut_ptr(), len as i32);
+        }
+        match written {
+            Ok(()) => self.stats_tx.stats.fetch_add(len, Ordering::Relaxed),
+            Err(_) => self.tx_errors.stats.fetch_add(len, Ordering::Relaxed),
+        };
+        Ok(len as u32)
+    }
+}
+
+impl PacketRx for PcapQueue {
+    /// Replay the next packets from the input capture. Note this method is internal to NetBricks (should not be
+    /// directly called).
+    #[inline]
+    fn recv(&self, pkts: &mut [*mut MBuf]) -> Result<u32> {
+        self.port.recv(pkts)
+    }
+}
+
+impl PacketTx for PcapQueue {
+    /// Write a batch of packets to the output capture. Note this method is internal to NetBricks (should not be
+    /// directly called).
+    #[inline]
+    fn send(&self, pkts: &mut [*mut MBuf]) -> Result<u32> {
+        self.port.send(pkts)
+    }
+}
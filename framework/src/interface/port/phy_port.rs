@@ -1,15 +1,16 @@
 use super::super::{PacketRx, PacketTx};
-use super::PortStats;
+use super::{PcapPort, PcapQueue, PortStats};
 use crate::allocators::*;
 use crate::common::*;
-use crate::config::{PortConfiguration, NUM_RXD, NUM_TXD};
+use crate::config::{PortConfiguration, DEFAULT_MTU, NUM_RXD, NUM_TXD};
 use crate::headers::MacAddress;
 use crate::native::zcsi::*;
+use crate::utils::ChecksumMode;
 use regex::Regex;
 use std::cmp::min;
 use std::ffi::CString;
 use std::fmt;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 /// A DPDK based PMD port. Send and receive should not be called directly on this structure but on the port queue
@@ -23,6 +24,12 @@ pub struct PmdPort {
     txqs: i32,
     stats_rx: Vec<Arc<CacheAligned<PortStats>>>,
     stats_tx: Vec<Arc<CacheAligned<PortStats>>>,
+    stats_tx_drop: Vec<Arc<CacheAligned<PortStats>>>,
+    csumoffload: bool,
+    rx_interrupts: bool,
+    rx_stopped: AtomicBool,
+    mtu: u16,
+    pcap: Option<Arc<PcapPort>>,
 }
 
 /// A port queue represents a single queue for a physical port, and should be used to send and receive data.
@@ -34,9 +41,11 @@ pub struct PortQueue {
     pub port: Arc<PmdPort>,
     stats_rx: Arc<CacheAligned<PortStats>>,
     stats_tx: Arc<CacheAligned<PortStats>>,
+    stats_tx_drop: Arc<CacheAligned<PortStats>>,
     port_id: u8,
     txq: i32,
     rxq: i32,
+    pcap: Option<PcapQueue>,
 }
 
 impl Drop for PmdPort {
@@ -71,6 +80,11 @@ impl PortQueue {
             let sent = send_pkts(self.port_id, queue, pkts, to_send);
             let update = self.stats_tx.stats.load(Ordering::Relaxed) + sent as usize;
             self.stats_tx.stats.store(update, Ordering::Relaxed);
+            if sent < to_send {
+                // The NIC did not take these, so they are dropped: the batch frees them on its next receive.
+                let update = self.stats_tx_drop.stats.load(Ordering::Relaxed) + (to_send - sent) as usize;
+                self.stats_tx_drop.stats.store(update, Ordering::Relaxed);
+            }
             Ok(sent as u32)
         }
     }
@@ -94,6 +108,41 @@ impl PortQueue {
     pub fn rxq(&self) -> i32 {
         self.rxq
     }
+
+    /// Add this queue's RX interrupt to the calling thread's wait set. Fails unless the port was set up with RX
+    /// interrupts and the driver supports them.
+    pub fn add_rx_interrupt(&self) -> Result<()> {
+        if self.port.rx_interrupts && unsafe { rx_intr_add(self.port_id, self.rxq) } == 0 {
+            Ok(())
+        } else {
+            Err(ErrorKind::BadRxQueue(self.port_id, self.rxq).into())
+        }
+    }
+
+    /// Enable or disable the RX interrupt of this queue.
+    #[inline]
+    pub fn set_rx_interrupt(&self, enabled: bool) {
+        unsafe {
+            if enabled {
+                rx_intr_enable(self.port_id, self.rxq);
+            } else {
+                rx_intr_disable(self.port_id, self.rxq);
+            }
+        }
+    }
+
+    /// Returns true if packets are waiting to be received on this queue, as far as the driver can tell.
+    #[inline]
+    pub fn rx_pending(&self) -> bool {
+        unsafe { rx_queue_count(self.port_id, self.rxq) > 0 }
+    }
+
+    /// Wait up to `timeout_ms` milliseconds for an enabled RX interrupt added by the calling thread. Returns false on
+    /// timeout or error.
+    #[inline]
+    pub fn wait_rx_interrupt(timeout_ms: i32) -> bool {
+        unsafe { rx_intr_wait(timeout_ms) > 0 }
+    }
 }
 
 impl PacketTx for PortQueue {
@@ -101,10 +150,25 @@ impl PacketTx for PortQueue {
     /// called).
     #[inline]
     fn send(&self, pkts: &mut [*mut MBuf]) -> Result<u32> {
+        if let Some(ref pcap) = self.pcap {
+            let sent = pcap.send(pkts)?;
+            self.stats_tx.stats.fetch_add(sent as usize, Ordering::Relaxed);
+            return Ok(sent);
+        }
         let txq = self.txq;
         let len = pkts.len() as i32;
         self.send_queue(txq, pkts.as_mut_ptr(), len)
     }
+
+    #[inline]
+    fn checksum_mode(&self) -> ChecksumMode {
+        self.port.checksum_mode()
+    }
+
+    #[inline]
+    fn mtu(&self) -> Option<u16> {
+        Some(self.port.mtu())
+    }
 }
 
 impl PacketRx for PortQueue {
@@ -112,6 +176,14 @@ impl PacketRx for PortQueue {
     /// called).
     #[inline]
     fn recv(&self, pkts: &mut [*mut MBuf]) -> Result<u32> {
+        if self.port.rx_stopped() {
+            return Ok(0);
+        }
+        if let Some(ref pcap) = self.pcap {
+            let recv = pcap.recv(pkts)?;
+            self.stats_rx.stats.fetch_add(recv as usize, Ordering::Relaxed);
+            return Ok(recv);
+        }
         let rxq = self.rxq;
         let len = pkts.len() as i32;
         self.recv_queue(rxq, pkts.as_mut_ptr(), len)
@@ -169,6 +241,8 @@ impl PmdPort {
                 rxq,
                 stats_rx: port.stats_rx[rxq as usize].clone(),
                 stats_tx: port.stats_tx[txq as usize].clone(),
+                stats_tx_drop: port.stats_tx_drop[txq as usize].clone(),
+                pcap: port.pcap.as_ref().map(|pcap| PcapPort::queue(pcap, rxq)),
             }))
         }
     }
@@ -179,6 +253,18 @@ impl PmdPort {
         self.port
     }
 
+    /// Checksum mode matching whether the port was set up with checksum offload.
+    #[inline]
+    pub fn checksum_mode(&self) -> ChecksumMode {
+        ChecksumMode::from_offload(self.csumoffload)
+    }
+
+    /// Largest IP packet sent on this port, from `PortConfiguration::mtu`.
+    #[inline]
+    pub fn mtu(&self) -> u16 {
+        self.mtu
+    }
+
     /// Get stats for an RX/TX queue pair.
     pub fn stats(&self, queue: i32) -> (usize, usize) {
         let idx = queue as usize;
@@ -188,6 +274,39 @@ impl PmdPort {
         )
     }
 
+    /// Packets a TX queue handed to the NIC that it did not accept, because its ring was full.
+    pub fn tx_drops(&self, queue: i32) -> usize {
+        self.stats_tx_drop[queue as usize].stats.load(Ordering::Relaxed)
+    }
+
+    /// Drops counted by the NIC for the whole port: packets missed because the RX rings were full, and packets
+    /// that could not be received for want of mbufs. `None` if the driver does not report them.
+    pub fn rx_drops(&self) -> Option<(u64, u64)> {
+        let (mut missed, mut nombuf) = (0, 0);
+        if unsafe { port_drops(self.port, &mut missed, &mut nombuf) } == 0 {
+            Some((missed, nombuf))
+        } else {
+            None
+        }
+    }
+
+    /// Stop receiving: from now on the queues of this port return no packets, leaving any still on the NIC in its
+    /// RX rings. Used to drain pipelines when shutting down.
+    pub fn stop_rx(&self) {
+        self.rx_stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// Resume receiving after `stop_rx`.
+    pub fn start_rx(&self) {
+        self.rx_stopped.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether `stop_rx` was called.
+    #[inline]
+    pub fn rx_stopped(&self) -> bool {
+        self.rx_stopped.load(Ordering::Relaxed)
+    }
+
     /// Create a PMD port with a given number of RX and TXQs.
     fn init_dpdk_port(
         port: u8,
@@ -200,10 +319,13 @@ impl PmdPort {
         loopback: bool,
         tso: bool,
         csumoffload: bool,
+        rx_interrupts: bool,
+        mtu: u16,
     ) -> Result<Arc<PmdPort>> {
         let loopbackv = i32_from_bool(loopback);
         let tsov = i32_from_bool(tso);
         let csumoffloadv = i32_from_bool(csumoffload);
+        let rx_interruptsv = i32_from_bool(rx_interrupts);
         let max_txqs = unsafe { max_txqs(port) };
         let max_rxqs = unsafe { max_rxqs(port) };
         let actual_rxqs = min(max_rxqs, rxqs);
@@ -222,9 +344,15 @@ impl PmdPort {
                     loopbackv,
                     tsov,
                     csumoffloadv,
+                    rx_interruptsv,
                 )
             };
             if ret == 0 {
+                // Drivers need not support changing the MTU, so leave the default alone.
+                if mtu != DEFAULT_MTU && unsafe { rte_eth_dev_set_mtu(port, mtu) } != 0 {
+                    unsafe { free_pmd_port(port) };
+                    return Err(ErrorKind::FailedToSetMtu(port, mtu).into());
+                }
                 Ok(Arc::new(PmdPort {
                     connected: true,
                     port,
@@ -233,6 +361,12 @@ impl PmdPort {
                     should_close: true,
                     stats_rx: (0..rxqs).map(|_| Arc::new(PortStats::new())).collect(),
                     stats_tx: (0..txqs).map(|_| Arc::new(PortStats::new())).collect(),
+                    stats_tx_drop: (0..txqs).map(|_| Arc::new(PortStats::new())).collect(),
+                    csumoffload,
+                    rx_interrupts,
+                    rx_stopped: AtomicBool::new(false),
+                    mtu,
+                    pcap: None,
                 }))
             } else {
                 Err(ErrorKind::FailedToInitializePort(port).into())
@@ -243,7 +377,7 @@ impl PmdPort {
     }
 
     /// Create a new port that can talk to BESS.
-    fn new_bess_port(name: &str, core: i32) -> Result<Arc<PmdPort>> {
+    fn new_bess_port(name: &str, core: i32, mtu: u16) -> Result<Arc<PmdPort>> {
         let ifname = CString::new(name).unwrap();
         // This call returns the port number
         let port = unsafe {
@@ -259,10 +393,16 @@ impl PmdPort {
             should_close: false,
             stats_rx: vec![Arc::new(PortStats::new())],
             stats_tx: vec![Arc::new(PortStats::new())],
+            stats_tx_drop: vec![Arc::new(PortStats::new())],
+            csumoffload: false,
+            rx_interrupts: false,
+            rx_stopped: AtomicBool::new(false),
+            mtu,
+            pcap: None,
         }))
     }
 
-    fn new_ovs_port(name: &str, core: i32) -> Result<Arc<PmdPort>> {
+    fn new_ovs_port(name: &str, core: i32, mtu: u16) -> Result<Arc<PmdPort>> {
         match name.parse() {
             Ok(iface) => {
                 // This call returns the port number
@@ -275,6 +415,12 @@ impl PmdPort {
                     should_close: false,
                     stats_rx: vec![Arc::new(PortStats::new())],
                     stats_tx: vec![Arc::new(PortStats::new())],
+                    stats_tx_drop: vec![Arc::new(PortStats::new())],
+                    csumoffload: false,
+                    rx_interrupts: false,
+                    rx_stopped: AtomicBool::new(false),
+                    mtu,
+                    pcap: None,
                 }))
             }
             _ => Err(ErrorKind::BadVdev(String::from(name)).into()),
@@ -292,6 +438,8 @@ impl PmdPort {
         loopback: bool,
         tso: bool,
         csumoffload: bool,
+        rx_interrupts: bool,
+        mtu: u16,
     ) -> Result<Arc<PmdPort>> {
         let cannonical_spec = PmdPort::cannonicalize_pci(spec);
         let port = unsafe { attach_pmd_device((cannonical_spec[..]).as_ptr()) };
@@ -307,11 +455,13 @@ impl PmdPort {
             loopback,
             tso,
             csumoffload,
+            rx_interrupts,
+            mtu,
         )
         .chain_err(|| ErrorKind::BadDev(String::from(spec)))
     }
 
-    fn null_port() -> Result<Arc<PmdPort>> {
+    fn null_port(mtu: u16) -> Result<Arc<PmdPort>> {
         Ok(Arc::new(PmdPort {
             connected: false,
             port: 0,
@@ -320,9 +470,42 @@ impl PmdPort {
             should_close: false,
             stats_rx: vec![Arc::new(PortStats::new())],
             stats_tx: vec![Arc::new(PortStats::new())],
+            stats_tx_drop: vec![Arc::new(PortStats::new())],
+            csumoffload: false,
+            rx_interrupts: false,
+            rx_stopped: AtomicBool::new(false),
+            mtu,
+            pcap: None,
         }))
     }
 
+    /// Create a port replaying and recording capture files, see `PcapSpec` for `spec`. It has no DPDK device behind
+    /// it, its queues read from and write to the `PcapPort`.
+    fn new_pcap_port(spec: &str, rxqs: i32, txqs: i32, mtu: u16) -> Result<Arc<PmdPort>> {
+        let pcap = PcapPort::new(spec)?;
+        Ok(Arc::new(PmdPort {
+            connected: false,
+            port: 0,
+            rxqs,
+            txqs,
+            should_close: false,
+            stats_rx: (0..rxqs.max(1)).map(|_| Arc::new(PortStats::new())).collect(),
+            stats_tx: (0..txqs.max(1)).map(|_| Arc::new(PortStats::new())).collect(),
+            stats_tx_drop: (0..txqs.max(1)).map(|_| Arc::new(PortStats::new())).collect(),
+            csumoffload: false,
+            rx_interrupts: false,
+            rx_stopped: AtomicBool::new(false),
+            mtu,
+            pcap: Some(pcap),
+        }))
+    }
+
+    /// The capture files behind a port configured with a `pcap:` name.
+    #[inline]
+    pub fn pcap(&self) -> Option<&Arc<PcapPort>> {
+        self.pcap.as_ref()
+    }
+
     /// Create a new port from a `PortConfiguration`.
     pub fn new_port_from_configuration(port_config: &PortConfiguration) -> Result<Arc<PmdPort>> {
         PmdPort::new_port_with_queues_descriptors_offloads(
@@ -336,17 +519,23 @@ impl PmdPort {
             port_config.loopback,
             port_config.tso,
             port_config.csum,
+            port_config.rx_interrupts,
+            port_config.mtu,
         )
     }
 
     /// Create a new port.
     ///
     /// Description
-    /// -   `name`: The name for a port. NetBricks currently supports Bess native vports, OVS shared memory ports and
-    ///     `dpdk` PMDs. DPDK PMDs can be used to input pcap (e.g., `dpdk:eth_pcap0,rx_pcap=<pcap_name>`), etc.
+    /// -   `name`: The name for a port. NetBricks currently supports Bess native vports, OVS shared memory ports,
+    ///     `dpdk` PMDs and capture files (`pcap:`, see `PcapSpec`). DPDK PMDs can be used to input pcap (e.g.,
+    ///     `dpdk:eth_pcap0,rx_pcap=<pcap_name>`), etc.
     /// -   `rxqs`, `txqs`: Number of RX and TX queues.
     /// -   `tx_cores`, `rx_cores`: Core affinity of where the queues will be used.
     /// -   `nrxd`, `ntxd`: RX and TX descriptors.
+    /// -   `rx_interrupts`: Set up RX interrupts, so idle schedulers can wait for packets (see `IdleBackoff`).
+    /// -   `mtu`: Largest IP packet sent, set on the device for DPDK PMDs. Packets are not checked against it on send,
+    ///     use `Batch::fragment` to enforce it.
     pub fn new_port_with_queues_descriptors_offloads(
         name: &str,
         rxqs: i32,
@@ -358,11 +547,13 @@ impl PmdPort {
         loopback: bool,
         tso: bool,
         csumoffload: bool,
+        rx_interrupts: bool,
+        mtu: u16,
     ) -> Result<Arc<PmdPort>> {
         let parts: Vec<_> = name.splitn(2, ':').collect();
         match parts[0] {
-            "bess" => PmdPort::new_bess_port(parts[1], rx_cores[0]),
-            "ovs" => PmdPort::new_ovs_port(parts[1], rx_cores[0]),
+            "bess" => PmdPort::new_bess_port(parts[1], rx_cores[0], mtu),
+            "ovs" => PmdPort::new_ovs_port(parts[1], rx_cores[0], mtu),
             "dpdk" => PmdPort::new_dpdk_port(
                 parts[1],
                 rxqs,
@@ -374,8 +565,12 @@ impl PmdPort {
                 loopback,
                 tso,
                 csumoffload,
+                rx_interrupts,
+                mtu,
             ),
-            "null" => PmdPort::null_port(),
+            "null" => PmdPort::null_port(mtu),
+            // Capture files are served by `PcapPort`, which does not go through DPDK.
+            "pcap" => PmdPort::new_pcap_port(parts.get(1).cloned().unwrap_or(""), rxqs, txqs, mtu),
             _ => PmdPort::new_dpdk_port(
                 name,
                 rxqs,
@@ -387,6 +582,8 @@ impl PmdPort {
                 loopback,
                 tso,
                 csumoffload,
+                rx_interrupts,
+                mtu,
             ),
         }
     }
@@ -400,7 +597,18 @@ impl PmdPort {
         tx_cores: &[i32],
     ) -> Result<Arc<PmdPort>> {
         PmdPort::new_port_with_queues_descriptors_offloads(
-            name, rxqs, txqs, rx_cores, tx_cores, NUM_RXD, NUM_TXD, false, false, false,
+            name,
+            rxqs,
+            txqs,
+            rx_cores,
+            tx_cores,
+            NUM_RXD,
+            NUM_TXD,
+            false,
+            false,
+            false,
+            false,
+            DEFAULT_MTU,
         )
     }
 
@@ -431,6 +639,9 @@ impl PmdPort {
     #[inline]
     pub fn mac_address(&self) -> MacAddress {
         let mut address = MacAddress { addr: [0; 6] };
+        if self.pcap.is_some() {
+            return address;
+        }
         unsafe {
             rte_eth_macaddr_get(self.port, &mut address as *mut MacAddress);
             address
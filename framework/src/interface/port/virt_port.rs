@@ -4,7 +4,7 @@ use crate::allocators::*;
 use crate::common::*;
 use crate::native::zcsi::*;
 use std::fmt;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 /// Virtual Port.
@@ -12,6 +12,7 @@ use std::sync::Arc;
 pub struct VirtualPort {
     stats_rx: Arc<CacheAligned<PortStats>>,
     stats_tx: Arc<CacheAligned<PortStats>>,
+    rx_stopped: Arc<AtomicBool>,
 }
 
 /// Virtual Queue.
@@ -19,6 +20,7 @@ pub struct VirtualPort {
 pub struct VirtualQueue {
     stats_rx: Arc<CacheAligned<PortStats>>,
     stats_tx: Arc<CacheAligned<PortStats>>,
+    rx_stopped: Arc<AtomicBool>,
 }
 
 impl fmt::Display for VirtualQueue {
@@ -45,6 +47,9 @@ impl PacketRx for VirtualQueue {
     /// called).
     #[inline]
     fn recv(&self, pkts: &mut [*mut MBuf]) -> Result<u32> {
+        if self.rx_stopped.load(Ordering::Relaxed) {
+            return Ok(0);
+        }
         let len = pkts.len() as i32;
         let status = unsafe { mbuf_alloc_bulk(pkts.as_mut_ptr(), 60, len) };
         let alloced = if status == 0 { len } else { 0 };
@@ -60,6 +65,7 @@ impl VirtualPort {
         Ok(Arc::new(VirtualPort {
             stats_rx: Arc::new(PortStats::new()),
             stats_tx: Arc::new(PortStats::new()),
+            rx_stopped: Arc::new(AtomicBool::new(false)),
         }))
     }
 
@@ -68,6 +74,7 @@ impl VirtualPort {
         Ok(CacheAligned::allocate(VirtualQueue {
             stats_rx: self.stats_rx.clone(),
             stats_tx: self.stats_tx.clone(),
+            rx_stopped: self.rx_stopped.clone(),
         }))
     }
 
@@ -78,4 +85,19 @@ impl VirtualPort {
             self.stats_tx.stats.load(Ordering::Relaxed),
         )
     }
+
+    /// Stop generating packets on the queues of this port.
+    pub fn stop_rx(&self) {
+        self.rx_stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// Resume generating packets after `stop_rx`.
+    pub fn start_rx(&self) {
+        self.rx_stopped.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether `stop_rx` was called.
+    pub fn rx_stopped(&self) -> bool {
+        self.rx_stopped.load(Ordering::Relaxed)
+    }
 }
@@ -58,6 +58,7 @@ pub mod queues;
 pub mod scheduler;
 pub mod shared_state;
 pub mod state;
+pub mod stats;
 pub mod utils;
 
 #[allow(dead_code)]
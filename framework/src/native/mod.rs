@@ -1,2 +1,3 @@
+#[cfg(not(feature = "heap_mbuf"))]
 pub(crate) mod libnuma;
 pub(crate) mod zcsi;
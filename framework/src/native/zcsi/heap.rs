@@ -0,0 +1,370 @@
+//! A pure Rust stand-in for the DPDK backed functions in `zcsi.rs`, enabled with the `heap_mbuf` feature.
+//!
+//! Mbufs are carved out of ordinary heap allocations laid out the way DPDK lays them out: the `rte_mbuf` header,
+//! followed by the metadata slots (DPDK's private area), followed by headroom and packet data. Freed mbufs are kept on a
+//! free list and handed out again, so the pool behaves like a fixed size mempool. There are no NICs in this mode: PMD
+//! ports fail to initialize and ring based ports never receive anything.
+use super::MBuf;
+use crate::config::{DEFAULT_CACHE_SIZE, DEFAULT_POOL_SIZE};
+use crate::headers::MacAddress;
+use crate::interface::METADATA_SLOTS;
+use crate::utils::calibrate_tsc_hz;
+use std::alloc::{alloc_zeroed, Layout};
+use std::mem::size_of;
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::Mutex;
+
+/// Bytes of headroom before packet data, same as `RTE_PKTMBUF_HEADROOM`.
+const HEADROOM: usize = 128;
+/// Bytes of packet data, same as `RTE_MBUF_DEFAULT_DATAROOM`.
+const DATAROOM: usize = 2048;
+/// Size of a metadata slot in bytes.
+const METADATA_SLOT_SIZE: usize = 8;
+/// Alignment of each mbuf allocation.
+const MBUF_ALIGN: usize = 64;
+/// TX offload flags, same values as DPDK's `PKT_TX_*`.
+const PKT_TX_TCP_CKSUM: u64 = 1 << 52;
+const PKT_TX_UDP_CKSUM: u64 = 3 << 52;
+const PKT_TX_IP_CKSUM: u64 = 1 << 54;
+const PKT_TX_IPV4: u64 = 1 << 55;
+const PKT_TX_IPV6: u64 = 1 << 56;
+/// Returned when the pool is exhausted, mirrors `-ENOENT` from `rte_mempool_get_bulk`.
+const ENOENT: i32 = 2;
+
+#[derive(Debug)]
+struct HeapMempool {
+    /// Mbufs ready to be handed out, stored as addresses so the pool can be shared between threads.
+    free: Vec<usize>,
+    /// Number of mbufs carved out so far.
+    allocated: usize,
+    /// Maximum number of mbufs in the pool.
+    capacity: usize,
+    /// Number of metadata slots following each mbuf.
+    metadata_slots: usize,
+}
+
+lazy_static! {
+    static ref MEMPOOL: Mutex<HeapMempool> = Mutex::new(HeapMempool {
+        free: Vec::new(),
+        allocated: 0,
+        capacity: DEFAULT_POOL_SIZE as usize,
+        metadata_slots: METADATA_SLOTS as usize,
+    });
+}
+
+impl HeapMempool {
+    #[inline]
+    fn layout(&self) -> Layout {
+        let size = size_of::<MBuf>() + self.metadata_slots * METADATA_SLOT_SIZE + HEADROOM + DATAROOM;
+        Layout::from_size_align(size, MBUF_ALIGN).unwrap()
+    }
+
+    /// Carve a new mbuf out of the heap. Returns null when the pool is at capacity.
+    fn grow(&mut self) -> *mut MBuf {
+        if self.allocated >= self.capacity {
+            return ptr::null_mut();
+        }
+        let base = unsafe { alloc_zeroed(self.layout()) };
+        if base.is_null() {
+            return ptr::null_mut();
+        }
+        self.allocated += 1;
+        let mbuf = base as *mut MBuf;
+        unsafe {
+            (*mbuf).buf_addr = base.add(size_of::<MBuf>() + self.metadata_slots * METADATA_SLOT_SIZE) as *mut _;
+            (*mbuf).buf_len = (HEADROOM + DATAROOM) as u16;
+            (*mbuf).priv_size = (self.metadata_slots * METADATA_SLOT_SIZE) as u16;
+        }
+        mbuf
+    }
+
+    fn get(&mut self) -> *mut MBuf {
+        match self.free.pop() {
+            Some(addr) => addr as *mut MBuf,
+            None => self.grow(),
+        }
+    }
+
+    fn put(&mut self, mbuf: *mut MBuf) {
+        self.free.push(mbuf as usize);
+    }
+}
+
+/// Reset an mbuf to the state `rte_pktmbuf_alloc` leaves it in, with `len` bytes of data.
+#[inline]
+unsafe fn reset_mbuf(mbuf: *mut MBuf, len: u16) {
+    (*mbuf).data_off = HEADROOM as u16;
+    (*mbuf).__bindgen_anon_1.refcnt = 1;
+    (*mbuf).nb_segs = 1;
+    (*mbuf).next = ptr::null_mut();
+    (*mbuf).ol_flags = 0;
+    (*mbuf).packet_type = 0;
+    (*mbuf).timestamp = 0;
+    (*mbuf).data_len = len;
+    (*mbuf).pkt_len = len as u32;
+}
+
+/// Drop a reference to an mbuf and to each mbuf chained to it, returning those nothing refers to anymore to the pool,
+/// like `rte_pktmbuf_free`.
+#[inline]
+unsafe fn release_mbuf(pool: &mut HeapMempool, mut mbuf: *mut MBuf) {
+    while !mbuf.is_null() {
+        let next = (*mbuf).next;
+        let refcnt = (*mbuf).__bindgen_anon_1.refcnt;
+        if refcnt <= 1 {
+            (*mbuf).__bindgen_anon_1.refcnt = 0;
+            pool.put(mbuf);
+        } else {
+            (*mbuf).__bindgen_anon_1.refcnt = refcnt - 1;
+        }
+        mbuf = next;
+    }
+}
+
+/// Size the heap mempool. Mbufs already carved out are kept.
+pub unsafe fn init_system_whitelisted(
+    _name: *const c_char,
+    _nlen: i32,
+    _core: i32,
+    _whitelist: *mut *const c_char,
+    _wlcount: i32,
+    pool_size: u32,
+    _cache_size: u32,
+    slots: u16,
+) -> i32 {
+    let mut pool = MEMPOOL.lock().unwrap();
+    if pool.allocated > 0 && slots as usize != pool.metadata_slots {
+        // Mbufs already handed out were laid out for a different number of slots.
+        return -1;
+    }
+    pool.capacity = pool_size as usize;
+    pool.metadata_slots = slots as usize;
+    0
+}
+
+/// There is no NUMA information without DPDK.
+pub unsafe fn init_thread(_tid: i32, _core: i32) -> i32 {
+    -1
+}
+
+/// Secondary processes share a DPDK mempool, which does not exist here; use a default sized private pool instead.
+pub unsafe fn init_secondary(
+    name: *const c_char,
+    nlen: i32,
+    core: i32,
+    _vdevs: *mut *const c_char,
+    _vdev_count: i32,
+) -> i32 {
+    init_system_whitelisted(
+        name,
+        nlen,
+        core,
+        ptr::null_mut(),
+        0,
+        DEFAULT_POOL_SIZE,
+        DEFAULT_CACHE_SIZE,
+        METADATA_SLOTS,
+    )
+}
+
+/// PMD ports need DPDK.
+pub unsafe fn init_pmd_port(
+    _port: u8,
+    _rxqs: i32,
+    _txqs: i32,
+    _rx_cores: *const i32,
+    _tx_cores: *const i32,
+    _nrxd: i32,
+    _ntxd: i32,
+    _loopback: i32,
+    _tso: i32,
+    _csumoffload: i32,
+    _rxintr: i32,
+) -> i32 {
+    -1
+}
+
+pub unsafe fn free_pmd_port(_port: u8) -> i32 {
+    0
+}
+
+pub unsafe fn recv_pkts(_port: u8, _qid: i32, _pkts: *mut *mut MBuf, _len: i32) -> i32 {
+    0
+}
+
+pub unsafe fn send_pkts(_port: u8, _qid: i32, _pkts: *mut *mut MBuf, _len: i32) -> i32 {
+    0
+}
+
+/// There are no NICs to count drops.
+pub unsafe fn port_drops(_port: u8, _missed: *mut u64, _nombuf: *mut u64) -> i32 {
+    -1
+}
+
+/// Rx interrupts need DPDK.
+pub unsafe fn rx_intr_add(_port: u8, _qid: i32) -> i32 {
+    -1
+}
+
+pub unsafe fn rx_intr_enable(_port: u8, _qid: i32) -> i32 {
+    -1
+}
+
+pub unsafe fn rx_intr_disable(_port: u8, _qid: i32) -> i32 {
+    -1
+}
+
+pub unsafe fn rx_intr_wait(_timeout: i32) -> i32 {
+    -1
+}
+
+pub unsafe fn rx_queue_count(_port: u8, _qid: i32) -> i32 {
+    -1
+}
+
+pub unsafe fn num_pmd_ports() -> i32 {
+    0
+}
+
+pub unsafe fn rte_eth_macaddr_get(_port: u8, address: *mut MacAddress) {
+    (*address).addr = [0; 6];
+}
+
+pub unsafe fn rte_eth_dev_set_mtu(_port: u8, _mtu: u16) -> i32 {
+    -1
+}
+
+/// There is no EAL to ask, so measure the TSC.
+pub unsafe fn rte_get_tsc_hz() -> u64 {
+    calibrate_tsc_hz()
+}
+
+pub unsafe fn init_bess_eth_ring(_ifname: *const c_char, _core: i32) -> u8 {
+    0
+}
+
+pub unsafe fn init_ovs_eth_ring(_iface: i32, _core: i32) -> u8 {
+    0
+}
+
+pub unsafe fn find_port_with_pci_address(_pciaddr: *const c_char) -> u8 {
+    0
+}
+
+pub unsafe fn attach_pmd_device(_dev: *const c_char) -> u8 {
+    0
+}
+
+pub unsafe fn max_rxqs(_port: u8) -> i32 {
+    0
+}
+
+pub unsafe fn max_txqs(_port: u8) -> i32 {
+    0
+}
+
+/// There is a single pool; mbufs not yet carved out count as available.
+pub unsafe fn mempool_usage(idx: i32, in_use: *mut u32, avail: *mut u32) -> i32 {
+    if idx != 0 {
+        return -ENOENT;
+    }
+    let pool = MEMPOOL.lock().unwrap();
+    let used = pool.allocated - pool.free.len();
+    *in_use = used as u32;
+    *avail = pool.capacity.saturating_sub(used) as u32;
+    0
+}
+
+pub unsafe fn mbuf_alloc() -> *mut MBuf {
+    let mbuf = MEMPOOL.lock().unwrap().get();
+    if !mbuf.is_null() {
+        reset_mbuf(mbuf, 0);
+    }
+    mbuf
+}
+
+pub unsafe fn mbuf_free(buf: *mut MBuf) {
+    release_mbuf(&mut MEMPOOL.lock().unwrap(), buf);
+}
+
+/// Allocate `cnt` mbufs with `len` bytes of data each. Either all or none are allocated.
+pub unsafe fn mbuf_alloc_bulk(array: *mut *mut MBuf, len: u16, cnt: i32) -> i32 {
+    let mut pool = MEMPOOL.lock().unwrap();
+    for i in 0..cnt as usize {
+        let mbuf = pool.get();
+        if mbuf.is_null() {
+            for j in 0..i {
+                pool.put(*array.add(j));
+            }
+            return -ENOENT;
+        }
+        reset_mbuf(mbuf, len);
+        *array.add(i) = mbuf;
+    }
+    0
+}
+
+pub unsafe fn mbuf_free_bulk(array: *mut *mut MBuf, cnt: i32) -> i32 {
+    let mut pool = MEMPOOL.lock().unwrap();
+    for i in 0..cnt as usize {
+        release_mbuf(&mut pool, *array.add(i));
+    }
+    0
+}
+
+lazy_static! {
+    static ref CRC32C_TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        for (i, entry) in table.iter_mut().enumerate() {
+            let mut crc = i as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0x82f6_3b78
+                } else {
+                    crc >> 1
+                };
+            }
+            *entry = crc;
+        }
+        table
+    };
+}
+
+/// Software CRC32-C, producing the same values as `rte_hash_crc` (no pre or post inversion).
+pub unsafe fn crc_hash_native(to_hash: *const u8, size: u32, iv: u32) -> u32 {
+    let bytes = std::slice::from_raw_parts(to_hash, size as usize);
+    bytes.iter().fold(iv, |crc, b| {
+        CRC32C_TABLE[((crc ^ *b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Checksum of a 20 byte IPv4 header, same as `rte_ipv4_cksum`.
+pub unsafe fn ipv4_cksum(payload: *const u8) -> u16 {
+    let bytes = std::slice::from_raw_parts(payload, 20);
+    let mut sum: u32 = bytes.chunks(2).map(|w| u16::from_ne_bytes([w[0], w[1]]) as u32).sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    let cksum = sum as u16;
+    if cksum == 0xffff {
+        cksum
+    } else {
+        !cksum
+    }
+}
+
+/// Record a checksum offload request the way DPDK does. There is no NIC to act on it.
+pub unsafe fn mbuf_csum_offload(buf: *mut MBuf, l2_len: u16, l3_len: u16, ipv6: i32, l4_proto: u8) {
+    (*buf).tx_offload = (l2_len as u64 & 0x7f) | ((l3_len as u64 & 0x1ff) << 7);
+    (*buf).ol_flags |= if ipv6 != 0 {
+        PKT_TX_IPV6
+    } else {
+        PKT_TX_IPV4 | PKT_TX_IP_CKSUM
+    };
+    match l4_proto {
+        6 => (*buf).ol_flags |= PKT_TX_TCP_CKSUM,
+        17 => (*buf).ol_flags |= PKT_TX_UDP_CKSUM,
+        _ => (),
+    }
+}
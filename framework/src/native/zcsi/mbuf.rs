@@ -59,8 +59,9 @@ impl MBuf {
         self.data_off as usize
     }
 
+    /// Returns the space left at the end of this mbuf segment.
     #[inline]
-    fn pkt_tailroom(&self) -> usize {
+    pub fn pkt_tailroom(&self) -> usize {
         self.buf_len() - self.data_off as usize - self.data_len()
     }
 
@@ -1,5 +1,11 @@
+#[cfg(feature = "heap_mbuf")]
+mod heap;
 mod mbuf;
+#[cfg(not(feature = "heap_mbuf"))]
 #[cfg_attr(feature = "dev", allow(module_inception))]
 mod zcsi;
+#[cfg(feature = "heap_mbuf")]
+pub use self::heap::*;
 pub use self::mbuf::*;
+#[cfg(not(feature = "heap_mbuf"))]
 pub use self::zcsi::*;
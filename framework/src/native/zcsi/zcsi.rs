@@ -27,12 +27,21 @@ extern "C" {
         loopback: i32,
         tso: i32,
         csumoffload: i32,
+        rxintr: i32,
     ) -> i32;
     pub fn free_pmd_port(port: u8) -> i32;
     pub fn recv_pkts(port: u8, qid: i32, pkts: *mut *mut MBuf, len: i32) -> i32;
     pub fn send_pkts(port: u8, qid: i32, pkts: *mut *mut MBuf, len: i32) -> i32;
+    pub fn port_drops(port: u8, missed: *mut u64, nombuf: *mut u64) -> i32;
+    pub fn rx_intr_add(port: u8, qid: i32) -> i32;
+    pub fn rx_intr_enable(port: u8, qid: i32) -> i32;
+    pub fn rx_intr_disable(port: u8, qid: i32) -> i32;
+    pub fn rx_intr_wait(timeout: i32) -> i32;
+    pub fn rx_queue_count(port: u8, qid: i32) -> i32;
     pub fn num_pmd_ports() -> i32;
     pub fn rte_eth_macaddr_get(port: u8, address: *mut MacAddress);
+    pub fn rte_eth_dev_set_mtu(port: u8, mtu: u16) -> i32;
+    pub fn rte_get_tsc_hz() -> u64;
     pub fn init_bess_eth_ring(ifname: *const c_char, core: i32) -> u8;
     pub fn init_ovs_eth_ring(iface: i32, core: i32) -> u8;
     pub fn find_port_with_pci_address(pciaddr: *const c_char) -> u8;
@@ -40,10 +49,12 @@ extern "C" {
     // FIXME: Generic PMD info
     pub fn max_rxqs(port: u8) -> i32;
     pub fn max_txqs(port: u8) -> i32;
+    pub fn mempool_usage(idx: i32, in_use: *mut u32, avail: *mut u32) -> i32;
     pub fn mbuf_alloc() -> *mut MBuf;
     pub fn mbuf_free(buf: *mut MBuf);
     pub fn mbuf_alloc_bulk(array: *mut *mut MBuf, len: u16, cnt: i32) -> i32;
     pub fn mbuf_free_bulk(array: *mut *mut MBuf, cnt: i32) -> i32;
     pub fn crc_hash_native(to_hash: *const u8, size: u32, iv: u32) -> u32;
     pub fn ipv4_cksum(payload: *const u8) -> u16;
+    pub fn mbuf_csum_offload(buf: *mut MBuf, l2_len: u16, l3_len: u16, ipv6: i32, l4_proto: u8);
 }
@@ -0,0 +1,38 @@
+// A hand written copy of the parts of DPDK's `rte_mbuf` NetBricks relies on, used by the `heap_mbuf` feature in place
+// of the bindgen output. Field names and layout follow DPDK so that code written against the generated bindings works
+// unchanged, and metadata slots (which live right after the mbuf) end up at the same offset.
+use std::os::raw::c_void;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_mbuf__bindgen_ty_1 {
+    pub refcnt_atomic: u16,
+    pub refcnt: u16,
+}
+
+#[repr(C, align(64))]
+#[derive(Copy, Clone)]
+pub struct rte_mbuf {
+    pub buf_addr: *mut c_void,
+    pub buf_iova: u64,
+    pub data_off: u16,
+    pub __bindgen_anon_1: rte_mbuf__bindgen_ty_1,
+    pub nb_segs: u16,
+    pub port: u16,
+    pub ol_flags: u64,
+    pub packet_type: u32,
+    pub pkt_len: u32,
+    pub data_len: u16,
+    pub vlan_tci: u16,
+    pub hash: u64,
+    pub vlan_tci_outer: u16,
+    pub buf_len: u16,
+    pub timestamp: u64,
+    pub udata64: u64,
+    pub pool: *mut c_void,
+    pub next: *mut rte_mbuf,
+    pub tx_offload: u64,
+    pub priv_size: u16,
+    pub timesync: u16,
+    pub seqn: u32,
+}
@@ -3,4 +3,8 @@
 #![allow(non_snake_case)]
 #![allow(dead_code)]
 
+#[cfg(not(feature = "heap_mbuf"))]
 include!(concat!(env!("OUT_DIR"), "/dpdk_bindings.rs"));
+
+#[cfg(feature = "heap_mbuf")]
+include!("heap_mbuf.rs");
@@ -50,9 +50,11 @@ impl Act for CompositionBatch {
 
 impl Executable for CompositionBatch {
     #[inline]
-    fn execute(&mut self) {
+    fn execute(&mut self) -> usize {
         self.act();
+        let processed = self.get_packet_batch().available();
         self.done();
+        processed
     }
 
     #[inline]
@@ -0,0 +1,125 @@
+use super::act::Act;
+use super::iterator::*;
+use super::packet_batch::PacketBatch;
+use super::Batch;
+use crate::common::*;
+use crate::headers::IpHeader;
+use crate::interface::PacketTx;
+use crate::state::{ConnMetadata, ConnTracker};
+use crate::utils::rdtsc_unsafe;
+
+/// Buckets of the connection table checked for timed out connections per batch.
+const EXPIRE_BUCKETS: usize = 4;
+
+/// Connection tracking batch.
+///
+/// Runs every IPv4 packet through a `ConnTracker` and replaces its metadata with the resulting `ConnMetadata`. All
+/// packets of a batch are tracked at the same time, taken right after the parent acted. Timed out connections are
+/// reclaimed a few table buckets per batch, so the table does not fill up with them between packets.
+pub struct ConntrackBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    parent: V,
+    tracker: ConnTracker,
+    applied: bool,
+}
+
+impl<V> ConntrackBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    /// Return a connection tracking batch using `tracker`.
+    pub fn new(parent: V, tracker: ConnTracker) -> ConntrackBatch<V> {
+        ConntrackBatch {
+            parent,
+            tracker,
+            applied: false,
+        }
+    }
+
+    /// The connection tracker, e.g., for reading its counters.
+    pub fn tracker(&self) -> &ConnTracker {
+        &self.tracker
+    }
+}
+
+impl<V> Batch for ConntrackBatch<V> where V: Batch<Header = IpHeader> + BatchIterator + Act {}
+
+impl<V> BatchIterator for ConntrackBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    type Header = IpHeader;
+    type Metadata = ConnMetadata;
+
+    #[inline]
+    fn start(&mut self) -> usize {
+        self.parent.start()
+    }
+
+    #[inline]
+    unsafe fn next_payload(&mut self, idx: usize) -> Option<PacketDescriptor<IpHeader, ConnMetadata>> {
+        self.parent.next_payload(idx).map(|p| PacketDescriptor {
+            packet: p.packet.reinterpret_metadata(),
+        })
+    }
+}
+
+impl<V> Act for ConntrackBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    #[inline]
+    fn act(&mut self) {
+        if !self.applied {
+            self.parent.act();
+            let now = rdtsc_unsafe();
+            {
+                let iter = PayloadEnumerator::<IpHeader, V::Metadata>::new(&mut self.parent);
+                while let Some(ParsedDescriptor { mut packet, .. }) = iter.next(&mut self.parent) {
+                    let metadata = self.tracker.track(packet.get_header(), packet.get_payload(), now);
+                    packet.write_metadata(&metadata).unwrap();
+                }
+            }
+            self.tracker.expire(EXPIRE_BUCKETS, now);
+            self.applied = true;
+        }
+    }
+
+    #[inline]
+    fn done(&mut self) {
+        self.applied = false;
+        self.parent.done();
+    }
+
+    #[inline]
+    fn send_q(&mut self, port: &dyn PacketTx) -> Result<u32> {
+        self.parent.send_q(port)
+    }
+
+    #[inline]
+    fn capacity(&self) -> i32 {
+        self.parent.capacity()
+    }
+
+    #[inline]
+    fn drop_packets(&mut self, idxes: &[usize]) -> Option<usize> {
+        self.parent.drop_packets(idxes)
+    }
+
+    #[inline]
+    fn clear_packets(&mut self) {
+        self.parent.clear_packets()
+    }
+
+    #[inline]
+    fn get_packet_batch(&mut self) -> &mut PacketBatch {
+        self.parent.get_packet_batch()
+    }
+
+    #[inline]
+    fn get_task_dependencies(&self) -> Vec<usize> {
+        self.parent.get_task_dependencies()
+    }
+}
@@ -0,0 +1,184 @@
+use super::act::Act;
+use super::iterator::*;
+use super::packet_batch::PacketBatch;
+use super::Batch;
+use crate::common::*;
+use crate::headers::IpHeader;
+use crate::interface::PacketTx;
+use crate::native::zcsi::mbuf_free;
+use serde::Serialize;
+
+/// Counters of a `FragmentBatch`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct FragmentStats {
+    /// Packets split into fragments.
+    pub fragmented: u64,
+    /// Fragments sent in their place, the trimmed packets included.
+    pub fragments: u64,
+    /// Packets dropped because they had the don't fragment flag set.
+    pub dont_fragment: u64,
+    /// Packets dropped because they were malformed or fragments could not be allocated.
+    pub failed: u64,
+    /// Packets dropped with their fragments because the batch had no room for them.
+    pub overflow: u64,
+}
+
+/// IPv4 fragmentation batch.
+///
+/// Splits IPv4 packets longer than the MTU into fragments, which take the place of the packet in the batch. Packets
+/// that are too long but must not be fragmented are dropped and counted, and so are packets that cannot be
+/// fragmented (e.g., with an MTU below the IP header and 8 bytes of data), and packets whose fragments would take the
+/// batch past its capacity. Packets that fit pass through.
+///
+/// No ICMP "fragmentation needed" error (type 3, code 4) is sent back for packets with the don't fragment flag set
+/// yet: it has to go out towards the sender, not on the port this batch sends to. NFs that need it can count on
+/// `FragmentStats::dont_fragment` and build it with `Packet::write_icmp_error` on their receive path.
+pub struct FragmentBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    parent: V,
+    mtu: usize,
+    stats: FragmentStats,
+    remove: Vec<usize>,
+}
+
+impl<V> FragmentBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    /// Return a fragmentation batch sending IP packets of at most `mtu` bytes.
+    pub fn new(parent: V, mtu: usize) -> FragmentBatch<V> {
+        let capacity = parent.capacity() as usize;
+        FragmentBatch {
+            parent,
+            mtu,
+            stats: FragmentStats::default(),
+            remove: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Counters of the batch.
+    pub fn stats(&self) -> FragmentStats {
+        self.stats
+    }
+}
+
+impl<V> Batch for FragmentBatch<V> where V: Batch<Header = IpHeader> + BatchIterator + Act {}
+
+impl<V> Act for FragmentBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    #[inline]
+    fn act(&mut self) {
+        self.parent.act();
+        // Fragments by index of the packet they follow, in order. Rarely needed, so only allocated when they are.
+        let mut inserts = Vec::new();
+        {
+            let iter = PayloadEnumerator::<IpHeader, V::Metadata>::new(&mut self.parent);
+            while let Some(ParsedDescriptor { mut packet, index: idx }) = iter.next(&mut self.parent) {
+                match packet.fragment(self.mtu) {
+                    Ok(ref fragments) if fragments.is_empty() => {}
+                    Ok(fragments) => {
+                        let mbufs: Vec<_> = fragments.into_iter().map(|f| unsafe { f.get_mbuf() }).collect();
+                        inserts.push((idx, mbufs));
+                    }
+                    Err(ref e) if matches!(e.kind(), ErrorKind::DontFragment(..)) => {
+                        self.stats.dont_fragment += 1;
+                        self.remove.push(idx);
+                    }
+                    Err(_) => {
+                        self.stats.failed += 1;
+                        self.remove.push(idx);
+                    }
+                }
+            }
+        }
+        if !self.remove.is_empty() {
+            self.parent
+                .drop_packets(&self.remove[..])
+                .expect("Fragmentation dropped packets incorrectly");
+        }
+        // Insert in order, so packets first in the batch get its room first. Packets whose fragments do not fit are
+        // dropped afterwards, their indices stay valid as later fragments go in after them.
+        let mut inserted = 0;
+        let mut overflow = Vec::new();
+        {
+            let batch = self.parent.get_packet_batch();
+            for (idx, mbufs) in &inserts {
+                let idx = idx - self.remove.iter().take_while(|&&r| r < *idx).count() + inserted;
+                if batch.insert_packets(idx, mbufs) {
+                    inserted += mbufs.len();
+                    self.stats.fragmented += 1;
+                    self.stats.fragments += mbufs.len() as u64 + 1;
+                } else {
+                    for &mbuf in mbufs {
+                        unsafe { mbuf_free(mbuf) };
+                    }
+                    overflow.push(idx);
+                }
+            }
+        }
+        if !overflow.is_empty() {
+            self.stats.overflow += overflow.len() as u64;
+            self.parent
+                .drop_packets(&overflow[..])
+                .expect("Fragmentation dropped packets incorrectly");
+        }
+        self.remove.clear();
+    }
+
+    #[inline]
+    fn done(&mut self) {
+        self.parent.done();
+    }
+
+    #[inline]
+    fn send_q(&mut self, port: &dyn PacketTx) -> Result<u32> {
+        self.parent.send_q(port)
+    }
+
+    #[inline]
+    fn capacity(&self) -> i32 {
+        self.parent.capacity()
+    }
+
+    #[inline]
+    fn drop_packets(&mut self, idxes: &[usize]) -> Option<usize> {
+        self.parent.drop_packets(idxes)
+    }
+
+    #[inline]
+    fn clear_packets(&mut self) {
+        self.parent.clear_packets()
+    }
+
+    #[inline]
+    fn get_packet_batch(&mut self) -> &mut PacketBatch {
+        self.parent.get_packet_batch()
+    }
+
+    #[inline]
+    fn get_task_dependencies(&self) -> Vec<usize> {
+        self.parent.get_task_dependencies()
+    }
+}
+
+impl<V> BatchIterator for FragmentBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    type Header = IpHeader;
+    type Metadata = V::Metadata;
+
+    #[inline]
+    fn start(&mut self) -> usize {
+        self.parent.start()
+    }
+
+    #[inline]
+    unsafe fn next_payload(&mut self, idx: usize) -> Option<PacketDescriptor<IpHeader, V::Metadata>> {
+        self.parent.next_payload(idx)
+    }
+}
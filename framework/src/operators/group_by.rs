@@ -50,18 +50,21 @@ where
     V: Batch + BatchIterator<Header = T> + Act + 'static,
 {
     #[inline]
-    fn execute(&mut self) {
+    fn execute(&mut self) -> usize {
         self.parent.act(); // Let the parent get some packets.
+        let mut processed = 0;
         {
             let iter = PayloadEnumerator::<T, V::Metadata>::new(&mut self.parent);
             while let Some(ParsedDescriptor { mut packet, .. }) = iter.next(&mut self.parent) {
                 let group = (self.group_fn)(&packet);
                 packet.save_header_and_offset();
                 self.producers[group].enqueue_one(packet);
+                processed += 1;
             }
         }
         self.parent.get_packet_batch().clear_packets();
         self.parent.done();
+        processed
     }
 
     #[inline]
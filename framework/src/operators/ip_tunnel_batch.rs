@@ -0,0 +1,275 @@
+use super::act::Act;
+use super::iterator::*;
+use super::packet_batch::PacketBatch;
+use super::Batch;
+use crate::common::*;
+use crate::headers::{header_bytes, IpHeader, IpTunnelTemplate};
+use crate::interface::PacketTx;
+use crate::utils::{ipv4_extract_flow, Flow};
+use std::collections::HashMap;
+
+/// Metadata of packets going through `IpEncapBatch` and `IpDecapBatch`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IpTunnelMetadata {
+    /// IP protocol number of the tunnel, `IPPROTO_IPIP` or `IPPROTO_GRE`, `None` for packets that were not tunneled.
+    pub protocol: Option<u8>,
+    /// GRE key of the tunnel, if it has one.
+    pub key: Option<u32>,
+    /// Tenant the GRE key maps to, if it does.
+    pub tenant: Option<u32>,
+    /// Flow of the inner packet, if it has one.
+    pub inner: Option<Flow>,
+}
+
+/// IP tunnel encapsulation batch.
+///
+/// Puts the outer headers of an `IpTunnelTemplate` in front of every IPv4 packet, see `IpTunnelTemplate::encap`, and
+/// replaces its metadata with the tunnel and the inner flow. Packets without the headroom for the outer headers are
+/// dropped and counted.
+pub struct IpEncapBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    parent: V,
+    template: IpTunnelTemplate,
+    applied: bool,
+    remove: Vec<usize>,
+    dropped: u64,
+}
+
+impl<V> IpEncapBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    /// Return an encapsulation batch using `template`.
+    pub fn new(parent: V, template: IpTunnelTemplate) -> IpEncapBatch<V> {
+        let capacity = parent.capacity() as usize;
+        IpEncapBatch {
+            parent,
+            template,
+            applied: false,
+            remove: Vec::with_capacity(capacity),
+            dropped: 0,
+        }
+    }
+
+    /// Packets dropped for lack of headroom.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+impl<V> Batch for IpEncapBatch<V> where V: Batch<Header = IpHeader> + BatchIterator + Act {}
+
+impl<V> BatchIterator for IpEncapBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    type Header = IpHeader;
+    type Metadata = IpTunnelMetadata;
+
+    #[inline]
+    fn start(&mut self) -> usize {
+        self.parent.start()
+    }
+
+    #[inline]
+    unsafe fn next_payload(&mut self, idx: usize) -> Option<PacketDescriptor<IpHeader, IpTunnelMetadata>> {
+        self.parent.next_payload(idx).map(|p| PacketDescriptor {
+            packet: p.packet.reinterpret_metadata(),
+        })
+    }
+}
+
+impl<V> Act for IpEncapBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    #[inline]
+    fn act(&mut self) {
+        if !self.applied {
+            self.parent.act();
+            {
+                let iter = PayloadEnumerator::<IpHeader, V::Metadata>::new(&mut self.parent);
+                while let Some(ParsedDescriptor { packet, index: idx }) = iter.next(&mut self.parent) {
+                    let metadata = IpTunnelMetadata {
+                        protocol: Some(self.template.protocol()),
+                        key: if self.template.gre { self.template.key } else { None },
+                        tenant: None,
+                        inner: ipv4_extract_flow(header_bytes(&packet)),
+                    };
+                    match self.template.encap(packet) {
+                        Some(mut packet) => packet.write_metadata(&metadata).unwrap(),
+                        None => self.remove.push(idx),
+                    }
+                }
+            }
+            if !self.remove.is_empty() {
+                self.dropped += self.remove.len() as u64;
+                self.parent
+                    .drop_packets(&self.remove[..])
+                    .expect("Encapsulation dropped packets incorrectly");
+                self.remove.clear();
+            }
+            self.applied = true;
+        }
+    }
+
+    #[inline]
+    fn done(&mut self) {
+        self.applied = false;
+        self.parent.done();
+    }
+
+    #[inline]
+    fn send_q(&mut self, port: &dyn PacketTx) -> Result<u32> {
+        self.parent.send_q(port)
+    }
+
+    #[inline]
+    fn capacity(&self) -> i32 {
+        self.parent.capacity()
+    }
+
+    #[inline]
+    fn drop_packets(&mut self, idxes: &[usize]) -> Option<usize> {
+        self.parent.drop_packets(idxes)
+    }
+
+    #[inline]
+    fn clear_packets(&mut self) {
+        self.parent.clear_packets()
+    }
+
+    #[inline]
+    fn get_packet_batch(&mut self) -> &mut PacketBatch {
+        self.parent.get_packet_batch()
+    }
+
+    #[inline]
+    fn get_task_dependencies(&self) -> Vec<usize> {
+        self.parent.get_task_dependencies()
+    }
+}
+
+/// IP tunnel decapsulation batch.
+///
+/// Strips the outer headers of IPv4 packets tunneled in IP-in-IP or GRE, see `Packet::decap_ip`, so that operators
+/// further down parse the inner IP header. The metadata is replaced with the tunnel, the inner flow and, for GRE keys
+/// found in the tenant map, the tenant; other packets pass through unchanged, with empty metadata.
+pub struct IpDecapBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    parent: V,
+    tenants: HashMap<u32, u32>,
+    applied: bool,
+}
+
+impl<V> IpDecapBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    /// Return a decapsulation batch mapping GRE keys to tenants with `tenants`.
+    pub fn new(parent: V, tenants: HashMap<u32, u32>) -> IpDecapBatch<V> {
+        IpDecapBatch {
+            parent,
+            tenants,
+            applied: false,
+        }
+    }
+
+    /// The map from GRE keys to tenants.
+    pub fn tenants(&mut self) -> &mut HashMap<u32, u32> {
+        &mut self.tenants
+    }
+}
+
+impl<V> Batch for IpDecapBatch<V> where V: Batch<Header = IpHeader> + BatchIterator + Act {}
+
+impl<V> BatchIterator for IpDecapBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    type Header = IpHeader;
+    type Metadata = IpTunnelMetadata;
+
+    #[inline]
+    fn start(&mut self) -> usize {
+        self.parent.start()
+    }
+
+    #[inline]
+    unsafe fn next_payload(&mut self, idx: usize) -> Option<PacketDescriptor<IpHeader, IpTunnelMetadata>> {
+        self.parent.next_payload(idx).map(|p| PacketDescriptor {
+            packet: p.packet.reinterpret_metadata(),
+        })
+    }
+}
+
+impl<V> Act for IpDecapBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    #[inline]
+    fn act(&mut self) {
+        if !self.applied {
+            self.parent.act();
+            {
+                let iter = PayloadEnumerator::<IpHeader, V::Metadata>::new(&mut self.parent);
+                while let Some(ParsedDescriptor { packet, .. }) = iter.next(&mut self.parent) {
+                    let protocol = packet.get_header().protocol();
+                    match packet.decap_ip() {
+                        Ok((mut inner, key)) => {
+                            let metadata = IpTunnelMetadata {
+                                protocol: Some(protocol),
+                                key,
+                                tenant: key.and_then(|key| self.tenants.get(&key).cloned()),
+                                inner: ipv4_extract_flow(header_bytes(&inner)),
+                            };
+                            inner.write_metadata(&metadata).unwrap();
+                        }
+                        Err(mut packet) => packet.write_metadata(&IpTunnelMetadata::default()).unwrap(),
+                    }
+                }
+            }
+            self.applied = true;
+        }
+    }
+
+    #[inline]
+    fn done(&mut self) {
+        self.applied = false;
+        self.parent.done();
+    }
+
+    #[inline]
+    fn send_q(&mut self, port: &dyn PacketTx) -> Result<u32> {
+        self.parent.send_q(port)
+    }
+
+    #[inline]
+    fn capacity(&self) -> i32 {
+        self.parent.capacity()
+    }
+
+    #[inline]
+    fn drop_packets(&mut self, idxes: &[usize]) -> Option<usize> {
+        self.parent.drop_packets(idxes)
+    }
+
+    #[inline]
+    fn clear_packets(&mut self) {
+        self.parent.clear_packets()
+    }
+
+    #[inline]
+    fn get_packet_batch(&mut self) -> &mut PacketBatch {
+        self.parent.get_packet_batch()
+    }
+
+    #[inline]
+    fn get_task_dependencies(&self) -> Vec<usize> {
+        self.parent.get_task_dependencies()
+    }
+}
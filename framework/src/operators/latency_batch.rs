@@ -0,0 +1,119 @@
+use super::act::Act;
+use super::iterator::*;
+use super::packet_batch::PacketBatch;
+use super::Batch;
+use crate::common::*;
+use crate::interface::PacketTx;
+use crate::utils::{rdtsc_unsafe, record_latency};
+
+/// Latency batch.
+///
+/// Records, for every packet stamped by `TimestampBatch`, the cycles elapsed since it was stamped into the latency
+/// histogram of the current core (see `utils::record_latency`), and clears the stamp so a packet is only counted once.
+/// Place it right before `send` to measure the latency through the whole pipeline. Packets without a stamp are not
+/// counted; mbufs have theirs cleared when they are allocated or received.
+#[derive(Debug)]
+pub struct LatencyBatch<V>
+where
+    V: Batch + BatchIterator + Act,
+{
+    parent: V,
+    enabled: bool,
+    applied: bool,
+}
+
+impl<V> LatencyBatch<V>
+where
+    V: Batch + BatchIterator + Act,
+{
+    /// Return a latency batch, which passes packets through untouched unless `enabled`.
+    pub fn new(parent: V, enabled: bool) -> LatencyBatch<V> {
+        LatencyBatch {
+            parent,
+            enabled,
+            applied: false,
+        }
+    }
+}
+
+impl<V> Batch for LatencyBatch<V> where V: Batch + BatchIterator + Act {}
+
+impl<V> Act for LatencyBatch<V>
+where
+    V: Batch + BatchIterator + Act,
+{
+    #[inline]
+    fn act(&mut self) {
+        if !self.applied {
+            self.parent.act();
+            if self.enabled {
+                let now = rdtsc_unsafe();
+                let iter = PayloadEnumerator::<V::Header, V::Metadata>::new(&mut self.parent);
+                while let Some(ParsedDescriptor { mut packet, .. }) = iter.next(&mut self.parent) {
+                    let stamp = packet.read_timestamp();
+                    if stamp != 0 {
+                        if stamp <= now {
+                            record_latency(now - stamp);
+                        }
+                        packet.write_timestamp(0);
+                    }
+                }
+            }
+            self.applied = true;
+        }
+    }
+
+    #[inline]
+    fn done(&mut self) {
+        self.applied = false;
+        self.parent.done();
+    }
+
+    #[inline]
+    fn send_q(&mut self, port: &dyn PacketTx) -> Result<u32> {
+        self.parent.send_q(port)
+    }
+
+    #[inline]
+    fn capacity(&self) -> i32 {
+        self.parent.capacity()
+    }
+
+    #[inline]
+    fn drop_packets(&mut self, idxes: &[usize]) -> Option<usize> {
+        self.parent.drop_packets(idxes)
+    }
+
+    #[inline]
+    fn clear_packets(&mut self) {
+        self.parent.clear_packets()
+    }
+
+    #[inline]
+    fn get_packet_batch(&mut self) -> &mut PacketBatch {
+        self.parent.get_packet_batch()
+    }
+
+    #[inline]
+    fn get_task_dependencies(&self) -> Vec<usize> {
+        self.parent.get_task_dependencies()
+    }
+}
+
+impl<V> BatchIterator for LatencyBatch<V>
+where
+    V: Batch + BatchIterator + Act,
+{
+    type Header = V::Header;
+    type Metadata = V::Metadata;
+
+    #[inline]
+    fn start(&mut self) -> usize {
+        self.parent.start()
+    }
+
+    #[inline]
+    unsafe fn next_payload(&mut self, idx: usize) -> Option<PacketDescriptor<V::Header, V::Metadata>> {
+        self.parent.next_payload(idx)
+    }
+}
@@ -101,9 +101,11 @@ impl<T: Batch> Act for MergeBatch<T> {
 
 impl<T: Batch> Executable for MergeBatch<T> {
     #[inline]
-    fn execute(&mut self) {
+    fn execute(&mut self) -> usize {
         self.act();
+        let processed = self.get_packet_batch().available();
         self.done();
+        processed
     }
 
     #[inline]
@@ -6,24 +6,33 @@ use self::add_metadata::MetadataFn;
 pub use self::add_metadata_mut::MutableAddMetadataBatch;
 use self::add_metadata_mut::MutableMetadataFn;
 pub use self::composition_batch::CompositionBatch;
+pub use self::conntrack_batch::ConntrackBatch;
 pub use self::deparsed_batch::DeparsedBatch;
 pub use self::filter_batch::FilterBatch;
+pub use self::fragment_batch::{FragmentBatch, FragmentStats};
 use self::filter_batch::FilterFn;
 pub use self::group_by::*;
+pub use self::ip_tunnel_batch::{IpDecapBatch, IpEncapBatch, IpTunnelMetadata};
 pub use self::iterator::BatchIterator;
+pub use self::latency_batch::LatencyBatch;
 pub use self::map_batch::MapBatch;
 use self::map_batch::MapFn;
 pub use self::merge_batch::MergeBatch;
 pub use self::parsed_batch::ParsedBatch;
+pub use self::reassemble_batch::ReassembleBatch;
 pub use self::receive_batch::ReceiveBatch;
 pub use self::reset_parse::ResetParsingBatch;
 pub use self::restore_header::*;
 pub use self::send_batch::SendBatch;
+pub use self::timestamp_batch::TimestampBatch;
 pub use self::transform_batch::TransformBatch;
+pub use self::tunnel_batch::{DecapBatch, EncapBatch, TunnelMetadata};
 use self::transform_batch::TransformFn;
 use crate::headers::*;
 use crate::interface::*;
 use crate::scheduler::Scheduler;
+use crate::state::{ConnTracker, Ipv4Reassembler};
+use std::collections::HashMap;
 
 #[macro_use]
 mod macros;
@@ -32,19 +41,26 @@ mod act;
 mod add_metadata;
 mod add_metadata_mut;
 mod composition_batch;
+mod conntrack_batch;
 mod deparsed_batch;
 mod filter_batch;
+mod fragment_batch;
 mod group_by;
+mod ip_tunnel_batch;
 mod iterator;
+mod latency_batch;
 mod map_batch;
 mod merge_batch;
 mod packet_batch;
 mod parsed_batch;
+mod reassemble_batch;
 mod receive_batch;
 mod reset_parse;
 mod restore_header;
 mod send_batch;
+mod timestamp_batch;
 mod transform_batch;
+mod tunnel_batch;
 
 /// Merge a vector of batches into one batch. Currently this just round-robins between merged batches, but in the future
 /// the precise batch being processed will be determined by the scheduling policy used.
@@ -58,13 +74,25 @@ pub fn merge<T: Batch>(batches: Vec<T>) -> MergeBatch<T> {
 /// sent to another thread.
 pub trait Batch: BatchIterator + Act + Send {
     /// Parse the payload as header of type T.
-    fn parse<T: EndOffset<PreviousHeader = Self::Header>>(self) -> ParsedBatch<T, Self>
+    fn parse<T: Follows<Self::Header>>(self) -> ParsedBatch<T, Self>
     where
         Self: Sized,
     {
         ParsedBatch::<T, Self>::new(self)
     }
 
+    /// Parse the payload as header of type T, first dropping packets whose T header is truncated or fails
+    /// `Follows::check_correct_after`. With the `verify_checksum` feature this drops IPv4, TCP and UDP packets with bad
+    /// checksums.
+    fn parse_checked<T: 'static + Follows<Self::Header>>(
+        self,
+    ) -> ParsedBatch<T, FilterBatch<Self::Header, Self>>
+    where
+        Self: Sized,
+    {
+        self.filter(box |p| p.payload_header_correct::<T>()).parse::<T>()
+    }
+
     /// Add metadata to the batch.
     // TODO:doc
     fn metadata<M: Sized + Send>(
@@ -89,6 +117,109 @@ pub trait Batch: BatchIterator + Act + Send {
         MutableAddMetadataBatch::new(self, generator)
     }
 
+    /// Stamp every packet with the current TSC, so that `measure_latency` further down the pipeline can record how
+    /// long it took to get there. Usually applied right after receiving.
+    fn timestamp(self) -> TimestampBatch<Self>
+    where
+        Self: Sized,
+    {
+        TimestampBatch::<Self>::new(self, true)
+    }
+
+    /// Like `timestamp`, but only stamps packets when `enabled` (e.g., `ExprParam::inst`), so that instrumentation
+    /// can be switched off without changing the shape of the pipeline.
+    fn timestamp_if(self, enabled: bool) -> TimestampBatch<Self>
+    where
+        Self: Sized,
+    {
+        TimestampBatch::<Self>::new(self, enabled)
+    }
+
+    /// Record the latency of every packet stamped by `timestamp` into the per-core latency histogram, see
+    /// `utils::latency_snapshot` for reading it. Usually applied right before sending.
+    fn measure_latency(self) -> LatencyBatch<Self>
+    where
+        Self: Sized,
+    {
+        LatencyBatch::<Self>::new(self, true)
+    }
+
+    /// Like `measure_latency`, but only records when `enabled`, the counterpart of `timestamp_if`.
+    fn measure_latency_if(self, enabled: bool) -> LatencyBatch<Self>
+    where
+        Self: Sized,
+    {
+        LatencyBatch::<Self>::new(self, enabled)
+    }
+
+    /// Track the connections of IPv4 packets with `tracker`, replacing their metadata with their connection id and
+    /// state, see `state::ConnTracker`. A tracker keeps per-core state, use one per pipeline.
+    fn conntrack(self, tracker: ConnTracker) -> ConntrackBatch<Self>
+    where
+        Self: Sized + Batch<Header = IpHeader>,
+    {
+        ConntrackBatch::<Self>::new(self, tracker)
+    }
+
+    /// Reassemble fragmented IPv4 datagrams with `reassembler`: fragments are buffered and dropped from the batch, the
+    /// fragment completing a datagram is replaced by the whole datagram, in chained mbufs if it does not fit into one.
+    /// Apply before looking past the IP header, as only the first fragment carries the transport header. A reassembler
+    /// keeps per-core state, use one per pipeline.
+    fn reassemble_ipv4(self, reassembler: Ipv4Reassembler) -> ReassembleBatch<Self>
+    where
+        Self: Sized + Batch<Header = IpHeader>,
+    {
+        ReassembleBatch::<Self>::new(self, reassembler)
+    }
+
+    /// Split IPv4 packets longer than `mtu` bytes into fragments, dropping those with the don't fragment flag set
+    /// without sending ICMP errors back (see `FragmentBatch`). Apply last before sending, with the MTU of the port,
+    /// e.g., `PmdPort::mtu`.
+    fn fragment(self, mtu: usize) -> FragmentBatch<Self>
+    where
+        Self: Sized + Batch<Header = IpHeader>,
+    {
+        FragmentBatch::<Self>::new(self, mtu)
+    }
+
+    /// Tunnel Ethernet frames in VXLAN over IPv4 with the outer headers of `template`, dropping those without the
+    /// headroom for them. The metadata is replaced with the VNI and the inner flow, see `TunnelMetadata`.
+    fn encap_vxlan(self, template: VxlanTemplate) -> EncapBatch<Self>
+    where
+        Self: Sized + Batch<Header = MacHeader>,
+    {
+        EncapBatch::<Self>::new(self, template)
+    }
+
+    /// Strip the outer headers of Ethernet frames tunneled in VXLAN or GENEVE over IPv4, so that parsing further down
+    /// starts at the inner MAC header. The metadata is replaced with the network identifier and the inner flow, see
+    /// `TunnelMetadata`; other packets pass through.
+    fn decap(self) -> DecapBatch<Self>
+    where
+        Self: Sized + Batch<Header = MacHeader>,
+    {
+        DecapBatch::<Self>::new(self)
+    }
+
+    /// Tunnel IPv4 packets in IP-in-IP or GRE with the outer headers of `template`, dropping those without the headroom
+    /// for them. The metadata is replaced with the tunnel and the inner flow, see `IpTunnelMetadata`.
+    fn encap_ip(self, template: IpTunnelTemplate) -> IpEncapBatch<Self>
+    where
+        Self: Sized + Batch<Header = IpHeader>,
+    {
+        IpEncapBatch::<Self>::new(self, template)
+    }
+
+    /// Strip the outer headers of IPv4 packets tunneled in IP-in-IP or GRE, so that parsing further down continues from
+    /// the inner IP header. The metadata is replaced with the tunnel, the inner flow and the tenant `tenants` maps the
+    /// GRE key to, see `IpTunnelMetadata`; other packets pass through.
+    fn decap_ip(self, tenants: HashMap<u32, u32>) -> IpDecapBatch<Self>
+    where
+        Self: Sized + Batch<Header = IpHeader>,
+    {
+        IpDecapBatch::<Self>::new(self, tenants)
+    }
+
     /// Send this batch out a particular port and queue.
     fn send<Port: PacketTx>(self, port: Port) -> SendBatch<Port, Self>
     where
@@ -13,6 +13,8 @@ use std::result;
 #[derive(Debug)]
 pub struct PacketBatch {
     array: Vec<*mut MBuf>,
+    /// Packets received at once, and held at most when operators add packets, e.g., fragments.
+    capacity: usize,
     scratch: Vec<*mut MBuf>,
     parent_tasks: Vec<usize>,
 }
@@ -25,6 +27,7 @@ impl PacketBatch {
     pub fn new(cnt: i32) -> PacketBatch {
         PacketBatch {
             array: Vec::<*mut MBuf>::with_capacity(cnt as usize),
+            capacity: cnt as usize,
             scratch: Vec::<*mut MBuf>::with_capacity(cnt as usize),
             parent_tasks: vec![],
         }
@@ -44,7 +47,7 @@ impl PacketBatch {
     /// a packet. We always allocate mbuf's of the same size.
     #[inline]
     pub fn allocate_batch_with_size(&mut self, len: u16) -> Result<&mut Self> {
-        let capacity = self.array.capacity() as i32;
+        let capacity = self.capacity as i32;
         self.alloc_packet_batch(len, capacity).map(|_| self)
     }
 
@@ -87,17 +90,32 @@ impl PacketBatch {
     // Assumes we have already deallocated batch.
     #[inline]
     unsafe fn recv_internal<Rx: PacketRx>(&mut self, port: &Rx) -> Result<u32> {
-        let capacity = self.array.capacity();
+        let capacity = self.capacity;
         self.add_to_batch(capacity);
         match port.recv(self.packet_ptr()) {
             e @ Err(_) => e,
             Ok(recv) => {
                 self.add_to_batch(recv as usize);
+                // Drivers hand out recycled mbufs, which may still carry the latency stamp of an earlier packet.
+                for &mbuf in &self.array {
+                    (*mbuf).timestamp = 0;
+                }
                 Ok(recv)
             }
         }
     }
 
+    /// Insert `mbufs` after the packet at `idx`, keeping them in order, and take them over. Returns false, leaving the
+    /// mbufs to the caller, if they would take the batch past its capacity.
+    #[inline]
+    pub(crate) fn insert_packets(&mut self, idx: usize, mbufs: &[*mut MBuf]) -> bool {
+        if self.array.len() + mbufs.len() > self.capacity {
+            return false;
+        }
+        self.array.splice(idx + 1..idx + 1, mbufs.iter().cloned());
+        true
+    }
+
     /// This drops packet buffers and keeps things ordered. We expect that idxes is an ordered vector of indices, no
     /// guarantees are made when this is not the case.
     #[inline]
@@ -272,7 +290,7 @@ impl Act for PacketBatch {
 
     #[inline]
     fn capacity(&self) -> i32 {
-        self.array.capacity() as i32
+        self.capacity as i32
     }
 
     #[inline]
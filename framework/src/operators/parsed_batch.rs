@@ -3,7 +3,7 @@ use super::iterator::*;
 use super::packet_batch::PacketBatch;
 use super::Batch;
 use crate::common::*;
-use crate::headers::EndOffset;
+use crate::headers::Follows;
 use crate::interface::*;
 use std::marker::PhantomData;
 
@@ -15,7 +15,7 @@ use std::marker::PhantomData;
 #[derive(Debug)]
 pub struct ParsedBatch<T, V>
 where
-    T: EndOffset<PreviousHeader = V::Header>,
+    T: Follows<V::Header>,
     V: Batch + BatchIterator + Act,
 {
     parent: V,
@@ -24,7 +24,7 @@ where
 
 impl<T, V> Act for ParsedBatch<T, V>
 where
-    T: EndOffset<PreviousHeader = V::Header>,
+    T: Follows<V::Header>,
     V: Batch + BatchIterator + Act,
 {
     act! {}
@@ -33,14 +33,14 @@ where
 impl<T, V> Batch for ParsedBatch<T, V>
 where
     V: Batch + BatchIterator + Act,
-    T: EndOffset<PreviousHeader = V::Header>,
+    T: Follows<V::Header>,
 {
 }
 
 impl<T, V> ParsedBatch<T, V>
 where
     V: Batch + BatchIterator + Act,
-    T: EndOffset<PreviousHeader = V::Header>,
+    T: Follows<V::Header>,
 {
     /// Return a parse batch.
     // TODO:doc
@@ -56,7 +56,7 @@ where
 impl<T, V> BatchIterator for ParsedBatch<T, V>
 where
     V: Batch + BatchIterator + Act,
-    T: EndOffset<PreviousHeader = V::Header>,
+    T: Follows<V::Header>,
 {
     type Header = T;
     type Metadata = V::Metadata;
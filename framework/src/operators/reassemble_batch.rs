@@ -0,0 +1,132 @@
+use super::act::Act;
+use super::iterator::*;
+use super::packet_batch::PacketBatch;
+use super::Batch;
+use crate::common::*;
+use crate::headers::IpHeader;
+use crate::interface::PacketTx;
+use crate::state::{Ipv4Reassembler, Reassembly};
+use crate::utils::rdtsc_unsafe;
+
+/// IPv4 reassembly batch.
+///
+/// Runs every IPv4 packet through an `Ipv4Reassembler`. Fragments are dropped from the batch and buffered, and the
+/// fragment completing a datagram is replaced by the whole datagram, copied into its mbuf and, for datagrams that do
+/// not fit, into mbufs chained to it (see `Packet::write_chained`). Packets that are not fragments pass through.
+/// Datagrams that timed out are dropped after every batch.
+pub struct ReassembleBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    parent: V,
+    reassembler: Ipv4Reassembler,
+    remove: Vec<usize>,
+}
+
+impl<V> ReassembleBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    /// Return a reassembly batch using `reassembler`.
+    pub fn new(parent: V, reassembler: Ipv4Reassembler) -> ReassembleBatch<V> {
+        let capacity = parent.capacity() as usize;
+        ReassembleBatch {
+            parent,
+            reassembler,
+            remove: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// The reassembler, e.g., for reading its counters.
+    pub fn reassembler(&self) -> &Ipv4Reassembler {
+        &self.reassembler
+    }
+}
+
+impl<V> Batch for ReassembleBatch<V> where V: Batch<Header = IpHeader> + BatchIterator + Act {}
+
+impl<V> Act for ReassembleBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    #[inline]
+    fn act(&mut self) {
+        self.parent.act();
+        let now = rdtsc_unsafe();
+        {
+            let iter = PayloadEnumerator::<IpHeader, V::Metadata>::new(&mut self.parent);
+            while let Some(ParsedDescriptor { mut packet, index: idx }) = iter.next(&mut self.parent) {
+                match self.reassembler.insert(packet.get_header(), packet.get_payload(), now) {
+                    Reassembly::Unfragmented => {}
+                    Reassembly::Buffered | Reassembly::Dropped => self.remove.push(idx),
+                    Reassembly::Complete(datagram) => {
+                        if packet.write_chained(&datagram).is_err() {
+                            self.reassembler.record_alloc_failed();
+                            self.remove.push(idx);
+                        }
+                    }
+                }
+            }
+        }
+        if !self.remove.is_empty() {
+            self.parent
+                .drop_packets(&self.remove[..])
+                .expect("Reassembly dropped packets incorrectly");
+        }
+        self.remove.clear();
+        self.reassembler.expire(now);
+    }
+
+    #[inline]
+    fn done(&mut self) {
+        self.parent.done();
+    }
+
+    #[inline]
+    fn send_q(&mut self, port: &dyn PacketTx) -> Result<u32> {
+        self.parent.send_q(port)
+    }
+
+    #[inline]
+    fn capacity(&self) -> i32 {
+        self.parent.capacity()
+    }
+
+    #[inline]
+    fn drop_packets(&mut self, idxes: &[usize]) -> Option<usize> {
+        self.parent.drop_packets(idxes)
+    }
+
+    #[inline]
+    fn clear_packets(&mut self) {
+        self.parent.clear_packets()
+    }
+
+    #[inline]
+    fn get_packet_batch(&mut self) -> &mut PacketBatch {
+        self.parent.get_packet_batch()
+    }
+
+    #[inline]
+    fn get_task_dependencies(&self) -> Vec<usize> {
+        self.parent.get_task_dependencies()
+    }
+}
+
+impl<V> BatchIterator for ReassembleBatch<V>
+where
+    V: Batch<Header = IpHeader> + BatchIterator + Act,
+{
+    type Header = IpHeader;
+    type Metadata = V::Metadata;
+
+    #[inline]
+    fn start(&mut self) -> usize {
+        self.parent.start()
+    }
+
+    #[inline]
+    unsafe fn next_payload(&mut self, idx: usize) -> Option<PacketDescriptor<IpHeader, V::Metadata>> {
+        self.parent.next_payload(idx)
+    }
+}
@@ -115,8 +115,10 @@ where
     V: Batch + BatchIterator + Act,
 {
     #[inline]
-    fn execute(&mut self) {
-        self.act()
+    fn execute(&mut self) -> usize {
+        let sent = self.sent;
+        self.act();
+        (self.sent - sent) as usize
     }
 
     #[inline]
@@ -0,0 +1,111 @@
+use super::act::Act;
+use super::iterator::*;
+use super::packet_batch::PacketBatch;
+use super::Batch;
+use crate::common::*;
+use crate::interface::PacketTx;
+use crate::utils::rdtsc_unsafe;
+
+/// Timestamp batch.
+///
+/// Stores the current TSC in the timestamp field of every packet's mbuf, to be picked up by `LatencyBatch`. All
+/// packets of a batch share one timestamp, taken right after the parent acted (e.g., received the batch).
+#[derive(Debug)]
+pub struct TimestampBatch<V>
+where
+    V: Batch + BatchIterator + Act,
+{
+    parent: V,
+    enabled: bool,
+    applied: bool,
+}
+
+impl<V> TimestampBatch<V>
+where
+    V: Batch + BatchIterator + Act,
+{
+    /// Return a timestamp batch, which passes packets through untouched unless `enabled`.
+    pub fn new(parent: V, enabled: bool) -> TimestampBatch<V> {
+        TimestampBatch {
+            parent,
+            enabled,
+            applied: false,
+        }
+    }
+}
+
+impl<V> Batch for TimestampBatch<V> where V: Batch + BatchIterator + Act {}
+
+impl<V> Act for TimestampBatch<V>
+where
+    V: Batch + BatchIterator + Act,
+{
+    #[inline]
+    fn act(&mut self) {
+        if !self.applied {
+            self.parent.act();
+            if self.enabled {
+                let now = rdtsc_unsafe();
+                let iter = PayloadEnumerator::<V::Header, V::Metadata>::new(&mut self.parent);
+                while let Some(ParsedDescriptor { mut packet, .. }) = iter.next(&mut self.parent) {
+                    packet.write_timestamp(now);
+                }
+            }
+            self.applied = true;
+        }
+    }
+
+    #[inline]
+    fn done(&mut self) {
+        self.applied = false;
+        self.parent.done();
+    }
+
+    #[inline]
+    fn send_q(&mut self, port: &dyn PacketTx) -> Result<u32> {
+        self.parent.send_q(port)
+    }
+
+    #[inline]
+    fn capacity(&self) -> i32 {
+        self.parent.capacity()
+    }
+
+    #[inline]
+    fn drop_packets(&mut self, idxes: &[usize]) -> Option<usize> {
+        self.parent.drop_packets(idxes)
+    }
+
+    #[inline]
+    fn clear_packets(&mut self) {
+        self.parent.clear_packets()
+    }
+
+    #[inline]
+    fn get_packet_batch(&mut self) -> &mut PacketBatch {
+        self.parent.get_packet_batch()
+    }
+
+    #[inline]
+    fn get_task_dependencies(&self) -> Vec<usize> {
+        self.parent.get_task_dependencies()
+    }
+}
+
+impl<V> BatchIterator for TimestampBatch<V>
+where
+    V: Batch + BatchIterator + Act,
+{
+    type Header = V::Header;
+    type Metadata = V::Metadata;
+
+    #[inline]
+    fn start(&mut self) -> usize {
+        self.parent.start()
+    }
+
+    #[inline]
+    unsafe fn next_payload(&mut self, idx: usize) -> Option<PacketDescriptor<V::Header, V::Metadata>> {
+        self.parent.next_payload(idx)
+    }
+}
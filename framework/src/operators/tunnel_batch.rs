@@ -0,0 +1,256 @@
+use super::act::Act;
+use super::iterator::*;
+use super::packet_batch::PacketBatch;
+use super::Batch;
+use crate::common::*;
+use crate::headers::{frame_flow, header_bytes, MacHeader, VxlanTemplate};
+use crate::interface::PacketTx;
+use crate::utils::Flow;
+
+/// Metadata of packets going through `EncapBatch` and `DecapBatch`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TunnelMetadata {
+    /// Network identifier of the tunnel, `None` for packets that were not tunneled.
+    pub vni: Option<u32>,
+    /// Flow of the inner IPv4 packet, if there is one. Outer headers hide it from operators that parse from the outer
+    /// MAC header.
+    pub inner: Option<Flow>,
+}
+
+/// VXLAN encapsulation batch.
+///
+/// Puts the outer headers of a `VxlanTemplate` in front of every Ethernet frame, see `VxlanTemplate::encap`, and
+/// replaces its metadata with the VNI and the inner flow. Packets without the headroom for the outer headers are
+/// dropped and counted.
+pub struct EncapBatch<V>
+where
+    V: Batch<Header = MacHeader> + BatchIterator + Act,
+{
+    parent: V,
+    template: VxlanTemplate,
+    applied: bool,
+    remove: Vec<usize>,
+    dropped: u64,
+}
+
+impl<V> EncapBatch<V>
+where
+    V: Batch<Header = MacHeader> + BatchIterator + Act,
+{
+    /// Return an encapsulation batch using `template`.
+    pub fn new(parent: V, template: VxlanTemplate) -> EncapBatch<V> {
+        let capacity = parent.capacity() as usize;
+        EncapBatch {
+            parent,
+            template,
+            applied: false,
+            remove: Vec::with_capacity(capacity),
+            dropped: 0,
+        }
+    }
+
+    /// Packets dropped for lack of headroom.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+impl<V> Batch for EncapBatch<V> where V: Batch<Header = MacHeader> + BatchIterator + Act {}
+
+impl<V> BatchIterator for EncapBatch<V>
+where
+    V: Batch<Header = MacHeader> + BatchIterator + Act,
+{
+    type Header = MacHeader;
+    type Metadata = TunnelMetadata;
+
+    #[inline]
+    fn start(&mut self) -> usize {
+        self.parent.start()
+    }
+
+    #[inline]
+    unsafe fn next_payload(&mut self, idx: usize) -> Option<PacketDescriptor<MacHeader, TunnelMetadata>> {
+        self.parent.next_payload(idx).map(|p| PacketDescriptor {
+            packet: p.packet.reinterpret_metadata(),
+        })
+    }
+}
+
+impl<V> Act for EncapBatch<V>
+where
+    V: Batch<Header = MacHeader> + BatchIterator + Act,
+{
+    #[inline]
+    fn act(&mut self) {
+        if !self.applied {
+            self.parent.act();
+            {
+                let iter = PayloadEnumerator::<MacHeader, V::Metadata>::new(&mut self.parent);
+                while let Some(ParsedDescriptor { packet, index: idx }) = iter.next(&mut self.parent) {
+                    let metadata = TunnelMetadata {
+                        vni: Some(self.template.vni),
+                        inner: frame_flow(header_bytes(&packet)),
+                    };
+                    match self.template.encap(packet) {
+                        Some(mut packet) => packet.write_metadata(&metadata).unwrap(),
+                        None => self.remove.push(idx),
+                    }
+                }
+            }
+            if !self.remove.is_empty() {
+                self.dropped += self.remove.len() as u64;
+                self.parent
+                    .drop_packets(&self.remove[..])
+                    .expect("Encapsulation dropped packets incorrectly");
+                self.remove.clear();
+            }
+            self.applied = true;
+        }
+    }
+
+    #[inline]
+    fn done(&mut self) {
+        self.applied = false;
+        self.parent.done();
+    }
+
+    #[inline]
+    fn send_q(&mut self, port: &dyn PacketTx) -> Result<u32> {
+        self.parent.send_q(port)
+    }
+
+    #[inline]
+    fn capacity(&self) -> i32 {
+        self.parent.capacity()
+    }
+
+    #[inline]
+    fn drop_packets(&mut self, idxes: &[usize]) -> Option<usize> {
+        self.parent.drop_packets(idxes)
+    }
+
+    #[inline]
+    fn clear_packets(&mut self) {
+        self.parent.clear_packets()
+    }
+
+    #[inline]
+    fn get_packet_batch(&mut self) -> &mut PacketBatch {
+        self.parent.get_packet_batch()
+    }
+
+    #[inline]
+    fn get_task_dependencies(&self) -> Vec<usize> {
+        self.parent.get_task_dependencies()
+    }
+}
+
+/// Tunnel decapsulation batch.
+///
+/// Strips the outer headers of Ethernet frames tunneled in VXLAN or GENEVE over IPv4, see `Packet::decap`, so that
+/// operators further down parse from the inner MAC header. The metadata is replaced with the network identifier and
+/// the inner flow; other packets pass through unchanged, without either.
+pub struct DecapBatch<V>
+where
+    V: Batch<Header = MacHeader> + BatchIterator + Act,
+{
+    parent: V,
+    applied: bool,
+}
+
+impl<V> DecapBatch<V>
+where
+    V: Batch<Header = MacHeader> + BatchIterator + Act,
+{
+    /// Return a decapsulation batch.
+    pub fn new(parent: V) -> DecapBatch<V> {
+        DecapBatch { parent, applied: false }
+    }
+}
+
+impl<V> Batch for DecapBatch<V> where V: Batch<Header = MacHeader> + BatchIterator + Act {}
+
+impl<V> BatchIterator for DecapBatch<V>
+where
+    V: Batch<Header = MacHeader> + BatchIterator + Act,
+{
+    type Header = MacHeader;
+    type Metadata = TunnelMetadata;
+
+    #[inline]
+    fn start(&mut self) -> usize {
+        self.parent.start()
+    }
+
+    #[inline]
+    unsafe fn next_payload(&mut self, idx: usize) -> Option<PacketDescriptor<MacHeader, TunnelMetadata>> {
+        self.parent.next_payload(idx).map(|p| PacketDescriptor {
+            packet: p.packet.reinterpret_metadata(),
+        })
+    }
+}
+
+impl<V> Act for DecapBatch<V>
+where
+    V: Batch<Header = MacHeader> + BatchIterator + Act,
+{
+    #[inline]
+    fn act(&mut self) {
+        if !self.applied {
+            self.parent.act();
+            {
+                let iter = PayloadEnumerator::<MacHeader, V::Metadata>::new(&mut self.parent);
+                while let Some(ParsedDescriptor { packet, .. }) = iter.next(&mut self.parent) {
+                    match packet.decap() {
+                        Ok((mut inner, vni)) => {
+                            let metadata = TunnelMetadata {
+                                vni: Some(vni),
+                                inner: frame_flow(header_bytes(&inner)),
+                            };
+                            inner.write_metadata(&metadata).unwrap();
+                        }
+                        Err(mut packet) => packet.write_metadata(&TunnelMetadata::default()).unwrap(),
+                    }
+                }
+            }
+            self.applied = true;
+        }
+    }
+
+    #[inline]
+    fn done(&mut self) {
+        self.applied = false;
+        self.parent.done();
+    }
+
+    #[inline]
+    fn send_q(&mut self, port: &dyn PacketTx) -> Result<u32> {
+        self.parent.send_q(port)
+    }
+
+    #[inline]
+    fn capacity(&self) -> i32 {
+        self.parent.capacity()
+    }
+
+    #[inline]
+    fn drop_packets(&mut self, idxes: &[usize]) -> Option<usize> {
+        self.parent.drop_packets(idxes)
+    }
+
+    #[inline]
+    fn clear_packets(&mut self) {
+        self.parent.clear_packets()
+    }
+
+    #[inline]
+    fn get_packet_batch(&mut self) -> &mut PacketBatch {
+        self.parent.get_packet_batch()
+    }
+
+    #[inline]
+    fn get_task_dependencies(&self) -> Vec<usize> {
+        self.parent.get_task_dependencies()
+    }
+}
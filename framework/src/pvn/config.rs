@@ -0,0 +1,248 @@
+//! Typed configuration for PVN experiments.
+//!
+//! The configuration used to be read ad hoc from a JSON file at a fixed path by each NF. It is now parsed once into a
+//! `PvnExperimentConfig`, from JSON or TOML, with defaults for missing keys. The file is given by `pvn_config` in the
+//! `NetbricksConfiguration` (or `--pvn_config` on the command line); NFs retrieve it with `experiment_config`.
+use crate::common::*;
+use crate::pvn::measure::{ExprParam, APP_MEASURE_TIME, LONG_MEASURE_TIME, MEDIUM_MEASURE_TIME, SHORT_MEASURE_TIME};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// Largest setup (workload level) the PVN NFs know about.
+pub const MAX_SETUP: usize = 20;
+
+/// How long an experiment runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExperimentMode {
+    /// Short experiment with instrumentation.
+    Short,
+    /// Medium experiment with instrumentation.
+    Medium,
+    /// Long experiment with instrumentation.
+    Long,
+    /// Application experiment.
+    App,
+}
+
+impl ExperimentMode {
+    /// Running time of the experiment in seconds.
+    pub fn expr_time(self) -> u64 {
+        match self {
+            ExperimentMode::Short => SHORT_MEASURE_TIME,
+            ExperimentMode::Medium => MEDIUM_MEASURE_TIME,
+            ExperimentMode::Long => LONG_MEASURE_TIME,
+            ExperimentMode::App => APP_MEASURE_TIME,
+        }
+    }
+}
+
+/// Disk backing the browser profiles of the RDR NF.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiskType {
+    /// Hard disk.
+    Hdd,
+    /// Solid state disk.
+    Ssd,
+}
+
+/// Parameters of a PVN experiment.
+///
+/// Setups select the workload level; the per-NF setups override `setup` for one NF when they are not 0. Numbers may
+/// be given as strings and `inst` as `"on"`/`"off"`, as in the JSON files written by the experiment scripts.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PvnExperimentConfig {
+    /// Setup (workload level).
+    #[serde(deserialize_with = "number_or_string")]
+    pub setup: usize,
+    /// TLSV setup.
+    #[serde(deserialize_with = "number_or_string")]
+    pub tlsv_setup: usize,
+    /// RDR setup.
+    #[serde(deserialize_with = "number_or_string")]
+    pub rdr_setup: usize,
+    /// XCDR setup.
+    #[serde(deserialize_with = "number_or_string")]
+    pub xcdr_setup: usize,
+    /// P2P setup.
+    #[serde(deserialize_with = "number_or_string")]
+    pub p2p_setup: usize,
+    /// Iteration of this run.
+    #[serde(deserialize_with = "number_or_string")]
+    pub iter: usize,
+    /// Whether latency instrumentation is turned on.
+    #[serde(deserialize_with = "on_off")]
+    pub inst: bool,
+    /// How long the experiment runs.
+    pub mode: ExperimentMode,
+    /// Kind of P2P workload.
+    pub p2p_type: String,
+    /// Disk for the RDR browser profiles.
+    pub disk: DiskType,
+}
+
+impl Default for PvnExperimentConfig {
+    fn default() -> PvnExperimentConfig {
+        PvnExperimentConfig {
+            setup: 1,
+            tlsv_setup: 0,
+            rdr_setup: 0,
+            xcdr_setup: 0,
+            p2p_setup: 0,
+            iter: 1,
+            inst: false,
+            mode: ExperimentMode::Short,
+            p2p_type: String::from("p2p_controlled"),
+            disk: DiskType::Hdd,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString {
+    Number(usize),
+    String(String),
+}
+
+fn number_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<usize, D::Error> {
+    match NumberOrString::deserialize(deserializer)? {
+        NumberOrString::Number(n) => Ok(n),
+        NumberOrString::String(s) => s
+            .trim()
+            .parse()
+            .map_err(|_| serde::de::Error::custom(format!("expected a number, found {:?}", s))),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BoolOrString {
+    Bool(bool),
+    String(String),
+}
+
+fn on_off<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<bool, D::Error> {
+    match BoolOrString::deserialize(deserializer)? {
+        BoolOrString::Bool(b) => Ok(b),
+        BoolOrString::String(ref s) if s == "on" => Ok(true),
+        BoolOrString::String(ref s) if s == "off" => Ok(false),
+        BoolOrString::String(s) => Err(serde::de::Error::custom(format!(
+            "expected \"on\" or \"off\", found {:?}",
+            s
+        ))),
+    }
+}
+
+/// Format of a configuration file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigFormat {
+    /// JSON.
+    Json,
+    /// TOML.
+    Toml,
+}
+
+impl ConfigFormat {
+    /// Guess the format from the file extension, or else from the contents: JSON files start with `{`.
+    pub fn detect(path: &str, contents: &str) -> ConfigFormat {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("toml") => ConfigFormat::Toml,
+            Some("json") => ConfigFormat::Json,
+            _ if contents.trim_start().starts_with('{') => ConfigFormat::Json,
+            _ => ConfigFormat::Toml,
+        }
+    }
+}
+
+impl PvnExperimentConfig {
+    /// Parse and validate a configuration. `name` is only used for error messages.
+    pub fn from_str(contents: &str, format: ConfigFormat, name: &str) -> Result<PvnExperimentConfig> {
+        let parsed = match format {
+            ConfigFormat::Json => serde_json::from_str(contents).map_err(|e| e.to_string()),
+            ConfigFormat::Toml => toml::from_str(contents).map_err(|e| e.to_string()),
+        };
+        let config: PvnExperimentConfig =
+            parsed.map_err(|reason| ErrorKind::BadExperimentConfig(name.to_string(), reason))?;
+        config.validate(name)?;
+        Ok(config)
+    }
+
+    /// Read, parse and validate a configuration file.
+    pub fn from_file(path: &str) -> Result<PvnExperimentConfig> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| ErrorKind::BadExperimentConfig(path.to_string(), e.to_string()))?;
+        PvnExperimentConfig::from_str(&contents, ConfigFormat::detect(path, &contents), path)
+    }
+
+    fn validate(&self, name: &str) -> Result<()> {
+        let bad = |reason: String| Err(ErrorKind::BadExperimentConfig(name.to_string(), reason).into());
+        if self.setup == 0 || self.setup > MAX_SETUP {
+            return bad(format!("setup {} is not between 1 and {}", self.setup, MAX_SETUP));
+        }
+        for &(key, value) in &[
+            ("tlsv_setup", self.tlsv_setup),
+            ("rdr_setup", self.rdr_setup),
+            ("xcdr_setup", self.xcdr_setup),
+            ("p2p_setup", self.p2p_setup),
+        ] {
+            if value > MAX_SETUP {
+                return bad(format!("{} {} is not between 0 and {}", key, value, MAX_SETUP));
+            }
+        }
+        Ok(())
+    }
+
+    /// The setup an NF should use given its own setup: `setup` unless the NF's is set (not 0).
+    #[inline]
+    pub fn effective_setup(&self, nf_setup: usize) -> usize {
+        if nf_setup != 0 {
+            nf_setup
+        } else {
+            self.setup
+        }
+    }
+
+    /// Running time of the experiment in seconds.
+    #[inline]
+    pub fn expr_time(&self) -> u64 {
+        self.mode.expr_time()
+    }
+
+    /// The parameters NFs capture in their closures.
+    pub fn expr_param(&self) -> ExprParam {
+        ExprParam {
+            setup: self.setup,
+            tlsv_setup: self.tlsv_setup,
+            rdr_setup: self.rdr_setup,
+            xcdr_setup: self.xcdr_setup,
+            p2p_setup: self.p2p_setup,
+            iter: self.iter,
+            inst: self.inst,
+            expr_time: self.expr_time(),
+        }
+    }
+}
+
+lazy_static! {
+    static ref EXPERIMENT_CONFIG: RwLock<Option<Arc<PvnExperimentConfig>>> = RwLock::new(None);
+}
+
+/// Set the configuration returned by `experiment_config`. `initialize_system` does this when the
+/// `NetbricksConfiguration` names a `pvn_config` file.
+pub fn set_experiment_config(config: PvnExperimentConfig) {
+    *EXPERIMENT_CONFIG.write().unwrap() = Some(Arc::new(config));
+}
+
+/// The configuration of the running experiment. Fails if none was set. NFs load it once, when their pipelines are
+/// built, and pass it on from there.
+pub fn experiment_config() -> Result<Arc<PvnExperimentConfig>> {
+    match *EXPERIMENT_CONFIG.read().unwrap() {
+        Some(ref config) => Ok(config.clone()),
+        None => Err(ErrorKind::NoExperimentConfig.into()),
+    }
+}
@@ -1,10 +1,11 @@
 //! Utils functions for measuring the PVN NFs.
-use crate::utils::Flow;
-use serde_json::{from_reader, Value};
+use crate::pvn::config::PvnExperimentConfig;
+use crate::scheduler::add_shutdown_hook;
+use crate::stats::{Summary, DEFAULT_PERCENTILES};
+use crate::utils::{core_latency_snapshots, latency_snapshot, Flow};
 use statrs::statistics::{Max, Mean, Median, Min};
 use statrs::statistics::{OrderStatistics, Variance};
 use std::collections::HashMap;
-use std::fs::File;
 use std::time::Instant;
 
 /// Epsilon.
@@ -37,7 +38,7 @@ pub fn fake_flow() -> Flow {
     }
 }
 
-/// experiment parameters.
+/// Experiment parameters NFs capture in their closures, see `PvnExperimentConfig::expr_param`.
 #[derive(Debug, Clone, Copy)]
 pub struct ExprParam {
     /// setup (workload level)
@@ -58,126 +59,30 @@ pub struct ExprParam {
     pub expr_time: u64,
 }
 
-/// Read various params from setup.
-///
-/// Currently returns: *setup* (which setup it is), *iter* (which iteration it
-/// is), *inst* (instrumentation for retrieving latencies for every packet),
-/// and *expr running time* (how long the NF will run).
-///
-/// This impl probably can be optimized.
+/// Read various params from setup. Forwards to `PvnExperimentConfig::from_file`.
+#[deprecated(since = "0.2.0", note = "use `PvnExperimentConfig::from_file`")]
 pub fn read_setup_param(file_path: String) -> Option<ExprParam> {
-    let file = File::open(file_path.clone()).expect("file should open read only");
-    let read_json = file_path + "should be proper JSON";
-    let json: Value = from_reader(file).expect(&read_json);
-
-    let setup: Option<String> = match serde_json::from_value(json.get("setup").expect("file should have setup").clone())
-    {
-        Ok(val) => Some(val),
+    match PvnExperimentConfig::from_file(&file_path) {
+        Ok(config) => Some(config.expr_param()),
         Err(e) => {
-            println!("Malformed JSON response for setup: {}", e);
+            println!("{}", e);
             None
         }
-    };
-    let setup = setup.unwrap().parse::<usize>();
+    }
+}
 
-    // setup all NF setups
-    let tlsv_setup: Option<String> =
-        match serde_json::from_value(json.get("tlsv_setup").expect("file could have tlsv setup").clone()) {
-            Ok(val) => Some(val),
-            Err(e) => {
-                println!("Malformed JSON response for tlsv_setup: {}", e);
-                None
-            }
-        };
-    let rdr_setup: Option<String> =
-        match serde_json::from_value(json.get("rdr_setup").expect("file should have rdr_setup").clone()) {
-            Ok(val) => Some(val),
-            Err(e) => {
-                println!("Malformed JSON response for rdr_setup: {}", e);
-                None
+/// Print the latencies recorded by `measure_latency_if(param.inst)`, per core and overall, once the system shuts down.
+/// Does nothing unless `param.inst` turned on instrumentation. Call it once per NF, e.g., from `main`.
+pub fn report_latency_at_shutdown(param: &ExprParam) {
+    if param.inst {
+        add_shutdown_hook(|| {
+            let mut cores: Vec<_> = core_latency_snapshots().into_iter().collect();
+            cores.sort_by_key(|&(core, _)| core);
+            for (core, latency) in cores {
+                println!("CORE {} LATENCY {}", core, latency);
             }
-        };
-    let xcdr_setup: Option<String> =
-        match serde_json::from_value(json.get("xcdr_setup").expect("file should have xcdr_setup").clone()) {
-            Ok(val) => Some(val),
-            Err(e) => {
-                println!("Malformed JSON response for xcdr_setup: {}", e);
-                None
-            }
-        };
-    let p2p_setup: Option<String> =
-        match serde_json::from_value(json.get("p2p_setup").expect("file should have p2p_setup").clone()) {
-            Ok(val) => Some(val),
-            Err(e) => {
-                println!("Malformed JSON response for p2p_setup: {}", e);
-                None
-            }
-        };
-    let tlsv_setup = tlsv_setup.unwrap().parse::<usize>();
-    let rdr_setup = rdr_setup.unwrap().parse::<usize>();
-    let xcdr_setup = xcdr_setup.unwrap().parse::<usize>();
-    let p2p_setup = p2p_setup.unwrap().parse::<usize>();
-
-    let iter: Option<String> = match serde_json::from_value(json.get("iter").expect("file should have iter").clone()) {
-        Ok(val) => Some(val),
-        Err(e) => {
-            println!("Malformed JSON response: {}", e);
-            None
-        }
-    };
-    let iter = iter.unwrap().parse::<usize>();
-
-    let inst: Option<String> = match serde_json::from_value(json.get("inst").expect("file should have inst").clone()) {
-        Ok(val) => Some(val),
-        Err(e) => {
-            println!("Malformed JSON response: {}", e);
-            None
-        }
-    };
-    let inst_val = match &*inst.unwrap() {
-        "on" => Some(true),
-        "off" => Some(false),
-        _ => None,
-    };
-
-    let mode: Option<String> = match serde_json::from_value(json.get("mode").expect("file should have mode").clone()) {
-        Ok(val) => Some(val),
-        Err(e) => {
-            println!("Malformed JSON response: {}", e);
-            None
-        }
-    };
-    let expr_time = match &*mode.unwrap() {
-        "short" => Some(SHORT_MEASURE_TIME),
-        "medium" => Some(MEDIUM_MEASURE_TIME),
-        "long" => Some(LONG_MEASURE_TIME),
-        _ => None,
-    };
-
-    if let (
-        Ok(setup),
-        Ok(tlsv_setup),
-        Ok(rdr_setup),
-        Ok(xcdr_setup),
-        Ok(p2p_setup),
-        Ok(iter),
-        Some(inst),
-        Some(expr_time),
-    ) = (
-        setup, tlsv_setup, rdr_setup, xcdr_setup, p2p_setup, iter, inst_val, expr_time,
-    ) {
-        Some(ExprParam {
-            setup,
-            tlsv_setup,
-            rdr_setup,
-            xcdr_setup,
-            p2p_setup,
-            iter,
-            inst,
-            expr_time,
-        })
-    } else {
-        None
+            println!("LATENCY {}", latency_snapshot());
+        });
     }
 }
 
@@ -215,10 +120,12 @@ pub fn merge_ts(
     actual_ts
 }
 
-/// Compute statistics for the latency results collected.
-pub fn compute_stat(mut tmp_results: Vec<u128>) {
+/// Compute statistics for the latency results collected, print them and return them as a `Summary` (over all results,
+/// unlike the printed groups, which leave out the top 1%) for `StatsReport`.
+pub fn compute_stat(mut tmp_results: Vec<u128>) -> Summary {
     tmp_results.sort_unstable();
     let mut results: Vec<f64> = tmp_results.into_iter().map(|item| item as f64).collect();
+    let summary = Summary::from_samples(&results, &DEFAULT_PERCENTILES, 100);
     let bar = results.percentile(99);
     let (rest, mut main): (_, Vec<_>) = results.into_iter().partition(|x| x >= &bar);
     println!("sorting and then type casting done",);
@@ -267,4 +174,5 @@ pub fn compute_stat(mut tmp_results: Vec<u128>) {
         main.percentile(75),
         max,
     );
+    summary
 }
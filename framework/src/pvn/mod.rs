@@ -1,4 +1,5 @@
 //! Various functionalities for PVN NFs.
+pub mod config;
 pub mod measure;
 pub mod p2p;
 pub mod rdr;
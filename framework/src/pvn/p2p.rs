@@ -1,5 +1,5 @@
 //! Utils functions for the PVN P2P NF.
-use crate::pvn::measure::read_setup_param;
+use crate::pvn::config::PvnExperimentConfig;
 use serde_json::{from_reader, Value};
 use std::collections::HashMap;
 use std::fs::File;
@@ -110,77 +110,28 @@ fn construct_p2p_job() -> Option<(HashMap<usize, usize>, HashMap<usize, usize>,
 /// 20 torrent job in total -- 50% pktgen sending rate
 /// 30 torrent job in total -- 75% pktgen sending rate
 /// 40 torrent job in total -- 100% pktgen sending rate
-pub fn p2p_retrieve_param(fp_setup: String) -> Option<usize> {
-    // let p2p_type = p2p_read_type(fp_setup.clone()).unwrap();
-    let param = read_setup_param(fp_setup).unwrap();
+pub fn p2p_retrieve_param(config: &PvnExperimentConfig) -> Option<usize> {
     let (_, mut p2p_controlled_map, _) = construct_p2p_job().unwrap();
-
-    if param.p2p_setup != 0 {
-        p2p_controlled_map.remove(&param.p2p_setup)
-    } else {
-        p2p_controlled_map.remove(&param.setup)
-    }
+    p2p_controlled_map.remove(&config.effective_setup(config.p2p_setup))
 }
 
-/// Retrieve the p2p type param in the pvn setup config file.
+/// Retrieve the p2p type param in the pvn setup config file. Forwards to `PvnExperimentConfig::from_file`.
+#[deprecated(since = "0.2.0", note = "use `PvnExperimentConfig::p2p_type`")]
 pub fn p2p_read_type(fp_setup: String) -> Option<String> {
-    println!("p2p read type: {}", fp_setup);
-    let file = File::open(fp_setup.clone()).expect("file should open read only");
-    let read_json = fp_setup + "should be proper JSON";
-    let json_data: Value = from_reader(file).expect(&read_json);
-
-    let p2p_type: Option<String> =
-        match serde_json::from_value(json_data.get("p2p_type").expect("file should have p2p type").clone()) {
-            Ok(val) => Some(val),
-            Err(e) => {
-                println!("Malformed JSON response for p2p_type: {}", e);
-                None
-            }
-        };
-    p2p_type
+    match PvnExperimentConfig::from_file(&fp_setup) {
+        Ok(config) => Some(config.p2p_type),
+        Err(e) => {
+            println!("{}", e);
+            None
+        }
+    }
 }
 
 /// Retrieve the corresponding workload based on pvn setup config file.
-pub fn p2p_fetch_workload(fp_setup: String) -> Option<String> {
-    println!("fetch worklaod");
-    let (mut p2p_ext_map, mut p2p_controlled_map, mut p2p_general_map) = construct_p2p_workload().unwrap();
-
-    let file = File::open(fp_setup.clone()).expect("file should open read only");
-    let read_json = fp_setup + "should be proper JSON";
-    let json_data: Value = from_reader(file).expect(&read_json);
-
-    let setup: Option<String> =
-        match serde_json::from_value(json_data.get("setup").expect("file should have setup").clone()) {
-            Ok(val) => Some(val),
-            Err(e) => {
-                println!("Malformed JSON response for setup: {}", e);
-                None
-            }
-        };
-    let p2p_setup: Option<String> =
-        match serde_json::from_value(json_data.get("p2p_setup").expect("file should have p2p_setup").clone()) {
-            Ok(val) => Some(val),
-            Err(e) => {
-                println!("Malformed JSON response for setup: {}", e);
-                None
-            }
-        };
-    let setup = if p2p_setup.clone().unwrap() != "0" {
-        p2p_setup
-    } else {
-        setup
-    };
-
-    let p2p_type: Option<String> =
-        match serde_json::from_value(json_data.get("p2p_type").expect("file should have setup").clone()) {
-            Ok(val) => Some(val),
-            Err(e) => {
-                println!("Malformed JSON response for p2p_type: {}", e);
-                None
-            }
-        };
-
-    p2p_controlled_map.remove(&*setup.unwrap())
+pub fn p2p_fetch_workload(config: &PvnExperimentConfig) -> Option<String> {
+    let (_, mut p2p_controlled_map, _) = construct_p2p_workload().unwrap();
+    let setup = config.effective_setup(config.p2p_setup).to_string();
+    p2p_controlled_map.remove(&*setup)
 }
 
 /// Parse the given p2p json workload.
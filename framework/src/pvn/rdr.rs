@@ -1,38 +1,23 @@
 //! Utils functions for the PVN RDR NF.
+use crate::pvn::config::{DiskType, PvnExperimentConfig};
 use crate::pvn::unresolvable::curate_unresolvable_records;
-use serde_json::{from_reader, json, Value};
+use serde_json::{from_reader, Value};
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{Error, ErrorKind, Result};
+use std::io::Result;
 use std::vec::Vec;
 
 /// setup profile and user data dir with different disk setup
-pub fn rdr_read_user_data_dir(file_path: String) -> Result<String> {
-    let file = File::open(file_path.clone()).expect("file should open read only");
-    let read_json = file_path + "should be proper JSON";
-    let json: Value = from_reader(file).expect(&read_json);
-
-    let disk_type = match json.get("disk") {
-        Some(val) => Some(val.clone()),
-        None => {
-            println!("disk setup should exist but not, using hdd by default..");
-            Some(json!("hdd"))
-        }
-    };
-    match disk_type {
-        // --profile-directory="C:\temp\profile" --user-data-dir="C:\temp\profile\userdata"
-        Some(val) => {
-            if val == "hdd" {
-                println!("running chrome with hdd");
-                Ok("/data/tmp/profile".to_string())
-            } else {
-                println!("running chrome with ssd");
-                Ok("/home/jethros/data/profile".to_string())
-            }
+pub fn rdr_read_user_data_dir(config: &PvnExperimentConfig) -> Result<String> {
+    // --profile-directory="C:\temp\profile" --user-data-dir="C:\temp\profile\userdata"
+    match config.disk {
+        DiskType::Hdd => {
+            println!("running chrome with hdd");
+            Ok("/data/tmp/profile".to_string())
         }
-        None => {
-            println!("unable to read disk setup");
-            Err(Error::new(ErrorKind::Other, "unable to read disk setup"))
+        DiskType::Ssd => {
+            println!("running chrome with ssd");
+            Ok("/home/jethros/data/profile".to_string())
         }
     }
 }
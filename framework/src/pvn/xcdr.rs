@@ -1,18 +1,23 @@
 //! Utils functions for the PVN Transcoder NF.
-use serde_json::{from_reader, Value};
+use crate::pvn::config::PvnExperimentConfig;
+use crate::pvn::measure;
 use std::collections::HashMap;
-use std::fs::File;
 
 /// Time for the short experiment with instrumentation.
-pub const SHORT_MEASURE_TIME: u64 = 181;
+#[deprecated(since = "0.2.0", note = "use `pvn::measure::SHORT_MEASURE_TIME`")]
+pub const SHORT_MEASURE_TIME: u64 = measure::SHORT_MEASURE_TIME;
 /// Time for the medium experiment with instrumentation.
-pub const MEDIUM_MEASURE_TIME: u64 = 301;
+#[deprecated(since = "0.2.0", note = "use `pvn::measure::MEDIUM_MEASURE_TIME`")]
+pub const MEDIUM_MEASURE_TIME: u64 = measure::MEDIUM_MEASURE_TIME;
 /// Time for the long experiment with instrumentation.
-pub const LONG_MEASURE_TIME: u64 = 601;
+#[deprecated(since = "0.2.0", note = "use `pvn::measure::LONG_MEASURE_TIME`")]
+pub const LONG_MEASURE_TIME: u64 = measure::LONG_MEASURE_TIME;
 /// Time for the application experiment.
-pub const APP_MEASURE_TIME: u64 = 610;
+#[deprecated(since = "0.2.0", note = "use `pvn::measure::APP_MEASURE_TIME`")]
+pub const APP_MEASURE_TIME: u64 = measure::APP_MEASURE_TIME;
 
 /// experiment parameters.
+#[deprecated(since = "0.2.0", note = "use `PvnExperimentConfig::expr_param`")]
 #[derive(Debug, Clone, Copy)]
 pub struct XcdrExprParam {
     /// setup (workload level)
@@ -27,78 +32,22 @@ pub struct XcdrExprParam {
     pub expr_time: u64,
 }
 
-/// Read setup for transcoder NF. This function returns <setup, expr number, inst, measure time>.
+/// Read setup for transcoder NF. Forwards to `PvnExperimentConfig::from_file`.
+#[deprecated(since = "0.2.0", note = "use `PvnExperimentConfig::from_file`")]
+#[allow(deprecated)]
 pub fn xcdr_read_setup(file_path: String) -> Option<XcdrExprParam> {
-    let file = File::open(file_path).expect("file should open read only");
-    let json: Value = from_reader(file).expect("file should be proper JSON");
-
-    let setup: Option<String> = match serde_json::from_value(json.get("setup").expect("file should have setup").clone())
-    {
-        Ok(val) => Some(val),
-        Err(e) => {
-            println!("Malformed JSON response: {}", e);
-            None
-        }
-    };
-    let xcdr_setup: Option<String> =
-        match serde_json::from_value(json.get("xcdr_setup").expect("file should have xcdr_setup").clone()) {
-            Ok(val) => Some(val),
-            Err(e) => {
-                println!("Malformed JSON response: {}", e);
-                None
-            }
-        };
-    let setup = setup.unwrap().parse::<usize>();
-    let xcdr_setup = xcdr_setup.unwrap().parse::<usize>();
-
-    let iter: Option<String> = match serde_json::from_value(json.get("iter").expect("file should have setup").clone()) {
-        Ok(val) => Some(val),
-        Err(e) => {
-            println!("Malformed JSON response: {}", e);
-            None
-        }
-    };
-    let iter = iter.unwrap().parse::<usize>();
-
-    let inst: Option<String> = match serde_json::from_value(json.get("inst").expect("file should have setup").clone()) {
-        Ok(val) => Some(val),
+    match PvnExperimentConfig::from_file(&file_path) {
+        Ok(config) => Some(XcdrExprParam {
+            setup: config.setup,
+            xcdr_setup: config.xcdr_setup,
+            iter: config.iter,
+            inst: config.inst,
+            expr_time: config.expr_time(),
+        }),
         Err(e) => {
-            println!("Malformed JSON response: {}", e);
+            println!("{}", e);
             None
         }
-    };
-    let inst_val = match &*inst.unwrap() {
-        "on" => Some(true),
-        "off" => Some(false),
-        _ => None,
-    };
-
-    let mode: Option<String> = match serde_json::from_value(json.get("mode").expect("file should have setup").clone()) {
-        Ok(val) => Some(val),
-        Err(e) => {
-            println!("Malformed JSON response: {}", e);
-            None
-        }
-    };
-    let expr_time = match &*mode.unwrap() {
-        "short" => Some(SHORT_MEASURE_TIME),
-        "medium" => Some(MEDIUM_MEASURE_TIME),
-        "long" => Some(LONG_MEASURE_TIME),
-        _ => None,
-    };
-
-    if let (Ok(setup), Ok(xcdr_setup), Ok(iter), Some(inst), Some(expr_time)) =
-        (setup, xcdr_setup, iter, inst_val, expr_time)
-    {
-        Some(XcdrExprParam {
-            setup,
-            xcdr_setup,
-            iter,
-            inst,
-            expr_time,
-        })
-    } else {
-        None
     }
 }
 
@@ -1,17 +1,31 @@
+use super::control::{PipelineRegistry, ShutdownSignal};
+use super::shutdown::{catch_shutdown_signals, run_shutdown_hooks};
 use crate::allocators::CacheAligned;
 use crate::config::NetbricksConfiguration;
+use crate::control::metrics::*;
+use crate::control::protocol::{clear_control_handler, set_control_handler, ControlServer};
+use crate::control::tcp::{TcpControlAgent, TcpControlServer};
 use crate::interface::dpdk::{init_system, init_thread};
-use crate::interface::{PmdPort, PortQueue, VirtualPort, VirtualQueue};
+use crate::interface::{PcapPort, PmdPort, PortQueue, VirtualPort, VirtualQueue};
+use crate::pvn::config::{set_experiment_config, PvnExperimentConfig};
 use crate::scheduler::*;
+use crate::stats::{PortStats, StatsReport, Summary, DEFAULT_PERCENTILES};
+use crate::utils::{latency_snapshot, tsc_hz};
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc::{sync_channel, SyncSender};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle, Thread};
+use std::time::{Duration, Instant};
 
 type AlignedPortQueue = CacheAligned<PortQueue>;
 type AlignedVirtualQueue = CacheAligned<VirtualQueue>;
 
+/// How long the metrics and control servers sleep between polls.
+const CONTROL_POLL: Duration = Duration::from_millis(10);
+
 /// A handle to schedulers paused on a barrier.
 #[derive(Debug)]
 pub struct BarrierHandle<'a> {
@@ -37,14 +51,29 @@ impl<'a> BarrierHandle<'a> {
 pub struct NetBricksContext {
     /// Ports.
     pub ports: HashMap<String, Arc<PmdPort>>,
-    /// RX queues.
+    /// RX queues, those of capture file ports included.
     pub rx_queues: HashMap<i32, Vec<CacheAligned<PortQueue>>>,
     /// Active cores.
     pub active_cores: Vec<i32>,
     /// Virtual ports.
     pub virtual_ports: HashMap<i32, Arc<VirtualPort>>,
+    /// Capture file ports, configured with a `pcap:` name. Their queues are in `rx_queues`.
+    pub pcap_ports: HashMap<String, Arc<PcapPort>>,
     scheduler_channels: HashMap<i32, SyncSender<SchedulerCommand>>,
     scheduler_handles: HashMap<i32, JoinHandle<()>>,
+    last_pipeline: Arc<AtomicUsize>,
+    pipeline_registry: Arc<PipelineRegistry>,
+    shutdown_signal: Arc<ShutdownSignal>,
+    name: String,
+    idle_backoff: Option<IdleBackoff>,
+    stats_report: Option<(String, String)>,
+    metrics_address: Option<SocketAddr>,
+    metrics_server: Option<(MetricsSourceHandle, Arc<AtomicBool>, JoinHandle<()>)>,
+    control_address: Option<SocketAddr>,
+    control_server: Option<(Arc<AtomicBool>, JoinHandle<()>)>,
+    drain_timeout: Duration,
+    duration: Option<Duration>,
+    run_until: Option<Instant>,
 }
 
 impl NetBricksContext {
@@ -54,6 +83,18 @@ impl NetBricksContext {
         for core in &cores {
             self.start_scheduler(*core);
         }
+        if let Some(address) = self.metrics_address {
+            match self.start_metrics_server(address) {
+                Ok(address) => println!("Serving metrics at http://{}/metrics", address),
+                Err(ref e) => println!("Could not serve metrics at {}: {}", address, e),
+            }
+        }
+        if let Some(address) = self.control_address {
+            match self.start_control_server(address) {
+                Ok(address) => println!("Accepting control requests at {}", address),
+                Err(ref e) => println!("Could not accept control requests at {}: {}", address, e),
+            }
+        }
     }
 
     /// Start a scheduler.
@@ -62,40 +103,43 @@ impl NetBricksContext {
         let builder = thread::Builder::new();
         let (sender, receiver) = sync_channel(0);
         self.scheduler_channels.insert(core, sender);
+        let backoff = self.idle_backoff;
         let join_handle = builder
             .name(format!("sched-{}", core))
             .spawn(move || {
                 init_thread(core, core);
                 // Other init?
                 let mut sched = StandaloneScheduler::new_with_channel(receiver);
+                sched.set_idle_backoff(backoff);
                 sched.handle_requests()
             })
             .unwrap();
         self.scheduler_handles.insert(core, join_handle);
     }
 
-    /// Run a function (which installs a pipeline) on all schedulers in the system.
-    pub fn add_pipeline_to_run<T>(&mut self, run: Arc<T>)
+    /// Run a function (which installs a pipeline) on all schedulers in the system. Returns a handle for the pipeline,
+    /// which can be used to remove, pause, resume or replace it later.
+    pub fn add_pipeline_to_run<T>(&mut self, run: Arc<T>) -> usize
     where
         T: Fn(Vec<AlignedPortQueue>, &mut StandaloneScheduler) + Send + Sync + 'static,
     {
+        let pipeline = self.new_pipeline();
         for (core, channel) in &self.scheduler_channels {
             let ports = match self.rx_queues.get(core) {
                 Some(v) => v.clone(),
                 None => vec![],
             };
-            let boxed_run = run.clone();
-            channel
-                .send(SchedulerCommand::Run(Arc::new(move |s| boxed_run(ports.clone(), s))))
-                .unwrap();
+            channel.send(port_pipeline(pipeline, ports, run.clone())).unwrap();
         }
+        pipeline
     }
 
-    /// Install a test pipeline.
-    pub fn add_test_pipeline<T>(&mut self, run: Arc<T>)
+    /// Install a test pipeline, returning its handle.
+    pub fn add_test_pipeline<T>(&mut self, run: Arc<T>) -> usize
     where
         T: Fn(Vec<AlignedVirtualQueue>, &mut StandaloneScheduler) + Send + Sync + 'static,
     {
+        let pipeline = self.new_pipeline();
         for (core, channel) in &self.scheduler_channels {
             let port = self
                 .virtual_ports
@@ -104,21 +148,24 @@ impl NetBricksContext {
             let boxed_run = run.clone();
             let queue = port.new_virtual_queue(1).unwrap();
             channel
-                .send(SchedulerCommand::Run(Arc::new(move |s| {
-                    boxed_run(vec![queue.clone()], s)
-                })))
+                .send(SchedulerCommand::RunPipeline(
+                    pipeline,
+                    Arc::new(move |s| boxed_run(vec![queue.clone()], s)),
+                ))
                 .unwrap();
         }
+        pipeline
     }
 
-    /// Install a test pipeline on a particular core.
+    /// Install a test pipeline on a particular core, returning its handle.
     pub fn add_test_pipeline_to_core<
         T: Fn(Vec<AlignedVirtualQueue>, &mut StandaloneScheduler) + Send + Sync + 'static,
     >(
         &mut self,
         core: i32,
         run: Arc<T>,
-    ) -> Result<()> {
+    ) -> Result<usize> {
+        let pipeline = self.new_pipeline();
         if let Some(channel) = self.scheduler_channels.get(&core) {
             let port = self
                 .virtual_ports
@@ -127,39 +174,107 @@ impl NetBricksContext {
             let boxed_run = run;
             let queue = port.new_virtual_queue(1).unwrap();
             channel
-                .send(SchedulerCommand::Run(Arc::new(move |s| {
-                    boxed_run(vec![queue.clone()], s)
-                })))
+                .send(SchedulerCommand::RunPipeline(
+                    pipeline,
+                    Arc::new(move |s| boxed_run(vec![queue.clone()], s)),
+                ))
                 .unwrap();
-            Ok(())
+            Ok(pipeline)
         } else {
             Err(ErrorKind::NoRunningSchedulerOnCore(core).into())
         }
     }
 
-    /// Install a pipeline on a particular core.
+    /// Install a pipeline on a particular core, returning its handle.
     pub fn add_pipeline_to_core<T: Fn(Vec<AlignedPortQueue>, &mut StandaloneScheduler) + Send + Sync + 'static>(
         &mut self,
         core: i32,
         run: Arc<T>,
-    ) -> Result<()> {
+    ) -> Result<usize> {
+        let pipeline = self.new_pipeline();
         if let Some(channel) = self.scheduler_channels.get(&core) {
             let ports = match self.rx_queues.get(&core) {
                 Some(v) => v.clone(),
                 None => vec![],
             };
-            let boxed_run = run;
-            channel
-                .send(SchedulerCommand::Run(Arc::new(move |s| boxed_run(ports.clone(), s))))
-                .unwrap();
-            Ok(())
+            channel.send(port_pipeline(pipeline, ports, run)).unwrap();
+            Ok(pipeline)
         } else {
             Err(ErrorKind::NoRunningSchedulerOnCore(core).into())
         }
     }
 
+    fn new_pipeline(&mut self) -> usize {
+        self.last_pipeline.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Register a pipeline under a name, so it can be installed on a core later through `ControlHandle::add_pipeline`
+    /// (e.g., with an `add` control request). Registering a name again replaces the pipeline.
+    pub fn register_pipeline<T>(&mut self, name: &str, run: Arc<T>)
+    where
+        T: Fn(Vec<AlignedPortQueue>, &mut StandaloneScheduler) + Send + Sync + 'static,
+    {
+        self.pipeline_registry.register(name, run);
+    }
+
+    fn send_to_core(&self, core: i32, command: SchedulerCommand) -> Result<()> {
+        match self.scheduler_channels.get(&core) {
+            Some(channel) => {
+                channel.send(command).unwrap();
+                Ok(())
+            }
+            None => Err(ErrorKind::NoRunningSchedulerOnCore(core).into()),
+        }
+    }
+
+    /// Remove a pipeline from a core. Its tasks are dropped once the scheduler gets to the command, i.e., between two
+    /// task runs.
+    pub fn remove_pipeline_from_core(&mut self, core: i32, pipeline: usize) -> Result<()> {
+        self.send_to_core(core, SchedulerCommand::RemovePipeline(pipeline))
+    }
+
+    /// Remove a pipeline from all cores.
+    pub fn remove_pipeline(&mut self, pipeline: usize) {
+        for channel in self.scheduler_channels.values() {
+            channel.send(SchedulerCommand::RemovePipeline(pipeline)).unwrap();
+        }
+    }
+
+    /// Stop running a pipeline on a core until it is resumed.
+    pub fn pause_pipeline_on_core(&mut self, core: i32, pipeline: usize) -> Result<()> {
+        self.send_to_core(core, SchedulerCommand::PausePipeline(pipeline))
+    }
+
+    /// Resume a paused pipeline on a core.
+    pub fn resume_pipeline_on_core(&mut self, core: i32, pipeline: usize) -> Result<()> {
+        self.send_to_core(core, SchedulerCommand::ResumePipeline(pipeline))
+    }
+
+    /// Replace a pipeline on a core with the one installed by `run`, which keeps the same handle. The scheduler drops
+    /// the old tasks and installs the new ones in one step, so no packets are processed by a mix of the two.
+    pub fn replace_pipeline_on_core<T>(&mut self, core: i32, pipeline: usize, run: Arc<T>) -> Result<()>
+    where
+        T: Fn(Vec<AlignedPortQueue>, &mut StandaloneScheduler) + Send + Sync + 'static,
+    {
+        let ports = match self.rx_queues.get(&core) {
+            Some(v) => v.clone(),
+            None => vec![],
+        };
+        self.send_to_core(
+            core,
+            SchedulerCommand::ReplacePipeline(
+                pipeline,
+                Arc::new(move |s| {
+                    s.watch_rx_queues(&ports);
+                    run(ports.clone(), s)
+                }),
+            ),
+        )
+    }
+
     /// Start scheduling pipelines.
     pub fn execute(&mut self) {
+        self.run_until = self.duration.map(|d| Instant::now() + d);
         for (core, channel) in &self.scheduler_channels {
             channel.send(SchedulerCommand::Execute).unwrap();
             println!("Starting scheduler on {}", core);
@@ -181,52 +296,303 @@ impl NetBricksContext {
         BarrierHandle::with_threads(self.scheduler_handles.values().map(|j| j.thread()).collect())
     }
 
-    /// Stop all schedulers, safely shutting down the system.
+    /// Collect the statistics of every scheduler and its tasks, keyed by core. Schedulers answer between passes over
+    /// their run queue, so this blocks while they are paused on a barrier.
+    pub fn scheduler_stats(&self) -> HashMap<i32, SchedulerStats> {
+        collect_scheduler_stats(&self.scheduler_channels)
+    }
+
+    /// Collect a machine-readable report of this run: the counters of every port and, if any packets were measured
+    /// with `Batch::measure_latency`, a `latency` summary in nanoseconds (merged over all cores).
+    pub fn stats_report(&self, name: &str) -> StatsReport {
+        collect_stats_report(name, &self.ports, &self.pcap_ports, &self.virtual_ports)
+    }
+
+    /// Serve a Prometheus-style metrics page (see `control::metrics`) at `http://<address>/metrics` from a thread of
+    /// its own, returning the address it listens on. Besides the counters NFs register with `register_counter`, the
+    /// page lists the packet counters and drops of the ports configured so far, mempool usage and the statistics of
+    /// every task on the running schedulers, so start it after `start_schedulers` (which does so if the configuration
+    /// has a `metrics_address`). Requests wait while the schedulers are paused on a barrier. The server stops with the
+    /// schedulers.
+    pub fn start_metrics_server(&mut self, address: SocketAddr) -> Result<SocketAddr> {
+        self.stop_metrics_server();
+        let server = MetricsServer::bind(address)?;
+        let address = server.local_addr();
+        let (running, thread) = serve("metrics", server)?;
+        let source = add_metrics_source(self.metrics_source());
+        self.metrics_server = Some((source, running, thread));
+        Ok(address)
+    }
+
+    /// A metrics source for the ports and schedulers of this context.
+    fn metrics_source(&self) -> impl Fn(&mut MetricsWriter) + Send + Sync + 'static {
+        let mut ports: Vec<_> = self.ports.iter().map(|(n, p)| (n.clone(), p.clone())).collect();
+        ports.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut pcap_ports: Vec<_> = self.pcap_ports.iter().map(|(n, p)| (n.clone(), p.clone())).collect();
+        pcap_ports.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut virtual_ports: Vec<_> = self.virtual_ports.iter().map(|(c, p)| (*c, p.clone())).collect();
+        virtual_ports.sort_by_key(|p| p.0);
+        let channels = Mutex::new(self.scheduler_channels.clone());
+        move |writer| {
+            let mut stats = vec![];
+            for (name, port) in &ports {
+                stats.extend(PortStats::pmd(name, port));
+            }
+            for (name, port) in &pcap_ports {
+                stats.push(PortStats::pcap(name, port));
+            }
+            for (core, port) in &virtual_ports {
+                stats.push(PortStats::virtual_port(&format!("virtual:{}", core), port));
+            }
+            write_port_stats(writer, &stats);
+            for (name, port) in &ports {
+                write_pmd_drops(writer, name, port);
+            }
+            write_mempool_usage(writer);
+            let mut schedulers: Vec<_> = collect_scheduler_stats(&channels.lock().unwrap()).into_iter().collect();
+            schedulers.sort_by_key(|s| s.0);
+            for (core, stats) in schedulers {
+                write_scheduler_stats(writer, core, &stats);
+            }
+        }
+    }
+
+    /// A handle through which other threads can inspect and change the running system, e.g., to serve control
+    /// requests. It sees the schedulers running now, so get it after `start_schedulers`.
+    pub fn control_handle(&self) -> ControlHandle {
+        ControlHandle {
+            name: self.name.clone(),
+            ports: self.ports.clone(),
+            pcap_ports: self.pcap_ports.clone(),
+            virtual_ports: self.virtual_ports.clone(),
+            rx_queues: self.rx_queues.clone(),
+            channels: self.scheduler_channels.clone(),
+            last_pipeline: self.last_pipeline.clone(),
+            registry: self.pipeline_registry.clone(),
+            shutdown: self.shutdown_signal.clone(),
+            drain_timeout: self.drain_timeout,
+        }
+    }
+
+    /// Accept runtime control requests (see `control::protocol`) at `address` on a thread of its own, returning the
+    /// address it listens on. Start it after `start_schedulers` (which does so if the configuration has a
+    /// `control_address`). A `shutdown` request only asks for the system to stop: the thread owning the context
+    /// should watch `wait_for_shutdown` and then call `stop`, which also stops the control server.
+    pub fn start_control_server(&mut self, address: SocketAddr) -> Result<SocketAddr> {
+        self.stop_control_server();
+        let server = ControlServer::bind(address)?;
+        let address = server.local_addr();
+        let handle = self.control_handle();
+        set_control_handler(Arc::new(move |request| handle.handle(request)));
+        self.control_server = Some(serve("control", server)?);
+        Ok(address)
+    }
+
+    /// Stop the control server, if one is running.
+    fn stop_control_server(&mut self) {
+        if let Some((running, thread)) = self.control_server.take() {
+            clear_control_handler();
+            running.store(false, Ordering::Relaxed);
+            thread.join().unwrap();
+        }
+    }
+
+    /// Ask for the system to be stopped, as a `shutdown` control request, SIGINT or SIGTERM do.
+    pub fn request_shutdown(&self) {
+        self.shutdown_signal.request();
+    }
+
+    /// Whether a shutdown was requested.
+    pub fn shutdown_requested(&self) -> bool {
+        self.shutdown_signal.requested()
+    }
+
+    /// Sleep for up to `timeout`, returning early with true if a shutdown is requested. If the configuration has a
+    /// `duration`, a shutdown is requested once that much time passed since `execute`. NFs use this in place of
+    /// `thread::sleep` in their main loop, and call `stop` once it returns true.
+    pub fn wait_for_shutdown(&self, timeout: Duration) -> bool {
+        if let (Some(deadline), Some(duration)) = (self.run_until, self.duration) {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left <= timeout {
+                if !self.shutdown_signal.wait(left) {
+                    println!("Have run for {:?}, system shutting down", duration);
+                    self.request_shutdown();
+                }
+                return true;
+            }
+        }
+        self.shutdown_signal.wait(timeout)
+    }
+
+    /// Stop the metrics server, if one is running.
+    fn stop_metrics_server(&mut self) {
+        if let Some((source, running, thread)) = self.metrics_server.take() {
+            remove_metrics_source(source);
+            running.store(false, Ordering::Relaxed);
+            thread.join().unwrap();
+        }
+    }
+
+    /// Stop all schedulers, safely shutting down the system. RX is stopped on every port first, and the schedulers keep
+    /// running until the packets already received (e.g., those queued by `group_by`) made it through the pipelines, or
+    /// the configured drain timeout passed. Once the schedulers exited, the hooks added with `add_shutdown_hook` run,
+    /// the statistics report is written and the ports are freed.
     pub fn stop(&mut self) {
+        self.drain();
         for (core, channel) in &self.scheduler_channels {
             channel.send(SchedulerCommand::Shutdown).unwrap();
             println!("Issued shutdown for core {}", core);
         }
-        for (core, join_handle) in self.scheduler_handles.drain() {
-            join_handle.join().unwrap();
-            println!("Core {} has shutdown", core);
+        self.wait();
+    }
+
+    /// Stop RX on every port, then wait for the pipelines to stop processing packets.
+    fn drain(&self) {
+        let idle = self.control_handle().stop_rx_and_wait(None, self.drain_timeout);
+        if !idle.unwrap_or(false) {
+            println!(
+                "Pipelines still busy after {:?}, shutting down anyway",
+                self.drain_timeout
+            );
+        }
+    }
+
+    /// Write the report configured with `stats_report` (if any), once the schedulers are done.
+    fn write_stats_report(&mut self) {
+        if let Some((name, path)) = self.stats_report.take() {
+            match self.stats_report(&name).write(&path) {
+                Ok(()) => println!("Wrote statistics to {}", path),
+                Err(ref e) => println!("Could not write statistics to {}: {}", path, e),
+            }
         }
-        println!("System shutdown");
     }
 
-    /// Wait and then stop the scheduler.
-    ///
-    /// [FIXME] Waiting is not implemented?
+    /// Wait for the schedulers to exit, then run the shutdown hooks, write the statistics report and free the ports.
     pub fn wait(&mut self) {
         for (core, join_handle) in self.scheduler_handles.drain() {
             join_handle.join().unwrap();
             println!("Core {} has shutdown", core);
         }
+        run_shutdown_hooks();
+        self.stop_control_server();
+        self.stop_metrics_server();
+        self.write_stats_report();
+        self.free_ports();
         println!("System shutdown");
     }
 
+    /// Drop our references to ports and their queues; a port is closed once the pipelines using it are gone too.
+    fn free_ports(&mut self) {
+        self.rx_queues.clear();
+        self.ports.clear();
+        self.pcap_ports.clear();
+        self.virtual_ports.clear();
+    }
+
     /// Shutdown all schedulers.
     pub fn shutdown(&mut self) {
         self.stop()
     }
 }
 
+/// A command installing a pipeline with the port queues of a core, whose RX interrupts the scheduler then waits on.
+pub(super) fn port_pipeline<T>(pipeline: usize, ports: Vec<AlignedPortQueue>, run: Arc<T>) -> SchedulerCommand
+where
+    T: Fn(Vec<AlignedPortQueue>, &mut StandaloneScheduler) + Send + Sync + ?Sized + 'static,
+{
+    SchedulerCommand::RunPipeline(
+        pipeline,
+        Arc::new(move |s| {
+            s.watch_rx_queues(&ports);
+            run(ports.clone(), s)
+        }),
+    )
+}
+
+/// Statistics of every scheduler that still answers, keyed by core. Schedulers that have shut down are skipped.
+pub(super) fn collect_scheduler_stats(
+    channels: &HashMap<i32, SyncSender<SchedulerCommand>>,
+) -> HashMap<i32, SchedulerStats> {
+    let mut stats = HashMap::with_capacity(channels.len());
+    for (core, channel) in channels {
+        let (sender, receiver) = sync_channel(1);
+        if channel.send(SchedulerCommand::Stats(sender)).is_ok() {
+            if let Ok(s) = receiver.recv() {
+                stats.insert(*core, s);
+            }
+        }
+    }
+    stats
+}
+
+/// Port counters and latency summary, see `NetBricksContext::stats_report`.
+pub(super) fn collect_stats_report(
+    name: &str,
+    ports: &HashMap<String, Arc<PmdPort>>,
+    pcap_ports: &HashMap<String, Arc<PcapPort>>,
+    virtual_ports: &HashMap<i32, Arc<VirtualPort>>,
+) -> StatsReport {
+    let mut report = StatsReport::new(name);
+    let mut ports: Vec<_> = ports.iter().collect();
+    ports.sort_by(|a, b| a.0.cmp(b.0));
+    for (port_name, port) in ports {
+        report.add_ports(PortStats::pmd(port_name, port));
+    }
+    let mut pcap_ports: Vec<_> = pcap_ports.iter().collect();
+    pcap_ports.sort_by(|a, b| a.0.cmp(b.0));
+    for (port_name, port) in pcap_ports {
+        report.add_ports(Some(PortStats::pcap(port_name, port)));
+    }
+    let mut virtual_ports: Vec<_> = virtual_ports.iter().collect();
+    virtual_ports.sort_by_key(|p| *p.0);
+    for (core, port) in virtual_ports {
+        report.add_ports(Some(PortStats::virtual_port(&format!("virtual:{}", core), port)));
+    }
+    let latency = latency_snapshot();
+    if latency.count() > 0 {
+        let scale = 1e9 / tsc_hz().max(1) as f64;
+        report.add_summary("latency", Summary::from_latency(&latency, &DEFAULT_PERCENTILES, scale));
+    }
+    report
+}
+
+/// Run a control server on a thread of its own until the returned flag is cleared.
+fn serve<T>(name: &str, mut server: TcpControlServer<T>) -> Result<(Arc<AtomicBool>, JoinHandle<()>)>
+where
+    T: TcpControlAgent + Send + 'static,
+{
+    let running = Arc::new(AtomicBool::new(true));
+    let keep_running = running.clone();
+    let thread = thread::Builder::new().name(name.to_string()).spawn(move || {
+        while keep_running.load(Ordering::Relaxed) {
+            server.execute();
+            thread::sleep(CONTROL_POLL);
+        }
+    })?;
+    Ok((running, thread))
+}
+
 /// Initialize the system from a configuration.
 pub fn initialize_system(configuration: &NetbricksConfiguration) -> Result<NetBricksContext> {
-    init_system(configuration);
+    if let Some(ref path) = configuration.pvn_config {
+        set_experiment_config(PvnExperimentConfig::from_file(path)?);
+    }
     let mut ctx: NetBricksContext = Default::default();
+    if configuration.shutdown_signals {
+        // Before DPDK starts threads of its own, which would otherwise get the signals.
+        catch_shutdown_signals(ctx.shutdown_signal.clone())?;
+    }
+    init_system(configuration);
     let mut cores: HashSet<_> = configuration.cores.iter().cloned().collect();
     for port in &configuration.ports {
-        if ctx.ports.contains_key(&port.name) {
+        if ctx.ports.contains_key(&port.name) || ctx.pcap_ports.contains_key(&port.name) {
             println!("Port {} appears twice in specification", port.name);
             return Err(
                 ErrorKind::ConfigurationError(format!("Port {} appears twice in specification", port.name)).into(),
             );
         } else {
-            match PmdPort::new_port_from_configuration(port) {
-                Ok(p) => {
-                    ctx.ports.insert(port.name.clone(), p);
-                }
+            let port_instance = match PmdPort::new_port_from_configuration(port) {
+                Ok(p) => p,
                 Err(e) => {
                     return Err(ErrorKind::ConfigurationError(format!(
                         "Port {} could not be initialized {:?}",
@@ -234,13 +600,11 @@ pub fn initialize_system(configuration: &NetbricksConfiguration) -> Result<NetBr
                     ))
                     .into())
                 }
-            }
-
-            let port_instance = &ctx.ports[&port.name];
+            };
 
             for (rx_q, core) in port.rx_queues.iter().enumerate() {
                 let rx_q = rx_q as i32;
-                match PmdPort::new_queue_pair(port_instance, rx_q, rx_q) {
+                match PmdPort::new_queue_pair(&port_instance, rx_q, rx_q) {
                     Ok(q) => {
                         ctx.rx_queues.entry(*core).or_insert_with(|| vec![]).push(q);
                     }
@@ -254,6 +618,16 @@ pub fn initialize_system(configuration: &NetbricksConfiguration) -> Result<NetBr
                     }
                 }
             }
+
+            // Capture files are reported and drained as pcap ports.
+            match port_instance.pcap() {
+                Some(pcap) => {
+                    ctx.pcap_ports.insert(port.name.clone(), pcap.clone());
+                }
+                None => {
+                    ctx.ports.insert(port.name.clone(), port_instance);
+                }
+            }
         }
     }
     if configuration.strict {
@@ -272,5 +646,29 @@ pub fn initialize_system(configuration: &NetbricksConfiguration) -> Result<NetBr
         cores.extend(ctx.rx_queues.keys());
     };
     ctx.active_cores = cores.into_iter().collect();
+    ctx.idle_backoff = configuration.idle_backoff;
+    ctx.metrics_address = match configuration.metrics_address {
+        Some(ref address) => Some(
+            address
+                .parse()
+                .map_err(|_| ErrorKind::ConfigurationError(format!("Could not parse metrics_address {}", address)))?,
+        ),
+        None => None,
+    };
+    ctx.control_address = match configuration.control_address {
+        Some(ref address) => Some(
+            address
+                .parse()
+                .map_err(|_| ErrorKind::ConfigurationError(format!("Could not parse control_address {}", address)))?,
+        ),
+        None => None,
+    };
+    ctx.name = configuration.name.clone();
+    ctx.drain_timeout = Duration::from_millis(configuration.drain_timeout_ms);
+    ctx.duration = configuration.duration.map(|d| Duration::from_secs(d.max(0) as u64));
+    ctx.stats_report = configuration
+        .stats_report
+        .as_ref()
+        .map(|path| (configuration.name.clone(), path.clone()));
     Ok(ctx)
 }
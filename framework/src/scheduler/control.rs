@@ -0,0 +1,417 @@
+use super::context::{collect_scheduler_stats, collect_stats_report, port_pipeline};
+use super::{SchedulerCommand, SchedulerStats, StandaloneScheduler};
+use crate::allocators::CacheAligned;
+use crate::common::*;
+use crate::control::protocol::{ControlRequest, CONTROL_USAGE};
+use crate::interface::{PcapPort, PmdPort, PortQueue, VirtualPort};
+use crate::stats::StatsReport;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::SyncSender;
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Pipelines count as idle once they processed no packets for `DRAIN_QUIET_POLLS` polls in a row, `DRAIN_POLL` apart.
+const DRAIN_POLL: Duration = Duration::from_millis(10);
+const DRAIN_QUIET_POLLS: u32 = 5;
+
+/// Poll `packets`, a count of the packets processed so far, until it stops increasing for `DRAIN_QUIET_POLLS` polls or
+/// `timeout` passes. Returns whether it stopped.
+pub(super) fn wait_until_idle<F: FnMut() -> u64>(mut packets: F, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    let mut last = packets();
+    let mut quiet = 0;
+    loop {
+        thread::sleep(DRAIN_POLL);
+        let now = packets();
+        if now == last {
+            quiet += 1;
+            if quiet == DRAIN_QUIET_POLLS {
+                return true;
+            }
+        } else {
+            quiet = 0;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        last = now;
+    }
+}
+
+/// A function installing a pipeline on a core, see `NetBricksContext::register_pipeline`.
+pub type PipelineFn = Arc<dyn Fn(Vec<CacheAligned<PortQueue>>, &mut StandaloneScheduler) + Send + Sync>;
+
+/// Pipelines registered by name.
+#[derive(Default)]
+pub(super) struct PipelineRegistry(RwLock<BTreeMap<String, PipelineFn>>);
+
+impl PipelineRegistry {
+    pub(super) fn register(&self, name: &str, run: PipelineFn) {
+        self.0.write().unwrap().insert(name.to_string(), run);
+    }
+
+    fn get(&self, name: &str) -> Option<PipelineFn> {
+        self.0.read().unwrap().get(name).cloned()
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.0.read().unwrap().keys().cloned().collect()
+    }
+}
+
+impl fmt::Debug for PipelineRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
+
+/// Set once a shutdown is requested; the thread owning the context waits on it.
+#[derive(Debug, Default)]
+pub(super) struct ShutdownSignal {
+    requested: Mutex<bool>,
+    changed: Condvar,
+}
+
+impl ShutdownSignal {
+    pub(super) fn request(&self) {
+        *self.requested.lock().unwrap() = true;
+        self.changed.notify_all();
+    }
+
+    pub(super) fn requested(&self) -> bool {
+        *self.requested.lock().unwrap()
+    }
+
+    pub(super) fn wait(&self, timeout: Duration) -> bool {
+        let requested = self.requested.lock().unwrap();
+        let (requested, _) = self
+            .changed
+            .wait_timeout_while(requested, timeout, |requested| !*requested)
+            .unwrap();
+        *requested
+    }
+}
+
+/// A port, as listed by `ControlHandle::ports`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PortInfo {
+    /// Port name.
+    pub name: String,
+    /// `pmd`, `pcap` or `virtual`.
+    pub kind: &'static str,
+    /// Number of RX queues.
+    pub rxqs: i32,
+    /// Number of TX queues.
+    pub txqs: i32,
+}
+
+/// An active core, as listed by `ControlHandle::cores`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CoreInfo {
+    /// Core.
+    pub core: i32,
+    /// Queues polled by pipelines on this core.
+    pub queues: Vec<String>,
+}
+
+/// A pipeline running on a core, as listed by `ControlHandle::pipelines`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PipelineInfo {
+    /// Core.
+    pub core: i32,
+    /// Pipeline handle.
+    pub pipeline: usize,
+    /// Number of tasks.
+    pub tasks: usize,
+    /// Whether all of its tasks are paused.
+    pub paused: bool,
+    /// Packets processed so far.
+    pub packets: u64,
+}
+
+/// A handle to a running system, obtained with `NetBricksContext::control_handle`, through which other threads can
+/// list ports, cores and pipelines, collect statistics, add and remove pipelines, drain cores and ask for a shutdown.
+/// It serves the requests of the runtime control protocol, see `control::protocol`.
+#[derive(Clone, Debug)]
+pub struct ControlHandle {
+    pub(super) name: String,
+    pub(super) ports: HashMap<String, Arc<PmdPort>>,
+    pub(super) pcap_ports: HashMap<String, Arc<PcapPort>>,
+    pub(super) virtual_ports: HashMap<i32, Arc<VirtualPort>>,
+    pub(super) rx_queues: HashMap<i32, Vec<CacheAligned<PortQueue>>>,
+    pub(super) channels: HashMap<i32, SyncSender<SchedulerCommand>>,
+    pub(super) last_pipeline: Arc<AtomicUsize>,
+    pub(super) registry: Arc<PipelineRegistry>,
+    pub(super) shutdown: Arc<ShutdownSignal>,
+    pub(super) drain_timeout: Duration,
+}
+
+impl ControlHandle {
+    /// All ports, sorted by name.
+    pub fn ports(&self) -> Vec<PortInfo> {
+        let mut ports: Vec<_> = self
+            .ports
+            .iter()
+            .map(|(name, port)| PortInfo {
+                name: name.clone(),
+                kind: "pmd",
+                rxqs: port.rxqs(),
+                txqs: port.txqs(),
+            })
+            .chain(self.pcap_ports.keys().map(|name| PortInfo {
+                name: name.clone(),
+                kind: "pcap",
+                rxqs: 1,
+                txqs: 1,
+            }))
+            .chain(self.virtual_ports.keys().map(|core| PortInfo {
+                name: format!("virtual:{}", core),
+                kind: "virtual",
+                rxqs: 1,
+                txqs: 1,
+            }))
+            .collect();
+        ports.sort_by(|a, b| a.name.cmp(&b.name));
+        ports
+    }
+
+    /// Cores running a scheduler, in increasing order.
+    pub fn cores(&self) -> Vec<CoreInfo> {
+        let mut cores: Vec<_> = self
+            .channels
+            .keys()
+            .map(|&core| {
+                let queues = self.rx_queues.get(&core).into_iter().flatten().map(|q| q.to_string());
+                CoreInfo {
+                    core,
+                    queues: queues.collect(),
+                }
+            })
+            .collect();
+        cores.sort_by_key(|c| c.core);
+        cores
+    }
+
+    /// Pipelines running on each core, sorted by core and handle. Tasks added outside of a pipeline are not listed.
+    pub fn pipelines(&self) -> Vec<PipelineInfo> {
+        let mut pipelines = BTreeMap::new();
+        for (core, stats) in self.scheduler_stats() {
+            for task in stats.tasks {
+                if let Some(pipeline) = task.pipeline {
+                    let info = pipelines.entry((core, pipeline)).or_insert(PipelineInfo {
+                        core,
+                        pipeline,
+                        tasks: 0,
+                        paused: true,
+                        packets: 0,
+                    });
+                    info.tasks += 1;
+                    info.paused &= task.paused;
+                    info.packets += task.packets;
+                }
+            }
+        }
+        pipelines.into_iter().map(|(_, info)| info).collect()
+    }
+
+    /// Names of the pipelines that can be installed with `add_pipeline`.
+    pub fn registered_pipelines(&self) -> Vec<String> {
+        self.registry.names()
+    }
+
+    /// Statistics of every scheduler, see `NetBricksContext::scheduler_stats`.
+    pub fn scheduler_stats(&self) -> BTreeMap<i32, SchedulerStats> {
+        collect_scheduler_stats(&self.channels).into_iter().collect()
+    }
+
+    /// Port counters and latency summary, see `NetBricksContext::stats_report`.
+    pub fn stats_report(&self) -> StatsReport {
+        collect_stats_report(&self.name, &self.ports, &self.pcap_ports, &self.virtual_ports)
+    }
+
+    /// Install the pipeline registered as `name` on `core`, returning its handle.
+    pub fn add_pipeline(&self, name: &str, core: i32) -> Result<usize> {
+        let run = self
+            .registry
+            .get(name)
+            .ok_or_else(|| ErrorKind::NoSuchPipeline(name.to_string()))?;
+        let pipeline = self.last_pipeline.fetch_add(1, Ordering::Relaxed) + 1;
+        let ports = self.rx_queues.get(&core).cloned().unwrap_or_default();
+        self.send(core, port_pipeline(pipeline, ports, run))?;
+        Ok(pipeline)
+    }
+
+    /// Remove a pipeline from `core`, or from all cores.
+    pub fn remove_pipeline(&self, pipeline: usize, core: Option<i32>) -> Result<()> {
+        for core in self.target_cores(core)? {
+            self.send(core, SchedulerCommand::RemovePipeline(pipeline))?;
+        }
+        Ok(())
+    }
+
+    /// Stop RX on the ports polled by `core` (or on all ports) and let its pipelines keep processing until they go
+    /// idle, i.e., process no packets for a while, or until `timeout` passes, and then pause them. Returns whether they
+    /// went idle. A port shared with other cores stops receiving for them too. `resume` undoes this.
+    pub fn drain(&self, core: Option<i32>, timeout: Duration) -> Result<bool> {
+        let idle = self.stop_rx_and_wait(core, timeout)?;
+        let cores = self.target_cores(core)?;
+        for info in self.pipelines().iter().filter(|p| cores.contains(&p.core)) {
+            self.send(info.core, SchedulerCommand::PausePipeline(info.pipeline))?;
+        }
+        Ok(idle)
+    }
+
+    /// Resume the pipelines on `core` (or all cores) and RX on the ports they poll.
+    pub fn resume(&self, core: Option<i32>) -> Result<()> {
+        let cores = self.target_cores(core)?;
+        for info in self.pipelines().iter().filter(|p| cores.contains(&p.core)) {
+            self.send(info.core, SchedulerCommand::ResumePipeline(info.pipeline))?;
+        }
+        self.set_rx(core, true);
+        Ok(())
+    }
+
+    /// Stop RX on the ports polled by `core` (or on all ports), then wait until the tasks on `core` (or all cores)
+    /// stop processing packets or `timeout` passes. Returns whether they stopped.
+    pub(super) fn stop_rx_and_wait(&self, core: Option<i32>, timeout: Duration) -> Result<bool> {
+        let cores = self.target_cores(core)?;
+        self.set_rx(core, false);
+        let packets = || -> u64 {
+            collect_scheduler_stats(&self.channels)
+                .iter()
+                .filter(|&(core, _)| cores.contains(core))
+                .flat_map(|(_, stats)| stats.tasks.iter())
+                .map(|task| task.packets)
+                .sum()
+        };
+        Ok(wait_until_idle(packets, timeout))
+    }
+
+    /// Drain `core` (or all cores) with the configured drain timeout on a thread of its own, so that the control
+    /// server keeps serving requests meanwhile. Returns the cores being drained.
+    fn drain_in_background(&self, core: Option<i32>) -> Result<Vec<i32>> {
+        let mut cores = self.target_cores(core)?;
+        cores.sort_unstable();
+        let handle = self.clone();
+        thread::Builder::new().name(String::from("drain")).spawn(move || {
+            match handle.drain(core, handle.drain_timeout) {
+                Ok(true) => println!("Drained {}", optional_core(core)),
+                Ok(false) => println!(
+                    "Pipelines on {} still busy after {:?}, paused anyway",
+                    optional_core(core),
+                    handle.drain_timeout
+                ),
+                Err(ref e) => println!("Could not drain {}: {}", optional_core(core), e),
+            }
+        })?;
+        Ok(cores)
+    }
+
+    /// Stop (or restart) RX on the ports polled by `core`, or on all ports.
+    fn set_rx(&self, core: Option<i32>, on: bool) {
+        let (ports, pcap_ports, virtual_ports): (Vec<_>, Vec<_>, Vec<_>) = match core {
+            Some(core) => (
+                self.rx_queues
+                    .get(&core)
+                    .into_iter()
+                    .flatten()
+                    .map(|q| q.port.clone())
+                    .collect(),
+                // Capture file ports are stopped through their port queues.
+                vec![],
+                self.virtual_ports.get(&core).into_iter().cloned().collect(),
+            ),
+            None => (
+                self.ports.values().cloned().collect(),
+                self.pcap_ports.values().cloned().collect(),
+                self.virtual_ports.values().cloned().collect(),
+            ),
+        };
+        for port in ports {
+            if on {
+                port.start_rx()
+            } else {
+                port.stop_rx()
+            }
+        }
+        for port in pcap_ports {
+            if on {
+                port.start_rx()
+            } else {
+                port.stop_rx()
+            }
+        }
+        for port in virtual_ports {
+            if on {
+                port.start_rx()
+            } else {
+                port.stop_rx()
+            }
+        }
+    }
+
+    /// Ask for the system to be stopped, see `NetBricksContext::wait_for_shutdown`.
+    pub fn request_shutdown(&self) {
+        self.shutdown.request();
+    }
+
+    /// Whether a shutdown was requested.
+    pub fn shutdown_requested(&self) -> bool {
+        self.shutdown.requested()
+    }
+
+    /// Serve a control request, returning its result or an error message.
+    pub fn handle(&self, request: &ControlRequest) -> std::result::Result<Value, String> {
+        let result = match *request {
+            ControlRequest::Ports => Ok(json!(self.ports())),
+            ControlRequest::Cores => Ok(json!(self.cores())),
+            ControlRequest::Pipelines => Ok(json!({
+                "running": self.pipelines(),
+                "registered": self.registered_pipelines(),
+            })),
+            ControlRequest::Stats => Ok(json!({
+                "report": self.stats_report(),
+                "schedulers": self.scheduler_stats(),
+            })),
+            ControlRequest::Add { ref name, core } => self
+                .add_pipeline(name, core)
+                .map(|pipeline| json!({ "pipeline": pipeline })),
+            ControlRequest::Remove { pipeline, core } => self.remove_pipeline(pipeline, core).map(|_| Value::Null),
+            ControlRequest::Drain { core } => self.drain_in_background(core).map(|cores| json!({ "draining": cores })),
+            ControlRequest::Resume { core } => self.resume(core).map(|_| Value::Null),
+            ControlRequest::Shutdown => {
+                self.request_shutdown();
+                Ok(Value::Null)
+            }
+            ControlRequest::Help => Ok(Value::from(CONTROL_USAGE)),
+        };
+        result.map_err(|e| e.to_string())
+    }
+
+    fn target_cores(&self, core: Option<i32>) -> Result<Vec<i32>> {
+        match core {
+            Some(core) if self.channels.contains_key(&core) => Ok(vec![core]),
+            Some(core) => Err(ErrorKind::NoRunningSchedulerOnCore(core).into()),
+            None => Ok(self.channels.keys().cloned().collect()),
+        }
+    }
+
+    fn send(&self, core: i32, command: SchedulerCommand) -> Result<()> {
+        match self.channels.get(&core) {
+            Some(channel) if channel.send(command).is_ok() => Ok(()),
+            _ => Err(ErrorKind::NoRunningSchedulerOnCore(core).into()),
+        }
+    }
+}
+
+fn optional_core(core: Option<i32>) -> String {
+    match core {
+        Some(core) => format!("core {}", core),
+        None => String::from("all cores"),
+    }
+}
@@ -0,0 +1,52 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Hooks around the packet processing of a pipeline. The scheduler runs them on its own thread between passes over its
+/// run queue, never in the packet path, which makes them the place for starting workloads and for timed reporting.
+/// Add them with `Scheduler::add_hooks` while installing the pipeline:
+///
+/// - `on_start` runs once, before the first pass after the hooks were added, i.e., once the scheduler is executing.
+/// - `on_tick` runs every `interval` after that. Ticks are checked between passes, so a long pass delays them.
+/// - `on_stop` runs once, when the pipeline is removed or the scheduler shuts down. `NetBricksContext::stop` drains the
+///   pipelines first, so this sees every packet the pipeline received.
+#[derive(Default)]
+pub struct PipelineHooks {
+    pub(super) on_start: Option<Box<dyn FnOnce()>>,
+    pub(super) on_tick: Option<(Duration, Box<dyn FnMut()>)>,
+    pub(super) on_stop: Option<Box<dyn FnOnce()>>,
+}
+
+impl PipelineHooks {
+    /// No hooks.
+    pub fn new() -> PipelineHooks {
+        Default::default()
+    }
+
+    /// Run `hook` once the scheduler starts executing the pipeline.
+    pub fn on_start<F: FnOnce() + 'static>(mut self, hook: F) -> PipelineHooks {
+        self.on_start = Some(Box::new(hook));
+        self
+    }
+
+    /// Run `hook` every `interval`.
+    pub fn on_tick<F: FnMut() + 'static>(mut self, interval: Duration, hook: F) -> PipelineHooks {
+        self.on_tick = Some((interval, Box::new(hook)));
+        self
+    }
+
+    /// Run `hook` when the pipeline stops.
+    pub fn on_stop<F: FnOnce() + 'static>(mut self, hook: F) -> PipelineHooks {
+        self.on_stop = Some(Box::new(hook));
+        self
+    }
+}
+
+impl fmt::Debug for PipelineHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PipelineHooks")
+            .field("on_start", &self.on_start.is_some())
+            .field("on_tick", &self.on_tick.as_ref().map(|t| t.0))
+            .field("on_stop", &self.on_stop.is_some())
+            .finish()
+    }
+}
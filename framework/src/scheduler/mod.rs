@@ -2,18 +2,27 @@
 //! Anything that implements Runnable can be polled by the scheduler. This thing can be a `Batch` (e.g., `SendBatch`) or
 //! something else (e.g., the `GroupBy` operator). Eventually this trait will have more stuff.
 pub use self::context::*;
+pub use self::control::*;
+pub use self::lifecycle::*;
+pub use self::shutdown::*;
 pub use self::standalone_scheduler::*;
+pub use self::timers::*;
 use crate::common::*;
 
 pub mod embedded_scheduler;
 mod standalone_scheduler;
 
 mod context;
+mod control;
+mod lifecycle;
+mod shutdown;
+mod timers;
 
 /// Executable.
 pub trait Executable {
-    /// Execute.
-    fn execute(&mut self);
+    /// Execute, returning how many packets were processed. A return of 0 tells the scheduler the task had nothing to
+    /// do, which strict priority scheduling uses to let lower priority tasks run.
+    fn execute(&mut self) -> usize;
     /// Dependencies.
     fn dependencies(&mut self) -> Vec<usize>;
 }
@@ -22,9 +31,10 @@ impl<F> Executable for F
 where
     F: FnMut(),
 {
-    /// Execute.
-    fn execute(&mut self) {
-        (*self)()
+    /// Execute. Closures cannot report the work they did, so they always count as idle.
+    fn execute(&mut self) -> usize {
+        (*self)();
+        0
     }
 
     /// Dependencies.
@@ -39,4 +49,10 @@ pub trait Scheduler {
     fn add_task<T: Executable + 'static>(&mut self, task: T) -> Result<usize>
     where
         Self: Sized;
+
+    /// Add lifecycle hooks for the pipeline being installed, see `PipelineHooks`. Schedulers that do not run a loop of
+    /// their own cannot run hooks.
+    fn add_hooks(&mut self, _hooks: PipelineHooks) -> Result<()> {
+        Err(ErrorKind::HooksNotSupported.into())
+    }
 }
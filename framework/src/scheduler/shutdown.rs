@@ -0,0 +1,70 @@
+use super::control::ShutdownSignal;
+use crate::common::*;
+use nix::sys::signal::{SigSet, Signal};
+use std::sync::{Arc, Mutex, Once};
+use std::thread;
+
+lazy_static! {
+    static ref HOOKS: Mutex<Vec<Box<dyn FnOnce() + Send>>> = Mutex::new(vec![]);
+    static ref SIGNAL_TARGET: Mutex<Option<Arc<ShutdownSignal>>> = Mutex::new(None);
+}
+
+static SIGNAL_THREAD: Once = Once::new();
+
+/// Run `hook` once when the system shuts down: `NetBricksContext::stop` runs hooks in the order they were added, after
+/// the pipelines drained and the schedulers exited but before the ports are freed. NFs use this to print their final
+/// statistics. Pipelines can add hooks from any thread.
+pub fn add_shutdown_hook<F>(hook: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    HOOKS.lock().unwrap().push(Box::new(hook));
+}
+
+/// Run and forget the hooks added so far.
+pub(super) fn run_shutdown_hooks() {
+    let hooks: Vec<_> = HOOKS.lock().unwrap().drain(..).collect();
+    for hook in hooks {
+        hook();
+    }
+}
+
+fn shutdown_signals() -> SigSet {
+    let mut signals = SigSet::empty();
+    signals.add(Signal::SIGINT);
+    signals.add(Signal::SIGTERM);
+    signals
+}
+
+/// Turn SIGINT and SIGTERM into shutdown requests on `signal`. The signals are blocked in the calling thread and the
+/// threads it starts from now on, and a thread of our own waits for them instead, so this has to run before any other
+/// thread (e.g., a DPDK one) is started. A second signal, received after the first one was not acted upon, kills the
+/// process as usual.
+pub(super) fn catch_shutdown_signals(signal: Arc<ShutdownSignal>) -> Result<()> {
+    shutdown_signals()
+        .thread_block()
+        .chain_err(|| ErrorKind::ConfigurationError(String::from("Could not block shutdown signals")))?;
+    *SIGNAL_TARGET.lock().unwrap() = Some(signal);
+    SIGNAL_THREAD.call_once(|| {
+        thread::Builder::new()
+            .name(String::from("signals"))
+            .spawn(|| {
+                let signals = shutdown_signals();
+                while let Ok(received) = signals.wait() {
+                    match *SIGNAL_TARGET.lock().unwrap() {
+                        Some(ref target) if !target.requested() => {
+                            println!("Received {:?}, shutting down", received);
+                            target.request();
+                            continue;
+                        }
+                        _ => {}
+                    }
+                    // Already asked to stop: let the default action end the process.
+                    let _ = signals.thread_unblock();
+                    let _ = nix::sys::signal::raise(received);
+                }
+            })
+            .expect("Could not start signal thread");
+    });
+    Ok(())
+}
@@ -1,41 +1,208 @@
-use super::{Executable, Scheduler};
+use super::{expire_timers, Executable, PipelineHooks, Scheduler};
+use crate::allocators::CacheAligned;
 use crate::common::*;
+use crate::interface::PortQueue;
 use crate::utils;
+use serde::Serialize;
 use std::default::Default;
 use std::sync::mpsc::{sync_channel, Receiver, RecvError, SyncSender};
 use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
+
+/// Cycles of credit a `SchedulingPolicy::Weighted` task earns per unit of weight each round.
+pub const WEIGHT_QUANTUM: u64 = 10_000;
+
+/// How a task shares its core with the other tasks on the same scheduler, chosen when the task is added (see
+/// `StandaloneScheduler::add_task_with_policy`).
+///
+/// Tasks are grouped into priority levels: tasks added with `Priority` are at the given level, all others at level 0.
+/// Levels are served in strict priority order: a lower level only gets a turn after every task at the levels above it
+/// came back idle (`Executable::execute` returned 0). Within a level tasks take turns in the order they were added, and
+/// the policy decides how long each turn lasts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum SchedulingPolicy {
+    /// Run once per turn. This is the default, and what `add_task` uses.
+    RoundRobin,
+    /// Deficit round robin: every turn adds `quantum` cycles of credit, and the task keeps running while it has credit
+    /// left and packets to process. Overruns are paid back in the next turn; idle tasks lose their remaining credit.
+    Drr {
+        /// Cycles of credit earned per turn.
+        quantum: u64,
+    },
+    /// Weighted fair share of cycles, i.e., deficit round robin with a quantum of `weight * WEIGHT_QUANTUM` cycles.
+    Weighted {
+        /// Relative share of the core.
+        weight: u32,
+    },
+    /// Strict priority at `level` (higher runs first), one run per turn.
+    Priority {
+        /// Priority level, higher levels run first.
+        level: u8,
+    },
+}
+
+impl Default for SchedulingPolicy {
+    fn default() -> SchedulingPolicy {
+        SchedulingPolicy::RoundRobin
+    }
+}
+
+impl SchedulingPolicy {
+    #[inline]
+    fn level(&self) -> u8 {
+        match *self {
+            SchedulingPolicy::Priority { level } => level,
+            _ => 0,
+        }
+    }
+
+    /// Cycles of credit per turn, 0 for policies that run once per turn.
+    #[inline]
+    fn quantum(&self) -> u64 {
+        match *self {
+            SchedulingPolicy::Drr { quantum } => quantum,
+            SchedulingPolicy::Weighted { weight } => weight as u64 * WEIGHT_QUANTUM,
+            _ => 0,
+        }
+    }
+}
+
+/// Opt-in backoff for schedulers whose tasks have nothing to do, instead of busy polling. A pass over the run queue is
+/// idle when no task processed packets (`Executable::execute` returned 0 for all of them); after `pause_after`
+/// consecutive idle passes the scheduler executes a `pause` between passes, and after `sleep_after` it sleeps. Any
+/// packet processed resets the count. Note closures always count as idle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdleBackoff {
+    /// Idle passes before pausing between passes.
+    pub pause_after: u64,
+    /// Idle passes before sleeping between passes.
+    pub sleep_after: u64,
+    /// How long to sleep, in microseconds.
+    pub sleep_us: u64,
+    /// Instead of sleeping, wait (at most as long) for an RX interrupt on the scheduler's port queues. Only queues of
+    /// ports configured with `rx_interrupts` and drivers that support them are waited on; without any the scheduler
+    /// just sleeps.
+    pub rx_interrupts: bool,
+}
+
+impl Default for IdleBackoff {
+    fn default() -> IdleBackoff {
+        IdleBackoff {
+            pause_after: 64,
+            sleep_after: 4096,
+            sleep_us: 100,
+            rx_interrupts: false,
+        }
+    }
+}
+
+/// Statistics for a scheduler, see `StandaloneScheduler::stats`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SchedulerStats {
+    /// Statistics for each task.
+    pub tasks: Vec<TaskStats>,
+    /// Cycles spent sleeping or waiting for RX interrupts because of `IdleBackoff`.
+    pub sleep_cycles: u64,
+    /// Number of times the scheduler slept.
+    pub sleeps: u64,
+}
+
+/// Statistics for one task on a scheduler, see `StandaloneScheduler::task_stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TaskStats {
+    /// Handle of the task, as returned by `add_task`.
+    pub task: usize,
+    /// Pipeline the task belongs to, if it was installed by `SchedulerCommand::RunPipeline`.
+    pub pipeline: Option<usize>,
+    /// Scheduling policy of the task.
+    pub policy: SchedulingPolicy,
+    /// Whether the task is paused.
+    pub paused: bool,
+    /// Cycles spent running the task.
+    pub cycles: u64,
+    /// Number of times the task was executed.
+    pub invocations: u64,
+    /// Packets processed, as reported by `Executable::execute`.
+    pub packets: u64,
+    /// Executions that processed no packets.
+    pub idle: u64,
+}
 
 /// Used to keep stats about each pipeline and eventually grant tokens, etc.
 struct Runnable {
+    pub id: usize,
+    pub pipeline: Option<usize>,
     pub task: Box<dyn Executable>,
+    pub paused: bool,
     pub cycles: u64,
-    pub last_run: u64,
+    pub invocations: u64,
+    pub packets: u64,
+    pub idle: u64,
+    pub policy: SchedulingPolicy,
+    /// Remaining DRR credit in cycles, negative after an overrun.
+    pub deficit: i64,
 }
 
 impl Runnable {
-    pub fn from_task<T: Executable + 'static>(task: T) -> Runnable {
-        Runnable {
-            task: box task,
-            cycles: 0,
-            last_run: utils::rdtsc_unsafe(),
-        }
+    pub fn from_task<T: Executable + 'static>(task: T, policy: SchedulingPolicy) -> Runnable {
+        Runnable::from_boxed_task(box task, policy)
     }
-    pub fn from_boxed_task(task: Box<dyn Executable>) -> Runnable {
+    pub fn from_boxed_task(task: Box<dyn Executable>, policy: SchedulingPolicy) -> Runnable {
         Runnable {
+            id: 0,
+            pipeline: None,
             task,
+            paused: false,
             cycles: 0,
-            last_run: utils::rdtsc_unsafe(),
+            invocations: 0,
+            packets: 0,
+            idle: 0,
+            policy,
+            deficit: 0,
         }
     }
 }
 
-/// A very simple round-robin scheduler. This should really be more of a DRR scheduler.
+/// Hooks added to a scheduler, see `PipelineHooks`.
+struct ScheduledHooks {
+    pipeline: Option<usize>,
+    hooks: PipelineHooks,
+    started: bool,
+    /// Tick interval in cycles.
+    interval: u64,
+    next_tick: u64,
+}
+
+/// A scheduler running the tasks of one core according to their `SchedulingPolicy`. With the default policy this is a
+/// plain round-robin scheduler.
 pub struct StandaloneScheduler {
     /// The set of runnable items. Note we currently don't have a blocked queue.
     run_q: Vec<Runnable>,
     /// Next task to run.
     next_task: usize,
+    /// Whether `next_task` is in the middle of its turn.
+    in_turn: bool,
+    /// Priority level currently being served.
+    level: u8,
+    /// Whether any task at `level` processed packets during the current pass over the run queue.
+    level_busy: bool,
+    /// Handle given to the next task added.
+    next_id: usize,
+    /// Pipeline that tasks added right now belong to.
+    pipeline: Option<usize>,
+    /// Idle backoff, if enabled.
+    backoff: Option<IdleBackoff>,
+    /// Whether any task processed packets during the current pass.
+    pass_busy: bool,
+    /// Consecutive idle passes.
+    idle_passes: u64,
+    /// Queues whose RX interrupts are waited on when sleeping.
+    rx_interrupt_queues: Vec<PortQueue>,
+    sleep_cycles: u64,
+    sleeps: u64,
+    /// Lifecycle hooks of the pipelines.
+    hooks: Vec<ScheduledHooks>,
     /// Channel to communicate and synchronize with scheduler.
     sched_channel: Receiver<SchedulerCommand>,
     /// Signal scheduler should continue executing tasks.
@@ -56,6 +223,18 @@ pub enum SchedulerCommand {
     Shutdown,
     /// Handshake command.
     Handshake(SyncSender<bool>),
+    /// Send back the scheduler's statistics.
+    Stats(SyncSender<SchedulerStats>),
+    /// Run a function that installs a pipeline, tagging every task it adds with the given pipeline handle.
+    RunPipeline(usize, Arc<dyn Fn(&mut StandaloneScheduler) + Send + Sync>),
+    /// Remove all tasks of a pipeline.
+    RemovePipeline(usize),
+    /// Stop running the tasks of a pipeline until it is resumed.
+    PausePipeline(usize),
+    /// Resume the tasks of a paused pipeline.
+    ResumePipeline(usize),
+    /// Replace all tasks of a pipeline with the ones installed by the function, without running any task in between.
+    ReplacePipeline(usize, Arc<dyn Fn(&mut StandaloneScheduler) + Send + Sync>),
 }
 
 const DEFAULT_Q_SIZE: usize = 256;
@@ -67,11 +246,32 @@ impl Default for StandaloneScheduler {
     }
 }
 
+impl Drop for StandaloneScheduler {
+    fn drop(&mut self) {
+        self.stop_hooks(None);
+    }
+}
+
 impl Scheduler for StandaloneScheduler {
     /// Add a task to the current scheduler.
     fn add_task<T: Executable + 'static>(&mut self, task: T) -> Result<usize> {
-        self.run_q.push(Runnable::from_task(task));
-        Ok(self.run_q.len())
+        self.add_task_with_policy(task, SchedulingPolicy::RoundRobin)
+    }
+
+    /// Add lifecycle hooks, which belong to the pipeline being installed (if any).
+    fn add_hooks(&mut self, hooks: PipelineHooks) -> Result<()> {
+        let interval = hooks
+            .on_tick
+            .as_ref()
+            .map_or(0, |t| utils::ns_to_cycles(t.0.as_nanos() as u64));
+        self.hooks.push(ScheduledHooks {
+            pipeline: self.pipeline,
+            hooks,
+            started: false,
+            interval,
+            next_tick: 0,
+        });
+        Ok(())
     }
 }
 
@@ -92,16 +292,225 @@ impl StandaloneScheduler {
         StandaloneScheduler {
             run_q: Vec::with_capacity(capacity),
             next_task: 0,
+            in_turn: false,
+            level: 0,
+            level_busy: false,
+            next_id: 1,
+            pipeline: None,
+            backoff: None,
+            pass_busy: false,
+            idle_passes: 0,
+            rx_interrupt_queues: vec![],
+            sleep_cycles: 0,
+            sleeps: 0,
+            hooks: vec![],
             sched_channel: channel,
             execute_loop: false,
             shutdown: true,
         }
     }
 
+    /// Add a task that shares the core according to `policy`, returning its handle.
+    pub fn add_task_with_policy<T: Executable + 'static>(&mut self, task: T, policy: SchedulingPolicy) -> Result<usize> {
+        Ok(self.push(Runnable::from_task(task, policy)))
+    }
+
+    fn push(&mut self, mut runnable: Runnable) -> usize {
+        runnable.id = self.next_id;
+        runnable.pipeline = self.pipeline;
+        self.next_id += 1;
+        // A task at a higher level than the one being served preempts it right away.
+        self.level = self.level.max(runnable.policy.level());
+        self.run_q.push(runnable);
+        self.next_id - 1
+    }
+
+    fn position(&self, task: usize) -> Result<usize> {
+        self.run_q
+            .iter()
+            .position(|r| r.id == task)
+            .ok_or_else(|| ErrorKind::NoSuchTask(task).into())
+    }
+
+    /// Remove a task from the run queue, dropping it.
+    pub fn remove_task(&mut self, task: usize) -> Result<()> {
+        let idx = self.position(task)?;
+        self.remove_at(idx);
+        Ok(())
+    }
+
+    fn remove_at(&mut self, idx: usize) {
+        self.run_q.remove(idx);
+        if idx < self.next_task {
+            self.next_task -= 1;
+        } else if idx == self.next_task {
+            self.in_turn = false;
+        }
+    }
+
+    /// Stop running a task until it is resumed. The task keeps its place in the run queue.
+    pub fn pause_task(&mut self, task: usize) -> Result<()> {
+        let idx = self.position(task)?;
+        self.run_q[idx].paused = true;
+        if idx == self.next_task {
+            self.in_turn = false;
+        }
+        Ok(())
+    }
+
+    /// Resume a paused task.
+    pub fn resume_task(&mut self, task: usize) -> Result<()> {
+        let idx = self.position(task)?;
+        self.run_q[idx].paused = false;
+        self.level = self.level.max(self.run_q[idx].policy.level());
+        Ok(())
+    }
+
+    /// Replace a task in place. The new task keeps the handle, pipeline, policy and place in the run queue of the old
+    /// one, but starts with fresh statistics.
+    pub fn replace_task<T: Executable + 'static>(&mut self, task: usize, new_task: T) -> Result<()> {
+        let idx = self.position(task)?;
+        let old = &self.run_q[idx];
+        let mut runnable = Runnable::from_task(new_task, old.policy);
+        runnable.id = old.id;
+        runnable.pipeline = old.pipeline;
+        runnable.paused = old.paused;
+        self.run_q[idx] = runnable;
+        if idx == self.next_task {
+            self.in_turn = false;
+        }
+        Ok(())
+    }
+
+    /// Run `install`, tagging every task it adds as part of `pipeline`.
+    pub fn run_pipeline(&mut self, pipeline: usize, install: &dyn Fn(&mut StandaloneScheduler)) {
+        let outer = self.pipeline.replace(pipeline);
+        install(self);
+        self.pipeline = outer;
+    }
+
+    /// Handles of the tasks belonging to `pipeline`.
+    pub fn pipeline_tasks(&self, pipeline: usize) -> Vec<usize> {
+        self.run_q
+            .iter()
+            .filter(|r| r.pipeline == Some(pipeline))
+            .map(|r| r.id)
+            .collect()
+    }
+
+    /// Remove all tasks of a pipeline, returning how many there were. The `on_stop` hooks of the pipeline run first.
+    pub fn remove_pipeline(&mut self, pipeline: usize) -> usize {
+        self.stop_hooks(Some(pipeline));
+        let tasks = self.pipeline_tasks(pipeline);
+        for &task in &tasks {
+            let idx = self.position(task).unwrap();
+            self.remove_at(idx);
+        }
+        tasks.len()
+    }
+
+    /// Pause or resume all tasks of a pipeline.
+    pub fn set_pipeline_paused(&mut self, pipeline: usize, paused: bool) {
+        for task in self.pipeline_tasks(pipeline) {
+            if paused {
+                self.pause_task(task).unwrap();
+            } else {
+                self.resume_task(task).unwrap();
+            }
+        }
+    }
+
+    /// Run `on_start` hooks that did not run yet, and the `on_tick` hooks that are due.
+    fn run_hooks(&mut self) {
+        if self.hooks.is_empty() {
+            return;
+        }
+        let now = utils::rdtsc_unsafe();
+        for scheduled in &mut self.hooks {
+            if !scheduled.started {
+                scheduled.started = true;
+                scheduled.next_tick = now + scheduled.interval;
+                if let Some(start) = scheduled.hooks.on_start.take() {
+                    start();
+                }
+            } else if now >= scheduled.next_tick {
+                if let Some((_, ref mut tick)) = scheduled.hooks.on_tick {
+                    scheduled.next_tick = now + scheduled.interval;
+                    tick();
+                }
+            }
+        }
+    }
+
+    /// Run and drop the `on_stop` hooks of `pipeline`, or of all pipelines.
+    fn stop_hooks(&mut self, pipeline: Option<usize>) {
+        let (stopped, kept) = self
+            .hooks
+            .drain(..)
+            .partition(|h| pipeline.is_none() || h.pipeline == pipeline);
+        self.hooks = kept;
+        for scheduled in stopped {
+            if let Some(stop) = scheduled.hooks.on_stop {
+                stop();
+            }
+        }
+    }
+
+    /// Enable or disable backing off when idle.
+    pub fn set_idle_backoff(&mut self, backoff: Option<IdleBackoff>) {
+        self.backoff = backoff;
+        self.idle_passes = 0;
+    }
+
+    /// Wait on the RX interrupts of `queues` when sleeping, if the idle backoff asks for RX interrupts. Queues that do
+    /// not support them, or are already watched, are skipped.
+    pub fn watch_rx_queues(&mut self, queues: &[CacheAligned<PortQueue>]) {
+        if !self.backoff.map_or(false, |b| b.rx_interrupts) {
+            return;
+        }
+        for queue in queues {
+            let watched = self
+                .rx_interrupt_queues
+                .iter()
+                .any(|q| q.port.name() == queue.port.name() && q.rxq() == queue.rxq());
+            if !watched && queue.add_rx_interrupt().is_ok() {
+                self.rx_interrupt_queues.push((**queue).clone());
+            }
+        }
+    }
+
+    /// Statistics for the scheduler and each of its tasks.
+    pub fn stats(&self) -> SchedulerStats {
+        SchedulerStats {
+            tasks: self.task_stats(),
+            sleep_cycles: self.sleep_cycles,
+            sleeps: self.sleeps,
+        }
+    }
+
+    /// Statistics for each task, in the order the tasks were added.
+    pub fn task_stats(&self) -> Vec<TaskStats> {
+        self.run_q
+            .iter()
+            .map(|r| TaskStats {
+                task: r.id,
+                pipeline: r.pipeline,
+                policy: r.policy,
+                paused: r.paused,
+                cycles: r.cycles,
+                invocations: r.invocations,
+                packets: r.packets,
+                idle: r.idle,
+            })
+            .collect()
+    }
+
     /// Handle one request and then exit.
     fn handle_request(&mut self, request: SchedulerCommand) {
         match request {
-            SchedulerCommand::Add(ex) => self.run_q.push(Runnable::from_boxed_task(ex)),
+            SchedulerCommand::Add(ex) => {
+                self.push(Runnable::from_boxed_task(ex, SchedulingPolicy::RoundRobin));
+            }
             SchedulerCommand::Run(f) => f(self),
             SchedulerCommand::Execute => self.execute_loop(),
             SchedulerCommand::Shutdown => {
@@ -112,6 +521,20 @@ impl StandaloneScheduler {
                 chan.send(true).unwrap(); // Inform context about reaching barrier.
                 thread::park();
             }
+            SchedulerCommand::Stats(chan) => {
+                // The requester may have given up waiting.
+                let _ = chan.send(self.stats());
+            }
+            SchedulerCommand::RunPipeline(pipeline, f) => self.run_pipeline(pipeline, &*f),
+            SchedulerCommand::RemovePipeline(pipeline) => {
+                self.remove_pipeline(pipeline);
+            }
+            SchedulerCommand::PausePipeline(pipeline) => self.set_pipeline_paused(pipeline, true),
+            SchedulerCommand::ResumePipeline(pipeline) => self.set_pipeline_paused(pipeline, false),
+            SchedulerCommand::ReplacePipeline(pipeline, f) => {
+                self.remove_pipeline(pipeline);
+                self.run_pipeline(pipeline, &*f);
+            }
         }
     }
 
@@ -128,51 +551,147 @@ impl StandaloneScheduler {
         } {
             self.handle_request(cmd)
         }
+        self.stop_hooks(None);
         println!(
             "Scheduler exiting {}",
             thread::current().name().unwrap_or("unknown-name")
         );
     }
 
-    /// Run the scheduling.
+    /// Called at the end of each pass over the run queue: pick the priority level to serve next, check for commands and
+    /// run hooks and timers that are due.
+    fn end_pass(&mut self) {
+        if let Ok(cmd) = self.sched_channel.try_recv() {
+            self.handle_request(cmd);
+        }
+        self.run_hooks();
+        expire_timers();
+        if let Some(backoff) = self.backoff {
+            self.idle_passes = if self.pass_busy { 0 } else { self.idle_passes + 1 };
+            if self.idle_passes >= backoff.sleep_after {
+                self.sleep(&backoff);
+            } else if self.idle_passes >= backoff.pause_after {
+                utils::pause();
+            }
+        }
+        self.pass_busy = false;
+        let current = self.level;
+        let levels = || self.run_q.iter().filter(|t| !t.paused).map(|t| t.policy.level());
+        let lower = levels().filter(|&level| level < current).max();
+        self.level = match lower {
+            Some(level) if !self.level_busy => level,
+            _ => levels().max().unwrap_or(0),
+        };
+        self.level_busy = false;
+    }
+
+    fn sleep(&mut self, backoff: &IdleBackoff) {
+        let start = utils::rdtsc_unsafe();
+        if backoff.rx_interrupts && !self.rx_interrupt_queues.is_empty() {
+            for queue in &self.rx_interrupt_queues {
+                queue.set_rx_interrupt(true);
+            }
+            // Packets that arrived before the interrupts were enabled raise none, so look once more before blocking.
+            if !self.rx_interrupt_queues.iter().any(|queue| queue.rx_pending()) {
+                let timeout_ms = ((backoff.sleep_us + 999) / 1000) as i32;
+                PortQueue::wait_rx_interrupt(timeout_ms);
+            }
+            for queue in &self.rx_interrupt_queues {
+                queue.set_rx_interrupt(false);
+            }
+        } else {
+            thread::sleep(Duration::from_micros(backoff.sleep_us));
+        }
+        self.sleep_cycles += utils::rdtsc_unsafe() - start;
+        self.sleeps += 1;
+    }
+
+    /// Find the next task at the level being served and start its turn. Gives up, returning false, once a full pass at
+    /// the current level found nothing to run, e.g., because all tasks are paused.
+    fn start_turn(&mut self) -> bool {
+        let mut skipped = 0;
+        loop {
+            if self.next_task >= self.run_q.len() {
+                self.next_task = 0;
+                self.end_pass();
+            }
+            // The rest of the previous pass plus a full pass at the level chosen at its end.
+            if skipped >= 2 * self.run_q.len() {
+                return false;
+            }
+            let level = self.level;
+            let task = &mut self.run_q[self.next_task];
+            if !task.paused && task.policy.level() == level {
+                let quantum = task.policy.quantum();
+                if quantum == 0 {
+                    self.in_turn = true;
+                    return true;
+                }
+                task.deficit += quantum as i64;
+                if task.deficit > 0 {
+                    self.in_turn = true;
+                    return true;
+                }
+            }
+            skipped += 1;
+            self.next_task += 1;
+        }
+    }
+
+    /// Run the scheduling. Only the task's own execution is charged to it, not the work done between passes.
     #[inline]
-    fn execute_internal(&mut self, begin: u64) -> u64 {
-        let time = {
+    fn execute_internal(&mut self) {
+        if !self.in_turn && !self.start_turn() {
+            return;
+        }
+        let processed = {
             let task = &mut (&mut self.run_q[self.next_task]);
-            task.task.execute();
+            let begin = utils::rdtsc_unsafe();
+            let processed = task.task.execute();
             let end = utils::rdtsc_unsafe();
             task.cycles += end - begin;
-            task.last_run = end;
-            end
-        };
-        let len = self.run_q.len();
-        let next = self.next_task + 1;
-        if next == len {
-            self.next_task = 0;
-            if let Ok(cmd) = self.sched_channel.try_recv() {
-                self.handle_request(cmd);
+            task.invocations += 1;
+            task.packets += processed as u64;
+            if processed == 0 {
+                task.idle += 1;
             }
-        } else {
-            self.next_task = next;
+            if task.policy.quantum() == 0 {
+                self.in_turn = false;
+            } else {
+                task.deficit -= (end - begin) as i64;
+                if processed == 0 {
+                    // Idle tasks lose their remaining credit, but still pay back what they overran.
+                    task.deficit = task.deficit.min(0);
+                }
+                self.in_turn = processed > 0 && task.deficit > 0;
+            }
+            processed
         };
-        time
+        if processed > 0 {
+            self.level_busy = true;
+            self.pass_busy = true;
+        }
+        if !self.in_turn {
+            self.next_task += 1;
+        }
     }
 
     /// Run the scheduling loop.
     pub fn execute_loop(&mut self) {
         self.execute_loop = true;
-        let mut begin_time = utils::rdtsc_unsafe();
-        if !self.run_q.is_empty() {
-            while self.execute_loop {
-                begin_time = self.execute_internal(begin_time)
-            }
+        self.run_hooks();
+        // Keep going with an empty run queue: pipelines may be added later, and commands are handled between passes.
+        while self.execute_loop {
+            self.execute_internal()
         }
     }
 
     /// Run the scheduling once.
     pub fn execute_one(&mut self) {
+        self.run_hooks();
+        expire_timers();
         if !self.run_q.is_empty() {
-            self.execute_internal(utils::rdtsc_unsafe());
+            self.execute_internal();
         }
     }
 }
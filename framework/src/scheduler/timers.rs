@@ -0,0 +1,44 @@
+use crate::utils::{rdtsc_unsafe, TimerId, TimerWheel};
+use std::cell::RefCell;
+use std::time::Duration;
+
+/// Resolution of the scheduler's timers.
+pub const TIMER_TICK: Duration = Duration::from_micros(100);
+
+type Callback = Box<dyn FnOnce()>;
+
+thread_local! {
+    static TIMERS: RefCell<TimerWheel<Callback>> = RefCell::new(TimerWheel::new(TIMER_TICK));
+}
+
+/// Run `callback` once `delay` passed, e.g., to expire the state of an idle flow. Timers are per core: every scheduler
+/// thread has its own wheel (see `utils::TimerWheel`), which it advances between passes over its run queue, and
+/// callbacks run on the thread that armed them, so they need not be `Send`. A timer fires at most `TIMER_TICK` late,
+/// plus however long the pass in progress takes; timers armed outside a scheduler thread never fire.
+pub fn arm_timer<F: FnOnce() + 'static>(delay: Duration, callback: F) -> TimerId {
+    TIMERS.with(|t| t.borrow_mut().arm(delay, Box::new(callback)))
+}
+
+/// Cancel a timer of the calling core, returning whether it was still armed.
+pub fn cancel_timer(timer: TimerId) -> bool {
+    TIMERS.with(|t| t.borrow_mut().cancel(timer).is_some())
+}
+
+/// Push back or bring forward a timer of the calling core, to fire `delay` from now. Returns whether it was still
+/// armed.
+pub fn reset_timer(timer: TimerId, delay: Duration) -> bool {
+    TIMERS.with(|t| t.borrow_mut().reset(timer, delay))
+}
+
+/// Number of timers armed on the calling core.
+pub fn pending_timers() -> usize {
+    TIMERS.with(|t| t.borrow().len())
+}
+
+/// Run the callbacks of the expired timers of the calling core. Callbacks may arm and cancel timers.
+pub(super) fn expire_timers() {
+    let expired = TIMERS.with(|t| t.borrow_mut().advance(rdtsc_unsafe()));
+    for callback in expired {
+        callback();
+    }
+}
@@ -0,0 +1,437 @@
+//! Connection tracking.
+//!
+//! A `ConnTracker` follows the TCP connections and UDP flows passing through an NF, in both directions, the way a
+//! stateful firewall sees them: the first packet of a connection is its original direction, and packets the other way
+//! are replies. TCP connections go through the states of RFC 793 as far as they can be told apart from the middle of
+//! the path, UDP flows are unreplied until a reply is seen. ICMP errors quoting a packet of a tracked connection are
+//! related to it. `Batch::conntrack` runs a tracker over a batch and attaches the result to each packet as
+//! `ConnMetadata`.
+use super::{FlowTable, FlowTableStats};
+use crate::headers::{IpHeader, ICMP_DEST_UNREACHABLE, ICMP_PARAMETER_PROBLEM, ICMP_REDIRECT, ICMP_TIME_EXCEEDED};
+use crate::utils::{ns_to_cycles, Flow};
+use byteorder::{BigEndian, ByteOrder};
+use serde::Serialize;
+use std::time::Duration;
+
+const TCP: u8 = 6;
+const UDP: u8 = 17;
+const ICMP: u8 = 1;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_RST: u8 = 0x04;
+const TCP_ACK: u8 = 0x10;
+
+/// ICMP source quench, deprecated but still an error quoting the packet.
+const ICMP_SOURCE_QUENCH: u8 = 4;
+
+/// State of a tracked connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub enum ConnState {
+    /// Not tracked: neither TCP, UDP nor a related ICMP error, or a non-first IP fragment.
+    Untracked,
+    /// A TCP packet that belongs to no connection and cannot start one, e.g., an ACK for a connection that was never
+    /// seen.
+    Invalid,
+    /// The originator sent a SYN.
+    SynSent,
+    /// The responder answered with SYN+ACK.
+    SynReceived,
+    /// The originator acknowledged the SYN+ACK.
+    Established,
+    /// One side sent a FIN.
+    FinWait,
+    /// The other side acknowledged the first FIN, but has not sent its own.
+    CloseWait,
+    /// Both sides sent a FIN.
+    LastAck,
+    /// The side that closed first acknowledged the second FIN.
+    TimeWait,
+    /// Reset.
+    Closed,
+    /// A UDP flow that only saw packets in its original direction.
+    UdpUnreplied,
+    /// A UDP flow that saw a reply.
+    UdpReplied,
+    /// An ICMP error about a packet of a tracked connection.
+    Related,
+}
+
+impl ConnState {
+    /// Whether the connection saw traffic both ways and is not closed, i.e., what a firewall's "established" rule
+    /// accepts.
+    pub fn is_established(&self) -> bool {
+        matches!(
+            *self,
+            ConnState::Established
+                | ConnState::FinWait
+                | ConnState::CloseWait
+                | ConnState::LastAck
+                | ConnState::UdpReplied
+        )
+    }
+}
+
+/// Connection tracking result of a packet, attached as metadata by `Batch::conntrack`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConnMetadata {
+    /// Connection the packet belongs to, unique per tracker. 0 for packets that belong to none.
+    pub id: u64,
+    /// State of the connection after the packet.
+    pub state: ConnState,
+    /// Whether the packet went in the reply direction.
+    pub reply: bool,
+}
+
+impl ConnMetadata {
+    fn untracked(state: ConnState) -> ConnMetadata {
+        ConnMetadata {
+            id: 0,
+            state,
+            reply: false,
+        }
+    }
+}
+
+/// Configuration of a `ConnTracker`. Idle connections time out after the timeout of their state, as in Linux
+/// netfilter, whose defaults these are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConntrackConfig {
+    /// Number of connections tracked at most, once full the least recently used ones are forgotten.
+    pub capacity: usize,
+    /// Start tracking TCP connections from any packet but a RST, not just from a SYN, so connections that were open
+    /// before the NF started are picked up as established.
+    pub loose: bool,
+    /// Timeout of connections in the SYN handshake.
+    pub syn_timeout: Duration,
+    /// Timeout of established connections.
+    pub established_timeout: Duration,
+    /// Timeout of closing connections (`FinWait`, `CloseWait` and `LastAck`).
+    pub closing_timeout: Duration,
+    /// Timeout of connections in `TimeWait`.
+    pub time_wait_timeout: Duration,
+    /// Timeout of reset connections.
+    pub closed_timeout: Duration,
+    /// Timeout of unreplied UDP flows.
+    pub udp_timeout: Duration,
+    /// Timeout of replied UDP flows.
+    pub udp_replied_timeout: Duration,
+}
+
+impl Default for ConntrackConfig {
+    fn default() -> ConntrackConfig {
+        ConntrackConfig {
+            capacity: 1 << 20,
+            loose: false,
+            syn_timeout: Duration::from_secs(120),
+            established_timeout: Duration::from_secs(5 * 24 * 3600),
+            closing_timeout: Duration::from_secs(120),
+            time_wait_timeout: Duration::from_secs(120),
+            closed_timeout: Duration::from_secs(10),
+            udp_timeout: Duration::from_secs(30),
+            udp_replied_timeout: Duration::from_secs(180),
+        }
+    }
+}
+
+/// Counters of a `ConnTracker`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ConntrackStats {
+    /// Connections started.
+    pub connections: u64,
+    /// Packets found invalid.
+    pub invalid: u64,
+    /// Connections that timed out.
+    pub timeouts: u64,
+    /// Counters of the connection table.
+    pub table: FlowTableStats,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Connection {
+    id: u64,
+    state: ConnState,
+    /// Whether the reply direction sent the first FIN.
+    reply_closed: bool,
+    /// TSC value after which the connection timed out.
+    deadline: u64,
+}
+
+/// Timeouts in cycles, indexed by state.
+#[derive(Clone, Copy, Debug)]
+struct Timeouts {
+    syn: u64,
+    established: u64,
+    closing: u64,
+    time_wait: u64,
+    closed: u64,
+    udp: u64,
+    udp_replied: u64,
+}
+
+impl Timeouts {
+    fn of(&self, state: ConnState) -> u64 {
+        match state {
+            ConnState::SynSent | ConnState::SynReceived => self.syn,
+            ConnState::Established => self.established,
+            ConnState::FinWait | ConnState::CloseWait | ConnState::LastAck => self.closing,
+            ConnState::TimeWait => self.time_wait,
+            ConnState::UdpUnreplied => self.udp,
+            ConnState::UdpReplied => self.udp_replied,
+            _ => self.closed,
+        }
+    }
+}
+
+fn cycles(duration: Duration) -> u64 {
+    ns_to_cycles(duration.as_nanos() as u64)
+}
+
+/// Tracks the connections of one core, see the module documentation.
+pub struct ConnTracker {
+    table: FlowTable<Connection>,
+    timeouts: Timeouts,
+    loose: bool,
+    next_id: u64,
+    stats: ConntrackStats,
+}
+
+impl ConnTracker {
+    /// Create a tracker for up to `capacity` connections, with default timeouts.
+    pub fn new(capacity: usize) -> ConnTracker {
+        ConnTracker::with_config(ConntrackConfig {
+            capacity,
+            ..Default::default()
+        })
+    }
+
+    /// Create a tracker.
+    pub fn with_config(config: ConntrackConfig) -> ConnTracker {
+        ConnTracker {
+            table: FlowTable::new(config.capacity, None),
+            timeouts: Timeouts {
+                syn: cycles(config.syn_timeout),
+                established: cycles(config.established_timeout),
+                closing: cycles(config.closing_timeout),
+                time_wait: cycles(config.time_wait_timeout),
+                closed: cycles(config.closed_timeout),
+                udp: cycles(config.udp_timeout),
+                udp_replied: cycles(config.udp_replied_timeout),
+            },
+            loose: config.loose,
+            next_id: 1,
+            stats: ConntrackStats::default(),
+        }
+    }
+
+    /// Number of connections tracked, including timed out ones not reclaimed yet.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Whether no connections are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// Counters of the tracker.
+    pub fn stats(&self) -> ConntrackStats {
+        ConntrackStats {
+            table: self.table.stats(),
+            ..self.stats
+        }
+    }
+
+    /// State of the connection of `flow` (in either direction) at TSC value `now`, without counting as traffic.
+    pub fn state(&self, flow: &Flow, now: u64) -> Option<(u64, ConnState)> {
+        match self.table.peek(flow) {
+            Some((conn, _)) if now <= conn.deadline => Some((conn.id, conn.state)),
+            _ => None,
+        }
+    }
+
+    /// Track an IPv4 packet, given its header and its payload, at TSC value `now`.
+    pub fn track(&mut self, ip: &IpHeader, payload: &[u8], now: u64) -> ConnMetadata {
+        if ip.fragment_offset() != 0 {
+            return ConnMetadata::untracked(ConnState::Untracked);
+        }
+        let proto = ip.protocol();
+        match proto {
+            TCP if payload.len() >= 14 => {
+                let flow = Flow {
+                    src_ip: ip.src(),
+                    dst_ip: ip.dst(),
+                    src_port: BigEndian::read_u16(&payload[0..2]),
+                    dst_port: BigEndian::read_u16(&payload[2..4]),
+                    proto,
+                };
+                self.track_tcp(&flow, payload[13], now)
+            }
+            UDP if payload.len() >= 4 => {
+                let flow = Flow {
+                    src_ip: ip.src(),
+                    dst_ip: ip.dst(),
+                    src_port: BigEndian::read_u16(&payload[0..2]),
+                    dst_port: BigEndian::read_u16(&payload[2..4]),
+                    proto,
+                };
+                self.track_udp(&flow, now)
+            }
+            ICMP => self.track_icmp(payload, now),
+            _ => ConnMetadata::untracked(ConnState::Untracked),
+        }
+    }
+
+    /// The live connection of `flow` and whether `flow` is its reply direction. Timed out connections are dropped.
+    fn lookup(&mut self, flow: &Flow, now: u64) -> Option<(&mut Connection, bool)> {
+        let timed_out = match self.table.get_directed(flow) {
+            Some((conn, _)) => now > conn.deadline,
+            None => return None,
+        };
+        if timed_out {
+            self.table.remove(flow);
+            self.stats.timeouts += 1;
+            return None;
+        }
+        self.table.get_directed(flow)
+    }
+
+    fn start(&mut self, flow: &Flow, state: ConnState, now: u64) -> ConnMetadata {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.stats.connections += 1;
+        self.table.insert(
+            *flow,
+            Connection {
+                id,
+                state,
+                reply_closed: false,
+                deadline: now + self.timeouts.of(state),
+            },
+        );
+        ConnMetadata {
+            id,
+            state,
+            reply: false,
+        }
+    }
+
+    fn track_tcp(&mut self, flow: &Flow, flags: u8, now: u64) -> ConnMetadata {
+        let (syn, ack, fin, rst) = (
+            flags & TCP_SYN != 0,
+            flags & TCP_ACK != 0,
+            flags & TCP_FIN != 0,
+            flags & TCP_RST != 0,
+        );
+        let timeouts = self.timeouts;
+        let (id, state, reply) = match self.lookup(flow, now) {
+            Some((conn, reply)) => {
+                // A new SYN on a finished connection reopens it.
+                let reopen =
+                    syn && !ack && !reply && (conn.state == ConnState::TimeWait || conn.state == ConnState::Closed);
+                if !reopen {
+                    conn.state = match conn.state {
+                        _ if rst => ConnState::Closed,
+                        ConnState::SynSent if reply && syn && ack => ConnState::SynReceived,
+                        ConnState::SynReceived if !reply && ack && !syn => ConnState::Established,
+                        ConnState::Established if fin => {
+                            conn.reply_closed = reply;
+                            ConnState::FinWait
+                        }
+                        ConnState::FinWait | ConnState::CloseWait if fin && reply != conn.reply_closed => {
+                            ConnState::LastAck
+                        }
+                        ConnState::FinWait if ack && reply != conn.reply_closed => ConnState::CloseWait,
+                        ConnState::LastAck if ack && reply == conn.reply_closed => ConnState::TimeWait,
+                        state => state,
+                    };
+                    conn.deadline = now + timeouts.of(conn.state);
+                    return ConnMetadata {
+                        id: conn.id,
+                        state: conn.state,
+                        reply,
+                    };
+                }
+                (conn.id, conn.state, reply)
+            }
+            None => (0, ConnState::Invalid, false),
+        };
+        if id != 0 {
+            // Reopened, start over as a new connection.
+            self.table.remove(flow);
+        }
+        if syn && !ack {
+            self.start(flow, ConnState::SynSent, now)
+        } else if self.loose && !rst {
+            self.start(flow, ConnState::Established, now)
+        } else {
+            self.stats.invalid += 1;
+            ConnMetadata { id, state, reply }
+        }
+    }
+
+    fn track_udp(&mut self, flow: &Flow, now: u64) -> ConnMetadata {
+        let timeouts = self.timeouts;
+        match self.lookup(flow, now) {
+            Some((conn, reply)) => {
+                if reply {
+                    conn.state = ConnState::UdpReplied;
+                }
+                conn.deadline = now + timeouts.of(conn.state);
+                ConnMetadata {
+                    id: conn.id,
+                    state: conn.state,
+                    reply,
+                }
+            }
+            None => self.start(flow, ConnState::UdpUnreplied, now),
+        }
+    }
+
+    /// ICMP errors quote the IP header and the first 8 bytes of the packet they are about, which hold the ports.
+    fn track_icmp(&mut self, payload: &[u8], now: u64) -> ConnMetadata {
+        let error = match payload.first() {
+            Some(&t) => {
+                t == ICMP_DEST_UNREACHABLE
+                    || t == ICMP_SOURCE_QUENCH
+                    || t == ICMP_REDIRECT
+                    || t == ICMP_TIME_EXCEEDED
+                    || t == ICMP_PARAMETER_PROBLEM
+            }
+            None => false,
+        };
+        let quoted = &payload[payload.len().min(8)..];
+        if !error || quoted.len() < 20 {
+            return ConnMetadata::untracked(ConnState::Untracked);
+        }
+        let ihl = (quoted[0] & 0xf) as usize * 4;
+        let proto = quoted[9];
+        if (proto != TCP && proto != UDP) || quoted.len() < ihl + 4 {
+            return ConnMetadata::untracked(ConnState::Untracked);
+        }
+        let flow = Flow {
+            src_ip: BigEndian::read_u32(&quoted[12..16]),
+            dst_ip: BigEndian::read_u32(&quoted[16..20]),
+            src_port: BigEndian::read_u16(&quoted[ihl..ihl + 2]),
+            dst_port: BigEndian::read_u16(&quoted[ihl + 2..ihl + 4]),
+            proto,
+        };
+        match self.table.peek(&flow) {
+            Some((conn, reverse)) if now <= conn.deadline => ConnMetadata {
+                id: conn.id,
+                state: ConnState::Related,
+                // The error travels against the direction of the quoted packet.
+                reply: !reverse,
+            },
+            _ => ConnMetadata::untracked(ConnState::Untracked),
+        }
+    }
+
+    /// Reclaim timed out connections in up to `buckets` buckets of the connection table, continuing where the previous
+    /// call stopped, and return how many there were.
+    pub fn expire(&mut self, buckets: usize, now: u64) -> usize {
+        let expired = self.table.retain(buckets, |_, conn| now <= conn.deadline);
+        self.stats.timeouts += expired as u64;
+        expired
+    }
+}
@@ -1,4 +1,4 @@
-use crate::utils::Flow;
+use crate::utils::{Flow, FlowKey};
 use fnv::FnvHasher;
 use std::collections::hash_map::Iter;
 use std::collections::HashMap;
@@ -19,16 +19,16 @@ const CACHE_SIZE: usize = 1 << 14;
 /// #[FIXME]
 /// Garbage collection.
 #[derive(Debug, Default, Clone)]
-pub struct DpMergeableStore<T: AddAssign<T> + Default> {
+pub struct DpMergeableStore<T: AddAssign<T> + Default, K: FlowKey = Flow> {
     /// Contains the counts on the data path.
-    state: HashMap<Flow, T, FnvHash>,
-    cache: Vec<(Flow, T)>,
+    state: HashMap<K, T, FnvHash>,
+    cache: Vec<(K, T)>,
     cache_size: usize,
 }
 
-impl<T: AddAssign<T> + Default> DpMergeableStore<T> {
+impl<T: AddAssign<T> + Default, K: FlowKey> DpMergeableStore<T, K> {
     /// Initialize the DpMergeableStore with size of cache vector and cache size.
-    pub fn with_cache_and_size(cache: usize, size: usize) -> DpMergeableStore<T> {
+    pub fn with_cache_and_size(cache: usize, size: usize) -> DpMergeableStore<T, K> {
         DpMergeableStore {
             state: HashMap::with_capacity_and_hasher(size, Default::default()),
             cache: Vec::with_capacity(cache),
@@ -37,7 +37,7 @@ impl<T: AddAssign<T> + Default> DpMergeableStore<T> {
     }
 
     /// Initialize the DpMergeableStore.
-    pub fn new() -> DpMergeableStore<T> {
+    pub fn new() -> DpMergeableStore<T, K> {
         DpMergeableStore::with_cache_and_size(CACHE_SIZE, VEC_SIZE)
     }
 
@@ -48,7 +48,7 @@ impl<T: AddAssign<T> + Default> DpMergeableStore<T> {
 
     /// Change the value for the given `Flow`.
     #[inline]
-    pub fn update(&mut self, flow: Flow, inc: T) {
+    pub fn update(&mut self, flow: K, inc: T) {
         {
             self.cache.push((flow, inc));
         }
@@ -59,7 +59,7 @@ impl<T: AddAssign<T> + Default> DpMergeableStore<T> {
 
     /// Remove an entry from the table.
     #[inline]
-    pub fn remove(&mut self, flow: &Flow) -> T {
+    pub fn remove(&mut self, flow: &K) -> T {
         self.merge_cache();
         self.state.remove(flow).unwrap_or_else(Default::default)
     }
@@ -68,7 +68,7 @@ impl<T: AddAssign<T> + Default> DpMergeableStore<T> {
     ///
     /// #[Warning]
     /// This might have severe performance penalties.
-    pub fn iter(&mut self) -> Iter<'_, Flow, T> {
+    pub fn iter(&mut self) -> Iter<'_, K, T> {
         self.merge_cache();
         self.state.iter()
     }
@@ -0,0 +1,428 @@
+use crate::utils::{ns_to_cycles, rdtsc_unsafe, round_to_power_of_2, Flow};
+use serde::Serialize;
+use std::mem;
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+/// Entries per bucket.
+const WAYS: usize = 8;
+/// Timestamps of tables with a TTL count units of `1 << TIME_SHIFT` TSC cycles (tens of microseconds), so they fit in
+/// 32 bits for TTLs of up to half a day.
+const TIME_SHIFT: u32 = 16;
+/// Tag of a free way. Tags of entries always have the low bit set.
+const FREE: u32 = 0;
+
+/// Counters of a `FlowTable`, see `FlowTable::stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct FlowTableStats {
+    /// Number of entries the table can hold.
+    pub capacity: usize,
+    /// Number of entries in the table.
+    pub occupancy: usize,
+    /// Lookups that found an entry.
+    pub hits: u64,
+    /// Lookups that found none.
+    pub misses: u64,
+    /// Entries inserted.
+    pub inserts: u64,
+    /// Entries evicted to make room for new ones, least recently used first.
+    pub evictions: u64,
+    /// Entries dropped because they were idle for longer than the TTL.
+    pub expirations: u64,
+}
+
+struct Entry<V> {
+    /// The flow as inserted.
+    flow: Flow,
+    /// When the entry was last looked up or inserted, see `FlowTable::now`. Never 0, so `Option<Entry>` needs no
+    /// extra space.
+    last_used: NonZeroU32,
+    value: V,
+}
+
+/// The entries a flow can be stored in. Their tags come first and fill half a cache line, so a lookup compares the tags
+/// in one line and then reads the one entry that matches, which is on the same page.
+#[repr(C, align(64))]
+struct Bucket<V> {
+    tags: [u32; WAYS],
+    entries: [Option<Entry<V>>; WAYS],
+}
+
+/// A fixed size table of per-flow state, meant to be owned by one core (e.g., captured by the closure of an operator).
+///
+/// A flow and its reverse are one key: looking up the reply direction of a connection finds the entry inserted for the
+/// original direction, and `get_directed` tells the two apart. All memory is allocated up front; once the table is
+/// full, inserting a new flow evicts the least recently used entry of the bucket it maps to. Entries idle for longer
+/// than the TTL, if any, are treated as gone: lookups skip them, inserts reuse them first, and `expire` reclaims them in
+/// the background.
+///
+/// Flows map to buckets of `WAYS` entries, each keeping a 32-bit hash tag per entry, so a lookup typically touches two
+/// cache lines on one page. Since a flow can only go into its bucket, LRU eviction is approximate: the entry evicted is
+/// the least recently used one of its bucket, not of the whole table.
+pub struct FlowTable<V> {
+    buckets: Vec<Bucket<V>>,
+    /// Buckets - 1, buckets are a power of 2.
+    mask: usize,
+    /// TTL in units of `TIME_SHIFT`, 0 if entries do not expire.
+    ttl: u32,
+    /// Next bucket `expire` and `retain` look at.
+    sweep: usize,
+    /// Operations so far, the clock of tables without TTL. It wraps around after 2^32 operations; see `now` for what
+    /// that means for eviction.
+    clock: u32,
+    stats: FlowTableStats,
+}
+
+/// Order-independent hash of a flow, i.e., equal for a flow and its reverse.
+#[inline]
+fn flow_hash(flow: &Flow) -> u64 {
+    let (a, b) = (
+        u64::from(flow.src_ip) << 16 | u64::from(flow.src_port),
+        u64::from(flow.dst_ip) << 16 | u64::from(flow.dst_port),
+    );
+    let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+    // Finalizer of MurmurHash3, so every bit of the key affects the bucket and the tag.
+    let mut h = lo ^ hi.rotate_left(29) ^ u64::from(flow.proto) << 56;
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ h >> 33
+}
+
+#[inline]
+fn tsc_stamp() -> NonZeroU32 {
+    NonZeroU32::new((rdtsc_unsafe() >> TIME_SHIFT) as u32 | 1).unwrap()
+}
+
+#[inline]
+fn age<V>(entry: &Entry<V>, now: NonZeroU32) -> u32 {
+    now.get().wrapping_sub(entry.last_used.get())
+}
+
+#[inline]
+fn same_flow(entry: &Flow, flow: &Flow) -> Option<bool> {
+    if entry == flow {
+        Some(false)
+    } else if *entry == flow.reverse_flow() {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+impl<V> FlowTable<V> {
+    /// Create a table for at least `capacity` flows (rounded up to a power of 2), whose entries expire after being idle
+    /// for `ttl`, if given.
+    pub fn new(capacity: usize, ttl: Option<Duration>) -> FlowTable<V> {
+        let buckets = round_to_power_of_2(capacity.saturating_sub(1) / WAYS + 1);
+        FlowTable {
+            buckets: (0..buckets)
+                .map(|_| Bucket {
+                    tags: [FREE; WAYS],
+                    entries: Default::default(),
+                })
+                .collect(),
+            mask: buckets - 1,
+            ttl: ttl.map_or(0, |ttl| {
+                (ns_to_cycles(ttl.as_nanos() as u64) >> TIME_SHIFT)
+                    .max(1)
+                    .min(i32::MAX as u64) as u32
+            }),
+            sweep: 0,
+            clock: 0,
+            stats: FlowTableStats {
+                capacity: buckets * WAYS,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Create the largest table that fits in `bytes` (but at least one bucket), see `new`.
+    pub fn with_memory(bytes: usize, ttl: Option<Duration>) -> FlowTable<V> {
+        let buckets = (bytes / mem::size_of::<Bucket<V>>()).max(1);
+        // Round down, so the budget holds.
+        let buckets = if buckets.is_power_of_two() {
+            buckets
+        } else {
+            round_to_power_of_2(buckets) / 2
+        };
+        FlowTable::new(buckets * WAYS, ttl)
+    }
+
+    /// Bytes of memory the table occupies.
+    pub fn memory(&self) -> usize {
+        self.buckets.len() * mem::size_of::<Bucket<V>>()
+    }
+
+    /// Number of entries in the table, including expired ones not reclaimed yet.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.stats.occupancy
+    }
+
+    /// Whether the table is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.stats.occupancy == 0
+    }
+
+    /// Number of entries the table can hold.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.stats.capacity
+    }
+
+    /// Counters of the table.
+    pub fn stats(&self) -> FlowTableStats {
+        self.stats
+    }
+
+    /// Timestamp for entries used now. Only tables with a TTL need the TSC, which is costly enough to read (it limits
+    /// how many cache misses of consecutive lookups overlap) that the others order entries by a count of operations.
+    /// Timestamps wrap around, ages are right as long as they fit in 31 bits. With a TTL, that is far longer than any
+    /// entry can stay unused before it expires. Without one, an entry left alone for more than 2^31 operations (some 20
+    /// seconds at 100M lookups per second) seems to have been used recently, and so may outlive younger entries
+    /// of its bucket. This only makes the approximate LRU eviction less accurate: lookups never miss an entry, and a
+    /// full bucket always evicts one.
+    #[inline]
+    fn now(&mut self) -> NonZeroU32 {
+        if self.ttl != 0 {
+            tsc_stamp()
+        } else {
+            self.clock = self.clock.wrapping_add(1);
+            NonZeroU32::new(self.clock | 1).unwrap()
+        }
+    }
+
+    #[inline]
+    fn expired(&self, entry: &Entry<V>, now: NonZeroU32) -> bool {
+        self.ttl != 0 && age(entry, now) > self.ttl
+    }
+
+    /// Find the index of the entry for `flow`, and whether `flow` is its reverse. Expired entries are dropped.
+    #[inline]
+    fn find(&mut self, flow: &Flow, hash: u64, now: NonZeroU32) -> Option<(usize, bool)> {
+        let bucket = hash as usize & self.mask;
+        let tag = (hash >> 32) as u32 | 1;
+        // Compare all tags at once, which compiles to a few vector instructions.
+        let mut matches = self.buckets[bucket]
+            .tags
+            .iter()
+            .enumerate()
+            .fold(0u32, |m, (way, &t)| m | u32::from(t == tag) << way);
+        while matches != 0 {
+            let idx = bucket * WAYS + matches.trailing_zeros() as usize;
+            matches &= matches - 1;
+            let found = match self.buckets[idx / WAYS].entries[idx % WAYS] {
+                Some(ref entry) => same_flow(&entry.flow, flow).map(|reverse| (reverse, self.expired(entry, now))),
+                None => None,
+            };
+            match found {
+                Some((reverse, false)) => return Some((idx, reverse)),
+                Some((_, true)) => {
+                    self.release(idx);
+                    self.stats.expirations += 1;
+                    return None;
+                }
+                None => {}
+            }
+        }
+        None
+    }
+
+    fn release(&mut self, idx: usize) -> Option<V> {
+        self.buckets[idx / WAYS].tags[idx % WAYS] = FREE;
+        let entry = self.buckets[idx / WAYS].entries[idx % WAYS].take()?;
+        self.stats.occupancy -= 1;
+        Some(entry.value)
+    }
+
+    /// The state of `flow` (or its reverse), refreshing its TTL and LRU position.
+    #[inline]
+    pub fn get(&mut self, flow: &Flow) -> Option<&mut V> {
+        self.get_directed(flow).map(|(value, _)| value)
+    }
+
+    /// The state of `flow` (or its reverse), and whether `flow` is the reverse of the flow the entry was inserted for.
+    /// Refreshes the TTL and LRU position of the entry.
+    #[inline]
+    pub fn get_directed(&mut self, flow: &Flow) -> Option<(&mut V, bool)> {
+        let now = self.now();
+        match self.find(flow, flow_hash(flow), now) {
+            Some((idx, reverse)) => {
+                self.stats.hits += 1;
+                let entry = self.buckets[idx / WAYS].entries[idx % WAYS].as_mut().unwrap();
+                entry.last_used = now;
+                Some((&mut entry.value, reverse))
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// The state of `flow` (or its reverse), and whether `flow` is the reverse of the flow the entry was inserted for.
+    /// Unlike `get_directed`, this neither refreshes the entry nor counts as a lookup.
+    pub fn peek(&self, flow: &Flow) -> Option<(&V, bool)> {
+        let bucket = flow_hash(flow) as usize & self.mask;
+        let now = tsc_stamp();
+        self.buckets[bucket]
+            .entries
+            .iter()
+            .flatten()
+            .filter(|e| !self.expired(e, now))
+            .find_map(|e| same_flow(&e.flow, flow).map(|reverse| (&e.value, reverse)))
+    }
+
+    /// Whether the table has an unexpired entry for `flow` (or its reverse). Unlike `get`, this neither refreshes the
+    /// entry nor counts as a lookup.
+    pub fn contains(&self, flow: &Flow) -> bool {
+        self.peek(flow).is_some()
+    }
+
+    /// Insert state for `flow`, returning the state it replaces if the flow (or its reverse) was in the table already.
+    /// A replaced entry keeps the direction it was inserted with.
+    pub fn insert(&mut self, flow: Flow, value: V) -> Option<V> {
+        let hash = flow_hash(&flow);
+        let now = self.now();
+        if let Some((idx, _)) = self.find(&flow, hash, now) {
+            let entry = self.buckets[idx / WAYS].entries[idx % WAYS].as_mut().unwrap();
+            entry.last_used = now;
+            return Some(mem::replace(&mut entry.value, value));
+        }
+        self.insert_new(flow, hash, now, value);
+        None
+    }
+
+    /// The state of `flow` (or its reverse), inserting `default()` for `flow` if there is none, and whether `flow` is
+    /// the reverse of the flow the entry was inserted for. Refreshes the TTL and LRU position of the entry.
+    #[inline]
+    pub fn get_or_insert_with<F: FnOnce() -> V>(&mut self, flow: &Flow, default: F) -> (&mut V, bool) {
+        let hash = flow_hash(flow);
+        let now = self.now();
+        let (idx, reverse) = match self.find(flow, hash, now) {
+            Some(found) => {
+                self.stats.hits += 1;
+                found
+            }
+            None => {
+                self.stats.misses += 1;
+                (self.insert_new(*flow, hash, now, default()), false)
+            }
+        };
+        let entry = self.buckets[idx / WAYS].entries[idx % WAYS].as_mut().unwrap();
+        entry.last_used = now;
+        (&mut entry.value, reverse)
+    }
+
+    /// Put a flow known not to be in the table into its bucket, evicting an expired entry or else the least recently
+    /// used one if the bucket is full. Returns the index of the new entry.
+    fn insert_new(&mut self, flow: Flow, hash: u64, now: NonZeroU32, value: V) -> usize {
+        let bucket = hash as usize & self.mask;
+        let way = match self.buckets[bucket].tags.iter().position(|&t| t == FREE) {
+            Some(way) => way,
+            None => {
+                let entries = &self.buckets[bucket].entries;
+                let (way, lru) = entries
+                    .iter()
+                    .enumerate()
+                    .map(|(way, e)| (way, e.as_ref().unwrap()))
+                    .max_by_key(|(_, e)| age(e, now))
+                    .unwrap();
+                if self.expired(lru, now) {
+                    self.stats.expirations += 1;
+                } else {
+                    self.stats.evictions += 1;
+                }
+                self.release(bucket * WAYS + way);
+                way
+            }
+        };
+        let idx = bucket * WAYS + way;
+        self.buckets[bucket].tags[way] = (hash >> 32) as u32 | 1;
+        self.buckets[idx / WAYS].entries[idx % WAYS] = Some(Entry {
+            flow,
+            last_used: now,
+            value,
+        });
+        self.stats.occupancy += 1;
+        self.stats.inserts += 1;
+        idx
+    }
+
+    /// Remove the state of `flow` (or its reverse), returning it unless it expired.
+    pub fn remove(&mut self, flow: &Flow) -> Option<V> {
+        let now = self.now();
+        let (idx, _) = self.find(flow, flow_hash(flow), now)?;
+        self.release(idx)
+    }
+
+    /// Reclaim the expired entries of up to `buckets` buckets, passing each to `expired`, and return how many there
+    /// were. Each call continues where the previous one stopped, so calling this regularly (e.g., from a
+    /// `PipelineHooks::on_tick` hook) with a small budget sweeps the whole table without stalling the pipeline. Tables
+    /// without a TTL have nothing to expire.
+    pub fn expire<F: FnMut(Flow, V)>(&mut self, buckets: usize, mut expired: F) -> usize {
+        if self.ttl == 0 {
+            return 0;
+        }
+        let now = self.now();
+        let mut count = 0;
+        for _ in 0..buckets.min(self.buckets.len()) {
+            let bucket = self.sweep;
+            self.sweep = (self.sweep + 1) & self.mask;
+            for idx in bucket * WAYS..(bucket + 1) * WAYS {
+                let expired_entry = match self.buckets[idx / WAYS].entries[idx % WAYS] {
+                    Some(ref entry) => self.expired(entry, now),
+                    None => false,
+                };
+                if expired_entry {
+                    let flow = self.buckets[idx / WAYS].entries[idx % WAYS].as_ref().unwrap().flow;
+                    let value = self.release(idx).unwrap();
+                    self.stats.expirations += 1;
+                    count += 1;
+                    expired(flow, value);
+                }
+            }
+        }
+        count
+    }
+
+    /// Remove the entries of up to `buckets` buckets that `keep` rejects, and return how many there were. This shares
+    /// its position with `expire`, and like it is meant to be called regularly with a small budget, e.g., to drop state
+    /// that timed out by rules of its own.
+    pub fn retain<F: FnMut(&Flow, &mut V) -> bool>(&mut self, buckets: usize, mut keep: F) -> usize {
+        let mut count = 0;
+        for _ in 0..buckets.min(self.buckets.len()) {
+            let bucket = self.sweep;
+            self.sweep = (self.sweep + 1) & self.mask;
+            for way in 0..WAYS {
+                let kept = match self.buckets[bucket].entries[way] {
+                    Some(ref mut entry) => keep(&entry.flow, &mut entry.value),
+                    None => true,
+                };
+                if !kept {
+                    self.release(bucket * WAYS + way);
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// Iterate over the flows (as inserted) and their state, including expired entries not reclaimed yet.
+    pub fn iter(&self) -> impl Iterator<Item = (&Flow, &V)> {
+        self.buckets
+            .iter()
+            .flat_map(|b| b.entries.iter().flatten())
+            .map(|e| (&e.flow, &e.value))
+    }
+
+    /// Remove all entries, keeping the counters.
+    pub fn clear(&mut self) {
+        for idx in 0..self.buckets.len() * WAYS {
+            self.release(idx);
+        }
+    }
+}
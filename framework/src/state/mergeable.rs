@@ -1,4 +1,4 @@
-use crate::utils::Flow;
+use crate::utils::{Flow, FlowKey};
 use fnv::FnvHasher;
 use std::cmp::max;
 use std::collections::hash_map::Iter;
@@ -18,21 +18,21 @@ const CHAN_SIZE: usize = 128;
 /// [`AddAssign`](https://doc.rust-lang.org/std/ops/trait.AddAssign.html) trait and overriding the `add_assign` method
 /// there. We assume that the quantity stored here does not need to be accessed by the control plane and can only be
 /// accessed from the data plane. The `cache_size` should be tuned depending on whether gets or puts are the most common
-/// operation in this table.
+/// operation in this table. Entries are keyed by `Flow` unless another `FlowKey` (e.g., `Ipv6Flow`) is given.
 ///
 /// #[FIXME]
 /// Garbage collection.
 /// The current version does not work well with large flow tables. The problem is we need to record a set of differences
 /// rather than copying the entire hashmap. This of course comes with some consistency issues, so we need to fix this.
 #[derive(Default, Debug)]
-pub struct MergeableStoreCP<T: AddAssign<T> + Default + Clone> {
-    flow_counters: HashMap<Flow, T, FnvHash>,
-    hashmaps: Vec<Arc<RwLock<HashMap<Flow, T, FnvHash>>>>,
+pub struct MergeableStoreCP<T: AddAssign<T> + Default + Clone, K: FlowKey = Flow> {
+    flow_counters: HashMap<K, T, FnvHash>,
+    hashmaps: Vec<Arc<RwLock<HashMap<K, T, FnvHash>>>>,
 }
 
-impl<T: AddAssign<T> + Default + Clone> MergeableStoreCP<T> {
+impl<T: AddAssign<T> + Default + Clone, K: FlowKey> MergeableStoreCP<T, K> {
     /// Initialize the MergeableStoreCP.
-    pub fn new() -> MergeableStoreCP<T> {
+    pub fn new() -> MergeableStoreCP<T, K> {
         MergeableStoreCP {
             flow_counters: HashMap::with_capacity_and_hasher(VEC_SIZE << 6, Default::default()),
             hashmaps: Vec::with_capacity(CHAN_SIZE),
@@ -40,7 +40,7 @@ impl<T: AddAssign<T> + Default + Clone> MergeableStoreCP<T> {
     }
 
     /// Transform the MergeableStoreCP to MergeableStoreDP with given capacity.
-    pub fn dp_store_with_cache_and_size(&mut self, cache: usize, size: usize) -> MergeableStoreDP<T> {
+    pub fn dp_store_with_cache_and_size(&mut self, cache: usize, size: usize) -> MergeableStoreDP<T, K> {
         let hmap = Arc::new(RwLock::new(HashMap::with_capacity_and_hasher(size, Default::default())));
         self.hashmaps.push(hmap.clone());
         MergeableStoreDP {
@@ -53,12 +53,12 @@ impl<T: AddAssign<T> + Default + Clone> MergeableStoreCP<T> {
     }
 
     /// Transform the MergeableStoreCP to MergeableStoreDP.
-    pub fn dp_store(&mut self) -> MergeableStoreDP<T> {
+    pub fn dp_store(&mut self) -> MergeableStoreDP<T, K> {
         MergeableStoreCP::dp_store_with_cache_and_size(self, CACHE_SIZE, VEC_SIZE)
     }
 
     /// Consume the HashMap and transform to a Vec.
-    fn hmap_to_vec(hash: &RwLockReadGuard<'_, HashMap<Flow, T, FnvHash>>) -> Vec<(Flow, T)> {
+    fn hmap_to_vec(hash: &RwLockReadGuard<'_, HashMap<K, T, FnvHash>>) -> Vec<(K, T)> {
         let mut t = Vec::with_capacity(hash.len());
         t.extend(hash.iter().map(|(f, v)| (*f, v.clone())));
         t
@@ -83,7 +83,7 @@ impl<T: AddAssign<T> + Default + Clone> MergeableStoreCP<T> {
     }
 
     /// Get the MergeableStoreCP.
-    pub fn get(&self, flow: &Flow) -> T {
+    pub fn get(&self, flow: &K) -> T {
         match self.flow_counters.get(flow) {
             Some(i) => i.clone(),
             None => Default::default(),
@@ -91,7 +91,7 @@ impl<T: AddAssign<T> + Default + Clone> MergeableStoreCP<T> {
     }
 
     /// Iterator of MergeableStoreCP.
-    pub fn iter(&self) -> Iter<'_, Flow, T> {
+    pub fn iter(&self) -> Iter<'_, K, T> {
         self.flow_counters.iter()
     }
 
@@ -109,15 +109,15 @@ impl<T: AddAssign<T> + Default + Clone> MergeableStoreCP<T> {
 /// A generic store for associating some merge-able type with each flow but with data path counts.
 /// Contains the counts on the data path.
 #[derive(Clone, Debug)]
-pub struct MergeableStoreDP<T: AddAssign<T> + Default + Clone> {
-    flow_counters: Arc<RwLock<HashMap<Flow, T, FnvHash>>>,
-    cache: Vec<(Flow, T)>,
+pub struct MergeableStoreDP<T: AddAssign<T> + Default + Clone, K: FlowKey = Flow> {
+    flow_counters: Arc<RwLock<HashMap<K, T, FnvHash>>>,
+    cache: Vec<(K, T)>,
     base_cache_size: usize,
     cache_size: usize,
     len: usize,
 }
 
-impl<T: AddAssign<T> + Default + Clone> MergeableStoreDP<T> {
+impl<T: AddAssign<T> + Default + Clone, K: FlowKey> MergeableStoreDP<T, K> {
     fn merge_cache(&mut self) {
         match self.flow_counters.try_write() {
             Ok(mut g) => {
@@ -131,7 +131,7 @@ impl<T: AddAssign<T> + Default + Clone> MergeableStoreDP<T> {
 
     /// Change the value for the given `Flow`.
     #[inline]
-    pub fn update(&mut self, flow: Flow, inc: T) {
+    pub fn update(&mut self, flow: K, inc: T) {
         {
             self.cache.push((flow, inc));
         }
@@ -142,7 +142,7 @@ impl<T: AddAssign<T> + Default + Clone> MergeableStoreDP<T> {
 
     /// Remove an entry from the table.
     #[inline]
-    pub fn remove(&mut self, flow: &Flow) -> T {
+    pub fn remove(&mut self, flow: &K) -> T {
         // self.merge_cache();
         match self.flow_counters.write() {
             Ok(mut g) => {
@@ -1,12 +1,18 @@
 //! test
 
+pub use self::conntrack::*;
 pub use self::cp_mergeable::*;
 pub use self::dp_mergeable::*;
+pub use self::flow_table::*;
 pub use self::mergeable::*;
+pub use self::reassembly::*;
 pub use self::reordered_buffer::*;
 pub use self::ring_buffer::*;
+mod conntrack;
 mod cp_mergeable;
 mod dp_mergeable;
+mod flow_table;
 mod mergeable;
+mod reassembly;
 pub mod reordered_buffer;
 mod ring_buffer;
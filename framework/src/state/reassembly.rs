@@ -0,0 +1,292 @@
+//! IPv4 fragment reassembly.
+//!
+//! An `Ipv4Reassembler` buffers the fragments of IPv4 datagrams, keyed by source, destination, identification and
+//! protocol (RFC 791), and hands out each datagram once all its fragments arrived. Datagrams not completed within a
+//! timeout are dropped, and so are the fragments that would take the reassembler over its memory limits. Fragments
+//! overlapping ones already received are treated as an attack (RFC 5722 says so for IPv6) and discard their datagram,
+//! fragments that only repeat received data are ignored. `Batch::reassemble_ipv4` runs a reassembler over a batch.
+use crate::headers::{IpHeader, MORE_FRAGMENTS};
+use crate::utils::{checksum, ns_to_cycles, TimerId, TimerWheel};
+use byteorder::{BigEndian, ByteOrder};
+use fnv::FnvHasher;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+use std::time::Duration;
+
+type FnvHash = BuildHasherDefault<FnvHasher>;
+
+/// Largest datagram payload, what is left of the 16-bit total length after a header without options.
+const MAX_PAYLOAD: usize = 65535 - 20;
+/// Resolution of the reassembly timeouts.
+const TIMEOUT_TICK: Duration = Duration::from_millis(10);
+
+/// Configuration of an `Ipv4Reassembler`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReassemblyConfig {
+    /// Time from the first fragment of a datagram after which it is dropped if incomplete.
+    pub timeout: Duration,
+    /// Number of datagrams reassembled at once at most.
+    pub max_datagrams: usize,
+    /// Bytes buffered at most, over all datagrams.
+    pub max_memory: usize,
+}
+
+impl Default for ReassemblyConfig {
+    /// The Linux defaults: 30 seconds and 4 MB.
+    fn default() -> ReassemblyConfig {
+        ReassemblyConfig {
+            timeout: Duration::from_secs(30),
+            max_datagrams: 1024,
+            max_memory: 4 << 20,
+        }
+    }
+}
+
+/// Counters of an `Ipv4Reassembler`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ReassemblyStats {
+    /// Fragments received.
+    pub fragments: u64,
+    /// Datagrams reassembled.
+    pub reassembled: u64,
+    /// Datagrams dropped because they were not completed in time.
+    pub timeouts: u64,
+    /// Fragments overlapping ones already received, duplicates included.
+    pub overlaps: u64,
+    /// Fragments dropped for being malformed or inconsistent with the other fragments of their datagram.
+    pub invalid: u64,
+    /// Fragments dropped because of the limits on datagrams and memory.
+    pub dropped: u64,
+    /// Reassembled datagrams dropped because mbufs to hold them could not be allocated.
+    pub alloc_failed: u64,
+    /// Datagrams being reassembled.
+    pub pending: usize,
+    /// Bytes buffered.
+    pub memory: usize,
+}
+
+/// What `Ipv4Reassembler::insert` did with a packet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reassembly {
+    /// The packet is not a fragment.
+    Unfragmented,
+    /// The fragment was buffered until the rest of its datagram arrives.
+    Buffered,
+    /// The fragment was dropped, see the counters for why.
+    Dropped,
+    /// The fragment completed its datagram, which is returned whole: the header of the first fragment, with the
+    /// length, flags and checksum fixed up, followed by the payload.
+    Complete(Vec<u8>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct DatagramKey {
+    src: u32,
+    dst: u32,
+    id: u16,
+    protocol: u8,
+}
+
+struct Datagram {
+    /// Header of the first fragment, empty until it arrives.
+    header: Vec<u8>,
+    data: Vec<u8>,
+    /// Byte ranges of `data` received so far, sorted, with adjacent ranges merged.
+    received: Vec<(usize, usize)>,
+    /// Payload length, known once the last fragment arrived.
+    total: Option<usize>,
+    timer: TimerId,
+}
+
+impl Datagram {
+    fn memory(&self) -> usize {
+        self.header.len() + self.data.len()
+    }
+
+    fn complete(&self) -> bool {
+        self.total.is_some() && !self.header.is_empty() && self.received == [(0, self.total.unwrap())]
+    }
+
+    /// Record the range `start..end`, returning false if it overlaps a range already received.
+    fn receive(&mut self, start: usize, end: usize) -> bool {
+        if self.received.iter().any(|&(s, e)| start < e && s < end) {
+            return false;
+        }
+        let at = self
+            .received
+            .iter()
+            .position(|&(s, _)| s > start)
+            .unwrap_or(self.received.len());
+        self.received.insert(at, (start, end));
+        // Merge with the neighbors.
+        if at + 1 < self.received.len() && self.received[at + 1].0 == end {
+            self.received[at].1 = self.received.remove(at + 1).1;
+        }
+        if at > 0 && self.received[at - 1].1 == start {
+            self.received[at - 1].1 = self.received.remove(at).1;
+        }
+        true
+    }
+}
+
+/// Reassembles the IPv4 datagrams of one core, see the module documentation.
+pub struct Ipv4Reassembler {
+    config: ReassemblyConfig,
+    datagrams: HashMap<DatagramKey, Datagram, FnvHash>,
+    timers: TimerWheel<DatagramKey>,
+    timeout: u64,
+    stats: ReassemblyStats,
+}
+
+impl Ipv4Reassembler {
+    /// Create a reassembler.
+    pub fn new(config: ReassemblyConfig) -> Ipv4Reassembler {
+        Ipv4Reassembler {
+            config,
+            datagrams: HashMap::with_capacity_and_hasher(config.max_datagrams, Default::default()),
+            // Starting at TSC 0 lets callers pass any clock, the wheel skips ahead to the first deadline.
+            timers: TimerWheel::with_tick_cycles(ns_to_cycles(TIMEOUT_TICK.as_nanos() as u64), 0),
+            timeout: ns_to_cycles(config.timeout.as_nanos() as u64),
+            stats: ReassemblyStats::default(),
+        }
+    }
+
+    /// Counters of the reassembler.
+    pub fn stats(&self) -> ReassemblyStats {
+        ReassemblyStats {
+            pending: self.datagrams.len(),
+            ..self.stats
+        }
+    }
+
+    /// Count a reassembled datagram dropped because mbufs to hold it could not be allocated.
+    pub(crate) fn record_alloc_failed(&mut self) {
+        self.stats.alloc_failed += 1;
+    }
+
+    /// Take an IPv4 packet, given its header and its payload (which may carry padding past the IP length), at TSC
+    /// value `now`.
+    pub fn insert(&mut self, ip: &IpHeader, payload: &[u8], now: u64) -> Reassembly {
+        let more = ip.flags() & MORE_FRAGMENTS != 0;
+        if !more && ip.fragment_offset() == 0 {
+            return Reassembly::Unfragmented;
+        }
+        self.stats.fragments += 1;
+        let key = DatagramKey {
+            src: ip.src(),
+            dst: ip.dst(),
+            id: ip.id(),
+            protocol: ip.protocol(),
+        };
+        let header = ip.header_bytes();
+        let len = (ip.length() as usize).saturating_sub(header.len());
+        let start = ip.fragment_offset() as usize * 8;
+        let end = start + len;
+        // All fragments but the last carry a multiple of 8 bytes.
+        if len > payload.len() || len == 0 || (more && len & 7 != 0) || end > MAX_PAYLOAD {
+            self.stats.invalid += 1;
+            return Reassembly::Dropped;
+        }
+
+        if !self.datagrams.contains_key(&key) {
+            if self.datagrams.len() >= self.config.max_datagrams {
+                self.stats.dropped += 1;
+                return Reassembly::Dropped;
+            }
+            let timer = self.timers.arm_at(now + self.timeout, key);
+            let datagram = Datagram {
+                header: vec![],
+                data: vec![],
+                received: vec![],
+                total: None,
+                timer,
+            };
+            self.datagrams.insert(key, datagram);
+        }
+        let datagram = self.datagrams.get_mut(&key).unwrap();
+
+        let total = if more { datagram.total } else { Some(end) };
+        let consistent = match total {
+            Some(total) => {
+                (more || datagram.total.unwrap_or(total) == total)
+                    && end <= total
+                    && datagram.received.last().map_or(0, |&(_, e)| e) <= total
+            }
+            None => true,
+        };
+        if !consistent {
+            self.stats.invalid += 1;
+            self.remove(&key);
+            return Reassembly::Dropped;
+        }
+
+        let growth = end.saturating_sub(datagram.data.len()) + if start == 0 { header.len() } else { 0 };
+        if self.stats.memory + growth > self.config.max_memory {
+            self.stats.dropped += 1;
+            if datagram.received.is_empty() {
+                self.remove(&key);
+            }
+            return Reassembly::Dropped;
+        }
+        if !datagram.receive(start, end) {
+            self.stats.overlaps += 1;
+            // Keep what was received first of duplicates, anything else is suspect.
+            if !datagram.received.iter().any(|&(s, e)| s <= start && end <= e) {
+                self.remove(&key);
+            }
+            return Reassembly::Dropped;
+        }
+        if datagram.data.len() < end {
+            datagram.data.resize(end, 0);
+        }
+        datagram.data[start..end].copy_from_slice(&payload[..len]);
+        if start == 0 {
+            datagram.header.extend_from_slice(header);
+        }
+        datagram.total = total;
+        self.stats.memory += growth;
+
+        if !datagram.complete() {
+            return Reassembly::Buffered;
+        }
+        let datagram = self.remove(&key);
+        let header_len = datagram.header.len();
+        if header_len + datagram.data.len() > 65535 {
+            self.stats.invalid += 1;
+            return Reassembly::Dropped;
+        }
+        self.stats.reassembled += 1;
+        let mut bytes = datagram.header;
+        bytes.extend_from_slice(&datagram.data);
+        let total = bytes.len() as u16;
+        BigEndian::write_u16(&mut bytes[2..4], total);
+        // Keep DF, clear MF and the offset.
+        bytes[6] &= 0x40;
+        bytes[7] = 0;
+        bytes[10] = 0;
+        bytes[11] = 0;
+        let csum = checksum(&bytes[..header_len]);
+        BigEndian::write_u16(&mut bytes[10..12], csum);
+        Reassembly::Complete(bytes)
+    }
+
+    /// Drop the datagrams whose timeout expired by TSC value `now`, returning how many there were.
+    pub fn expire(&mut self, now: u64) -> usize {
+        let expired = self.timers.advance(now);
+        for key in &expired {
+            if let Some(datagram) = self.datagrams.remove(key) {
+                self.stats.memory -= datagram.memory();
+            }
+        }
+        self.stats.timeouts += expired.len() as u64;
+        expired.len()
+    }
+
+    fn remove(&mut self, key: &DatagramKey) -> Datagram {
+        let datagram = self.datagrams.remove(key).unwrap();
+        self.timers.cancel(datagram.timer);
+        self.stats.memory -= datagram.memory();
+        datagram
+    }
+}
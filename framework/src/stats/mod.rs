@@ -0,0 +1,8 @@
+//! Machine-readable statistics.
+//!
+//! A `StatsReport` collects latency summaries and port counters of one run and writes them as JSON or CSV, so that
+//! analysis scripts do not have to scrape the console output.
+pub use self::report::*;
+pub use self::summary::*;
+mod report;
+mod summary;
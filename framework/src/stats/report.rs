@@ -0,0 +1,150 @@
+use super::summary::Summary;
+use crate::common::*;
+use crate::interface::{PcapPort, PmdPort, VirtualPort};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::path::Path;
+
+/// Packet counters of a port, or of one queue of a port.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PortStats {
+    /// Port name.
+    pub port: String,
+    /// Queue, if the counters are per queue.
+    pub queue: Option<i32>,
+    /// Packets received.
+    pub rx: u64,
+    /// Packets sent.
+    pub tx: u64,
+}
+
+impl PortStats {
+    /// Counters of every queue of a PMD port, see `PmdPort::stats`.
+    pub fn pmd(name: &str, port: &PmdPort) -> Vec<PortStats> {
+        (0..port.rxqs())
+            .map(|queue| {
+                let (rx, tx) = port.stats(queue);
+                PortStats {
+                    port: name.to_string(),
+                    queue: Some(queue),
+                    rx: rx as u64,
+                    tx: tx as u64,
+                }
+            })
+            .collect()
+    }
+
+    /// Counters of a virtual port, see `VirtualPort::stats`.
+    pub fn virtual_port(name: &str, port: &VirtualPort) -> PortStats {
+        let (rx, tx) = port.stats();
+        PortStats {
+            port: name.to_string(),
+            queue: None,
+            rx: rx as u64,
+            tx: tx as u64,
+        }
+    }
+
+    /// Counters of a capture file port, see `PcapPort::stats`.
+    pub fn pcap(name: &str, port: &PcapPort) -> PortStats {
+        let (rx, tx) = port.stats();
+        PortStats {
+            port: name.to_string(),
+            queue: None,
+            rx: rx as u64,
+            tx: tx as u64,
+        }
+    }
+}
+
+/// Statistics of one run: named summaries (e.g., of latencies) and port counters.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct StatsReport {
+    /// Name of the run.
+    pub name: String,
+    /// Summaries by name.
+    pub summaries: BTreeMap<String, Summary>,
+    /// Port counters.
+    pub ports: Vec<PortStats>,
+}
+
+impl StatsReport {
+    /// Create an empty report.
+    pub fn new(name: &str) -> StatsReport {
+        StatsReport {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Add (or replace) a summary.
+    pub fn add_summary(&mut self, name: &str, summary: Summary) -> &mut StatsReport {
+        self.summaries.insert(name.to_string(), summary);
+        self
+    }
+
+    /// Add port counters.
+    pub fn add_ports<I: IntoIterator<Item = PortStats>>(&mut self, ports: I) -> &mut StatsReport {
+        self.ports.extend(ports);
+        self
+    }
+
+    /// The report as pretty printed JSON.
+    pub fn to_json(&self) -> String {
+        // Only strings, numbers and sequences are serialized here, which cannot fail.
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// The report as CSV with the columns `section,name,key,value`. Summaries produce `summary` rows (with keys
+    /// `count`, `min`, `max`, `mean`, `stddev` and `p<percentile>`) and `histogram` rows (with keys `<low>:<high>`);
+    /// ports produce `port` rows with keys `rx` and `tx`, named `<port>` or `<port>/<queue>`.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("section,name,key,value\n");
+        let mut row = |section: &str, name: &str, key: &str, value: &dyn std::fmt::Display| {
+            let _ = writeln!(csv, "{},{},{},{}", section, csv_field(name), csv_field(key), value);
+        };
+        for (name, summary) in &self.summaries {
+            row("summary", name, "count", &summary.count);
+            row("summary", name, "min", &summary.min);
+            row("summary", name, "max", &summary.max);
+            row("summary", name, "mean", &summary.mean);
+            row("summary", name, "stddev", &summary.stddev);
+            for p in &summary.percentiles {
+                row("summary", name, &format!("p{}", p.percentile), &p.value);
+            }
+            for b in &summary.histogram {
+                row("histogram", name, &format!("{}:{}", b.low, b.high), &b.count);
+            }
+        }
+        for port in &self.ports {
+            let name = match port.queue {
+                Some(queue) => format!("{}/{}", port.port, queue),
+                None => port.port.clone(),
+            };
+            row("port", &name, "rx", &port.rx);
+            row("port", &name, "tx", &port.tx);
+        }
+        csv
+    }
+
+    /// Write the report to `path`, as CSV if it ends in `.csv` and as JSON otherwise.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let contents = match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => self.to_csv(),
+            _ => self.to_json(),
+        };
+        fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,161 @@
+use crate::utils::LatencySnapshot;
+use serde::Serialize;
+
+/// Percentiles reported when none are asked for.
+pub const DEFAULT_PERCENTILES: [f64; 9] = [1.0, 5.0, 25.0, 50.0, 75.0, 90.0, 95.0, 99.0, 99.9];
+
+/// Value of one percentile.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Percentile {
+    /// Percentile, between 0 and 100.
+    pub percentile: f64,
+    /// Value at or below which `percentile` percent of the samples lie.
+    pub value: f64,
+}
+
+/// One histogram bucket, counting the samples between `low` and `high` (both inclusive).
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct HistogramBucket {
+    /// Smallest value of the bucket.
+    pub low: f64,
+    /// Largest value of the bucket.
+    pub high: f64,
+    /// Number of samples in the bucket.
+    pub count: u64,
+}
+
+/// Summary of a set of samples, e.g., packet latencies.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Summary {
+    /// Number of samples.
+    pub count: u64,
+    /// Smallest sample.
+    pub min: f64,
+    /// Largest sample.
+    pub max: f64,
+    /// Mean.
+    pub mean: f64,
+    /// Sample standard deviation.
+    pub stddev: f64,
+    /// Requested percentiles.
+    pub percentiles: Vec<Percentile>,
+    /// Histogram of the samples; empty buckets are left out.
+    pub histogram: Vec<HistogramBucket>,
+}
+
+impl Summary {
+    /// Summarize `samples` exactly. The histogram has `buckets` buckets of equal width between the smallest and the
+    /// largest sample. NaN samples are left out.
+    pub fn from_samples(samples: &[f64], percentiles: &[f64], buckets: usize) -> Summary {
+        let mut sorted: Vec<f64> = samples.iter().copied().filter(|s| !s.is_nan()).collect();
+        if sorted.is_empty() {
+            return Summary::default();
+        }
+        sorted.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+        let count = sorted.len();
+        let (min, max) = (sorted[0], sorted[count - 1]);
+        let mean = sorted.iter().sum::<f64>() / count as f64;
+        let stddev = if count > 1 {
+            (sorted.iter().map(|s| (s - mean) * (s - mean)).sum::<f64>() / (count - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+        let percentiles = percentiles
+            .iter()
+            .map(|&percentile| Percentile {
+                percentile,
+                value: sorted[rank(percentile, count as u64) as usize - 1],
+            })
+            .collect();
+
+        let buckets = buckets.max(1);
+        let width = (max - min) / buckets as f64;
+        let mut counts = vec![0u64; buckets];
+        for s in &sorted {
+            let idx = if width > 0.0 { ((s - min) / width) as usize } else { 0 };
+            counts[idx.min(buckets - 1)] += 1;
+        }
+        let histogram = counts
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, n)| n > 0)
+            .map(|(idx, n)| HistogramBucket {
+                low: min + width * idx as f64,
+                high: if idx == buckets - 1 {
+                    max
+                } else {
+                    min + width * (idx + 1) as f64
+                },
+                count: n,
+            })
+            .collect();
+
+        Summary {
+            count: count as u64,
+            min,
+            max,
+            mean,
+            stddev,
+            percentiles,
+            histogram,
+        }
+    }
+
+    /// Summarize a latency histogram, scaling every value (in cycles) by `scale`, e.g., `1e9 / tsc_hz()` to get
+    /// nanoseconds. Percentiles and the standard deviation are accurate to the histogram's precision.
+    pub fn from_latency(snapshot: &LatencySnapshot, percentiles: &[f64], scale: f64) -> Summary {
+        if snapshot.count() == 0 {
+            return Summary::default();
+        }
+        let mean = snapshot.mean() * scale;
+        let histogram: Vec<_> = snapshot
+            .buckets()
+            .map(|(low, high, count)| HistogramBucket {
+                low: low as f64 * scale,
+                high: high as f64 * scale,
+                count,
+            })
+            .collect();
+        let count = snapshot.count();
+        let stddev = if count > 1 {
+            let squares: f64 = histogram
+                .iter()
+                .map(|b| {
+                    let mid = (b.low + b.high) / 2.0;
+                    (mid - mean) * (mid - mean) * b.count as f64
+                })
+                .sum();
+            (squares / (count - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+        Summary {
+            count,
+            min: snapshot.min() as f64 * scale,
+            max: snapshot.max() as f64 * scale,
+            mean,
+            stddev,
+            percentiles: percentiles
+                .iter()
+                .map(|&percentile| Percentile {
+                    percentile,
+                    value: snapshot.percentile(percentile) as f64 * scale,
+                })
+                .collect(),
+            histogram,
+        }
+    }
+
+    /// The value of `percentile`, if it was computed.
+    pub fn percentile(&self, percentile: f64) -> Option<f64> {
+        self.percentiles
+            .iter()
+            .find(|p| (p.percentile - percentile).abs() < f64::EPSILON)
+            .map(|p| p.value)
+    }
+}
+
+/// Nearest rank (1 based) of `percentile` among `count` samples.
+fn rank(percentile: f64, count: u64) -> u64 {
+    ((percentile.max(0.0).min(100.0) / 100.0 * count as f64).ceil() as u64).max(1).min(count)
+}
@@ -0,0 +1,94 @@
+use byteorder::{BigEndian, ByteOrder};
+
+/// How checksums are filled in on packets about to be sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChecksumMode {
+    /// Compute all checksums in software.
+    Software,
+    /// Leave the IPv4 header and TCP/UDP checksums to the NIC, as configured by `PortConfiguration.csum`.
+    Offload,
+}
+
+impl ChecksumMode {
+    /// Mode matching a port's checksum offload setting.
+    #[inline]
+    pub fn from_offload(offload: bool) -> ChecksumMode {
+        if offload {
+            ChecksumMode::Offload
+        } else {
+            ChecksumMode::Software
+        }
+    }
+}
+
+/// Add `bytes`, taken as big endian 16-bit words (an odd last byte is padded with zero), to the one's complement sum
+/// `sum`. The result is folded to 16 bits but returned as a `u32` so that partial sums can be added together.
+#[inline]
+pub fn ones_complement_add(sum: u32, bytes: &[u8]) -> u32 {
+    let mut words = bytes.chunks_exact(2);
+    let mut total = sum as u64;
+    for word in &mut words {
+        total += BigEndian::read_u16(word) as u64;
+    }
+    if let [last] = words.remainder() {
+        total += (*last as u64) << 8;
+    }
+    while total > 0xffff {
+        total = (total & 0xffff) + (total >> 16);
+    }
+    total as u32
+}
+
+/// Fold a one's complement sum to 16 bits.
+#[inline]
+pub fn ones_complement_fold(mut sum: u32) -> u16 {
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum as u16
+}
+
+/// Internet checksum (RFC 1071) of `bytes`. Computing it over data that includes a correct checksum yields 0.
+#[inline]
+pub fn checksum(bytes: &[u8]) -> u16 {
+    !ones_complement_fold(ones_complement_add(0, bytes))
+}
+
+/// Update checksum `csum` after a 16-bit word of the data it covers changed from `old` to `new` (RFC 1624, eqn. 3).
+#[inline]
+pub fn checksum_adjust(csum: u16, old: u16, new: u16) -> u16 {
+    !ones_complement_fold((!csum) as u32 + (!old) as u32 + new as u32)
+}
+
+/// Update checksum `csum` after a 32-bit word, e.g., an IPv4 address, changed from `old` to `new`.
+#[inline]
+pub fn checksum_adjust_u32(csum: u16, old: u32, new: u32) -> u16 {
+    let csum = checksum_adjust(csum, (old >> 16) as u16, (new >> 16) as u16);
+    checksum_adjust(csum, old as u16, new as u16)
+}
+
+/// Update checksum `csum` after a 128-bit value, e.g., an IPv6 address, changed from `old` to `new`.
+#[inline]
+pub fn checksum_adjust_u128(csum: u16, old: u128, new: u128) -> u16 {
+    (0..8).rev().fold(csum, |csum, i| {
+        checksum_adjust(csum, (old >> (i * 16)) as u16, (new >> (i * 16)) as u16)
+    })
+}
+
+/// Partial sum of the IPv4 pseudo-header covered by TCP and UDP checksums (RFC 793), for an upper layer message of
+/// `len` bytes.
+#[inline]
+pub fn ipv4_pseudo_header_sum(src: u32, dst: u32, protocol: u8, len: u16) -> u32 {
+    let sum = (src >> 16) + (src & 0xffff) + (dst >> 16) + (dst & 0xffff) + protocol as u32 + len as u32;
+    ones_complement_fold(sum) as u32
+}
+
+/// Partial sum of the IPv6 pseudo-header covered by TCP, UDP and ICMPv6 checksums (RFC 8200, section 8.1).
+#[inline]
+pub fn ipv6_pseudo_header_sum(src: u128, dst: u128, protocol: u8, len: u32) -> u32 {
+    let mut bytes = [0u8; 32];
+    BigEndian::write_u128(&mut bytes[..16], src);
+    BigEndian::write_u128(&mut bytes[16..], dst);
+    let sum = ones_complement_add(0, &bytes) + (len >> 16) + (len & 0xffff) + protocol as u32;
+    ones_complement_fold(sum) as u32
+}
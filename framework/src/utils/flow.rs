@@ -1,7 +1,10 @@
+use super::checksum::*;
+use crate::headers::{ipv6_upper_layer, ipv6_walk};
 use crate::native::zcsi::*;
 use byteorder::{BigEndian, ByteOrder};
 use fnv::FnvHasher;
-use std::hash::Hasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::mem;
 use std::slice;
 
@@ -24,6 +27,30 @@ pub struct Flow {
     pub proto: u8,
 }
 
+/// A flow with 128-bit addresses, used for IPv6 traffic. IPv4 flows convert into IPv4-mapped addresses
+/// (`::ffff:a.b.c.d`), so dual-stack NFs can key all their state by `Ipv6Flow`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, Ord, PartialOrd)]
+#[repr(C)]
+pub struct Ipv6Flow {
+    /// Source IP.
+    pub src_ip: u128,
+    /// Destination IP.
+    pub dst_ip: u128,
+    /// Source port number.
+    pub src_port: u16,
+    /// Destination port number.
+    pub dst_port: u16,
+    /// Protocol type.
+    pub proto: u8,
+}
+
+/// A key identifying a flow in the flow keyed state stores.
+pub trait FlowKey: Copy + Eq + Hash + Send + fmt::Debug {}
+
+impl FlowKey for Flow {}
+
+impl FlowKey for Ipv6Flow {}
+
 /// The main type of IPv4 prefix.
 #[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, Ord, PartialOrd)]
 pub struct Ipv4Prefix {
@@ -60,9 +87,13 @@ impl Ipv4Prefix {
 
 const IHL_TO_BYTE_FACTOR: usize = 4; // IHL is in terms of number of 32-bit words.
 
-/// This assumes the function is given the Mac Payload
+/// This assumes the function is given the Mac Payload. Non-first fragments carry no ports, they return None.
 #[inline]
 pub fn ipv4_extract_flow(bytes: &[u8]) -> Option<Flow> {
+    // Only the first fragment of a datagram starts with the ports.
+    if BigEndian::read_u16(&bytes[6..8]) & 0x1fff != 0 {
+        return None;
+    }
     let port_start = (bytes[0] & 0xf) as usize * IHL_TO_BYTE_FACTOR;
     Some(Flow {
         proto: bytes[9],
@@ -88,19 +119,156 @@ impl Flow {
         }
     }
 
-    /// IPv4 stamp flow.
-    // TODO:doc
+    /// Write this flow's addresses and ports into the IPv4 packet in `bytes` (the Mac payload). The IP header
+    /// checksum is recomputed, so it is correct even if it was not before (e.g., left to offload). The TCP/UDP
+    /// checksum is updated incrementally (RFC 1624), so a segment that had a correct checksum keeps it. Non-first
+    /// fragments carry no transport header, only their addresses are written.
     #[inline]
     pub fn ipv4_stamp_flow(&self, bytes: &mut [u8]) {
         let port_start = (bytes[0] & 0xf) as usize * IHL_TO_BYTE_FACTOR;
+        let old = ipv4_extract_flow(bytes);
         BigEndian::write_u32(&mut bytes[12..16], self.src_ip);
         BigEndian::write_u32(&mut bytes[16..20], self.dst_ip);
-        BigEndian::write_u16(&mut bytes[(port_start)..(port_start + 2)], self.src_port);
-        BigEndian::write_u16(&mut bytes[(port_start + 2)..(port_start + 4)], self.dst_port);
         BigEndian::write_u16(&mut bytes[10..12], 0);
-        let csum = ipcsum(bytes);
+        let csum = checksum(&bytes[..port_start.min(bytes.len())]);
         BigEndian::write_u16(&mut bytes[10..12], csum);
-        // FIXME: l4 cksum
+        let old = match old {
+            Some(old) => old,
+            None => return,
+        };
+        BigEndian::write_u16(&mut bytes[(port_start)..(port_start + 2)], self.src_port);
+        BigEndian::write_u16(&mut bytes[(port_start + 2)..(port_start + 4)], self.dst_port);
+        adjust_l4_checksum(bytes, port_start, old.proto, |csum| {
+            let csum = checksum_adjust_u32(csum, old.src_ip, self.src_ip);
+            let csum = checksum_adjust_u32(csum, old.dst_ip, self.dst_ip);
+            let csum = checksum_adjust(csum, old.src_port, self.src_port);
+            checksum_adjust(csum, old.dst_port, self.dst_port)
+        });
+    }
+}
+
+/// Apply `update` to the TCP, UDP or ICMPv6 checksum of the segment at `port_start`. A zero UDP checksum means there
+/// is none, and is left alone.
+#[inline]
+fn adjust_l4_checksum<F: FnOnce(u16) -> u16>(bytes: &mut [u8], port_start: usize, proto: u8, update: F) {
+    let csum_offset = match proto {
+        6 => port_start + 16,
+        17 => port_start + 6,
+        58 => port_start + 2,
+        _ => return,
+    };
+    if bytes.len() < csum_offset + 2 {
+        return;
+    }
+    let csum = BigEndian::read_u16(&bytes[csum_offset..csum_offset + 2]);
+    if proto == 17 && csum == 0 {
+        return;
+    }
+    let mut csum = update(csum);
+    if proto == 17 && csum == 0 {
+        csum = 0xffff;
+    }
+    BigEndian::write_u16(&mut bytes[csum_offset..csum_offset + 2], csum);
+}
+
+/// Prefix of IPv4-mapped IPv6 addresses.
+const IPV4_MAPPED: u128 = 0xffff_0000_0000;
+
+/// This assumes the function is given the Mac Payload. Extension headers are skipped; returns `None` unless the packet
+/// carries TCP or UDP.
+#[inline]
+pub fn ipv6_extract_flow(bytes: &[u8]) -> Option<Ipv6Flow> {
+    let (proto, port_start) = ipv6_upper_layer(bytes)?;
+    if (proto != 6 && proto != 17) || bytes.len() < port_start + 4 {
+        return None;
+    }
+    Some(Ipv6Flow {
+        proto,
+        src_ip: BigEndian::read_u128(&bytes[8..24]),
+        dst_ip: BigEndian::read_u128(&bytes[24..40]),
+        src_port: BigEndian::read_u16(&bytes[(port_start)..(port_start + 2)]),
+        dst_port: BigEndian::read_u16(&bytes[(port_start + 2)..(port_start + 4)]),
+    })
+}
+
+impl Ipv6Flow {
+    /// Reverse the flow.
+    #[inline]
+    pub fn reverse_flow(&self) -> Ipv6Flow {
+        Ipv6Flow {
+            src_ip: self.dst_ip,
+            dst_ip: self.src_ip,
+            src_port: self.dst_port,
+            dst_port: self.src_port,
+            proto: self.proto,
+        }
+    }
+
+    /// The IPv4 flow, if both addresses are IPv4-mapped.
+    #[inline]
+    pub fn to_ipv4(&self) -> Option<Flow> {
+        if self.src_ip >> 32 == IPV4_MAPPED >> 32 && self.dst_ip >> 32 == IPV4_MAPPED >> 32 {
+            Some(Flow {
+                src_ip: self.src_ip as u32,
+                dst_ip: self.dst_ip as u32,
+                src_port: self.src_port,
+                dst_port: self.dst_port,
+                proto: self.proto,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// IPv6 stamp flow, given the Mac payload. IPv6 has no header checksum; the TCP/UDP and ICMPv6 checksums, which
+    /// cover the addresses, are updated incrementally (RFC 1624). Ports are written into TCP and UDP packets and into
+    /// the first fragment of their datagrams, like `ipv4_stamp_flow` does; later fragments carry no transport header
+    /// and only get new addresses.
+    #[inline]
+    pub fn ipv6_stamp_flow(&self, bytes: &mut [u8]) {
+        if bytes.len() < 40 {
+            return;
+        }
+        let old_src = BigEndian::read_u128(&bytes[8..24]);
+        let old_dst = BigEndian::read_u128(&bytes[24..40]);
+        BigEndian::write_u128(&mut bytes[8..24], self.src_ip);
+        BigEndian::write_u128(&mut bytes[24..40], self.dst_ip);
+        let (proto, port_start) = match ipv6_walk(bytes) {
+            Some((proto, port_start, _)) if proto == 6 || proto == 17 => (proto, port_start),
+            Some((58, icmp_start, _)) => {
+                adjust_l4_checksum(bytes, icmp_start, 58, |csum| {
+                    let csum = checksum_adjust_u128(csum, old_src, self.src_ip);
+                    checksum_adjust_u128(csum, old_dst, self.dst_ip)
+                });
+                return;
+            }
+            _ => return,
+        };
+        if bytes.len() < port_start + 4 {
+            return;
+        }
+        let old_src_port = BigEndian::read_u16(&bytes[(port_start)..(port_start + 2)]);
+        let old_dst_port = BigEndian::read_u16(&bytes[(port_start + 2)..(port_start + 4)]);
+        BigEndian::write_u16(&mut bytes[(port_start)..(port_start + 2)], self.src_port);
+        BigEndian::write_u16(&mut bytes[(port_start + 2)..(port_start + 4)], self.dst_port);
+        adjust_l4_checksum(bytes, port_start, proto, |csum| {
+            let csum = checksum_adjust_u128(csum, old_src, self.src_ip);
+            let csum = checksum_adjust_u128(csum, old_dst, self.dst_ip);
+            let csum = checksum_adjust(csum, old_src_port, self.src_port);
+            checksum_adjust(csum, old_dst_port, self.dst_port)
+        });
+    }
+}
+
+impl From<Flow> for Ipv6Flow {
+    fn from(flow: Flow) -> Ipv6Flow {
+        Ipv6Flow {
+            src_ip: IPV4_MAPPED | flow.src_ip as u128,
+            dst_ip: IPV4_MAPPED | flow.dst_ip as u128,
+            src_port: flow.src_port,
+            dst_port: flow.dst_port,
+            proto: flow.proto,
+        }
     }
 }
 
@@ -115,6 +283,18 @@ pub fn ipv4_flow_hash(bytes: &[u8], _iv: u32) -> usize {
     }
 }
 
+/// Given the MAC payload of an IPv6 packet, generate a flow hash.
+#[inline]
+pub fn ipv6_flow_hash(bytes: &[u8], _iv: u32) -> usize {
+    if let Some(flow) = ipv6_extract_flow(bytes) {
+        let mut hasher = FnvHasher::default();
+        flow.hash(&mut hasher);
+        hasher.finish() as usize
+    } else {
+        0
+    }
+}
+
 /// Generate a hash value based on a given flow.
 #[inline]
 pub fn flow_hash(flow: &Flow) -> usize {
@@ -142,8 +322,3 @@ fn flow_as_u8(flow: &Flow) -> &[u8] {
     let size = mem::size_of::<Flow>();
     unsafe { slice::from_raw_parts((flow as *const Flow) as *const u8, size) }
 }
-
-#[inline]
-fn ipcsum(payload: &[u8]) -> u16 {
-    unsafe { ipv4_cksum(payload.as_ptr()) }
-}
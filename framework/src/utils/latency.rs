@@ -0,0 +1,303 @@
+//! Per-core latency histograms.
+//!
+//! Latencies are recorded in TSC cycles into an HDR (high dynamic range) histogram: values below 128 cycles are
+//! counted exactly, larger values in buckets with a relative width of at most 1/64, so percentiles are accurate to
+//! within 1.6% over the whole `u64` range. Each thread records into its own histogram (see `record_latency`), which is
+//! registered under the core the thread was bound to with `init_thread` (see `set_latency_core`). Buckets are atomics,
+//! so any thread can take a snapshot without stopping the recording core.
+use super::asm::rdtsc_unsafe;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SUB_BUCKET_BITS: u32 = 7;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+const HALF_SUB_BUCKETS: u64 = SUB_BUCKETS / 2;
+const BUCKETS: usize = ((64 - SUB_BUCKET_BITS as usize) * HALF_SUB_BUCKETS as usize) + SUB_BUCKETS as usize;
+
+#[inline]
+fn bucket_index(value: u64) -> usize {
+    if value < SUB_BUCKETS {
+        value as usize
+    } else {
+        let shift = 63 - value.leading_zeros() - (SUB_BUCKET_BITS - 1);
+        (shift as u64 * HALF_SUB_BUCKETS + (value >> shift)) as usize
+    }
+}
+
+/// Smallest and largest value counted in bucket `idx`.
+#[inline]
+fn bucket_range(idx: usize) -> (u64, u64) {
+    let idx = idx as u64;
+    if idx < SUB_BUCKETS {
+        (idx, idx)
+    } else {
+        let shift = idx / HALF_SUB_BUCKETS - 1;
+        let sub = idx - shift * HALF_SUB_BUCKETS;
+        (sub << shift, (sub << shift).saturating_add((1 << shift) - 1))
+    }
+}
+
+/// A lock-free HDR histogram of latencies in cycles.
+pub struct LatencyHistogram {
+    buckets: Box<[AtomicU64]>,
+    count: AtomicU64,
+    sum: AtomicU64,
+    min: AtomicU64,
+    max: AtomicU64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> LatencyHistogram {
+        LatencyHistogram::new()
+    }
+}
+
+impl LatencyHistogram {
+    /// Create an empty histogram.
+    pub fn new() -> LatencyHistogram {
+        LatencyHistogram {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            min: AtomicU64::new(u64::MAX),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    /// Record one latency.
+    #[inline]
+    pub fn record(&self, cycles: u64) {
+        self.buckets[bucket_index(cycles)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(cycles, Ordering::Relaxed);
+        self.min.fetch_min(cycles, Ordering::Relaxed);
+        self.max.fetch_max(cycles, Ordering::Relaxed);
+    }
+
+    /// Copy out the current counts.
+    pub fn snapshot(&self) -> LatencySnapshot {
+        LatencySnapshot {
+            buckets: self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect(),
+            count: self.count.load(Ordering::Relaxed),
+            sum: self.sum.load(Ordering::Relaxed),
+            min: self.min.load(Ordering::Relaxed),
+            max: self.max.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Forget everything recorded so far.
+    pub fn reset(&self) {
+        for bucket in self.buckets.iter() {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.count.store(0, Ordering::Relaxed);
+        self.sum.store(0, Ordering::Relaxed);
+        self.min.store(u64::MAX, Ordering::Relaxed);
+        self.max.store(0, Ordering::Relaxed);
+    }
+}
+
+/// A point in time copy of a `LatencyHistogram`. All values are in cycles.
+#[derive(Clone, Debug)]
+pub struct LatencySnapshot {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Default for LatencySnapshot {
+    fn default() -> LatencySnapshot {
+        LatencySnapshot {
+            buckets: vec![0; BUCKETS],
+            count: 0,
+            sum: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+}
+
+impl LatencySnapshot {
+    /// Number of latencies recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Smallest latency recorded, 0 if none were.
+    pub fn min(&self) -> u64 {
+        if self.count == 0 {
+            0
+        } else {
+            self.min
+        }
+    }
+
+    /// Largest latency recorded.
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// Mean latency, 0 if none were recorded.
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum as f64 / self.count as f64
+        }
+    }
+
+    /// Latency at or below which `percentile` percent (0 to 100) of the recorded latencies lie, 0 if none were
+    /// recorded.
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = ((percentile.max(0.0).min(100.0) / 100.0) * self.count as f64)
+            .ceil()
+            .max(1.0) as u64;
+        let mut seen = 0;
+        for (idx, &n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return bucket_range(idx).1.min(self.max).max(self.min);
+            }
+        }
+        self.max
+    }
+
+    /// The non-empty buckets as (smallest value, largest value, count), in increasing order.
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, u64, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|&(_, &n)| n > 0)
+            .map(|(idx, &n)| {
+                let (low, high) = bucket_range(idx);
+                (low, high, n)
+            })
+    }
+
+    /// Add the latencies of `other` to this snapshot.
+    pub fn merge(&mut self, other: &LatencySnapshot) {
+        for (mine, theirs) in self.buckets.iter_mut().zip(other.buckets.iter()) {
+            *mine += theirs;
+        }
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+}
+
+impl fmt::Display for LatencySnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "count {} mean {:.0} min {} p50 {} p99 {} p99.9 {} max {} (ns)",
+            self.count,
+            self.mean() * 1e9 / tsc_hz().max(1) as f64,
+            cycles_to_ns(self.min()),
+            cycles_to_ns(self.percentile(50.0)),
+            cycles_to_ns(self.percentile(99.0)),
+            cycles_to_ns(self.percentile(99.9)),
+            cycles_to_ns(self.max)
+        )
+    }
+}
+
+lazy_static! {
+    static ref CORE_HISTOGRAMS: RwLock<Vec<(i32, Arc<LatencyHistogram>)>> = RwLock::new(vec![]);
+}
+
+/// TSC frequency in Hz, 0 until set or calibrated.
+static TSC_HZ: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static LATENCY_CORE: Cell<i32> = Cell::new(-1);
+    static LOCAL_HISTOGRAM: Arc<LatencyHistogram> = {
+        let histogram = Arc::new(LatencyHistogram::new());
+        CORE_HISTOGRAMS.write().unwrap().push((LATENCY_CORE.with(|c| c.get()), histogram.clone()));
+        histogram
+    };
+}
+
+/// Report the latencies recorded by the calling thread under `core`. `init_thread` does this before the thread
+/// records anything.
+pub fn set_latency_core(core: i32) {
+    LATENCY_CORE.with(|c| c.set(core));
+}
+
+/// Record a latency into the histogram of the calling core.
+#[inline]
+pub fn record_latency(cycles: u64) {
+    LOCAL_HISTOGRAM.with(|h| h.record(cycles))
+}
+
+/// Snapshots of the latencies recorded on each core. Threads not bound to a core are reported under core -1.
+pub fn core_latency_snapshots() -> HashMap<i32, LatencySnapshot> {
+    let mut snapshots = HashMap::<i32, LatencySnapshot>::new();
+    for (core, histogram) in CORE_HISTOGRAMS.read().unwrap().iter() {
+        snapshots.entry(*core).or_default().merge(&histogram.snapshot());
+    }
+    snapshots
+}
+
+/// Snapshot of the latencies recorded on all cores.
+pub fn latency_snapshot() -> LatencySnapshot {
+    let mut snapshot = LatencySnapshot::default();
+    for (_, histogram) in CORE_HISTOGRAMS.read().unwrap().iter() {
+        snapshot.merge(&histogram.snapshot());
+    }
+    snapshot
+}
+
+/// Clear the latency histograms of all cores.
+pub fn reset_latencies() {
+    for (_, histogram) in CORE_HISTOGRAMS.read().unwrap().iter() {
+        histogram.reset();
+    }
+}
+
+/// Measure the TSC frequency against the system clock, which takes 50ms.
+pub fn calibrate_tsc_hz() -> u64 {
+    let (start, tsc) = (Instant::now(), rdtsc_unsafe());
+    thread::sleep(Duration::from_millis(50));
+    let cycles = rdtsc_unsafe() - tsc;
+    (cycles as f64 / start.elapsed().as_secs_f64()) as u64
+}
+
+/// Set the TSC frequency in Hz. `init_system` does this, so that no packet processing core has to calibrate it.
+pub fn set_tsc_hz(hz: u64) {
+    TSC_HZ.store(hz, Ordering::Relaxed);
+}
+
+/// TSC frequency in Hz, as set by `init_system`. Outside of a NetBricks system it is calibrated on first use.
+pub fn tsc_hz() -> u64 {
+    match TSC_HZ.load(Ordering::Relaxed) {
+        0 => {
+            let hz = calibrate_tsc_hz();
+            set_tsc_hz(hz);
+            hz
+        }
+        hz => hz,
+    }
+}
+
+/// Convert a number of cycles to nanoseconds.
+#[inline]
+pub fn cycles_to_ns(cycles: u64) -> u64 {
+    (cycles as u128 * 1_000_000_000 / u128::from(tsc_hz().max(1))) as u64
+}
+
+/// Convert a number of nanoseconds to cycles.
+#[inline]
+pub fn ns_to_cycles(ns: u64) -> u64 {
+    (ns as u128 * u128::from(tsc_hz()) / 1_000_000_000) as u64
+}
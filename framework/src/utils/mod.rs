@@ -1,9 +1,15 @@
 //! NetBricks utils
 
 pub use self::asm::*;
+pub use self::checksum::*;
 pub use self::flow::*;
+pub use self::latency::*;
+pub use self::timer_wheel::*;
 mod asm;
+mod checksum;
 mod flow;
+mod latency;
+mod timer_wheel;
 
 /// Hard code page size.
 ///
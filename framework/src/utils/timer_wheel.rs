@@ -0,0 +1,270 @@
+//! Hierarchical timer wheel.
+//!
+//! Time is counted in ticks of a fixed number of TSC cycles since the wheel was created. The wheel has `LEVELS` levels
+//! of `SLOTS` slots each: level 0 holds the timers due in the next `SLOTS` ticks, one slot per tick, and each level
+//! above covers `SLOTS` times the span of the one below it. When the wheel turns past a slot of a higher level, the
+//! timers in it move down to the level matching their remaining time, so arming, cancelling and expiring a timer take
+//! constant time however far out it is. Timers further out than the wheel spans are parked in its top level and move
+//! down once they come within range.
+//!
+//! Timers can be pushed back cheaply (see `TimerWheel::reset`): the new deadline is only recorded, and the timer is
+//! moved once its old slot comes up. This keeps refreshing an idle timeout on every packet of a flow cheap.
+use super::{ns_to_cycles, rdtsc_unsafe};
+use std::mem;
+use std::time::Duration;
+
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const SLOT_MASK: u64 = SLOTS as u64 - 1;
+const LEVELS: usize = 6;
+/// Number of ticks spanned by the whole wheel.
+const SPAN: u64 = 1 << (SLOT_BITS * LEVELS as u32);
+
+/// Handle of a timer armed on a `TimerWheel`. Handles stay unique: once a timer expired or was cancelled, its handle
+/// refers to no timer, even if the wheel reuses its storage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimerId {
+    index: u32,
+    generation: u32,
+}
+
+/// Reference from a slot to an entry, valid as long as the entry was not moved (see `Entry::placement`).
+#[derive(Clone, Copy)]
+struct SlotRef {
+    index: u32,
+    placement: u64,
+}
+
+struct Entry<T> {
+    generation: u32,
+    /// Identifies the slot reference to this entry, stale references are skipped.
+    placement: u64,
+    /// Tick the timer is due.
+    expires: u64,
+    value: Option<T>,
+}
+
+/// A hierarchical timer wheel holding a value of type `T` for each armed timer, driven by the TSC. The wheel does not
+/// run anything itself: `advance` returns the values of the timers that expired, and whoever owns the wheel decides
+/// what to do with them (e.g., the scheduler's timers are callbacks, see `scheduler::arm_timer`).
+pub struct TimerWheel<T> {
+    /// Cycles per tick.
+    tick: u64,
+    /// TSC value of tick 0.
+    start: u64,
+    /// Current tick, all timers due up to and including it have expired.
+    now: u64,
+    slots: Vec<Vec<SlotRef>>,
+    /// Bitmap of the non-empty slots of each level.
+    occupied: [u64; LEVELS],
+    entries: Vec<Entry<T>>,
+    free: Vec<u32>,
+    next_placement: u64,
+    len: usize,
+}
+
+impl<T> TimerWheel<T> {
+    /// Create an empty wheel with a resolution of `tick`, starting now. Timers fire at most one tick late, plus however
+    /// long it takes until the wheel is advanced next.
+    pub fn new(tick: Duration) -> TimerWheel<T> {
+        TimerWheel::with_tick_cycles(ns_to_cycles(tick.as_nanos() as u64), rdtsc_unsafe())
+    }
+
+    /// Create an empty wheel with ticks of `tick` cycles, whose tick 0 is at TSC value `start`.
+    pub fn with_tick_cycles(tick: u64, start: u64) -> TimerWheel<T> {
+        TimerWheel {
+            tick: tick.max(1),
+            start,
+            now: 0,
+            slots: (0..SLOTS * LEVELS).map(|_| vec![]).collect(),
+            occupied: [0; LEVELS],
+            entries: vec![],
+            free: vec![],
+            next_placement: 0,
+            len: 0,
+        }
+    }
+
+    /// Number of armed timers.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no timers are armed.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Tick containing TSC value `cycles`, rounded up so timers never fire early.
+    #[inline]
+    fn tick_at(&self, cycles: u64) -> u64 {
+        let elapsed = cycles.saturating_sub(self.start);
+        let ticks = elapsed / self.tick;
+        if ticks * self.tick < elapsed {
+            ticks + 1
+        } else {
+            ticks
+        }
+    }
+
+    /// Arm a timer expiring `delay` from now, holding `value`.
+    pub fn arm(&mut self, delay: Duration, value: T) -> TimerId {
+        let deadline = rdtsc_unsafe() + ns_to_cycles(delay.as_nanos() as u64);
+        self.arm_at(deadline, value)
+    }
+
+    /// Arm a timer expiring once the TSC reaches `deadline`, holding `value`. Deadlines that already passed expire at
+    /// the next tick.
+    pub fn arm_at(&mut self, deadline: u64, value: T) -> TimerId {
+        let expires = self.tick_at(deadline).max(self.now + 1);
+        let mut entry = Entry {
+            generation: 0,
+            placement: 0,
+            expires,
+            value: Some(value),
+        };
+        let index = match self.free.pop() {
+            Some(index) => {
+                let old = &mut self.entries[index as usize];
+                entry.generation = old.generation;
+                *old = entry;
+                index
+            }
+            None => {
+                self.entries.push(entry);
+                (self.entries.len() - 1) as u32
+            }
+        };
+        self.len += 1;
+        self.place(index);
+        TimerId {
+            index,
+            generation: self.entries[index as usize].generation,
+        }
+    }
+
+    fn entry(&self, timer: TimerId) -> Option<&Entry<T>> {
+        self.entries
+            .get(timer.index as usize)
+            .filter(|e| e.generation == timer.generation && e.value.is_some())
+    }
+
+    /// Whether `timer` is still armed.
+    pub fn is_armed(&self, timer: TimerId) -> bool {
+        self.entry(timer).is_some()
+    }
+
+    /// Cancel `timer`, returning its value if it was still armed.
+    pub fn cancel(&mut self, timer: TimerId) -> Option<T> {
+        self.entry(timer)?;
+        Some(self.release(timer.index))
+    }
+
+    /// Move `timer` to expire `delay` from now instead, returning whether it was still armed.
+    pub fn reset(&mut self, timer: TimerId, delay: Duration) -> bool {
+        let deadline = rdtsc_unsafe() + ns_to_cycles(delay.as_nanos() as u64);
+        self.reset_at(timer, deadline)
+    }
+
+    /// Move `timer` to expire once the TSC reaches `deadline` instead, returning whether it was still armed. Pushing a
+    /// timer back only records the new deadline, bringing it forward moves it right away.
+    pub fn reset_at(&mut self, timer: TimerId, deadline: u64) -> bool {
+        if self.entry(timer).is_none() {
+            return false;
+        }
+        let expires = self.tick_at(deadline).max(self.now + 1);
+        let entry = &mut self.entries[timer.index as usize];
+        let earlier = expires < entry.expires;
+        entry.expires = expires;
+        if earlier {
+            self.place(timer.index);
+        }
+        true
+    }
+
+    /// Advance the wheel to TSC value `now`, returning the values of the timers that expired, tick by tick. Stretches
+    /// of ticks without anything to do are skipped, so this is cheap however long ago the wheel was advanced last.
+    pub fn advance(&mut self, now: u64) -> Vec<T> {
+        let target = (now.saturating_sub(self.start) / self.tick).max(self.now);
+        let mut expired = vec![];
+        while self.len > 0 {
+            match self.next_turn() {
+                Some(tick) if tick <= target => self.now = tick,
+                _ => break,
+            }
+            // Cascade from the top, so timers moving down two levels are not skipped.
+            for level in (1..LEVELS).rev() {
+                let shift = SLOT_BITS * level as u32;
+                if self.now & ((1 << shift) - 1) == 0 {
+                    self.turn(level, ((self.now >> shift) & SLOT_MASK) as usize, &mut expired);
+                }
+            }
+            self.turn(0, (self.now & SLOT_MASK) as usize, &mut expired);
+        }
+        self.now = target;
+        expired
+    }
+
+    /// First tick after the current one at which the wheel reaches an occupied slot, if any.
+    fn next_turn(&self) -> Option<u64> {
+        (0..LEVELS)
+            .filter(|&level| self.occupied[level] != 0)
+            .map(|level| {
+                let shift = SLOT_BITS * level as u32;
+                let current = self.now >> shift;
+                let first = (current + 1) & SLOT_MASK;
+                // Slots of a level are reached in order, every `SLOTS ^ level` ticks.
+                let ahead = u64::from(self.occupied[level].rotate_right(first as u32).trailing_zeros()) + 1;
+                (current + ahead) << shift
+            })
+            .min()
+    }
+
+    /// Empty the slot at `slot`, expiring the timers that are due and moving the others further down.
+    fn turn(&mut self, level: usize, slot: usize, expired: &mut Vec<T>) {
+        let mut refs = mem::take(&mut self.slots[level * SLOTS + slot]);
+        self.occupied[level] &= !(1 << slot);
+        for r in refs.drain(..) {
+            let entry = &self.entries[r.index as usize];
+            if entry.placement != r.placement || entry.value.is_none() {
+                continue;
+            }
+            if entry.expires <= self.now {
+                expired.push(self.release(r.index));
+            } else {
+                self.place(r.index);
+            }
+        }
+        // Keep the allocation, unless timers were moved into this slot meanwhile.
+        if self.slots[level * SLOTS + slot].is_empty() {
+            self.slots[level * SLOTS + slot] = refs;
+        }
+    }
+
+    /// Put the entry at `index` into the slot matching its remaining time, invalidating any reference to it.
+    fn place(&mut self, index: u32) {
+        let placement = self.next_placement;
+        self.next_placement += 1;
+        let entry = &mut self.entries[index as usize];
+        entry.placement = placement;
+        let expires = entry.expires.min(self.now + SPAN - 1);
+        let delta = expires - self.now;
+        let level = (0..LEVELS)
+            .find(|&level| delta < 1 << (SLOT_BITS * (level as u32 + 1)))
+            .unwrap_or(LEVELS - 1);
+        let slot = (expires >> (SLOT_BITS * level as u32)) & SLOT_MASK;
+        self.slots[level * SLOTS + slot as usize].push(SlotRef { index, placement });
+        self.occupied[level] |= 1 << slot;
+    }
+
+    /// Free the entry at `index`, returning its value.
+    fn release(&mut self, index: u32) -> T {
+        let entry = &mut self.entries[index as usize];
+        entry.generation = entry.generation.wrapping_add(1);
+        self.free.push(index);
+        self.len -= 1;
+        entry.value.take().unwrap()
+    }
+}
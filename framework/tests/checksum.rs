@@ -0,0 +1,216 @@
+#![cfg(feature = "heap_mbuf")]
+extern crate e2d2;
+use e2d2::common::EmptyMetadata;
+use e2d2::headers::*;
+use e2d2::interface::*;
+use e2d2::utils::*;
+
+fn mac_payload<T: EndOffset>(pkt: &mut Packet<T, EmptyMetadata>) -> &mut [u8] {
+    let hdr = pkt.get_mut_header() as *mut T as *mut u8;
+    let len = pkt.data_len() - 14;
+    unsafe { std::slice::from_raw_parts_mut(hdr, len) }
+}
+
+fn ipv4_tcp_packet(payload: usize) -> Packet<IpHeader, EmptyMetadata> {
+    let mac = MacHeader::new();
+    let mut ip = IpHeader::new();
+    ip.set_version(4);
+    ip.set_ihl(5);
+    ip.set_ttl(64);
+    ip.set_protocol(6);
+    ip.set_length((20 + 20 + payload) as u16);
+    ip.set_src(0x0a00_0001);
+    ip.set_dst(0xc0a8_0102);
+    let mut tcp = TcpHeader::new();
+    tcp.set_src_port(1234);
+    tcp.set_dst_port(80);
+    tcp.set_data_offset(5);
+    let mut pkt = new_packet()
+        .unwrap()
+        .push_header(&mac)
+        .unwrap()
+        .push_header(&ip)
+        .unwrap()
+        .push_header(&tcp)
+        .unwrap();
+    pkt.add_to_payload_tail(payload).unwrap();
+    for (i, b) in pkt.get_mut_payload().iter_mut().enumerate() {
+        *b = (i * 7) as u8;
+    }
+    pkt.deparse_header(20)
+}
+
+#[test]
+fn checksum_helpers() {
+    // Example from RFC 1071, section 3.
+    let bytes = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7];
+    assert_eq!(ones_complement_fold(ones_complement_add(0, &bytes)), 0xddf2);
+    assert_eq!(checksum(&bytes), !0xddf2);
+
+    let mut data = [0x45u8, 0x00, 0x12, 0x34, 0xab, 0xcd, 0x00, 0x00, 0x01];
+    let csum = checksum(&data);
+    data[2] = 0x56;
+    data[3] = 0x78;
+    assert_eq!(checksum_adjust(csum, 0x1234, 0x5678), checksum(&data));
+    data[4..8].copy_from_slice(&[1, 2, 3, 4]);
+    let adjusted = checksum_adjust_u32(checksum_adjust(csum, 0x1234, 0x5678), 0xabcd_0000, 0x0102_0304);
+    assert_eq!(adjusted, checksum(&data));
+}
+
+#[test]
+fn ipv4_header_checksum() {
+    let mut pkt = ipv4_tcp_packet(0);
+    assert!(!pkt.get_header().checksum_valid());
+    pkt.get_mut_header().update_checksum();
+    assert!(pkt.get_header().checksum_valid());
+    assert_eq!(pkt.get_header().csum(), pkt.get_header().compute_checksum());
+    assert_eq!(checksum(&mac_payload(&mut pkt)[..20]), 0);
+
+    assert_eq!(pkt.get_mut_header().decrement_ttl(), 63);
+    assert!(pkt.get_header().checksum_valid());
+
+    // The accessors use host byte order both ways.
+    pkt.get_mut_header().set_csum(0x1234);
+    assert_eq!(pkt.get_header().csum(), 0x1234);
+    assert_eq!(&mac_payload(&mut pkt)[10..12], &[0x12, 0x34]);
+    pkt.free_packet();
+}
+
+#[test]
+fn l4_checksums() {
+    let mut pkt = ipv4_tcp_packet(33);
+    assert!(!pkt.checksums_valid());
+    pkt.update_checksums(ChecksumMode::Software);
+    assert!(pkt.checksums_valid());
+
+    // Independent check of the TCP checksum over the pseudo-header and the segment.
+    let bytes = mac_payload(&mut pkt).to_vec();
+    let pseudo = ipv4_pseudo_header_sum(0x0a00_0001, 0xc0a8_0102, 6, 53);
+    assert_eq!(ones_complement_fold(ones_complement_add(pseudo, &bytes[20..])), 0xffff);
+
+    // Rewriting addresses and ports updates both checksums incrementally.
+    let flow = Flow {
+        src_ip: 0x0b0b_0b0b,
+        dst_ip: 0xc0a8_0102,
+        src_port: 40000,
+        dst_port: 80,
+        proto: 6,
+    };
+    flow.ipv4_stamp_flow(mac_payload(&mut pkt));
+    assert_eq!(pkt.get_header().src(), 0x0b0b_0b0b);
+    assert!(pkt.checksums_valid());
+
+    mac_payload(&mut pkt)[60] ^= 0xff;
+    assert!(!pkt.checksums_valid());
+    pkt.free_packet();
+}
+
+#[test]
+fn stamp_recomputes_ip_checksum() {
+    // The IP checksum was left to offload, the TCP one is correct.
+    let mut pkt = ipv4_tcp_packet(33);
+    pkt.update_checksums(ChecksumMode::Software);
+    pkt.get_mut_header().set_csum(0);
+    let flow = Flow {
+        src_ip: 0x0b0b_0b0b,
+        dst_ip: 0xc0a8_0102,
+        src_port: 40000,
+        dst_port: 80,
+        proto: 6,
+    };
+    flow.ipv4_stamp_flow(mac_payload(&mut pkt));
+    assert!(pkt.get_header().checksum_valid());
+    assert!(pkt.checksums_valid());
+    pkt.free_packet();
+}
+
+#[test]
+fn stamp_non_first_fragment() {
+    let mut pkt = ipv4_tcp_packet(33);
+    pkt.get_mut_header().set_fragment_offset(185);
+    pkt.update_checksums(ChecksumMode::Software);
+    let before = mac_payload(&mut pkt)[20..].to_vec();
+
+    // Only the addresses are rewritten, the payload is not a transport header.
+    let flow = Flow {
+        src_ip: 0x0b0b_0b0b,
+        dst_ip: 0x0c0c_0c0c,
+        src_port: 40000,
+        dst_port: 8080,
+        proto: 6,
+    };
+    flow.ipv4_stamp_flow(mac_payload(&mut pkt));
+    assert_eq!(
+        (pkt.get_header().src(), pkt.get_header().dst()),
+        (0x0b0b_0b0b, 0x0c0c_0c0c)
+    );
+    assert!(pkt.get_header().checksum_valid());
+    assert_eq!(&mac_payload(&mut pkt)[20..], &before[..]);
+    pkt.free_packet();
+}
+
+#[test]
+fn truncated_segment_checksum() {
+    let mut pkt = ipv4_tcp_packet(33);
+    pkt.update_checksums(ChecksumMode::Software);
+    let tcp = unsafe { &*(pkt.get_payload().as_ptr() as *const TcpHeader) };
+    assert!(tcp.checksum_valid(pkt.get_header(), 53));
+    assert!(!tcp.checksum_valid(pkt.get_header(), 52));
+    assert!(pkt.payload_header_correct::<TcpHeader>());
+
+    // A segment cut short by the end of the packet fails the check instead of being read past it.
+    pkt.remove_from_payload_tail(10).unwrap();
+    assert!(!tcp.checksum_valid(pkt.get_header(), pkt.payload_size()));
+    assert_eq!(
+        pkt.payload_header_correct::<TcpHeader>(),
+        !cfg!(feature = "verify_checksum")
+    );
+    pkt.free_packet();
+}
+
+#[test]
+fn l4_checksum_offload() {
+    let mut pkt = ipv4_tcp_packet(10);
+    pkt.get_mut_header().update_checksum();
+    pkt.update_checksums(ChecksumMode::Offload);
+    assert_eq!(pkt.get_header().csum(), 0);
+    let tcp = pkt.parse_header::<TcpHeader>();
+    let pseudo = ipv4_pseudo_header_sum(0x0a00_0001, 0xc0a8_0102, 6, 30);
+    assert_eq!(tcp.get_header().checksum(), ones_complement_fold(pseudo));
+    tcp.free_packet();
+}
+
+#[test]
+fn ipv6_udp_checksum() {
+    let mut mac = MacHeader::new();
+    mac.set_etype(0x86dd);
+    let mut ip = Ipv6Header::new();
+    ip.set_payload_len(8 + 5);
+    ip.set_next_header(17);
+    ip.set_src(0x2001_0db8 << 96 | 1);
+    ip.set_dst(0x2001_0db8 << 96 | 2);
+    let mut pkt = new_packet()
+        .unwrap()
+        .push_header(&mac)
+        .unwrap()
+        .push_header(&ip)
+        .unwrap();
+    pkt.add_to_payload_tail(8 + 5).unwrap();
+    pkt.get_mut_payload().copy_from_slice(&[
+        0x04, 0xd2, 0x00, 0x35, 0x00, 0x0d, 0x00, 0x00, b'h', b'e', b'l', b'l', b'o',
+    ]);
+    pkt.update_checksums(ChecksumMode::Software);
+    assert!(pkt.checksums_valid());
+    assert_ne!(&pkt.get_payload()[6..8], &[0, 0]);
+
+    let flow = pkt
+        .get_header()
+        .flow(pkt.payload_offset() + pkt.payload_size())
+        .unwrap();
+    let mut rewritten = flow;
+    rewritten.src_ip = 0xfd00 << 112 | 7;
+    rewritten.src_port = 5353;
+    rewritten.ipv6_stamp_flow(mac_payload(&mut pkt));
+    assert!(pkt.checksums_valid());
+    pkt.free_packet();
+}
@@ -0,0 +1,68 @@
+//! Packet builders shared by the integration tests. Not every test uses all of them.
+#![allow(dead_code)]
+use e2d2::common::EmptyMetadata;
+use e2d2::headers::*;
+use e2d2::interface::*;
+
+/// The bytes of `pkt` from its current header to the end of its payload.
+pub fn header_bytes<T: EndOffset>(pkt: &Packet<T, EmptyMetadata>) -> Vec<u8> {
+    let hdr = pkt.get_header() as *const T as *const u8;
+    let len = pkt.payload_offset() + pkt.payload_size();
+    unsafe { std::slice::from_raw_parts(hdr, len).to_vec() }
+}
+
+/// The Ethernet header in front of an IP packet.
+pub fn l2_bytes(pkt: &Packet<IpHeader, EmptyMetadata>) -> Vec<u8> {
+    let hdr = pkt.get_header() as *const IpHeader as *const u8;
+    unsafe { std::slice::from_raw_parts(hdr.sub(14), 14).to_vec() }
+}
+
+/// An IPv4 header from `src` to `dst` carrying `len` bytes of `proto`.
+pub fn ipv4_header(src: u32, dst: u32, proto: u8, len: usize) -> IpHeader {
+    let mut ip = IpHeader::new();
+    ip.set_version(4);
+    ip.set_ihl(5);
+    ip.set_length((20 + len) as u16);
+    ip.set_ttl(64);
+    ip.set_protocol(proto);
+    ip.set_src(src);
+    ip.set_dst(dst);
+    ip
+}
+
+/// A UDP header from `src_port` to `dst_port` with `len` bytes of data.
+pub fn udp_header(src_port: u16, dst_port: u16, len: usize) -> UdpHeader {
+    let mut udp = UdpHeader::new();
+    udp.set_src_port(src_port);
+    udp.set_dst_port(dst_port);
+    udp.set_length((8 + len) as u16);
+    udp
+}
+
+/// Push `ip` and `l4` behind an Ethernet header from 02:00:00:00:00:0a to 02:00:00:00:00:0b, followed by `len` bytes of
+/// data numbered from 0. The packet is returned at its IP header, with the IP checksum set.
+pub fn ipv4_packet<T: EndOffset<PreviousHeader = IpHeader>>(
+    ip: &IpHeader,
+    l4: &T,
+    len: usize,
+) -> Packet<IpHeader, EmptyMetadata> {
+    let mut mac = MacHeader::new();
+    mac.set_etype(0x0800);
+    mac.src = MacAddress::new(2, 0, 0, 0, 0, 0xa);
+    mac.dst = MacAddress::new(2, 0, 0, 0, 0, 0xb);
+    let mut pkt = new_packet()
+        .unwrap()
+        .push_header(&mac)
+        .unwrap()
+        .push_header(ip)
+        .unwrap()
+        .push_header(l4)
+        .unwrap();
+    pkt.add_to_payload_tail(len).unwrap();
+    for (i, b) in pkt.get_mut_payload().iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    let mut pkt = pkt.deparse_header(ip.offset());
+    pkt.get_mut_header().update_checksum();
+    pkt
+}
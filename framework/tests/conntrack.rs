@@ -0,0 +1,156 @@
+extern crate e2d2;
+use e2d2::headers::IpHeader;
+use e2d2::state::*;
+use e2d2::utils::ns_to_cycles;
+
+const CLIENT: u32 = 0x0a00_0001;
+const SERVER: u32 = 0xc0a8_0001;
+
+const FIN: u8 = 0x01;
+const SYN: u8 = 0x02;
+const RST: u8 = 0x04;
+const ACK: u8 = 0x10;
+
+fn ip(src: u32, dst: u32, proto: u8) -> IpHeader {
+    let mut ip = IpHeader::new();
+    ip.set_src(src);
+    ip.set_dst(dst);
+    ip.set_protocol(proto);
+    ip
+}
+
+fn ports(src: u16, dst: u16) -> Vec<u8> {
+    let mut payload = vec![0; 20];
+    payload[0..2].copy_from_slice(&src.to_be_bytes());
+    payload[2..4].copy_from_slice(&dst.to_be_bytes());
+    payload
+}
+
+/// Send a TCP segment with `flags`, from the client if `forward`, else from the server.
+fn tcp(tracker: &mut ConnTracker, forward: bool, flags: u8, now: u64) -> ConnMetadata {
+    let (header, mut payload) = if forward {
+        (ip(CLIENT, SERVER, 6), ports(40_000, 80))
+    } else {
+        (ip(SERVER, CLIENT, 6), ports(80, 40_000))
+    };
+    payload[13] = flags;
+    tracker.track(&header, &payload, now)
+}
+
+#[test]
+fn tcp_handshake_and_teardown() {
+    let mut tracker = ConnTracker::new(1024);
+    let syn = tcp(&mut tracker, true, SYN, 0);
+    assert_eq!((syn.state, syn.reply), (ConnState::SynSent, false));
+    assert_ne!(syn.id, 0);
+    let steps = [
+        (false, SYN | ACK, ConnState::SynReceived),
+        (true, ACK, ConnState::Established),
+        (false, ACK, ConnState::Established),
+        (false, FIN | ACK, ConnState::FinWait),
+        (true, ACK, ConnState::CloseWait),
+        (true, FIN | ACK, ConnState::LastAck),
+        (false, ACK, ConnState::TimeWait),
+    ];
+    for &(forward, flags, state) in &steps {
+        let m = tcp(&mut tracker, forward, flags, 1);
+        assert_eq!(
+            m,
+            ConnMetadata {
+                id: syn.id,
+                state,
+                reply: !forward
+            }
+        );
+    }
+    // A new SYN reuses the ports for a new connection.
+    let again = tcp(&mut tracker, true, SYN, 2);
+    assert_eq!(again.state, ConnState::SynSent);
+    assert_ne!(again.id, syn.id);
+    assert_eq!(tcp(&mut tracker, false, RST, 3).state, ConnState::Closed);
+    assert_eq!(tracker.stats().connections, 2);
+}
+
+#[test]
+fn tcp_strict_and_loose() {
+    let mut strict = ConnTracker::new(1024);
+    let ack = tcp(&mut strict, true, ACK, 0);
+    assert_eq!((ack.id, ack.state), (0, ConnState::Invalid));
+    assert_eq!(strict.stats().invalid, 1);
+    assert!(strict.is_empty());
+
+    let mut loose = ConnTracker::with_config(ConntrackConfig {
+        capacity: 1024,
+        loose: true,
+        ..Default::default()
+    });
+    let picked = tcp(&mut loose, true, ACK, 0);
+    assert_eq!(picked.state, ConnState::Established);
+    assert!(picked.state.is_established());
+    assert_eq!(tcp(&mut loose, false, ACK, 1).id, picked.id);
+}
+
+#[test]
+fn udp_and_related_icmp() {
+    let mut tracker = ConnTracker::new(1024);
+    let query = tracker.track(&ip(CLIENT, SERVER, 17), &ports(5353, 53), 0);
+    assert_eq!(query.state, ConnState::UdpUnreplied);
+    let answer = tracker.track(&ip(SERVER, CLIENT, 17), &ports(53, 5353), 1);
+    assert_eq!(
+        (answer.id, answer.state, answer.reply),
+        (query.id, ConnState::UdpReplied, true)
+    );
+
+    // Port unreachable from the client, quoting the answer.
+    let mut icmp = vec![3, 3, 0, 0, 0, 0, 0, 0, 0x45, 0, 0, 28, 0, 0, 0, 0, 64, 17, 0, 0];
+    icmp.extend_from_slice(&SERVER.to_be_bytes());
+    icmp.extend_from_slice(&CLIENT.to_be_bytes());
+    icmp.extend_from_slice(&ports(53, 5353)[..8]);
+    let error = tracker.track(&ip(CLIENT, SERVER, 1), &icmp, 2);
+    assert_eq!(
+        (error.id, error.state, error.reply),
+        (query.id, ConnState::Related, false)
+    );
+
+    // Echo requests and unknown protocols are not tracked.
+    let echo = tracker.track(&ip(CLIENT, SERVER, 1), &[8, 0, 0, 0, 0, 0, 0, 0], 3);
+    assert_eq!((echo.id, echo.state), (0, ConnState::Untracked));
+    assert_eq!(
+        tracker.track(&ip(CLIENT, SERVER, 47), &[0; 8], 3).state,
+        ConnState::Untracked
+    );
+}
+
+#[test]
+fn timeouts() {
+    let mut tracker = ConnTracker::new(1024);
+    let second = ns_to_cycles(1_000_000_000);
+    let first = tracker.track(&ip(CLIENT, SERVER, 17), &ports(5353, 53), 0);
+    let kept = tcp(&mut tracker, true, SYN, 0);
+    tcp(&mut tracker, false, SYN | ACK, 0);
+    tcp(&mut tracker, true, ACK, 0);
+
+    // Unreplied UDP flows time out after 30s, established connections live on.
+    let later = tracker.track(&ip(CLIENT, SERVER, 17), &ports(5353, 53), 31 * second);
+    assert_ne!(later.id, first.id);
+    assert_eq!(tracker.stats().timeouts, 1);
+    assert_eq!(
+        tracker.state(&kept_flow(), 31 * second),
+        Some((kept.id, ConnState::Established))
+    );
+
+    // The new UDP flow is reclaimed by sweeping.
+    assert_eq!(tracker.expire(usize::MAX, 62 * second), 1);
+    assert_eq!(tracker.len(), 1);
+    assert_eq!(tracker.stats().timeouts, 2);
+}
+
+fn kept_flow() -> e2d2::utils::Flow {
+    e2d2::utils::Flow {
+        src_ip: SERVER,
+        dst_ip: CLIENT,
+        src_port: 80,
+        dst_port: 40_000,
+        proto: 6,
+    }
+}
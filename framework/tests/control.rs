@@ -0,0 +1,141 @@
+#![cfg(feature = "heap_mbuf")]
+extern crate e2d2;
+extern crate serde_json;
+use e2d2::config::NetbricksConfiguration;
+use e2d2::control::protocol::*;
+use e2d2::scheduler::*;
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn parse_requests() {
+    for line in &[
+        "ports",
+        "cores",
+        "pipelines",
+        "stats",
+        "add nat 2",
+        "remove 3",
+        "remove 3 1",
+        "drain",
+        "drain 0",
+        "resume 0",
+        "shutdown",
+    ] {
+        assert_eq!(ControlRequest::parse(line).unwrap().to_string(), *line);
+    }
+    assert_eq!(
+        ControlRequest::parse("  add  nat 2\r\n").unwrap(),
+        ControlRequest::Add {
+            name: String::from("nat"),
+            core: 2
+        }
+    );
+    for line in &[
+        "",
+        "reboot",
+        "add nat",
+        "add nat two",
+        "remove x",
+        "drain 0 1",
+        "ports all",
+    ] {
+        assert!(ControlRequest::parse(line).is_err(), "{}", line);
+    }
+    let response: Value = serde_json::from_str(&handle_control_line("reboot")).unwrap();
+    assert_eq!(response["ok"], false);
+    assert!(response["error"].as_str().unwrap().contains("reboot"));
+}
+
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Client {
+    fn request(&mut self, line: &str) -> Value {
+        writeln!(self.writer, "{}", line).unwrap();
+        let mut response = String::new();
+        self.reader.read_line(&mut response).unwrap();
+        serde_json::from_str(&response).unwrap()
+    }
+}
+
+#[test]
+fn control_running_system() {
+    let mut configuration = NetbricksConfiguration::new_with_name("control-test");
+    configuration.cores = vec![0];
+    let mut context = initialize_system(&configuration).unwrap();
+    context.start_schedulers();
+    context.register_pipeline(
+        "idle",
+        Arc::new(|_, s: &mut StandaloneScheduler| {
+            s.add_task(|| {}).unwrap();
+        }),
+    );
+    let runs = Arc::new(AtomicUsize::new(0));
+    let counted = runs.clone();
+    context.register_pipeline(
+        "count",
+        Arc::new(move |_, s: &mut StandaloneScheduler| {
+            let runs = counted.clone();
+            s.add_task(move || {
+                runs.fetch_add(1, Ordering::Relaxed);
+            })
+            .unwrap();
+        }),
+    );
+    // The core starts with no tasks at all.
+    context.execute();
+    let address = context.start_control_server("127.0.0.1:0".parse().unwrap()).unwrap();
+
+    let stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    let mut client = Client {
+        reader: BufReader::new(stream.try_clone().unwrap()),
+        writer: stream,
+    };
+    let cores = client.request("cores");
+    assert_eq!(cores["ok"], true, "{}", cores);
+    assert_eq!(cores["result"][0]["core"], 0);
+
+    // Pipelines added to a core that started out empty get to run.
+    let counting = client.request("add count 0")["result"]["pipeline"].as_u64().unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while runs.load(Ordering::Relaxed) == 0 {
+        assert!(Instant::now() < deadline, "added pipeline did not run");
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(client.request(&format!("remove {}", counting))["ok"], true);
+
+    let added = client.request("add idle 0");
+    let pipeline = added["result"]["pipeline"].as_u64().unwrap();
+    let pipelines = client.request("pipelines");
+    assert_eq!(pipelines["result"]["registered"], serde_json::json!(["count", "idle"]));
+    assert_eq!(pipelines["result"]["running"][0]["pipeline"], pipeline);
+    assert_eq!(pipelines["result"]["running"][0]["paused"], false);
+
+    // The drain runs in the background: the server keeps answering until the pipeline is paused.
+    assert_eq!(client.request("drain 0")["result"]["draining"][0], 0);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while client.request("pipelines")["result"]["running"][0]["paused"] != true {
+        assert!(Instant::now() < deadline, "drain did not pause the pipeline");
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(client.request("resume")["ok"], true);
+    assert_eq!(client.request("add nope 0")["ok"], false);
+    assert_eq!(client.request("drain 7")["ok"], false);
+    assert!(client.request("stats")["result"]["schedulers"]["0"]["tasks"].is_array());
+    assert_eq!(client.request(&format!("remove {}", pipeline))["ok"], true);
+    assert_eq!(client.request("pipelines")["result"]["running"], Value::Array(vec![]));
+
+    assert!(!context.wait_for_shutdown(Duration::from_millis(1)));
+    assert_eq!(client.request("shutdown")["ok"], true);
+    assert!(context.wait_for_shutdown(Duration::from_secs(10)));
+    context.stop();
+}
@@ -0,0 +1,91 @@
+extern crate e2d2;
+use e2d2::state::*;
+use e2d2::utils::Flow;
+use std::thread;
+use std::time::Duration;
+
+fn flow(i: u32) -> Flow {
+    Flow {
+        src_ip: 0x0a00_0000 | i,
+        dst_ip: 0xc0a8_0001,
+        src_port: (i % 60_000) as u16 + 1024,
+        dst_port: 443,
+        proto: 6,
+    }
+}
+
+#[test]
+fn bidirectional_lookup() {
+    let mut table = FlowTable::new(1024, None);
+    assert_eq!(table.insert(flow(1), "a"), None);
+    assert_eq!(table.get_directed(&flow(1)).map(|(v, r)| (*v, r)), Some(("a", false)));
+    assert_eq!(
+        table.get_directed(&flow(1).reverse_flow()).map(|(v, r)| (*v, r)),
+        Some(("a", true))
+    );
+    // Inserting the reverse replaces the entry.
+    assert_eq!(table.insert(flow(1).reverse_flow(), "b"), Some("a"));
+    assert_eq!(table.len(), 1);
+    assert!(table.contains(&flow(1)));
+    assert!(!table.contains(&flow(2)));
+
+    let (value, reverse) = table.get_or_insert_with(&flow(2).reverse_flow(), || "c");
+    assert_eq!((*value, reverse), ("c", false));
+    assert_eq!(table.get_directed(&flow(2)).map(|(_, r)| r), Some(true));
+
+    assert_eq!(table.remove(&flow(1).reverse_flow()), Some("b"));
+    assert_eq!(table.get(&flow(1)), None);
+    let stats = table.stats();
+    assert_eq!((stats.occupancy, stats.inserts, stats.hits, stats.misses), (1, 2, 3, 2));
+}
+
+#[test]
+fn fixed_capacity_evicts_lru() {
+    let mut table = FlowTable::new(4096, None);
+    assert_eq!(table.capacity(), 4096);
+    for i in 0..4096 {
+        table.insert(flow(i), i);
+    }
+    let full = table.len();
+    assert!(full > 3500 && full <= 4096);
+    // Keep using a quarter of the flows while inserting many more.
+    for i in 4096..100_000 {
+        for j in 0..4 {
+            let hot = (i * 4 + j) % 1024;
+            table.get_or_insert_with(&flow(hot), || hot);
+        }
+        table.insert(flow(i), i);
+    }
+    let stats = table.stats();
+    assert!(stats.occupancy <= 4096);
+    assert_eq!(stats.inserts - stats.evictions, stats.occupancy as u64);
+    assert!((0..1024).all(|i| table.contains(&flow(i))));
+    assert!(stats.hits > 4 * 90_000);
+}
+
+#[test]
+fn memory_budget() {
+    let table = FlowTable::<[u64; 4]>::with_memory(1 << 20, None);
+    assert!(table.memory() <= 1 << 20);
+    assert!(table.memory() > 1 << 19);
+    assert_eq!(FlowTable::<u8>::with_memory(0, None).capacity(), 8);
+}
+
+#[test]
+fn ttl_expiry() {
+    let mut table = FlowTable::new(64, Some(Duration::from_millis(20)));
+    table.insert(flow(1), 1);
+    table.insert(flow(2), 2);
+    table.insert(flow(3), 3);
+    thread::sleep(Duration::from_millis(10));
+    assert_eq!(table.get(&flow(1)), Some(&mut 1));
+    thread::sleep(Duration::from_millis(15));
+    // Flow 1 was refreshed, the others are idle for too long.
+    assert!(table.contains(&flow(1)));
+    assert_eq!(table.get(&flow(2)), None);
+    let mut expired = vec![];
+    assert_eq!(table.expire(usize::MAX, |f, v| expired.push((f, v))), 1);
+    assert_eq!(expired, vec![(flow(3), 3)]);
+    let stats = table.stats();
+    assert_eq!((stats.occupancy, stats.expirations, stats.evictions), (1, 2, 0));
+}
@@ -0,0 +1,237 @@
+#![cfg(feature = "heap_mbuf")]
+extern crate e2d2;
+mod common;
+use common::*;
+use e2d2::common::{EmptyMetadata, ErrorKind};
+use e2d2::config::{read_configuration_from_str, DEFAULT_MTU};
+use e2d2::headers::*;
+use e2d2::interface::*;
+use e2d2::operators::*;
+use e2d2::scheduler::*;
+use e2d2::state::Ipv4Reassembler;
+use e2d2::utils::checksum;
+use std::sync::{Arc, Mutex};
+
+/// A UDP datagram of `len` bytes (header included) behind an Ethernet header, with `options` in its IP header.
+fn udp_packet(options: &[u8], len: usize, flags: u8) -> Packet<IpHeader, EmptyMetadata> {
+    let ihl = 20 + options.len();
+    let mut ip = ipv4_header(0x0a00_0001, 0xc0a8_0001, 17, len);
+    ip.set_ihl((ihl / 4) as u8);
+    ip.set_length((ihl + len) as u16);
+    ip.set_id(0x4242);
+    ip.set_flags(flags);
+    let mut pkt = ipv4_packet(&ip, &udp_header(1234, 53, len - 8), len - 8);
+    // Pushing the header makes room for its options.
+    unsafe {
+        let hdr = pkt.get_mut_header() as *mut IpHeader as *mut u8;
+        std::slice::from_raw_parts_mut(hdr.add(20), options.len()).copy_from_slice(options);
+    }
+    pkt.get_mut_header().update_checksum();
+    pkt.write_timestamp(1234);
+    pkt
+}
+
+/// An Ethernet frame carrying the IPv4 fragment of datagram 1 with `data` at byte `offset`.
+fn fragment_frame(offset: usize, more: bool, data: &[u8]) -> Vec<u8> {
+    let mut ip = ipv4_header(0x0a00_0001, 0xc0a8_0001, 17, data.len());
+    ip.set_id(1);
+    ip.set_flags(if more { 0x1 } else { 0 });
+    ip.set_fragment_offset((offset / 8) as u16);
+    ip.update_checksum();
+    let mut frame = vec![0; 12];
+    frame.extend_from_slice(&[0x08, 0x00]);
+    frame.extend_from_slice(unsafe { std::slice::from_raw_parts(&ip as *const IpHeader as *const u8, 20) });
+    frame.extend_from_slice(data);
+    frame
+}
+
+#[test]
+fn fragment_datagram() {
+    let mut pkt = udp_packet(&[], 1960, 0);
+    let data = pkt.get_payload().to_vec();
+    let l2 = l2_bytes(&pkt);
+    let mut fragments = pkt.fragment(1000).unwrap();
+    assert_eq!(fragments.len(), 2);
+
+    let mut reassembled = vec![];
+    for (i, frag) in Some(&pkt).into_iter().chain(fragments.iter()).enumerate() {
+        let ip = frag.get_header();
+        let last = i == 2;
+        assert_eq!(ip.length() as usize, if last { 28 } else { 996 });
+        assert_eq!(ip.fragment_offset() as usize * 8, i * 976);
+        assert_eq!(ip.flags(), if last { 0 } else { 1 });
+        assert_eq!((ip.id(), ip.protocol()), (0x4242, 17));
+        assert!(ip.checksum_valid());
+        assert_eq!(header_bytes(frag).len(), ip.length() as usize);
+        assert_eq!(l2_bytes(frag), l2);
+        assert_eq!(frag.read_timestamp(), 1234);
+        reassembled.extend_from_slice(frag.get_payload());
+    }
+    assert_eq!(reassembled, data);
+
+    // Packets that fit are left alone.
+    assert!(pkt.fragment(1000).unwrap().is_empty());
+    assert!(fragments[1].fragment(68).unwrap().is_empty());
+}
+
+#[test]
+#[cfg(not(feature = "performance"))]
+fn fragment_options() {
+    // A copied option (loose source route, 7 bytes), an option that is not (record route, 7 bytes) and padding.
+    let options = [0x83, 7, 4, 10, 0, 0, 2, 7, 7, 4, 0, 0, 0, 0, 0, 0];
+    let mut pkt = udp_packet(&options, 200, 0);
+    let fragments = pkt.fragment(100).unwrap();
+    assert_eq!(pkt.get_header().ihl(), 9);
+    assert_eq!(pkt.payload_size(), 64);
+    assert_eq!(&header_bytes(&pkt)[20..36], &options);
+    assert_eq!(fragments.len(), 2);
+    for frag in &fragments {
+        let bytes = header_bytes(frag);
+        assert_eq!(frag.get_header().ihl(), 7);
+        assert_eq!(&bytes[20..28], &[0x83, 7, 4, 10, 0, 0, 2, 0]);
+        assert_eq!(checksum(&bytes[..28]), 0);
+    }
+    assert_eq!(fragments[0].payload_size(), 72);
+    assert_eq!(fragments[0].get_header().fragment_offset(), 8);
+    assert_eq!(fragments[1].payload_size(), 64);
+    assert_eq!(fragments[1].get_header().fragment_offset(), 17);
+}
+
+#[test]
+fn fragment_errors() {
+    let mut pkt = udp_packet(&[], 1800, 0x2);
+    let before = header_bytes(&pkt);
+    match pkt.fragment(1500) {
+        Err(e) => assert!(matches!(e.kind(), ErrorKind::DontFragment(1820, 1500))),
+        Ok(_) => panic!("fragmented a packet with DF set"),
+    }
+    assert_eq!(header_bytes(&pkt), before);
+
+    let mut pkt = udp_packet(&[0; 40], 1800, 0);
+    match pkt.fragment(64) {
+        Err(e) => assert!(matches!(e.kind(), ErrorKind::BadMtu(64))),
+        Ok(_) => panic!("fragmented with no room for data"),
+    }
+
+    // Every fragment must carry at least 8 bytes of data.
+    let mut pkt = udp_packet(&[], 64, 0);
+    for &mtu in &[20, 27] {
+        match pkt.fragment(mtu) {
+            Err(e) => assert!(matches!(e.kind(), ErrorKind::BadMtu(m) if *m == mtu)),
+            Ok(_) => panic!("fragmented with less than 8 bytes of data per fragment"),
+        }
+    }
+    let fragments = pkt.fragment(28).unwrap();
+    assert_eq!(pkt.get_header().length(), 28);
+    assert_eq!(fragments.len(), 64 / 8 - 1);
+    for frag in fragments {
+        frag.free_packet();
+    }
+    pkt.free_packet();
+}
+
+#[test]
+fn port_mtu() {
+    let config =
+        read_configuration_from_str("[[ports]]\nname = \"a\"\n[[ports]]\nname = \"b\"\nmtu = 9000\n", "mtu").unwrap();
+    assert_eq!(config.ports[0].mtu, DEFAULT_MTU);
+    assert_eq!(config.ports[1].mtu, 9000);
+    assert!(read_configuration_from_str("[[ports]]\nname = \"a\"\nmtu = 40\n", "mtu").is_err());
+}
+
+#[test]
+fn reassemble_pipeline() {
+    // Datagrams too long for an mbuf, each followed by a packet that is not fragmented.
+    let data: Vec<u8> = (0..3000).map(|i| (i * 7) as u8).collect();
+    let frames = [
+        fragment_frame(1480, true, &data[1480..2960]),
+        fragment_frame(2960, false, &data[2960..]),
+        fragment_frame(0, true, &data[..1480]),
+        fragment_frame(0, false, &data[..100]),
+    ];
+    let mut next = 0;
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let record = Arc::clone(&seen);
+
+    let port = VirtualPort::new(1).unwrap();
+    let mut sched = StandaloneScheduler::new();
+    let pipeline = ReceiveBatch::new(port.new_virtual_queue(0).unwrap())
+        .transform(Box::new(move |pkt| {
+            let frame = &frames[next % frames.len()];
+            next += 1;
+            let len = pkt.payload_size();
+            if frame.len() > len {
+                pkt.add_to_payload_tail(frame.len() - len).unwrap();
+            } else {
+                pkt.remove_from_payload_tail(len - frame.len()).unwrap();
+            }
+            pkt.get_mut_payload().copy_from_slice(frame);
+        }))
+        .parse::<MacHeader>()
+        .parse::<IpHeader>()
+        .reassemble_ipv4(Ipv4Reassembler::new(Default::default()))
+        .map(Box::new(move |pkt| {
+            let payload: Vec<u8> = pkt.payload_segments().flatten().copied().collect();
+            record.lock().unwrap().push((pkt.is_chained(), payload));
+        }))
+        .fragment(usize::from(DEFAULT_MTU))
+        .send(port.new_virtual_queue(0).unwrap());
+    sched.add_task(pipeline).unwrap();
+    sched.execute_one();
+
+    let (rx, tx) = port.stats();
+    assert!(rx > 0 && rx % 4 == 0);
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), rx / 2);
+    for (i, (chained, payload)) in seen.iter().enumerate() {
+        if i % 2 == 0 {
+            assert!(chained);
+            assert_eq!(payload, &data);
+        } else {
+            assert!(!chained);
+            assert_eq!(payload, &data[..100]);
+        }
+    }
+    // Each datagram goes out again as the three fragments it came in.
+    assert_eq!(tx, rx);
+}
+
+#[test]
+fn fragment_pipeline_capacity() {
+    // One long packet and three short ones, of which the filter keeps one.
+    let data: Vec<u8> = (0..1480).map(|i| i as u8).collect();
+    let frames = [
+        fragment_frame(0, false, &data),
+        fragment_frame(0, false, &data[..100]),
+        fragment_frame(0, false, &data[..100]),
+        fragment_frame(0, false, &data[..100]),
+    ];
+    let (mut next, mut kept) = (0, 0);
+
+    let port = VirtualPort::new(1).unwrap();
+    let mut sched = StandaloneScheduler::new();
+    let pipeline = ReceiveBatch::new(port.new_virtual_queue(0).unwrap())
+        .transform(Box::new(move |pkt| {
+            let frame = &frames[next % frames.len()];
+            next += 1;
+            let len = pkt.payload_size();
+            pkt.add_to_payload_tail(frame.len() - len).unwrap();
+            pkt.get_mut_payload().copy_from_slice(frame);
+        }))
+        .filter(Box::new(move |_| {
+            kept += 1;
+            kept % 4 < 2
+        }))
+        .parse::<MacHeader>()
+        .parse::<IpHeader>()
+        .fragment(400)
+        .send(port.new_virtual_queue(0).unwrap());
+    sched.add_task(pipeline).unwrap();
+    sched.execute_one();
+
+    // Of the 32 packets received, 16 are left: the 8 long ones split into 4 fragments each, which only 5 of them fit
+    // into the batch with. The other 3 are dropped.
+    let (rx, tx) = port.stats();
+    assert_eq!(rx, 32);
+    assert_eq!(tx, 16 + 5 * 3 - 3);
+}
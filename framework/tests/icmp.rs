@@ -0,0 +1,133 @@
+#![cfg(feature = "heap_mbuf")]
+extern crate e2d2;
+mod common;
+use common::*;
+use e2d2::common::EmptyMetadata;
+use e2d2::headers::*;
+use e2d2::interface::*;
+use e2d2::utils::checksum;
+
+/// Build an IPv4 packet from `src` to `dst` carrying `proto` with an 8 byte header of `msg_type` and `extra` bytes of
+/// payload.
+fn icmp_packet(src: u32, dst: u32, ttl: u8, proto: u8, msg_type: u8, extra: usize) -> Packet<IpHeader, EmptyMetadata> {
+    let mut ip = ipv4_header(src, dst, proto, 8 + extra);
+    ip.set_ttl(ttl);
+    let mut icmp = IcmpHeader::new();
+    icmp.set_msg_type(msg_type);
+    icmp.set_identifier(0x1234);
+    icmp.set_sequence(7);
+    let mut pkt = ipv4_packet(&ip, &icmp, extra);
+    let csum = checksum(&pkt.get_payload()[..8 + extra]);
+    pkt.get_mut_payload()[2] = (csum >> 8) as u8;
+    pkt.get_mut_payload()[3] = csum as u8;
+    pkt
+}
+
+#[test]
+fn icmp_echo_reply() {
+    let mut pkt = icmp_packet(0x0a00_0002, 0x0a00_0001, 3, 1, ICMP_ECHO_REQUEST, 32);
+    assert!(pkt.write_echo_reply());
+    assert_eq!(pkt.get_header().src(), 0x0a00_0001);
+    assert_eq!(pkt.get_header().dst(), 0x0a00_0002);
+    assert_eq!(pkt.get_header().ttl(), 64);
+    let bytes = header_bytes(&pkt);
+    assert_eq!(checksum(&bytes[..20]), 0);
+    assert_eq!(checksum(&bytes[20..]), 0);
+
+    let icmp = pkt.parse_header::<IcmpHeader>();
+    assert_eq!(icmp.get_header().msg_type(), ICMP_ECHO_REPLY);
+    assert_eq!(icmp.get_header().identifier(), 0x1234);
+    assert_eq!(icmp.get_header().sequence(), 7);
+    assert_eq!(icmp.get_payload()[31], 31);
+
+    // Only echo requests are answered.
+    let mut pkt = icmp.deparse_header(20);
+    assert!(!pkt.write_echo_reply());
+    pkt.free_packet();
+}
+
+#[test]
+fn icmp_time_exceeded() {
+    let mut pkt = icmp_packet(0x0a00_0002, 0x0b00_0001, 1, 17, 0, 100);
+    let original = header_bytes(&pkt);
+    assert!(pkt.write_icmp_error(0x0a00_0001, ICMP_TIME_EXCEEDED, 0));
+    assert_eq!(pkt.data_len(), 14 + 20 + 8 + 20 + 8);
+    assert_eq!(pkt.get_header().src(), 0x0a00_0001);
+    assert_eq!(pkt.get_header().dst(), 0x0a00_0002);
+    assert_eq!(pkt.get_header().protocol(), 1);
+    assert_eq!(pkt.get_header().length(), 20 + 8 + 20 + 8);
+    let bytes = header_bytes(&pkt);
+    assert_eq!(checksum(&bytes[..20]), 0);
+    assert_eq!(checksum(&bytes[20..]), 0);
+    assert_eq!(&bytes[28..], &original[..28]);
+
+    // The result is itself an ICMP error, so it must not trigger another one.
+    let icmp = pkt.parse_header::<IcmpHeader>();
+    assert_eq!(icmp.get_header().msg_type(), ICMP_TIME_EXCEEDED);
+    assert!(icmp.get_header().is_error());
+    let mut pkt = icmp.deparse_header(20);
+    assert!(!pkt.write_icmp_error(0x0a00_0001, ICMP_TIME_EXCEEDED, 0));
+    pkt.free_packet();
+
+    // Nor do non-initial fragments.
+    let mut pkt = icmp_packet(0x0a00_0002, 0x0b00_0001, 1, 17, 0, 0);
+    pkt.get_mut_header().set_fragment_offset(8);
+    assert!(!pkt.write_icmp_error(0x0a00_0001, ICMP_TIME_EXCEEDED, 0));
+    pkt.free_packet();
+}
+
+#[test]
+fn icmpv6_echo_reply() {
+    let mut mac = MacHeader::new();
+    mac.set_etype(0x86dd);
+    let mut ip = Ipv6Header::new();
+    ip.set_payload_len(8);
+    ip.set_next_header(58);
+    ip.set_hop_limit(1);
+    ip.set_src(1);
+    ip.set_dst(2);
+    let mut pkt = new_packet()
+        .unwrap()
+        .push_header(&mac)
+        .unwrap()
+        .push_header(&ip)
+        .unwrap();
+    pkt.add_to_payload_tail(8).unwrap();
+    pkt.get_mut_payload()
+        .copy_from_slice(&[ICMPV6_ECHO_REQUEST, 0, 0x10, 0, 0, 1, 0, 1]);
+    assert!(pkt.write_echo_reply());
+    assert_eq!(pkt.get_header().src(), 2);
+    assert_eq!(pkt.get_header().dst(), 1);
+    assert_eq!(pkt.get_header().hop_limit(), 64);
+    let icmp = pkt.parse_header::<IcmpHeader>();
+    assert_eq!(icmp.get_header().msg_type(), ICMPV6_ECHO_REPLY);
+    // Type moved from 0x80 to 0x81 in the high byte of the first word.
+    assert_eq!(icmp.get_header().checksum(), 0x1000 - 0x0100);
+    icmp.free_packet();
+}
+
+#[test]
+fn icmpv6_checked_after_ipv6() {
+    let ipv6_packet = |next_header: u8| {
+        let mut mac = MacHeader::new();
+        mac.set_etype(0x86dd);
+        let mut ip = Ipv6Header::new();
+        ip.set_payload_len(20);
+        ip.set_next_header(next_header);
+        let mut pkt = new_packet()
+            .unwrap()
+            .push_header(&mac)
+            .unwrap()
+            .push_header(&ip)
+            .unwrap();
+        pkt.add_to_payload_tail(20).unwrap();
+        pkt
+    };
+    let pkt = ipv6_packet(58);
+    assert!(pkt.payload_header_correct::<IcmpHeader>());
+    pkt.free_packet();
+    // TCP over IPv6 is not ICMPv6.
+    let pkt = ipv6_packet(6);
+    assert!(!pkt.payload_header_correct::<IcmpHeader>());
+    pkt.free_packet();
+}
@@ -0,0 +1,138 @@
+#![cfg(feature = "heap_mbuf")]
+extern crate e2d2;
+mod common;
+use common::*;
+use e2d2::common::EmptyMetadata;
+use e2d2::headers::*;
+use e2d2::interface::*;
+use e2d2::operators::*;
+use e2d2::scheduler::*;
+use e2d2::utils::{checksum, checksum_adjust};
+use std::collections::HashMap;
+
+const LOCAL: u32 = 0x0a00_0001;
+const REMOTE: u32 = 0x0a00_0002;
+
+/// A UDP datagram with `len` bytes of data and `padding` bytes of Ethernet padding behind it.
+fn inner_packet(len: usize, padding: usize) -> Packet<IpHeader, EmptyMetadata> {
+    let mut ip = ipv4_header(0xc0a8_0001, 0xc0a8_0002, 17, 8 + len);
+    ip.set_dscp(46);
+    ipv4_packet(&ip, &udp_header(1234, 53, len), len + padding)
+}
+
+#[test]
+fn gre_key_encap_decap() {
+    let mut template = IpTunnelTemplate::gre(LOCAL, REMOTE, Some(0xdead_beef));
+    let inner = inner_packet(100, 6);
+    let l2 = l2_bytes(&inner);
+    let before = header_bytes(&inner);
+    let before = before[..before.len() - 6].to_vec();
+    let outer = template.encap(inner).unwrap();
+    assert_eq!(l2_bytes(&outer), l2);
+    let ip = outer.get_header();
+    assert_eq!((ip.src(), ip.dst(), ip.protocol()), (LOCAL, REMOTE, IPPROTO_GRE));
+    assert_eq!(ip.length() as usize, 28 + before.len());
+    assert_eq!(ip.dscp(), 46);
+    assert!(ip.checksum_valid());
+    assert_eq!(outer.data_len(), 14 + 28 + before.len());
+
+    let gre = outer.parse_header::<GreHeader>();
+    assert_eq!(gre.get_header().key(), Some(0xdead_beef));
+    assert_eq!(gre.get_header().protocol(), 0x0800);
+    assert_eq!((gre.get_header().sequence(), gre.get_header().checksum()), (None, None));
+    assert_eq!(gre.get_payload(), &before[..]);
+    let outer = gre.deparse_header(20);
+
+    let (inner, key) = outer.decap_ip().ok().unwrap();
+    assert_eq!(key, Some(0xdead_beef));
+    assert_eq!(header_bytes(&inner), before);
+    assert_eq!(l2_bytes(&inner), l2);
+    assert_eq!(inner.data_len(), 14 + before.len());
+    inner.free_packet();
+}
+
+#[test]
+fn ipip_encap_decap() {
+    let mut template = IpTunnelTemplate::ipip(LOCAL, REMOTE);
+    let inner = inner_packet(40, 0);
+    let before = header_bytes(&inner);
+    let outer = template.encap(inner).unwrap();
+    assert_eq!(outer.get_header().protocol(), IPPROTO_IPIP);
+    assert_eq!(outer.get_header().length() as usize, 20 + before.len());
+    assert_eq!(outer.get_payload(), &before[..]);
+
+    let (inner, key) = outer.decap_ip().ok().unwrap();
+    assert_eq!(key, None);
+    assert_eq!(header_bytes(&inner), before);
+
+    // Not tunneled.
+    let inner = inner.decap_ip().err().unwrap();
+    assert_eq!(header_bytes(&inner), before);
+    inner.free_packet();
+}
+
+#[test]
+fn gre_checksum_and_sequence() {
+    let inner = header_bytes(&inner_packet(20, 0));
+    let mut gre = GreHeader::new();
+    gre.set_checksum_present(true);
+    gre.set_sequence_present(true);
+    assert_eq!(gre.offset(), 12);
+    let mut ip = IpHeader::new();
+    ip.set_version(4);
+    ip.set_ihl(5);
+    ip.set_length((20 + 12 + inner.len()) as u16);
+    ip.set_protocol(IPPROTO_GRE);
+    let mut mac = MacHeader::new();
+    mac.set_etype(0x0800);
+    let mut pkt = new_packet()
+        .unwrap()
+        .push_header(&mac)
+        .unwrap()
+        .push_header(&ip)
+        .unwrap()
+        .push_header(&gre)
+        .unwrap();
+    pkt.get_mut_header().set_sequence(7);
+    pkt.add_to_payload_tail(inner.len()).unwrap();
+    pkt.get_mut_payload().copy_from_slice(&inner);
+    let csum = unsafe {
+        let hdr = pkt.get_mut_header() as *mut GreHeader as *mut u8;
+        std::slice::from_raw_parts_mut(hdr.add(4), 4).copy_from_slice(&[0xff, 0xff, 0, 0]);
+        let csum = checksum(std::slice::from_raw_parts(hdr, 12 + inner.len()));
+        checksum_adjust(csum, 0xffff, 0)
+    };
+    assert_eq!(pkt.get_header().sequence(), Some(7));
+    assert_eq!(pkt.get_header().key(), None);
+
+    // A bad checksum is left alone, a good one decapsulates.
+    let outer = pkt.deparse_header(20).decap_ip().err().unwrap();
+    let mut pkt = outer.parse_header::<GreHeader>();
+    unsafe {
+        let hdr = pkt.get_mut_header() as *mut GreHeader as *mut u8;
+        std::slice::from_raw_parts_mut(hdr.add(4), 2).copy_from_slice(&csum.to_be_bytes());
+    }
+    assert_eq!(pkt.get_header().checksum(), Some(csum));
+    let (inner_pkt, key) = pkt.deparse_header(20).decap_ip().ok().unwrap();
+    assert_eq!(key, None);
+    assert_eq!(header_bytes(&inner_pkt), inner);
+    inner_pkt.free_packet();
+}
+
+#[test]
+fn ip_tunnel_pipeline() {
+    let port = VirtualPort::new(1).unwrap();
+    let mut sched = StandaloneScheduler::new();
+    let mut tenants = HashMap::new();
+    tenants.insert(7, 1);
+    let pipeline = ReceiveBatch::new(port.new_virtual_queue(0).unwrap())
+        .parse::<MacHeader>()
+        .parse::<IpHeader>()
+        .encap_ip(IpTunnelTemplate::gre(LOCAL, REMOTE, Some(7)))
+        .decap_ip(tenants)
+        .send(port.new_virtual_queue(0).unwrap());
+    sched.add_task(pipeline).unwrap();
+    sched.execute_one();
+    let (rx, tx) = port.stats();
+    assert_eq!(rx, tx);
+}
@@ -0,0 +1,143 @@
+extern crate e2d2;
+use e2d2::headers::*;
+use e2d2::utils::*;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+fn ipv6_packet(next_header: u8, extensions: &[u8], l4: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0x60, 0, 0, 0];
+    let len = (extensions.len() + l4.len()) as u16;
+    bytes.extend_from_slice(&len.to_be_bytes());
+    bytes.push(next_header);
+    bytes.push(64);
+    bytes.extend_from_slice(&Ipv6Addr::from_str("2001:db8::1").unwrap().octets());
+    bytes.extend_from_slice(&Ipv6Addr::from_str("2001:db8::2").unwrap().octets());
+    bytes.extend_from_slice(extensions);
+    bytes.extend_from_slice(l4);
+    bytes
+}
+
+#[test]
+fn ipv6_flow_without_extensions() {
+    let bytes = ipv6_packet(6, &[], &[0x04, 0xd2, 0x01, 0xbb, 0, 0, 0, 0]);
+    assert_eq!(ipv6_upper_layer(&bytes), Some((6, 40)));
+    let flow = ipv6_extract_flow(&bytes).unwrap();
+    assert_eq!(Ipv6Addr::from(flow.src_ip), Ipv6Addr::from_str("2001:db8::1").unwrap());
+    assert_eq!(Ipv6Addr::from(flow.dst_ip), Ipv6Addr::from_str("2001:db8::2").unwrap());
+    assert_eq!(flow.src_port, 1234);
+    assert_eq!(flow.dst_port, 443);
+    assert_eq!(flow.proto, 6);
+    assert_eq!(flow.reverse_flow().reverse_flow(), flow);
+}
+
+#[test]
+fn ipv6_flow_skips_extensions() {
+    // Hop-by-hop (8 bytes) -> destination options (16 bytes) -> first fragment (8 bytes) -> UDP.
+    let mut ext = vec![IPV6_DEST_OPTS, 0, 0, 0, 0, 0, 0, 0];
+    ext.extend_from_slice(&[IPV6_FRAGMENT, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    ext.extend_from_slice(&[17, 0, 0, 1, 0, 0, 0, 7]);
+    let bytes = ipv6_packet(IPV6_HOP_BY_HOP, &ext, &[0, 53, 0x30, 0x39, 0, 8, 0, 0]);
+    assert_eq!(ipv6_upper_layer(&bytes), Some((17, 72)));
+    let flow = ipv6_extract_flow(&bytes).unwrap();
+    assert_eq!((flow.src_port, flow.dst_port, flow.proto), (53, 12345, 17));
+}
+
+#[test]
+fn ipv6_no_flow() {
+    // Non-first fragment: the upper layer header is elsewhere.
+    let bytes = ipv6_packet(IPV6_FRAGMENT, &[6, 0, 0, 0x10, 0, 0, 0, 7], &[0; 8]);
+    assert_eq!(ipv6_upper_layer(&bytes), Some((IPV6_FRAGMENT, 40)));
+    assert!(ipv6_extract_flow(&bytes).is_none());
+    // No next header.
+    let bytes = ipv6_packet(IPV6_NO_NEXT_HEADER, &[], &[]);
+    assert!(ipv6_upper_layer(&bytes).is_none());
+    // Truncated extension header.
+    let bytes = ipv6_packet(IPV6_ROUTING, &[6, 4, 0, 0, 0, 0, 0, 0], &[]);
+    assert!(ipv6_extract_flow(&bytes).is_none());
+}
+
+#[test]
+fn ipv4_mapped_flow() {
+    let flow = Flow {
+        src_ip: u32::from(Ipv4Addr::from_str("192.168.0.1").unwrap()),
+        dst_ip: u32::from(Ipv4Addr::from_str("10.0.0.1").unwrap()),
+        src_port: 1234,
+        dst_port: 80,
+        proto: 6,
+    };
+    let mapped = Ipv6Flow::from(flow);
+    assert_eq!(
+        Ipv6Addr::from(mapped.src_ip),
+        Ipv6Addr::from_str("::ffff:192.168.0.1").unwrap()
+    );
+    assert_eq!(mapped.to_ipv4(), Some(flow));
+    let native = ipv6_extract_flow(&ipv6_packet(6, &[], &[0, 1, 0, 2])).unwrap();
+    assert!(native.to_ipv4().is_none());
+}
+
+#[test]
+fn ipv6_header_bounded_by_packet() {
+    // The payload length claims a destination options header that is not there.
+    let mut bytes = ipv6_packet(IPV6_DEST_OPTS, &[6, 0, 0, 0, 0, 0, 0, 0], &[0, 1, 0, 2, 0, 0, 0, 0]);
+    bytes[4..6].copy_from_slice(&2000u16.to_be_bytes());
+    let ip = unsafe { &*(bytes.as_ptr() as *const Ipv6Header) };
+    assert_eq!(ip.upper_layer(bytes.len()), Some((6, 48)));
+    assert!(ip.flow(bytes.len()).is_some());
+    assert_eq!(ip.upper_layer(44), None);
+    assert_eq!(ip.offset_within(44), 40);
+    assert_eq!(ip.offset(), 40);
+}
+
+#[test]
+#[cfg(not(feature = "performance"))]
+fn ipv6_payload_size_skips_extensions() {
+    let bytes = ipv6_packet(IPV6_DEST_OPTS, &[6, 0, 0, 0, 0, 0, 0, 0], &[0, 1, 0, 2, 0, 0, 0, 0]);
+    let ip = unsafe { &*(bytes.as_ptr() as *const Ipv6Header) };
+    assert_eq!(ip.offset_within(bytes.len()), 48);
+    assert_eq!(ip.payload_size(bytes.len()), 8);
+}
+
+#[test]
+fn ipv6_stamp_flow_ports() {
+    let flow = Ipv6Flow {
+        src_ip: 7,
+        dst_ip: 8,
+        src_port: 1111,
+        dst_port: 2222,
+        proto: 17,
+    };
+    // Unfragmented UDP gets its ports rewritten.
+    let mut bytes = ipv6_packet(17, &[], &[0, 53, 0, 54, 0, 8, 0, 0]);
+    flow.ipv6_stamp_flow(&mut bytes);
+    assert_eq!(ipv6_extract_flow(&bytes), Some(flow));
+
+    // A non-first fragment only has its addresses rewritten, its fragment header is left alone.
+    let ext = [17, 0, 0, 0x10, 0, 0, 0, 7];
+    let mut bytes = ipv6_packet(IPV6_FRAGMENT, &ext, &[0; 8]);
+    flow.ipv6_stamp_flow(&mut bytes);
+    assert_eq!(&bytes[40..48], &ext);
+    assert_eq!(&bytes[48..], &[0; 8]);
+    assert_eq!(bytes[8..24], 7u128.to_be_bytes());
+
+    // The first one carries the ports, which are rewritten and folded into the checksum.
+    let ext = [17, 0, 0, 1, 0, 0, 0, 7];
+    let mut bytes = ipv6_packet(IPV6_FRAGMENT, &ext, &[0, 53, 0, 54, 0, 8, 0x12, 0x34]);
+    flow.ipv6_stamp_flow(&mut bytes);
+    assert_eq!(ipv6_extract_flow(&bytes), Some(flow));
+    let mut expected = 0x1234;
+    expected = checksum_adjust_u128(expected, u128::from(Ipv6Addr::from_str("2001:db8::1").unwrap()), 7);
+    expected = checksum_adjust_u128(expected, u128::from(Ipv6Addr::from_str("2001:db8::2").unwrap()), 8);
+    expected = checksum_adjust(checksum_adjust(expected, 53, 1111), 54, 2222);
+    assert_eq!(&bytes[54..56], &expected.to_be_bytes());
+
+    // ICMPv6 only has its addresses rewritten, which its checksum covers.
+    let mut bytes = ipv6_packet(58, &[], &[128, 0, 0x12, 0x34, 0, 1, 0, 2]);
+    flow.ipv6_stamp_flow(&mut bytes);
+    assert_eq!(&bytes[40..42], &[128, 0]);
+    assert_eq!(&bytes[44..], &[0, 1, 0, 2]);
+    assert_eq!(bytes[24..40], 8u128.to_be_bytes());
+    let mut expected = 0x1234;
+    expected = checksum_adjust_u128(expected, u128::from(Ipv6Addr::from_str("2001:db8::1").unwrap()), 7);
+    expected = checksum_adjust_u128(expected, u128::from(Ipv6Addr::from_str("2001:db8::2").unwrap()), 8);
+    assert_eq!(&bytes[42..44], &expected.to_be_bytes());
+}
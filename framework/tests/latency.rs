@@ -0,0 +1,79 @@
+extern crate e2d2;
+use e2d2::utils::*;
+
+#[test]
+fn histogram_percentiles() {
+    let histogram = LatencyHistogram::new();
+    assert_eq!(histogram.snapshot().percentile(50.0), 0);
+    for cycles in 1..=10_000 {
+        histogram.record(cycles);
+    }
+    let snapshot = histogram.snapshot();
+    assert_eq!(snapshot.count(), 10_000);
+    assert_eq!((snapshot.min(), snapshot.max()), (1, 10_000));
+    assert!((snapshot.mean() - 5_000.5).abs() < 1e-6);
+    // Small values are exact, larger ones within the bucket precision of 1/64.
+    assert_eq!(snapshot.percentile(1.0), 100);
+    for &(percentile, expected) in &[(50.0, 5_000.0), (99.0, 9_900.0), (99.9, 9_990.0)] {
+        let value = snapshot.percentile(percentile) as f64;
+        assert!(
+            (value - expected).abs() / expected < 1.0 / 64.0,
+            "p{} = {}",
+            percentile,
+            value
+        );
+    }
+    assert_eq!(snapshot.percentile(100.0), 10_000);
+
+    let mut merged = LatencySnapshot::default();
+    merged.merge(&snapshot);
+    merged.merge(&snapshot);
+    assert_eq!(merged.count(), 20_000);
+    histogram.record(u64::MAX);
+    assert_eq!(histogram.snapshot().percentile(100.0), u64::MAX);
+    histogram.reset();
+    assert_eq!(histogram.snapshot().count(), 0);
+}
+
+#[cfg(feature = "heap_mbuf")]
+#[test]
+fn pipeline_latency() {
+    use e2d2::headers::*;
+    use e2d2::interface::*;
+    use e2d2::operators::*;
+    use e2d2::scheduler::*;
+
+    let port = VirtualPort::new(1).unwrap();
+    let mut sched = StandaloneScheduler::new();
+    let pipeline = ReceiveBatch::new(port.new_virtual_queue(0).unwrap())
+        .timestamp()
+        .parse::<MacHeader>()
+        .transform(Box::new(|p| p.get_mut_header().swap_addresses()))
+        .measure_latency()
+        .send(port.new_virtual_queue(0).unwrap());
+    sched.add_task(pipeline).unwrap();
+    // Packets that were not stamped are not counted.
+    let unstamped = VirtualPort::new(1).unwrap();
+    let pipeline = ReceiveBatch::new(unstamped.new_virtual_queue(0).unwrap())
+        .timestamp_if(false)
+        .measure_latency()
+        .send(unstamped.new_virtual_queue(0).unwrap());
+    sched.add_task(pipeline).unwrap();
+    sched.execute_one();
+    sched.execute_one();
+    let (rx, _) = port.stats();
+    let snapshot = latency_snapshot();
+    assert!(rx > 0);
+    assert!(unstamped.stats().0 > 0);
+    assert_eq!(snapshot.count(), rx as u64);
+    assert!(snapshot.max() > 0);
+    assert!(cycles_to_ns(tsc_hz()) > 900_000_000);
+
+    // A recycled mbuf does not keep the stamp of the packet it held before.
+    let mut stamped = new_packet().unwrap();
+    stamped.write_timestamp(rdtsc_unsafe());
+    stamped.free_packet();
+    let fresh = new_packet().unwrap();
+    assert_eq!(fresh.read_timestamp(), 0);
+    fresh.free_packet();
+}
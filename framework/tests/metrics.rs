@@ -0,0 +1,120 @@
+extern crate e2d2;
+use e2d2::control::metrics::*;
+use e2d2::scheduler::*;
+use e2d2::stats::PortStats;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+struct Idle;
+
+impl Executable for Idle {
+    fn execute(&mut self) -> usize {
+        0
+    }
+
+    fn dependencies(&mut self) -> Vec<usize> {
+        vec![]
+    }
+}
+
+#[test]
+fn text_format() {
+    let mut writer = MetricsWriter::new();
+    let ports = [PortStats {
+        port: String::from("0000:01:00.0"),
+        queue: Some(0),
+        rx: 10,
+        tx: 7,
+    }];
+    write_port_stats(&mut writer, &ports);
+    writer.gauge("x_ratio", "A \"ratio\"\nover lines.", &[("name", "a\"b\\c")], 0.5);
+    writer.gauge("x_ratio", "ignored", &[("name", "d")], f64::INFINITY);
+    assert_eq!(
+        writer.render(),
+        "# HELP netbricks_port_rx_packets_total Packets received.\n\
+         # TYPE netbricks_port_rx_packets_total counter\n\
+         netbricks_port_rx_packets_total{port=\"0000:01:00.0\",queue=\"0\"} 10\n\
+         # HELP netbricks_port_tx_packets_total Packets sent.\n\
+         # TYPE netbricks_port_tx_packets_total counter\n\
+         netbricks_port_tx_packets_total{port=\"0000:01:00.0\",queue=\"0\"} 7\n\
+         # HELP x_ratio A \"ratio\"\\nover lines.\n\
+         # TYPE x_ratio gauge\n\
+         x_ratio{name=\"a\\\"b\\\\c\"} 0.5\n\
+         x_ratio{name=\"d\"} +Inf\n"
+    );
+
+    let mut sched = StandaloneScheduler::new();
+    let task = sched.add_task(Idle).unwrap();
+    sched.execute_one();
+    let mut writer = MetricsWriter::new();
+    write_scheduler_stats(&mut writer, 3, &sched.stats());
+    let page = writer.render();
+    let labels = format!("{{core=\"3\",task=\"{}\",pipeline=\"\"}}", task);
+    assert!(
+        page.contains(&format!("netbricks_task_invocations_total{} 1\n", labels)),
+        "{}",
+        page
+    );
+    assert!(page.contains(&format!("netbricks_task_idle_total{} 1\n", labels)));
+    assert!(page.contains("netbricks_scheduler_sleeps_total{core=\"3\"} 0\n"));
+}
+
+#[test]
+fn counters_and_sources() {
+    let counter = register_counter("test_nf_flows_total", "Flows seen.").unwrap();
+    counter.add(2);
+    register_counter("test_nf_flows_total", "Flows seen again.")
+        .unwrap()
+        .inc();
+    assert_eq!(counter.get(), 3);
+    assert!(register_counter("3flows", "").is_err());
+    assert!(register_counter("flows-total", "").is_err());
+
+    let source = add_metrics_source(|w| w.gauge("test_source_up", "Source is up.", &[], 1.0));
+    let page = render_metrics();
+    assert!(page.contains("# HELP test_nf_flows_total Flows seen.\n"));
+    assert!(page.contains("\ntest_nf_flows_total 3\n"));
+    assert!(page.contains("\ntest_source_up 1\n"));
+    remove_metrics_source(source);
+    assert!(!render_metrics().contains("test_source_up"));
+}
+
+fn get(address: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn serve_over_http() {
+    let mut server = MetricsServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let address = server.local_addr().to_string();
+    let running = Arc::new(AtomicBool::new(true));
+    let keep_running = running.clone();
+    let thread = thread::spawn(move || {
+        while keep_running.load(Ordering::Relaxed) {
+            server.execute();
+            thread::sleep(Duration::from_millis(1));
+        }
+    });
+    register_counter("test_http_requests_total", "Requests.").unwrap().inc();
+
+    let response = get(&address, "/metrics?x=1");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+    let body = &response[response.find("\r\n\r\n").unwrap() + 4..];
+    assert!(response.contains(&format!("Content-Length: {}\r\n", body.len())));
+    assert!(body.contains("\ntest_http_requests_total 1\n"));
+
+    assert!(get(&address, "/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+    running.store(false, Ordering::Relaxed);
+    thread.join().unwrap();
+}
@@ -0,0 +1,189 @@
+#![cfg(feature = "heap_mbuf")]
+extern crate e2d2;
+use e2d2::config::{NetbricksConfiguration, PortConfiguration};
+use e2d2::interface::*;
+use e2d2::operators::*;
+use e2d2::scheduler::*;
+use std::fs;
+
+fn temp_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("e2d2-{}-{}.pcap", name, std::process::id()));
+    path.to_str().unwrap().to_string()
+}
+
+fn le32(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_le_bytes().to_vec()).collect()
+}
+
+/// A classic microsecond capture of `frames`, with snap length `snaplen`.
+fn write_pcap(path: &str, snaplen: u32, frames: &[Vec<u8>]) {
+    let mut file = le32(&[0xa1b2_c3d4, 0x0004_0002, 0, 0, snaplen, 1]);
+    for (i, frame) in frames.iter().enumerate() {
+        file.extend(le32(&[1, i as u32, frame.len() as u32, frame.len() as u32]));
+        file.extend_from_slice(frame);
+    }
+    fs::write(path, file).unwrap();
+}
+
+/// The frames in a classic little endian capture.
+fn read_pcap(path: &str) -> Vec<Vec<u8>> {
+    let file = fs::read(path).unwrap();
+    let mut frames = vec![];
+    let mut offset = 24;
+    while offset < file.len() {
+        let mut len = [0u8; 4];
+        len.copy_from_slice(&file[offset + 8..offset + 12]);
+        let len = u32::from_le_bytes(len) as usize;
+        frames.push(file[offset + 16..offset + 16 + len].to_vec());
+        offset += 16 + len;
+    }
+    frames
+}
+
+fn frame(seq: u8, len: usize) -> Vec<u8> {
+    let mut frame = vec![seq; len];
+    frame[..14].copy_from_slice(&[2, 0, 0, 0, 0, 1, 2, 0, 0, 0, 0, seq, 0x08, 0x00]);
+    frame
+}
+
+/// Run a pipeline forwarding from `rx` to `tx` until it is out of packets.
+fn forward<T: PacketRx + PacketTx + Clone + 'static>(rx: T, tx: T) {
+    let mut sched = StandaloneScheduler::new();
+    sched.add_task(ReceiveBatch::new(rx).send(tx)).unwrap();
+    for _ in 0..4 {
+        sched.execute_one();
+    }
+}
+
+#[test]
+fn pcap_round_trip() {
+    let (input, output) = (temp_path("round-trip-in"), temp_path("round-trip-out"));
+    let frames = vec![frame(1, 60), frame(2, 100), frame(3, 1514)];
+    write_pcap(&input, 65535, &frames);
+
+    let port = PcapPort::new(&format!("rx_pcap={},tx_pcap={},loop=2", input, output)).unwrap();
+    let queue = PcapPort::new_pcap_queue(&port, 0).unwrap();
+    forward(queue.clone(), queue);
+    assert_eq!(port.stats(), (6, 6));
+    assert!(port.rx_done());
+    drop(port);
+
+    let written = read_pcap(&output);
+    assert_eq!(written.len(), 6);
+    assert_eq!(&written[..3], &frames[..]);
+    assert_eq!(&written[3..], &frames[..]);
+    fs::remove_file(&input).unwrap();
+    fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn pcap_rejects_oversized_record() {
+    let path = temp_path("oversized");
+    write_pcap(&path, 1500, &[frame(1, 1600)]);
+    let port = PcapPort::new(&format!("rx_pcap={}", path)).unwrap();
+    let queue = PcapPort::new_pcap_queue(&port, 0).unwrap();
+    let mut mbufs = [std::ptr::null_mut(); 4];
+    assert!(queue.recv(&mut mbufs).is_err());
+    assert_eq!(port.stats(), (0, 0));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn pcap_skips_empty_record() {
+    let path = temp_path("empty-record");
+    write_pcap(&path, 65535, &[frame(1, 60), vec![], frame(2, 100)]);
+    let port = PcapPort::new(&format!("rx_pcap={}", path)).unwrap();
+    let queue = PcapPort::new_pcap_queue(&port, 0).unwrap();
+    forward(queue.clone(), queue);
+    assert_eq!(port.stats(), (2, 2));
+    assert_eq!(port.truncated(), 0);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn pcap_truncates_record_larger_than_mbuf() {
+    let (input, output) = (temp_path("truncate-in"), temp_path("truncate-out"));
+    let frames = vec![frame(1, 4000), frame(2, 100)];
+    write_pcap(&input, 65535, &frames);
+    let port = PcapPort::new(&format!("rx_pcap={},tx_pcap={}", input, output)).unwrap();
+    let queue = PcapPort::new_pcap_queue(&port, 0).unwrap();
+    forward(queue.clone(), queue);
+    assert_eq!(port.stats(), (2, 2));
+    assert_eq!(port.truncated(), 1);
+    drop(port);
+
+    let written = read_pcap(&output);
+    assert_eq!(written.len(), 2);
+    assert!(written[0].len() < frames[0].len());
+    assert_eq!(&written[0][..], &frames[0][..written[0].len()]);
+    assert_eq!(written[1], frames[1]);
+    fs::remove_file(&input).unwrap();
+    fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn pcap_port_from_configuration() {
+    let (input, output) = (temp_path("config-in"), temp_path("config-out"));
+    let frames = vec![frame(7, 64), frame(8, 128)];
+    write_pcap(&input, 65535, &frames);
+
+    let mut configuration = NetbricksConfiguration::new_with_name("pcap-test");
+    configuration.cores = vec![0];
+    configuration.shutdown_signals = false;
+    let name = format!("pcap:rx_pcap={},tx_pcap={}", input, output);
+    configuration.ports = vec![PortConfiguration::new_with_queues(&name, &[0], &[0])];
+    let mut context = initialize_system(&configuration).unwrap();
+
+    // The capture is served through a regular port queue.
+    assert_eq!(context.rx_queues[&0].len(), 1);
+    let queue = context.rx_queues[&0][0].clone();
+    forward(queue.clone(), queue.clone());
+    assert_eq!(queue.port.stats(0), (2, 2));
+    assert_eq!(context.pcap_ports[&name].stats(), (2, 2));
+    context.rx_queues.clear();
+    context.pcap_ports.clear();
+    drop(queue);
+
+    assert_eq!(read_pcap(&output), frames);
+    fs::remove_file(&input).unwrap();
+    fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn port_leaves_mtu_to_fragment() {
+    let (input, output) = (temp_path("mtu-in"), temp_path("mtu-out"));
+    let frames = vec![frame(1, 60), frame(2, 1514), frame(3, 1014)];
+    write_pcap(&input, 65535, &frames);
+
+    let mut config =
+        PortConfiguration::new_with_queues(&format!("pcap:rx_pcap={},tx_pcap={}", input, output), &[0], &[0]);
+    config.mtu = 1000;
+    let port = PmdPort::new_port_from_configuration(&config).unwrap();
+    assert_eq!(port.mtu(), 1000);
+    let queue = PmdPort::new_queue_pair(&port, 0, 0).unwrap();
+    assert_eq!(queue.mtu(), Some(1000));
+    // Frames are not checked against the MTU on send, that is up to `Batch::fragment`.
+    forward(queue.clone(), queue.clone());
+    assert_eq!(port.stats(0), (3, 3));
+    assert_eq!(port.tx_drops(0), 0);
+    drop(queue);
+    drop(port);
+
+    assert_eq!(read_pcap(&output), frames);
+    fs::remove_file(&input).unwrap();
+    fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn pcap_counts_write_errors() {
+    let input = temp_path("write-error-in");
+    write_pcap(&input, 65535, &[frame(1, 60), frame(2, 100)]);
+
+    // Writes to /dev/full fail once the output is flushed.
+    let port = PcapPort::new(&format!("rx_pcap={},tx_pcap=/dev/full", input)).unwrap();
+    let queue = PcapPort::new_pcap_queue(&port, 0).unwrap();
+    forward(queue.clone(), queue);
+    assert_eq!(port.stats(), (2, 0));
+    assert_eq!(port.tx_errors(), 2);
+    fs::remove_file(&input).unwrap();
+}
@@ -0,0 +1,70 @@
+extern crate e2d2;
+use e2d2::common::ErrorKind;
+use e2d2::pvn::config::*;
+use e2d2::pvn::measure::{LONG_MEASURE_TIME, SHORT_MEASURE_TIME};
+
+#[test]
+fn legacy_json_setup() {
+    let json = r#"{"setup": "3", "tlsv_setup": "0", "rdr_setup": "0", "xcdr_setup": "0", "p2p_setup": "5",
+                   "iter": "2", "inst": "on", "mode": "long", "p2p_type": "app_p2p-controlled", "disk": "ssd"}"#;
+    let config =
+        PvnExperimentConfig::from_str(json, ConfigFormat::detect("/home/jethros/setup", json), "setup").unwrap();
+    assert_eq!(config.setup, 3);
+    assert_eq!(config.iter, 2);
+    assert!(config.inst);
+    assert_eq!(config.disk, DiskType::Ssd);
+    assert_eq!(config.p2p_type, "app_p2p-controlled");
+    assert_eq!(config.effective_setup(config.p2p_setup), 5);
+    assert_eq!(config.effective_setup(config.rdr_setup), 3);
+    let param = config.expr_param();
+    assert_eq!(
+        (param.setup, param.p2p_setup, param.expr_time),
+        (3, 5, LONG_MEASURE_TIME)
+    );
+}
+
+#[test]
+fn toml_with_defaults() {
+    let toml = "setup = 4\ninst = false\n";
+    assert_eq!(ConfigFormat::detect("setup.toml", toml), ConfigFormat::Toml);
+    let config = PvnExperimentConfig::from_str(toml, ConfigFormat::Toml, "setup.toml").unwrap();
+    assert_eq!(
+        config,
+        PvnExperimentConfig {
+            setup: 4,
+            ..Default::default()
+        }
+    );
+    assert_eq!(config.expr_time(), SHORT_MEASURE_TIME);
+}
+
+#[test]
+fn bad_configs() {
+    let err = PvnExperimentConfig::from_str(r#"{"mode": "forever"}"#, ConfigFormat::Json, "a").unwrap_err();
+    match err.kind() {
+        ErrorKind::BadExperimentConfig(path, reason) => {
+            assert_eq!(path, "a");
+            assert!(reason.contains("forever"), "{}", reason);
+        }
+        e => panic!("unexpected error {}", e),
+    }
+    let err = PvnExperimentConfig::from_str(r#"{"setup": "0"}"#, ConfigFormat::Json, "b").unwrap_err();
+    assert!(err.to_string().contains("setup 0"), "{}", err);
+    let err = PvnExperimentConfig::from_str("inst = \"maybe\"", ConfigFormat::Toml, "c").unwrap_err();
+    assert!(err.to_string().contains("maybe"), "{}", err);
+    assert!(PvnExperimentConfig::from_file("/nonexistent/setup.json").is_err());
+}
+
+#[test]
+fn experiment_config_must_be_set() {
+    // No file is guessed at when none was configured.
+    match experiment_config() {
+        Err(e) => assert!(matches!(e.kind(), ErrorKind::NoExperimentConfig)),
+        Ok(_) => panic!("loaded a configuration nobody set"),
+    }
+    set_experiment_config(PvnExperimentConfig {
+        setup: 2,
+        ..Default::default()
+    });
+    assert_eq!(experiment_config().unwrap().setup, 2);
+}
@@ -0,0 +1,160 @@
+extern crate e2d2;
+use e2d2::headers::IpHeader;
+use e2d2::state::*;
+use e2d2::utils::{checksum, ipv4_extract_flow, ns_to_cycles};
+use std::time::Duration;
+
+/// Fragment of datagram `id` holding `data` at byte `offset`.
+fn fragment(id: u16, offset: usize, more: bool, data: &[u8]) -> (IpHeader, Vec<u8>) {
+    let mut ip = IpHeader::new();
+    ip.set_version(4);
+    ip.set_ihl(5);
+    ip.set_length((20 + data.len()) as u16);
+    ip.set_id(id);
+    ip.set_flags(if more { 0x1 } else { 0 });
+    ip.set_fragment_offset((offset / 8) as u16);
+    ip.set_ttl(64);
+    ip.set_protocol(17);
+    ip.set_src(0x0a00_0001);
+    ip.set_dst(0xc0a8_0001);
+    (ip, data.to_vec())
+}
+
+fn insert(reassembler: &mut Ipv4Reassembler, frag: &(IpHeader, Vec<u8>), now: u64) -> Reassembly {
+    reassembler.insert(&frag.0, &frag.1, now)
+}
+
+fn datagram() -> Vec<u8> {
+    (0..3000).map(|i| (i * 7) as u8).collect()
+}
+
+#[test]
+fn reassemble_out_of_order() {
+    let mut reassembler = Ipv4Reassembler::new(Default::default());
+    let data = datagram();
+    let first = fragment(1, 0, true, &data[..1480]);
+    let middle = fragment(1, 1480, true, &data[1480..2960]);
+    // Ethernet padding after the IP length is ignored.
+    let mut last = fragment(1, 2960, false, &data[2960..]);
+    last.1.extend_from_slice(&[0xff; 6]);
+
+    assert_eq!(insert(&mut reassembler, &last, 0), Reassembly::Buffered);
+    assert_eq!(insert(&mut reassembler, &first, 0), Reassembly::Buffered);
+    assert_eq!(reassembler.stats().pending, 1);
+    let bytes = match insert(&mut reassembler, &middle, 0) {
+        Reassembly::Complete(bytes) => bytes,
+        other => panic!("expected a datagram, got {:?}", other),
+    };
+    assert_eq!(bytes.len(), 3020);
+    assert_eq!(&bytes[20..], &data[..]);
+    assert_eq!(&bytes[2..4], &3020u16.to_be_bytes());
+    assert_eq!(&bytes[6..8], &[0, 0]);
+    assert_eq!(checksum(&bytes[..20]), 0);
+
+    let stats = reassembler.stats();
+    assert_eq!(
+        (stats.fragments, stats.reassembled, stats.pending, stats.memory),
+        (3, 1, 0, 0)
+    );
+
+    let (ip, payload) = fragment(2, 0, false, &data[..100]);
+    assert_eq!(reassembler.insert(&ip, &payload, 0), Reassembly::Unfragmented);
+}
+
+#[test]
+fn overlaps() {
+    let mut reassembler = Ipv4Reassembler::new(Default::default());
+    let data = datagram();
+    let first = fragment(1, 0, true, &data[..1480]);
+    let last = fragment(1, 1480, false, &data[1480..]);
+    assert_eq!(insert(&mut reassembler, &first, 0), Reassembly::Buffered);
+    // Duplicates are ignored.
+    assert_eq!(insert(&mut reassembler, &first, 0), Reassembly::Dropped);
+    assert_eq!(reassembler.stats().pending, 1);
+    match insert(&mut reassembler, &last, 0) {
+        Reassembly::Complete(bytes) => assert_eq!(&bytes[20..], &data[..]),
+        other => panic!("expected a datagram, got {:?}", other),
+    }
+
+    // Anything else overlapping discards the datagram.
+    assert_eq!(
+        insert(&mut reassembler, &fragment(2, 0, true, &data[..1480]), 0),
+        Reassembly::Buffered
+    );
+    assert_eq!(
+        insert(&mut reassembler, &fragment(2, 1472, false, &data[1472..]), 0),
+        Reassembly::Dropped
+    );
+    let stats = reassembler.stats();
+    assert_eq!((stats.overlaps, stats.pending, stats.memory), (2, 0, 0));
+}
+
+#[test]
+fn invalid_fragments() {
+    let mut reassembler = Ipv4Reassembler::new(Default::default());
+    let data = datagram();
+    // Not a multiple of 8 bytes, past the largest datagram, or truncated.
+    let odd = fragment(1, 0, true, &data[..100]);
+    let huge = fragment(1, 65528, true, &data[..16]);
+    let (ip, payload) = fragment(1, 0, true, &data[..1480]);
+    assert_eq!(insert(&mut reassembler, &odd, 0), Reassembly::Dropped);
+    assert_eq!(insert(&mut reassembler, &huge, 0), Reassembly::Dropped);
+    assert_eq!(reassembler.insert(&ip, &payload[..1000], 0), Reassembly::Dropped);
+
+    // Data past the end given by the last fragment.
+    assert_eq!(
+        insert(&mut reassembler, &fragment(2, 1480, false, &data[1480..1600]), 0),
+        Reassembly::Buffered
+    );
+    assert_eq!(
+        insert(&mut reassembler, &fragment(2, 1600, true, &data[1600..1608]), 0),
+        Reassembly::Dropped
+    );
+    let stats = reassembler.stats();
+    assert_eq!((stats.invalid, stats.pending, stats.memory), (4, 0, 0));
+}
+
+#[test]
+fn timeouts_and_limits() {
+    let mut reassembler = Ipv4Reassembler::new(ReassemblyConfig {
+        timeout: Duration::from_secs(1),
+        max_datagrams: 2,
+        max_memory: 4000,
+    });
+    let second = ns_to_cycles(1_000_000_000);
+    let data = datagram();
+    assert_eq!(
+        insert(&mut reassembler, &fragment(1, 0, true, &data[..1480]), 0),
+        Reassembly::Buffered
+    );
+    assert_eq!(
+        insert(&mut reassembler, &fragment(2, 0, true, &data[..1480]), second / 2),
+        Reassembly::Buffered
+    );
+    // Too many datagrams, then too much memory.
+    assert_eq!(
+        insert(&mut reassembler, &fragment(3, 0, true, &data[..1480]), second / 2),
+        Reassembly::Dropped
+    );
+    assert_eq!(
+        insert(&mut reassembler, &fragment(2, 1480, true, &data[..1480]), second / 2),
+        Reassembly::Dropped
+    );
+    assert_eq!(reassembler.stats().dropped, 2);
+
+    assert_eq!(reassembler.expire(second / 2), 0);
+    assert_eq!(reassembler.expire(second + second / 10), 1);
+    assert_eq!(reassembler.stats().memory, 1500);
+    assert_eq!(reassembler.expire(2 * second), 1);
+    let stats = reassembler.stats();
+    assert_eq!((stats.timeouts, stats.pending, stats.memory), (2, 0, 0));
+}
+
+#[test]
+fn non_first_fragments_have_no_flow() {
+    let mut bytes = vec![0x45, 0, 0, 28, 0, 1, 0x20, 0, 64, 17, 0, 0, 10, 0, 0, 1, 192, 168, 0, 1];
+    bytes.extend_from_slice(&[0x12, 0x34, 0, 53, 0, 8, 0, 0]);
+    assert_eq!(ipv4_extract_flow(&bytes).map(|f| f.src_port), Some(0x1234));
+    bytes[7] = 185;
+    assert_eq!(ipv4_extract_flow(&bytes), None);
+}
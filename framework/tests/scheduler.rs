@@ -0,0 +1,312 @@
+extern crate e2d2;
+use e2d2::scheduler::*;
+use e2d2::utils::rdtsc_unsafe;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// A task that spins for `cycles` and reports `work` packets, counting how often it ran.
+struct SpinTask {
+    runs: Rc<Cell<usize>>,
+    work: Rc<Cell<usize>>,
+    cycles: u64,
+}
+
+impl SpinTask {
+    fn new(cycles: u64, work: usize) -> (SpinTask, Rc<Cell<usize>>, Rc<Cell<usize>>) {
+        let runs = Rc::new(Cell::new(0));
+        let work = Rc::new(Cell::new(work));
+        let task = SpinTask {
+            runs: runs.clone(),
+            work: work.clone(),
+            cycles,
+        };
+        (task, runs, work)
+    }
+}
+
+impl Executable for SpinTask {
+    fn execute(&mut self) -> usize {
+        let start = rdtsc_unsafe();
+        while rdtsc_unsafe() - start < self.cycles {}
+        self.runs.set(self.runs.get() + 1);
+        self.work.get()
+    }
+
+    fn dependencies(&mut self) -> Vec<usize> {
+        vec![]
+    }
+}
+
+#[test]
+fn round_robin_by_default() {
+    let mut sched = StandaloneScheduler::new();
+    let (a, a_runs, _) = SpinTask::new(0, 1);
+    let (b, b_runs, _) = SpinTask::new(0, 0);
+    sched.add_task(a).unwrap();
+    sched.add_task(b).unwrap();
+    for _ in 0..10 {
+        sched.execute_one();
+    }
+    assert_eq!(a_runs.get(), 5);
+    assert_eq!(b_runs.get(), 5);
+}
+
+#[test]
+fn strict_priority() {
+    let mut sched = StandaloneScheduler::new();
+    let (high, high_runs, high_work) = SpinTask::new(0, 1);
+    let (low, low_runs, _) = SpinTask::new(0, 1);
+    sched.add_task(low).unwrap();
+    sched
+        .add_task_with_policy(high, SchedulingPolicy::Priority { level: 1 })
+        .unwrap();
+    for _ in 0..10 {
+        sched.execute_one();
+    }
+    assert_eq!(high_runs.get(), 10);
+    assert_eq!(low_runs.get(), 0);
+
+    // Once the high priority task goes idle, the low priority one gets every other turn.
+    high_work.set(0);
+    for _ in 0..10 {
+        sched.execute_one();
+    }
+    assert_eq!(high_runs.get(), 15);
+    assert_eq!(low_runs.get(), 5);
+}
+
+#[test]
+fn weighted_share() {
+    let mut sched = StandaloneScheduler::new();
+    let (light, light_runs, _) = SpinTask::new(2_000, 1);
+    let (heavy, heavy_runs, _) = SpinTask::new(2_000, 1);
+    sched
+        .add_task_with_policy(light, SchedulingPolicy::Weighted { weight: 1 })
+        .unwrap();
+    sched
+        .add_task_with_policy(
+            heavy,
+            SchedulingPolicy::Drr {
+                quantum: 3 * WEIGHT_QUANTUM,
+            },
+        )
+        .unwrap();
+    for _ in 0..2_000 {
+        sched.execute_one();
+    }
+    let ratio = heavy_runs.get() as f64 / light_runs.get() as f64;
+    assert!(ratio > 2.0 && ratio < 4.0, "ratio {}", ratio);
+}
+
+#[test]
+fn drr_overrun_paid_back() {
+    let mut sched = StandaloneScheduler::new();
+    // Overruns its quantum four times over on every run, and finds nothing to do.
+    let (slow, slow_runs, _) = SpinTask::new(4 * WEIGHT_QUANTUM, 0);
+    let (fast, fast_runs, _) = SpinTask::new(0, 1);
+    sched
+        .add_task_with_policy(
+            slow,
+            SchedulingPolicy::Drr {
+                quantum: WEIGHT_QUANTUM,
+            },
+        )
+        .unwrap();
+    sched.add_task(fast).unwrap();
+    for _ in 0..20 {
+        sched.execute_one();
+    }
+    // The overrun is paid back by skipping the next turns, even though the task was idle.
+    assert_eq!(slow_runs.get() + fast_runs.get(), 20);
+    assert!(
+        fast_runs.get() >= 3 * slow_runs.get(),
+        "{} vs {}",
+        fast_runs.get(),
+        slow_runs.get()
+    );
+}
+
+#[test]
+fn task_stats() {
+    let mut sched = StandaloneScheduler::new();
+    let (busy, _, _) = SpinTask::new(0, 4);
+    let (idle, _, _) = SpinTask::new(0, 0);
+    sched.add_task(busy).unwrap();
+    sched.add_task(idle).unwrap();
+    for _ in 0..6 {
+        sched.execute_one();
+    }
+    let stats = sched.task_stats();
+    assert_eq!(stats.len(), 2);
+    assert_eq!(
+        (stats[0].task, stats[0].invocations, stats[0].packets, stats[0].idle),
+        (1, 3, 12, 0)
+    );
+    assert_eq!(
+        (stats[1].task, stats[1].invocations, stats[1].packets, stats[1].idle),
+        (2, 3, 0, 3)
+    );
+    assert!(stats[0].cycles > 0);
+    assert_eq!(stats[1].policy, SchedulingPolicy::RoundRobin);
+}
+
+#[test]
+fn remove_pause_replace_tasks() {
+    let mut sched = StandaloneScheduler::new();
+    let (a, a_runs, _) = SpinTask::new(0, 1);
+    let (b, b_runs, _) = SpinTask::new(0, 1);
+    let (c, c_runs, _) = SpinTask::new(0, 1);
+    let a = sched.add_task(a).unwrap();
+    let b = sched.add_task(b).unwrap();
+    sched.add_task(c).unwrap();
+
+    sched.pause_task(b).unwrap();
+    for _ in 0..4 {
+        sched.execute_one();
+    }
+    assert_eq!((a_runs.get(), b_runs.get(), c_runs.get()), (2, 0, 2));
+
+    sched.resume_task(b).unwrap();
+    sched.remove_task(a).unwrap();
+    assert!(sched.remove_task(a).is_err());
+    for _ in 0..4 {
+        sched.execute_one();
+    }
+    assert_eq!((a_runs.get(), b_runs.get(), c_runs.get()), (2, 2, 4));
+
+    let (d, d_runs, _) = SpinTask::new(0, 1);
+    sched.replace_task(b, d).unwrap();
+    for _ in 0..4 {
+        sched.execute_one();
+    }
+    assert_eq!((b_runs.get(), c_runs.get(), d_runs.get()), (2, 6, 2));
+    let stats = sched.task_stats();
+    assert_eq!(stats[0].task, b);
+    assert_eq!(stats[0].invocations, 2);
+
+    // With every task paused nothing runs, and the scheduler does not spin forever looking for work.
+    for task in sched.task_stats().iter().map(|t| t.task) {
+        sched.pause_task(task).unwrap();
+    }
+    sched.execute_one();
+    assert_eq!((c_runs.get(), d_runs.get()), (6, 2));
+}
+
+#[test]
+fn pipelines() {
+    let mut sched = StandaloneScheduler::new();
+    let (a, a_runs, _) = SpinTask::new(0, 1);
+    let (b, b_runs, _) = SpinTask::new(0, 1);
+    let (c, c_runs, _) = SpinTask::new(0, 1);
+    let tasks = std::cell::RefCell::new(vec![a, b]);
+    sched.run_pipeline(7, &|s| {
+        for task in tasks.borrow_mut().drain(..) {
+            s.add_task(task).unwrap();
+        }
+    });
+    sched.add_task(c).unwrap();
+    assert_eq!(sched.pipeline_tasks(7), vec![1, 2]);
+    assert_eq!(sched.task_stats()[2].pipeline, None);
+
+    sched.set_pipeline_paused(7, true);
+    for _ in 0..3 {
+        sched.execute_one();
+    }
+    assert_eq!((a_runs.get(), b_runs.get(), c_runs.get()), (0, 0, 3));
+
+    sched.set_pipeline_paused(7, false);
+    assert_eq!(sched.remove_pipeline(7), 2);
+    assert!(sched.pipeline_tasks(7).is_empty());
+    for _ in 0..3 {
+        sched.execute_one();
+    }
+    assert_eq!((a_runs.get(), b_runs.get(), c_runs.get()), (0, 0, 6));
+}
+
+#[test]
+fn idle_backoff() {
+    let mut sched = StandaloneScheduler::new();
+    let (task, runs, work) = SpinTask::new(0, 0);
+    sched.add_task(task).unwrap();
+    sched.set_idle_backoff(Some(IdleBackoff {
+        pause_after: 1,
+        sleep_after: 3,
+        sleep_us: 1_000,
+        rx_interrupts: false,
+    }));
+    for _ in 0..5 {
+        sched.execute_one();
+    }
+    let stats = sched.stats();
+    assert_eq!(runs.get(), 5);
+    assert_eq!(stats.sleeps, 2);
+    assert!(stats.sleep_cycles > 0);
+    // Time asleep is not charged to the task.
+    assert!(stats.tasks[0].cycles < stats.sleep_cycles);
+
+    // Work resets the count of idle passes.
+    work.set(1);
+    for _ in 0..5 {
+        sched.execute_one();
+    }
+    assert_eq!(sched.stats().sleeps, 3);
+}
+
+#[test]
+fn hooks_not_charged_to_tasks() {
+    let mut sched = StandaloneScheduler::new();
+    let (task, runs, _) = SpinTask::new(0, 1);
+    sched.add_task(task).unwrap();
+    // A hook ticking on every pass, spinning far longer than the task runs.
+    sched
+        .add_hooks(PipelineHooks::new().on_tick(std::time::Duration::from_millis(0), || {
+            let start = rdtsc_unsafe();
+            while rdtsc_unsafe() - start < 10_000_000 {}
+        }))
+        .unwrap();
+    for _ in 0..4 {
+        sched.execute_one();
+    }
+    assert_eq!(runs.get(), 4);
+    assert!(sched.task_stats()[0].cycles < 10_000_000);
+}
+
+#[test]
+fn pipeline_hooks() {
+    let mut sched = StandaloneScheduler::new();
+    let (task, runs, _) = SpinTask::new(0, 1);
+    let events = Rc::new(std::cell::RefCell::new(vec![]));
+    let (start, tick, stop) = (events.clone(), events.clone(), events.clone());
+    let ticks_at_start = runs.clone();
+    let hooks = std::cell::RefCell::new(Some(
+        PipelineHooks::new()
+            .on_start(move || start.borrow_mut().push(format!("start {}", ticks_at_start.get())))
+            .on_tick(std::time::Duration::from_millis(5), move || {
+                tick.borrow_mut().push(String::from("tick"))
+            })
+            .on_stop(move || stop.borrow_mut().push(String::from("stop"))),
+    ));
+    let task = std::cell::RefCell::new(Some(task));
+    sched.run_pipeline(3, &|s| {
+        s.add_task(task.borrow_mut().take().unwrap()).unwrap();
+        s.add_hooks(hooks.borrow_mut().take().unwrap()).unwrap();
+    });
+    assert!(events.borrow().is_empty());
+
+    sched.execute_one();
+    assert_eq!(*events.borrow(), vec!["start 0"]);
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    sched.execute_one();
+    sched.execute_one();
+    assert_eq!(*events.borrow(), vec!["start 0", "tick"]);
+
+    sched.remove_pipeline(3);
+    assert_eq!(*events.borrow(), vec!["start 0", "tick", "stop"]);
+    assert_eq!(runs.get(), 3);
+    drop(sched);
+    assert_eq!(events.borrow().len(), 3);
+
+    assert!(embedded_scheduler::EmbeddedScheduler::new()
+        .add_hooks(PipelineHooks::new())
+        .is_err());
+}
@@ -0,0 +1,68 @@
+#![cfg(feature = "heap_mbuf")]
+extern crate e2d2;
+use e2d2::allocators::CacheAligned;
+use e2d2::config::NetbricksConfiguration;
+use e2d2::headers::*;
+use e2d2::interface::VirtualQueue;
+use e2d2::operators::*;
+use e2d2::scheduler::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn stop_drains_queued_packets() {
+    let mut configuration = NetbricksConfiguration::new_with_name("shutdown-test");
+    configuration.cores = vec![0];
+    configuration.shutdown_signals = false;
+    let mut context = initialize_system(&configuration).unwrap();
+    context.start_schedulers();
+
+    let grouped = Arc::new(AtomicUsize::new(0));
+    let consumed = Arc::new(AtomicUsize::new(0));
+    let at_shutdown = Arc::new(Mutex::new(None));
+    let (g, c, s) = (grouped.clone(), consumed.clone(), at_shutdown.clone());
+    context.add_test_pipeline(Arc::new(
+        move |ports: Vec<CacheAligned<VirtualQueue>>, sched: &mut StandaloneScheduler| {
+            let (grouped, backlog, consumed) = (g.clone(), g.clone(), c.clone());
+            let (counted, seen) = (c.clone(), c.clone());
+            // Keep the group by queue from overflowing, which would lose packets.
+            let mut groups = ReceiveBatch::new(ports[0].clone())
+                .parse::<MacHeader>()
+                .filter(Box::new(move |_| {
+                    backlog.load(Ordering::Relaxed) - consumed.load(Ordering::Relaxed) < 512
+                }))
+                .group_by(
+                    1,
+                    Box::new(move |_| {
+                        grouped.fetch_add(1, Ordering::Relaxed);
+                        0
+                    }),
+                    sched,
+                );
+            let consumer = groups
+                .get_group(0)
+                .unwrap()
+                .transform(Box::new(move |_| {
+                    counted.fetch_add(1, Ordering::Relaxed);
+                }))
+                .send(ports[0].clone());
+            // Let the consumer run far less often than the group by, so packets queue up between them.
+            sched
+                .add_task_with_policy(consumer, SchedulingPolicy::Drr { quantum: 1000 })
+                .unwrap();
+            let (seen, at_shutdown) = (seen.clone(), s.clone());
+            add_shutdown_hook(move || *at_shutdown.lock().unwrap() = Some(seen.load(Ordering::Relaxed)));
+        },
+    ));
+    context.execute();
+    thread::sleep(Duration::from_millis(100));
+
+    context.stop();
+    let grouped = grouped.load(Ordering::Relaxed);
+    assert!(grouped > 0);
+    assert_eq!(consumed.load(Ordering::Relaxed), grouped);
+    assert_eq!(*at_shutdown.lock().unwrap(), Some(grouped));
+    assert!(context.virtual_ports.is_empty());
+}
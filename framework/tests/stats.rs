@@ -0,0 +1,75 @@
+extern crate e2d2;
+extern crate serde_json;
+use e2d2::interface::VirtualPort;
+use e2d2::stats::*;
+use e2d2::utils::LatencyHistogram;
+use serde_json::Value;
+
+#[test]
+fn summary_from_samples() {
+    let samples: Vec<f64> = (1..=100).map(f64::from).collect();
+    let summary = Summary::from_samples(&samples, &[50.0, 99.0, 100.0], 10);
+    assert_eq!(summary.count, 100);
+    assert_eq!((summary.min, summary.max, summary.mean), (1.0, 100.0, 50.5));
+    assert!((summary.stddev - 29.011).abs() < 1e-3);
+    assert_eq!(summary.percentile(50.0), Some(50.0));
+    assert_eq!(summary.percentile(99.0), Some(99.0));
+    assert_eq!(summary.percentile(100.0), Some(100.0));
+    assert_eq!(summary.percentile(90.0), None);
+    assert_eq!(summary.histogram.len(), 10);
+    assert_eq!(summary.histogram.iter().map(|b| b.count).sum::<u64>(), 100);
+    assert_eq!(summary.histogram[9].high, 100.0);
+
+    assert_eq!(Summary::from_samples(&[], &DEFAULT_PERCENTILES, 10), Summary::default());
+    assert_eq!(Summary::from_samples(&[f64::NAN], &DEFAULT_PERCENTILES, 10), Summary::default());
+
+    let summary = Summary::from_samples(&[3.0, f64::NAN, 1.0, 2.0], &[50.0], 1);
+    assert_eq!((summary.count, summary.min, summary.max, summary.mean), (3, 1.0, 3.0, 2.0));
+}
+
+#[test]
+fn summary_from_latency() {
+    let histogram = LatencyHistogram::new();
+    for cycles in 1..=1_000 {
+        histogram.record(cycles);
+    }
+    let summary = Summary::from_latency(&histogram.snapshot(), &[50.0], 2.0);
+    assert_eq!(summary.count, 1_000);
+    assert_eq!((summary.min, summary.max), (2.0, 2_000.0));
+    let median = summary.percentile(50.0).unwrap();
+    assert!((median - 1_000.0).abs() / 1_000.0 < 1.0 / 64.0, "{}", median);
+    assert!((summary.stddev - 577.639).abs() / 577.639 < 0.01, "{}", summary.stddev);
+}
+
+#[test]
+fn report_export() {
+    let port = VirtualPort::new(1).unwrap();
+    let mut report = StatsReport::new("run, 1");
+    report
+        .add_summary("latency", Summary::from_samples(&[1.0, 2.0, 3.0], &[50.0], 1))
+        .add_ports(Some(PortStats::virtual_port("virtual:0", &port)));
+
+    let json: Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(json["name"], "run, 1");
+    assert_eq!(json["summaries"]["latency"]["count"], 3);
+    assert_eq!(json["summaries"]["latency"]["percentiles"][0]["value"], 2.0);
+    assert_eq!(json["ports"][0]["queue"], Value::Null);
+
+    let csv = report.to_csv();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines[0], "section,name,key,value");
+    assert!(lines.contains(&"summary,latency,count,3"));
+    assert!(lines.contains(&"summary,latency,p50,2"));
+    assert!(lines.contains(&"histogram,latency,1:3,3"));
+    assert!(lines.contains(&"port,virtual:0,rx,0"));
+
+    let dir = std::env::temp_dir();
+    let csv_path = dir.join(format!("stats-{}.csv", std::process::id()));
+    let json_path = dir.join(format!("stats-{}.json", std::process::id()));
+    report.write(&csv_path).unwrap();
+    report.write(&json_path).unwrap();
+    assert_eq!(std::fs::read_to_string(&csv_path).unwrap(), csv);
+    assert_eq!(std::fs::read_to_string(&json_path).unwrap(), report.to_json());
+    std::fs::remove_file(csv_path).unwrap();
+    std::fs::remove_file(json_path).unwrap();
+}
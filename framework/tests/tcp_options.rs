@@ -0,0 +1,122 @@
+extern crate e2d2;
+use e2d2::headers::*;
+use e2d2::utils::{checksum, ChecksumMode};
+
+/// A segment with `options`, whose checksum covers the segment alone (no pseudo-header) so it can be checked with
+/// `checksum`.
+fn segment(options: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0u8; 20];
+    bytes[0..2].copy_from_slice(&40_000u16.to_be_bytes());
+    bytes[2..4].copy_from_slice(&80u16.to_be_bytes());
+    bytes[12] = (((20 + options.len()) / 4) << 4) as u8;
+    bytes[13] = 0x02;
+    bytes.extend_from_slice(options);
+    bytes.extend_from_slice(b"payload");
+    let csum = checksum(&bytes);
+    bytes[16..18].copy_from_slice(&csum.to_be_bytes());
+    bytes
+}
+
+fn header(bytes: &mut [u8]) -> &mut TcpHeader {
+    unsafe { &mut *(bytes.as_mut_ptr() as *mut TcpHeader) }
+}
+
+const SYN_OPTIONS: [u8; 20] = [
+    2, 4, 0x05, 0xb4, // MSS 1460
+    4, 2, // SACK permitted
+    8, 10, 0, 0, 0, 1, 0, 0, 0, 0, // timestamps 1, 0
+    1, // NOP
+    3, 3, 7, // window scale 7
+];
+
+#[test]
+fn parse_syn_options() {
+    let mut bytes = segment(&SYN_OPTIONS);
+    let tcp = header(&mut bytes);
+    assert_eq!(tcp.option_bytes().len(), 20);
+    let options: Vec<_> = tcp.options().collect();
+    assert_eq!(
+        options,
+        vec![
+            TcpOption::Mss(1460),
+            TcpOption::SackPermitted,
+            TcpOption::Timestamps { value: 1, echo: 0 },
+            TcpOption::WindowScale(7),
+        ]
+    );
+    assert_eq!(tcp.mss(), Some(1460));
+    assert_eq!(tcp.window_scale(), Some(7));
+    assert!(tcp.sack_permitted());
+    assert_eq!(tcp.timestamps(), Some((1, 0)));
+}
+
+#[test]
+fn parse_sack_and_malformed() {
+    let mut bytes = segment(&[
+        1, 1, 5, 18, 0, 0, 0, 10, 0, 0, 0, 20, 0, 0, 0, 30, 0, 0, 0, 40, 0, 0, 0, 0,
+    ]);
+    let sack = match header(&mut bytes).options().next() {
+        Some(TcpOption::Sack(blocks)) => blocks.collect::<Vec<_>>(),
+        other => panic!("expected SACK, got {:?}", other),
+    };
+    assert_eq!(sack, vec![(10, 20), (30, 40)]);
+
+    // Options after the end of the list are ignored, a length running past the header stops iteration.
+    let mut bytes = segment(&[3, 3, 2, 0, 2, 4, 5, 0]);
+    assert_eq!(header(&mut bytes).options().count(), 1);
+    let mut bytes = segment(&[3, 3, 2, 2, 40, 5, 0, 0]);
+    assert_eq!(
+        header(&mut bytes).options().collect::<Vec<_>>(),
+        vec![TcpOption::WindowScale(2)]
+    );
+    let mut bytes = segment(&[2, 3, 5, 1]);
+    assert_eq!(
+        header(&mut bytes).options().next(),
+        Some(TcpOption::Unknown { kind: 2, data: &[5] })
+    );
+    let mut bytes = segment(&[]);
+    assert_eq!(header(&mut bytes).options().next(), None);
+}
+
+#[test]
+fn clamp_mss() {
+    let mut bytes = segment(&SYN_OPTIONS);
+    assert!(!header(&mut bytes).clamp_mss(1500, ChecksumMode::Software));
+    assert!(header(&mut bytes).clamp_mss(1400, ChecksumMode::Software));
+    assert_eq!(header(&mut bytes).mss(), Some(1400));
+    assert_eq!(checksum(&bytes), 0);
+
+    let mut bytes = segment(&[1, 1, 1, 1]);
+    assert!(!header(&mut bytes).clamp_mss(1400, ChecksumMode::Software));
+}
+
+#[test]
+fn rewrite_options_offload() {
+    // The checksum field holds the pseudo-header seed left for the NIC, which the options do not change.
+    let mut bytes = segment(&SYN_OPTIONS);
+    bytes[16..18].copy_from_slice(&0x1234u16.to_be_bytes());
+    assert!(header(&mut bytes).clamp_mss(1400, ChecksumMode::Offload));
+    assert_eq!(header(&mut bytes).mss(), Some(1400));
+    assert_eq!(header(&mut bytes).checksum(), 0x1234);
+    let stripped =
+        header(&mut bytes).strip_options(ChecksumMode::Offload, |option| option.kind() == TCP_OPT_TIMESTAMPS);
+    assert_eq!(stripped, 1);
+    assert_eq!(header(&mut bytes).checksum(), 0x1234);
+}
+
+#[test]
+fn strip_options() {
+    let mut bytes = segment(&SYN_OPTIONS);
+    let stripped = header(&mut bytes).strip_options(ChecksumMode::Software, |option| {
+        option.kind() == TCP_OPT_TIMESTAMPS || option.kind() == TCP_OPT_SACK_PERMITTED
+    });
+    assert_eq!(stripped, 2);
+    assert_eq!(checksum(&bytes), 0);
+    let tcp = header(&mut bytes);
+    assert_eq!(
+        tcp.options().collect::<Vec<_>>(),
+        vec![TcpOption::Mss(1460), TcpOption::WindowScale(7)]
+    );
+    assert_eq!(tcp.option_bytes().len(), 20);
+    assert_eq!(&bytes[40..], b"payload");
+}
@@ -0,0 +1,103 @@
+extern crate e2d2;
+use e2d2::scheduler::*;
+use e2d2::utils::TimerWheel;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn expires_at_deadline() {
+    // One cycle per tick, so deadlines are ticks.
+    let mut wheel = TimerWheel::with_tick_cycles(1, 0);
+    for &deadline in &[5u64, 63, 64, 65, 4095, 4096, 300_000, 20_000_000, 1 << 40] {
+        wheel.arm_at(deadline, deadline);
+    }
+    assert_eq!(wheel.len(), 9);
+    let mut now = 0;
+    for &deadline in &[5u64, 63, 64, 65, 4095, 4096, 300_000, 20_000_000, 1 << 40] {
+        assert!(wheel.advance(deadline - 1).is_empty(), "{} expired early", deadline);
+        assert_eq!(wheel.advance(deadline), vec![deadline]);
+        now = deadline;
+    }
+    assert!(wheel.is_empty());
+    assert!(wheel.advance(now + 1_000_000).is_empty());
+}
+
+#[test]
+fn expires_in_order() {
+    let mut wheel = TimerWheel::with_tick_cycles(1, 0);
+    for deadline in (1..10_000u64).rev().step_by(7) {
+        wheel.arm_at(deadline, deadline);
+    }
+    let expired = wheel.advance(10_000);
+    let mut sorted = expired.clone();
+    sorted.sort();
+    assert_eq!(expired, sorted);
+    assert_eq!(expired.len(), 1429);
+}
+
+#[test]
+fn cancel_and_reset() {
+    let mut wheel = TimerWheel::with_tick_cycles(10, 0);
+    let a = wheel.arm_at(100, 'a');
+    let b = wheel.arm_at(200, 'b');
+    let c = wheel.arm_at(5_000, 'c');
+    assert_eq!(wheel.cancel(a), Some('a'));
+    assert_eq!(wheel.cancel(a), None);
+    assert!(!wheel.is_armed(a));
+
+    // Push back, then bring forward.
+    assert!(wheel.reset_at(b, 1_000));
+    assert!(wheel.reset_at(c, 300));
+    assert_eq!(wheel.advance(999), vec!['c']);
+    assert!(wheel.is_armed(b));
+    assert_eq!(wheel.advance(1_000), vec!['b']);
+    assert!(!wheel.reset_at(b, 2_000));
+
+    // Handles of freed timers do not refer to timers reusing their storage.
+    let d = wheel.arm_at(1_100, 'd');
+    assert_ne!(d, a);
+    assert_eq!(wheel.cancel(b), None);
+    assert!(wheel.is_armed(d));
+    assert_eq!(wheel.advance(1_100), vec!['d']);
+}
+
+#[test]
+fn past_deadlines_expire_next_tick() {
+    let mut wheel = TimerWheel::with_tick_cycles(10, 1_000);
+    wheel.advance(2_000);
+    wheel.arm_at(500, 1);
+    assert!(wheel.advance(2_005).is_empty());
+    assert_eq!(wheel.advance(2_010), vec![1]);
+}
+
+#[test]
+fn scheduler_runs_timers() {
+    let mut sched = StandaloneScheduler::new();
+    sched.add_task(|| {}).unwrap();
+    let fired = Rc::new(RefCell::new(vec![]));
+
+    let (f, g) = (fired.clone(), fired.clone());
+    arm_timer(Duration::from_millis(1), move || {
+        f.borrow_mut().push("short");
+        // Callbacks can arm timers of their own.
+        arm_timer(Duration::from_millis(1), move || g.borrow_mut().push("rearmed"));
+    });
+    let f = fired.clone();
+    let cancelled = arm_timer(Duration::from_millis(1), move || f.borrow_mut().push("cancelled"));
+    let f = fired.clone();
+    arm_timer(Duration::from_secs(60), move || f.borrow_mut().push("long"));
+    assert!(cancel_timer(cancelled));
+    assert_eq!(pending_timers(), 2);
+
+    sched.execute_one();
+    assert!(fired.borrow().is_empty());
+    thread::sleep(Duration::from_millis(5));
+    sched.execute_one();
+    assert_eq!(*fired.borrow(), vec!["short"]);
+    thread::sleep(Duration::from_millis(5));
+    sched.execute_one();
+    assert_eq!(*fired.borrow(), vec!["short", "rearmed"]);
+    assert_eq!(pending_timers(), 1);
+}
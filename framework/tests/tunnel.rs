@@ -0,0 +1,217 @@
+#![cfg(feature = "heap_mbuf")]
+extern crate e2d2;
+mod common;
+use common::*;
+use e2d2::common::EmptyMetadata;
+use e2d2::headers::*;
+use e2d2::interface::*;
+use e2d2::operators::*;
+use e2d2::scheduler::*;
+
+const LOCAL: u32 = 0x0a00_0001;
+const REMOTE: u32 = 0x0a00_0002;
+
+/// An Ethernet frame carrying a UDP datagram from `src_port` with `len` bytes of data.
+fn inner_frame(src_port: u16, len: usize) -> Packet<MacHeader, EmptyMetadata> {
+    let ip = ipv4_header(0xc0a8_0001, 0xc0a8_0002, 17, 8 + len);
+    ipv4_packet(&ip, &udp_header(src_port, 53, len), len).deparse_header(14)
+}
+
+fn template() -> VxlanTemplate {
+    VxlanTemplate::new(
+        MacAddress::new(2, 0, 0, 0, 0, 1),
+        MacAddress::new(2, 0, 0, 0, 0, 2),
+        LOCAL,
+        REMOTE,
+        0x12_3456,
+    )
+}
+
+#[test]
+fn vxlan_encap_decap() {
+    let mut template = template();
+    let inner = inner_frame(1000, 100);
+    let before = header_bytes(&inner);
+    let outer = template.encap(inner).unwrap();
+    assert_eq!(outer.data_len(), VXLAN_ENCAP_SIZE + before.len());
+    assert_eq!(outer.get_header().etype(), 0x0800);
+    assert_eq!(&header_bytes(&outer)[VXLAN_ENCAP_SIZE..], &before[..]);
+
+    let ip = outer.parse_header::<IpHeader>();
+    assert_eq!((ip.get_header().src(), ip.get_header().dst()), (LOCAL, REMOTE));
+    assert_eq!(ip.get_header().length() as usize, 36 + before.len());
+    assert!(ip.get_header().checksum_valid());
+    let udp = ip.parse_header::<UdpHeader>();
+    let src_port = udp.get_header().src_port();
+    assert!(src_port >= 49152);
+    assert_eq!(udp.get_header().dst_port(), VXLAN_PORT);
+    assert_eq!(udp.get_header().length() as usize, 16 + before.len());
+    let vxlan = udp.parse_header::<VxlanHeader>();
+    assert_eq!(vxlan.get_header().vni(), 0x12_3456);
+    assert_eq!(vxlan.get_payload(), &before[..]);
+    let outer = vxlan.deparse_header(8).deparse_header(20).deparse_header(14);
+
+    // The same inner flow always takes the same source port.
+    let again = template
+        .encap(inner_frame(1000, 10))
+        .unwrap()
+        .parse_header::<IpHeader>();
+    assert_eq!(again.get_header().id(), 1);
+    let again = again.parse_header::<UdpHeader>();
+    assert_eq!(again.get_header().src_port(), src_port);
+    again.free_packet();
+
+    let (inner, vni) = outer.decap().ok().unwrap();
+    assert_eq!(vni, 0x12_3456);
+    assert_eq!(header_bytes(&inner), before);
+    assert_eq!(inner.data_len(), before.len());
+    assert_eq!(frame_flow(&before).unwrap().src_port, 1000);
+    inner.free_packet();
+}
+
+#[test]
+fn geneve_decap() {
+    let inner = header_bytes(&inner_frame(2000, 40));
+    let mut options = vec![];
+    GeneveOption {
+        class: 0x0102,
+        option_type: 0x80,
+        data: &[1, 2, 3, 4, 5],
+    }
+    .write(&mut options);
+    GeneveOption {
+        class: 0xffff,
+        option_type: 1,
+        data: &[],
+    }
+    .write(&mut options);
+    assert_eq!(options.len(), 16);
+
+    let mut geneve = GeneveHeader::new();
+    geneve.set_vni(77);
+    geneve.set_options_len(options.len());
+    geneve.set_critical(true);
+    let mut mac = MacHeader::new();
+    mac.set_etype(0x0800);
+    let mut ip = IpHeader::new();
+    ip.set_version(4);
+    ip.set_ihl(5);
+    ip.set_length((20 + 8 + 8 + options.len() + inner.len()) as u16);
+    ip.set_protocol(17);
+    let mut udp = UdpHeader::new();
+    udp.set_dst_port(GENEVE_PORT);
+    udp.set_length((8 + 8 + options.len() + inner.len()) as u16);
+    let mut pkt = new_packet()
+        .unwrap()
+        .push_header(&mac)
+        .unwrap()
+        .push_header(&ip)
+        .unwrap()
+        .push_header(&udp)
+        .unwrap()
+        .push_header(&geneve)
+        .unwrap();
+    // Pushing the header makes room for its options; a few bytes of Ethernet padding follow the inner frame.
+    pkt.add_to_payload_tail(inner.len() + 4).unwrap();
+    unsafe {
+        let hdr = pkt.get_mut_header() as *mut GeneveHeader as *mut u8;
+        std::slice::from_raw_parts_mut(hdr.add(8), options.len()).copy_from_slice(&options);
+    }
+    pkt.get_mut_payload()[..inner.len()].copy_from_slice(&inner);
+
+    let parsed: Vec<_> = pkt.get_header().options().collect();
+    assert_eq!(parsed.len(), 2);
+    assert_eq!(parsed[0].class, 0x0102);
+    assert!(parsed[0].is_critical());
+    assert_eq!(parsed[0].data, &[1, 2, 3, 4, 5, 0, 0, 0]);
+    assert_eq!((parsed[1].class, parsed[1].data.len()), (0xffff, 0));
+    assert_eq!(pkt.payload_size(), inner.len() + 4);
+
+    let mac_pkt = pkt.deparse_header(8).deparse_header(20).deparse_header(14);
+    let (decapped, vni) = mac_pkt.decap().ok().unwrap();
+    assert_eq!(vni, 77);
+    assert_eq!(header_bytes(&decapped), inner);
+    decapped.free_packet();
+}
+
+#[test]
+fn decap_passthrough() {
+    let pkt = inner_frame(53, 20);
+    let before = header_bytes(&pkt);
+    let pkt = pkt.decap().err().unwrap();
+    assert_eq!(header_bytes(&pkt), before);
+    pkt.free_packet();
+
+    // Tunnel packets with a bad VXLAN header, or fragmented ones, are left alone.
+    let mut outer = template()
+        .encap(inner_frame(53, 20))
+        .unwrap()
+        .parse_header::<IpHeader>();
+    outer.get_mut_header().set_flags(1);
+    let outer = outer.deparse_header(14).decap().err().unwrap();
+    let mut outer = outer.parse_header::<IpHeader>();
+    outer.get_mut_header().set_flags(0);
+    let mut vxlan = outer.parse_header::<UdpHeader>().parse_header::<VxlanHeader>();
+    vxlan.get_mut_header().set_flags(0);
+    let outer = vxlan.deparse_header(8).deparse_header(20).deparse_header(14);
+    let outer = outer.decap().err().unwrap();
+    assert_eq!(&header_bytes(&outer)[VXLAN_ENCAP_SIZE..], &before[..]);
+    outer.free_packet();
+}
+
+#[test]
+fn decap_passthrough_underlay() {
+    // A tagged underlay is left alone, its tag is not read as the outer IP header.
+    let pkt = template().encap(inner_frame(53, 20)).unwrap().push_vlan(100).unwrap();
+    let pkt = pkt.deparse_header(14);
+    let before = header_bytes(&pkt);
+    let pkt = pkt.decap().err().unwrap();
+    assert_eq!(header_bytes(&pkt), before);
+    pkt.free_packet();
+
+    // So is an underlay that is not IPv4.
+    let mut pkt = template().encap(inner_frame(53, 20)).unwrap();
+    pkt.get_mut_header().set_etype(0x86dd);
+    let pkt = pkt.decap().err().unwrap();
+    pkt.free_packet();
+
+    // And UDP to another port, which is not parsed as VXLAN either.
+    let mut udp = template()
+        .encap(inner_frame(53, 20))
+        .unwrap()
+        .parse_header::<IpHeader>()
+        .parse_header::<UdpHeader>();
+    assert!(udp.payload_header_correct::<VxlanHeader>());
+    udp.get_mut_header().set_dst_port(4790);
+    assert!(!udp.payload_header_correct::<VxlanHeader>());
+    assert!(!udp.payload_header_correct::<GeneveHeader>());
+    let before = header_bytes(&udp);
+    let pkt = udp.deparse_header(20).deparse_header(14).decap().err().unwrap();
+    assert_eq!(&header_bytes(&pkt)[14 + 20..], &before[..]);
+    pkt.free_packet();
+}
+
+#[test]
+fn tunnel_source_ports() {
+    let a = header_bytes(&inner_frame(1, 0));
+    let b = header_bytes(&inner_frame(1, 30));
+    assert_eq!(tunnel_source_port(&a), tunnel_source_port(&b));
+    assert!(tunnel_source_port(&a) >= 49152);
+    assert!(tunnel_source_port(&[0; 14]) >= 49152);
+}
+
+#[test]
+fn tunnel_pipeline() {
+    let port = VirtualPort::new(1).unwrap();
+    let mut sched = StandaloneScheduler::new();
+    let pipeline = ReceiveBatch::new(port.new_virtual_queue(0).unwrap())
+        .parse::<MacHeader>()
+        .encap_vxlan(template())
+        .decap()
+        .parse::<IpHeader>()
+        .send(port.new_virtual_queue(0).unwrap());
+    sched.add_task(pipeline).unwrap();
+    sched.execute_one();
+    let (rx, tx) = port.stats();
+    assert_eq!(rx, tx);
+}
@@ -0,0 +1,182 @@
+#![cfg(feature = "heap_mbuf")]
+extern crate e2d2;
+use e2d2::common::EmptyMetadata;
+use e2d2::headers::*;
+use e2d2::interface::*;
+use e2d2::operators::*;
+use e2d2::scheduler::*;
+
+#[test]
+fn vlan_push_pop() {
+    let mut mac = MacHeader::new();
+    mac.set_etype(0x0800);
+    let mut ip = IpHeader::new();
+    ip.set_version(4);
+    ip.set_ihl(5);
+    ip.set_length(20);
+    ip.set_src(0x0a00_0001);
+    let pkt = new_packet()
+        .unwrap()
+        .push_header(&mac)
+        .unwrap()
+        .push_header(&ip)
+        .unwrap();
+    let mac_pkt = pkt.deparse_header(14);
+
+    let tagged = mac_pkt.push_vlan(0x2064).unwrap();
+    assert_eq!(tagged.get_header().vid(), 100);
+    assert_eq!(tagged.get_header().pcp(), 1);
+    assert_eq!(tagged.get_header().etype(), 0x0800);
+    assert_eq!(tagged.data_len(), 14 + 4 + 20);
+    let mac_pkt = tagged.deparse_header(14);
+    assert_eq!(mac_pkt.get_header().etype(), ETHERTYPE_VLAN);
+
+    let double = mac_pkt.push_vlan(200).unwrap();
+    assert_eq!(double.get_header().etype(), ETHERTYPE_VLAN);
+    let ip_pkt = double.parse_header::<VlanHeader>().parse_header::<IpHeader>();
+    assert_eq!(ip_pkt.get_header().src(), 0x0a00_0001);
+
+    // Deparsing always returns to the `PreviousHeader` (`MacHeader` for IP), so skip both tags at once.
+    let mut mac_pkt = ip_pkt.deparse_header(14 + 4 + 4);
+    assert_eq!(mac_pkt.get_header().etype(), ETHERTYPE_QINQ);
+    assert_eq!(mac_pkt.pop_vlan(), Some(200));
+    assert_eq!(mac_pkt.get_header().etype(), ETHERTYPE_VLAN);
+    assert_eq!(mac_pkt.pop_vlan(), Some(0x2064));
+    assert_eq!(mac_pkt.get_header().etype(), 0x0800);
+    assert_eq!(mac_pkt.pop_vlan(), None);
+    assert_eq!(mac_pkt.data_len(), 14 + 20);
+    let ip_pkt = mac_pkt.parse_header::<IpHeader>();
+    assert_eq!(ip_pkt.get_header().src(), 0x0a00_0001);
+    ip_pkt.free_packet();
+}
+
+#[test]
+fn vlan_parse_chain() {
+    let port = VirtualPort::new(1).unwrap();
+    let mut sched = StandaloneScheduler::new();
+    let pipeline = ReceiveBatch::new(port.new_virtual_queue(0).unwrap())
+        .parse::<MacHeader>()
+        .parse::<VlanHeader>()
+        .parse::<VlanHeader>()
+        .parse::<IpHeader>()
+        .send(port.new_virtual_queue(0).unwrap());
+    sched.add_task(pipeline).unwrap();
+    sched.execute_one();
+    let (rx, tx) = port.stats();
+    assert_eq!(rx, tx);
+}
+
+#[test]
+fn vlan_checked_ip() {
+    let mut mac = MacHeader::new();
+    mac.set_etype(0x0800);
+    let mut ip = IpHeader::new();
+    ip.set_version(4);
+    ip.set_ihl(5);
+    ip.set_length(20);
+    ip.set_ttl(64);
+    ip.set_src(0x0a00_0001);
+    ip.update_checksum();
+    let tagged = new_packet()
+        .unwrap()
+        .push_header(&mac)
+        .unwrap()
+        .push_header(&ip)
+        .unwrap()
+        .deparse_header(14)
+        .push_vlan(100)
+        .unwrap();
+    assert!(tagged.payload_header_correct::<IpHeader>());
+    let mut ip_pkt = tagged.parse_header::<IpHeader>();
+    assert!(ip_pkt.get_header().checksum_valid());
+
+    // The IP header is checked the same way after a tag as right after the Ethernet header.
+    ip_pkt.get_mut_header().set_csum(0x1234);
+    let mut mac_pkt = ip_pkt.deparse_header(14 + 4);
+    let tagged = mac_pkt.parse_header::<VlanHeader>();
+    assert_eq!(
+        tagged.payload_header_correct::<IpHeader>(),
+        !cfg!(feature = "verify_checksum")
+    );
+    // A tag following a tag must have been announced by it.
+    assert!(!tagged.payload_header_correct::<VlanHeader>());
+    mac_pkt = tagged.deparse_header(14);
+    assert_eq!(mac_pkt.pop_vlan(), Some(100));
+    assert!(!mac_pkt.payload_header_correct::<VlanHeader>());
+    mac_pkt.free_packet();
+}
+
+#[test]
+fn vlan_parse_checked() {
+    let port = VirtualPort::new(1).unwrap();
+    let mut sched = StandaloneScheduler::new();
+    // Heap mbufs are recycled across tests, so clear what an earlier test may have left in them.
+    let untag = Box::new(|pkt: &mut Packet<NullHeader, EmptyMetadata>| {
+        for b in pkt.get_mut_payload() {
+            *b = 0;
+        }
+    });
+    let pipeline = ReceiveBatch::new(port.new_virtual_queue(0).unwrap())
+        .transform(untag)
+        .parse::<MacHeader>()
+        .parse_checked::<VlanHeader>()
+        .parse_checked::<IpHeader>()
+        .send(port.new_virtual_queue(0).unwrap());
+    sched.add_task(pipeline).unwrap();
+    sched.execute_one();
+    // Untagged packets fail the check.
+    let (rx, tx) = port.stats();
+    assert!(rx > 0);
+    assert_eq!(tx, 0);
+}
+
+/// An IPv4 frame tagged with VLAN 100, padded to the size of virtual port packets.
+fn tagged_frame() -> Vec<u8> {
+    let mut ip = IpHeader::new();
+    ip.set_version(4);
+    ip.set_ihl(5);
+    ip.set_length(20);
+    ip.set_ttl(64);
+    ip.set_src(0x0a00_0001);
+    ip.update_checksum();
+    let mut frame = vec![0; 12];
+    frame.extend_from_slice(&[0x81, 0x00, 0x00, 100, 0x08, 0x00]);
+    frame.extend_from_slice(unsafe { std::slice::from_raw_parts(&ip as *const IpHeader as *const u8, 20) });
+    frame.resize(60, 0);
+    frame
+}
+
+#[test]
+fn tagged_ip_parse_checked() {
+    let frame = tagged_frame();
+    let tag = Box::new(move |pkt: &mut Packet<NullHeader, EmptyMetadata>| {
+        pkt.get_mut_payload().copy_from_slice(&frame);
+    });
+    // `MacHeader` does not skip tags, so the tag is not taken for an IP header.
+    let port = VirtualPort::new(1).unwrap();
+    let mut sched = StandaloneScheduler::new();
+    let pipeline = ReceiveBatch::new(port.new_virtual_queue(0).unwrap())
+        .transform(tag.clone())
+        .parse::<MacHeader>()
+        .parse_checked::<IpHeader>()
+        .send(port.new_virtual_queue(0).unwrap());
+    sched.add_task(pipeline).unwrap();
+    sched.execute_one();
+    let (rx, tx) = port.stats();
+    assert!(rx > 0);
+    assert_eq!(tx, 0);
+
+    let port = VirtualPort::new(1).unwrap();
+    let mut sched = StandaloneScheduler::new();
+    let pipeline = ReceiveBatch::new(port.new_virtual_queue(0).unwrap())
+        .transform(tag)
+        .parse::<MacHeader>()
+        .parse_checked::<VlanHeader>()
+        .parse_checked::<IpHeader>()
+        .send(port.new_virtual_queue(0).unwrap());
+    sched.add_task(pipeline).unwrap();
+    sched.execute_one();
+    let (rx, tx) = port.stats();
+    assert!(rx > 0);
+    assert_eq!(tx, rx);
+}
@@ -4,8 +4,14 @@ int num_pmd_ports();
 int get_pmd_ports(struct rte_eth_dev_info* info, int len);
 void enumerate_pmd_ports();
 int init_pmd_port(int port, int rxqs, int txqs, int rxq_core[], int txq_core[], int nrxd, int ntxd,
-                  int loopback, int tso, int csumoffload);
+                  int loopback, int tso, int csumoffload, int rxintr);
 int free_pmd_port(int port);
 int recv_pkts(int port, int qid, mbuf_array_t pkts, int len);
 int send_pkts(int port, int qid, mbuf_array_t pkts, int len);
+int port_drops(int port, uint64_t* missed, uint64_t* nombuf);
+int rx_intr_add(int port, int qid);
+int rx_intr_enable(int port, int qid);
+int rx_intr_disable(int port, int qid);
+int rx_intr_wait(int timeout);
+int rx_queue_count(int port, int qid);
 #endif
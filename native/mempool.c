@@ -214,8 +214,38 @@ int init_secondary_mempool(const char *mempool_name) {
     return 0;
 }
 
+/* Usage of the idx-th distinct packet mempool. Returns -ENOENT if there are not that many pools. */
+int mempool_usage(int idx, unsigned int *in_use, unsigned int *avail) {
+    int pools = sizeof(pframe_pool) / sizeof(pframe_pool[0]);
+    int seen  = 0;
+    for (int i = 0; i < pools; i++) {
+        struct rte_mempool *mp = pframe_pool[i];
+        int dup                = 0;
+        if (mp == NULL) {
+            continue;
+        }
+        for (int j = 0; j < i && !dup; j++) {
+            dup = pframe_pool[j] == mp;
+        }
+        if (dup) {
+            continue;
+        }
+        if (seen++ == idx) {
+            *in_use = rte_mempool_in_use_count(mp);
+            *avail  = rte_mempool_avail_count(mp);
+            return 0;
+        }
+    }
+    return -ENOENT;
+}
+
 struct rte_mbuf *mbuf_alloc() {
-    return rte_pktmbuf_alloc(current_pframe_pool());
+    struct rte_mbuf *mbuf = rte_pktmbuf_alloc(current_pframe_pool());
+    /* rte_pktmbuf_reset leaves the timestamp alone, clear any stale latency stamp. */
+    if (mbuf) {
+        mbuf->timestamp = 0;
+    }
+    return mbuf;
 }
 
 void mbuf_free(struct rte_mbuf *buf) {
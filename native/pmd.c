@@ -1,6 +1,7 @@
 #include <rte_config.h>
 #include <rte_eal.h>
 #include <rte_ethdev.h>
+#include <rte_interrupts.h>
 #include <rte_pci.h>
 #include "mempool.h"
 
@@ -112,7 +113,7 @@ void enumerate_pmd_ports() {
 }
 
 int init_pmd_port(int port, int rxqs, int txqs, int rxq_core[], int txq_core[], int nrxd, int ntxd,
-                  int loopback, int tso, int csumoffload) {
+                  int loopback, int tso, int csumoffload, int rxintr) {
     struct rte_eth_dev_info dev_info = {};
     struct rte_eth_conf eth_conf;
     struct rte_eth_rxconf eth_rxconf;
@@ -129,6 +130,7 @@ int init_pmd_port(int port, int rxqs, int txqs, int rxq_core[], int txq_core[],
 
     eth_conf           = default_eth_conf;
     eth_conf.lpbk_mode = !(!loopback);
+    eth_conf.intr_conf.rxq = !(!rxintr);
 
     /* Use defaut rx/tx configuration as provided by PMD drivers,
      * with minor tweaks */
@@ -205,6 +207,42 @@ int send_pkts(int port, int qid, mbuf_array_t pkts, int len) {
     return rte_eth_tx_burst(port, (uint16_t)qid, (struct rte_mbuf**)pkts, (uint16_t)len);
 }
 
+/* Drops counted by the NIC: packets missed because the RX rings were full, and RX failures for want of mbufs. */
+int port_drops(int port, uint64_t* missed, uint64_t* nombuf) {
+    struct rte_eth_stats stats;
+    int ret = rte_eth_stats_get(port, &stats);
+    if (ret == 0) {
+        *missed = stats.imissed;
+        *nombuf = stats.rx_nombuf;
+    }
+    return ret;
+}
+
+/* Add the rx interrupt of a queue to the calling thread's epoll set. The port must have been initialized with rxintr. */
+int rx_intr_add(int port, int qid) {
+    return rte_eth_dev_rx_intr_ctl_q(port, qid, RTE_EPOLL_PER_THREAD, RTE_INTR_EVENT_ADD, NULL);
+}
+
+int rx_intr_enable(int port, int qid) {
+    return rte_eth_dev_rx_intr_enable(port, qid);
+}
+
+int rx_intr_disable(int port, int qid) {
+    return rte_eth_dev_rx_intr_disable(port, qid);
+}
+
+/* Number of packets received on a queue but not yet read, negative if the driver cannot tell. */
+int rx_queue_count(int port, int qid) {
+    return rte_eth_rx_queue_count(port, qid);
+}
+
+/* Wait up to timeout milliseconds for any rx interrupt added by this thread. Returns the number of events, 0 on
+ * timeout. */
+int rx_intr_wait(int timeout) {
+    struct rte_epoll_event event[1];
+    return rte_epoll_wait(RTE_EPOLL_PER_THREAD, event, 1, timeout);
+}
+
 int find_port_with_pci_address(const char* pci) {
     struct rte_pci_addr addr;
     char devargs[1024];
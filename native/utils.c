@@ -1,6 +1,7 @@
 #include <rte_config.h>
 #include <rte_hash_crc.h>
 #include <rte_ip.h>
+#include <rte_mbuf.h>
 
 // Make rte_hash_crc available to Rust. This adds some cost, will look into producing a pure Rust
 // version.
@@ -11,3 +12,16 @@ uint32_t crc_hash_native(const void* data, uint32_t len, uint32_t initial) {
 uint16_t ipv4_cksum(const void* iphdr) {
     return rte_ipv4_cksum((const struct ipv4_hdr*)iphdr);
 }
+
+// Ask the NIC to fill in the IPv4 header and TCP/UDP checksums of buf on transmit. The caller must have zeroed the
+// IPv4 checksum and stored the pseudo-header checksum in the TCP/UDP header, as DPDK expects.
+void mbuf_csum_offload(struct rte_mbuf* buf, uint16_t l2_len, uint16_t l3_len, int ipv6, uint8_t l4_proto) {
+    buf->l2_len = l2_len;
+    buf->l3_len = l3_len;
+    buf->ol_flags |= ipv6 ? PKT_TX_IPV6 : (PKT_TX_IPV4 | PKT_TX_IP_CKSUM);
+    if (l4_proto == IPPROTO_TCP) {
+        buf->ol_flags |= PKT_TX_TCP_CKSUM;
+    } else if (l4_proto == IPPROTO_UDP) {
+        buf->ol_flags |= PKT_TX_UDP_CKSUM;
+    }
+}
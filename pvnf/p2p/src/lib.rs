@@ -21,12 +21,10 @@ use e2d2::headers::{IpHeader, MacHeader, NullHeader, TcpHeader};
 use e2d2::interface::*;
 use e2d2::operators::ReceiveBatch;
 use e2d2::operators::{merge, Batch, CompositionBatch};
+use e2d2::pvn::config::PvnExperimentConfig;
 use e2d2::pvn::measure::*;
-use e2d2::pvn::p2p::{p2p_fetch_workload, p2p_load_json, p2p_read_rand_seed, p2p_read_type, p2p_retrieve_param};
-use e2d2::scheduler::Scheduler;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use e2d2::pvn::p2p::{p2p_load_json, p2p_read_rand_seed, p2p_retrieve_param};
+use e2d2::scheduler::{PipelineHooks, Scheduler};
 use tokio::runtime::Runtime;
 
 pub mod utils;
@@ -34,38 +32,29 @@ pub mod utils;
 pub fn p2p<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
     parent: T,
     sched: &mut S,
+    config: &PvnExperimentConfig,
 ) -> CompositionBatch {
     // setup for this run
-    let param = read_setup_param("/home/jethros/setup".to_string()).unwrap();
-    let num_of_torrents = p2p_retrieve_param("/home/jethros/setup".to_string()).unwrap();
-    let p2p_type = p2p_read_type("/home/jethros/setup".to_string()).unwrap();
-
-    // Measurement code
-    //
-    // NOTE: Store timestamps and calculate the delta to get the processing time for individual
-    // packet is disabled here (TOTAL_MEASURED_PKT removed)
-    let mut metric_exec = true;
-
-    // start timestamps will be a vec protected with arc and mutex.
-    let start_ts = Arc::new(Mutex::new(Vec::<Instant>::with_capacity(EPSILON)));
-    let mut stop_ts_not_matched: HashMap<usize, Instant> = HashMap::with_capacity(EPSILON);
-    let stop_ts_matched = Arc::new(Mutex::new(Vec::<Instant>::with_capacity(EPSILON)));
-
-    let t1_1 = Arc::clone(&start_ts);
-    let t1_2 = Arc::clone(&start_ts);
-    let t2_1 = Arc::clone(&stop_ts_matched);
-    let t2_2 = Arc::clone(&stop_ts_matched);
+    let param = config.expr_param();
+    let num_of_torrents = p2p_retrieve_param(config).unwrap();
+    let p2p_type = config.p2p_type.clone();
 
     let torrents_dir = "/home/jethros/dev/pvn/utils/workloads/torrent_files/";
 
-    // pkt count
-    let mut pkt_count = 0;
+    // Workload: start the torrents once the scheduler runs the pipeline, outside of the packet path.
+    let iter = param.iter;
+    sched
+        .add_hooks(PipelineHooks::new().on_start(move || {
+            println!("p2p type: {}", p2p_type);
+            println!("match p2p controlled before btrun");
+            let p2p_torrents =
+                p2p_read_rand_seed(num_of_torrents, iter.to_string(), "p2p_controlled".to_string()).unwrap();
 
-    let _pivot = 0 as usize;
-    let now = Instant::now();
-    let mut start = Instant::now();
+            let _ = bt_run_torrents(p2p_torrents);
 
-    let mut workload_exec = true;
+            println!("bt run is not blocking");
+        }))
+        .unwrap();
 
     // States that this NF needs to maintain.
     //
@@ -74,17 +63,7 @@ pub fn p2p<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
     // also need to maintain a content cache for the bulk HTTP request and response pairs.
 
     let mut groups = parent
-        .transform(box move |_p| {
-            pkt_count += 1;
-
-            if pkt_count > NUM_TO_IGNORE {
-                let mut w = t1_1.lock().unwrap();
-                let start = Instant::now();
-                if param.inst {
-                    w.push(start);
-                }
-            }
-        })
+        .timestamp_if(param.inst)
         .parse::<MacHeader>()
         .parse::<IpHeader>()
         .metadata(box move |p| {
@@ -98,7 +77,6 @@ pub fn p2p<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
         .group_by(
             2,
             box move |p| {
-                pkt_count += 1;
                 let f = p.read_metadata();
 
                 let mut matched = false;
@@ -115,46 +93,6 @@ pub fn p2p<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
                     matched = true
                 }
 
-                if now.elapsed().as_secs() >= param.expr_time && metric_exec {
-                    if param.inst {
-                        println!("pkt count {:?}", pkt_count);
-                        let w1 = t1_2.lock().unwrap();
-                        let w2 = t2_2.lock().unwrap();
-                        println!(
-                            "# of start ts\n w1 {:#?}, hashmap {:#?}, # of stop ts: {:#?}",
-                            w1.len(),
-                            stop_ts_not_matched.len(),
-                            w2.len(),
-                        );
-                        let actual_stop_ts = merge_ts(pkt_count - 1, w2.clone(), stop_ts_not_matched.clone());
-                        let num = actual_stop_ts.len();
-                        println!(
-                            "stop ts matched len: {:?}, actual_stop_ts len: {:?}",
-                            w2.len(),
-                            actual_stop_ts.len()
-                        );
-                        println!("Latency results start: {:?}", num);
-                        let mut tmp_results = Vec::<u128>::with_capacity(num);
-                        for i in 0..num {
-                            let stop = actual_stop_ts.get(&i).unwrap();
-                            let since_the_epoch = stop.checked_duration_since(w1[i]).unwrap();
-                            tmp_results.push(since_the_epoch.as_nanos());
-                            // print!("{:?}, ", since_the_epoch1);
-                            // total_time1 = total_time1 + since_the_epoch1;
-                        }
-                        compute_stat(tmp_results);
-                        println!("\nLatency results end",);
-                    }
-                    metric_exec = false;
-                }
-
-                if pkt_count > NUM_TO_IGNORE && !matched {
-                    let stop = Instant::now();
-                    if param.inst {
-                        stop_ts_not_matched.insert(pkt_count - NUM_TO_IGNORE, stop);
-                    }
-                }
-
                 if matched {
                     0
                 } else {
@@ -168,46 +106,19 @@ pub fn p2p<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
     let pipe = groups
         .get_group(0)
         .unwrap()
-        .transform(box move |_| {
-            if workload_exec {
-                // Workload
-                let fp_workload = p2p_fetch_workload("/home/jethros/setup".to_string()).unwrap();
-
-                println!("p2p type: {}", p2p_type);
-                println!("match p2p controlled before btrun");
-                let p2p_torrents =
-                    p2p_read_rand_seed(num_of_torrents, param.iter.to_string(), "p2p_controlled".to_string()).unwrap();
-
-                let _ = bt_run_torrents(p2p_torrents);
-
-                println!("bt run is not blocking");
-                // workload_exec = false;
-
-                workload_exec = false;
-            }
-
-            if start.elapsed().as_secs() >= 1 as u64 {
-                start = Instant::now();
-            }
-
-            pkt_count += 1;
-            // println!("pkt count {:?}", pkt_count);
-
-            if pkt_count > NUM_TO_IGNORE {
-                let mut w = t2_1.lock().unwrap();
-                let end = Instant::now();
-                if param.inst {
-                    w.push(end);
-                }
-            }
-        })
         .reset()
         .compose();
-    merge(vec![pipe, groups.get_group(1).unwrap().compose()]).compose()
+    merge(vec![pipe, groups.get_group(1).unwrap().compose()])
+        .measure_latency_if(param.inst)
+        .compose()
 }
 
 /// Test for the p2p downloader network function to schedule pipelines.
-pub fn p2p_test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>, sched: &mut S) {
+pub fn p2p_test<S: Scheduler + Sized>(
+    ports: Vec<CacheAligned<PortQueue>>,
+    sched: &mut S,
+    config: &PvnExperimentConfig,
+) {
     for port in &ports {
         println!(
             "Receiving port {} rxq {} txq {}",
@@ -220,7 +131,7 @@ pub fn p2p_test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>, sched
     // create a pipeline for each port
     let pipelines: Vec<_> = ports
         .iter()
-        .map(|port| p2p(ReceiveBatch::new(port.clone()), sched).send(port.clone()))
+        .map(|port| p2p(ReceiveBatch::new(port.clone()), sched, config).send(port.clone()))
         .collect();
 
     println!("Running {} pipelines", pipelines.len());
@@ -16,6 +16,7 @@ use e2d2::allocators::CacheAligned;
 use e2d2::headers::{IpHeader, MacHeader, NullHeader, TcpHeader};
 use e2d2::interface::*;
 use e2d2::operators::*;
+use e2d2::pvn::config::PvnExperimentConfig;
 use e2d2::pvn::measure::*;
 use e2d2::pvn::rdr::{rdr_load_workload, rdr_read_rand_seed, rdr_read_user_data_dir, rdr_retrieve_users};
 use e2d2::scheduler::*;
@@ -26,8 +27,23 @@ use std::time::Instant;
 
 pub mod utils;
 
+/// Metrics for the performance of the RDR proxy.
+#[derive(Debug, Default)]
+struct RdrMetric {
+    num_of_ok: usize,
+    num_of_err: usize,
+    num_of_timeout: usize,
+    num_of_closed: usize,
+    num_of_visit: usize,
+    elapsed_time: Vec<usize>,
+}
+
 /// Test for the rdr proxy network function to schedule pipelines.
-pub fn rdr_proxy_test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>, sched: &mut S) {
+pub fn rdr_proxy_test<S: Scheduler + Sized>(
+    ports: Vec<CacheAligned<PortQueue>>,
+    sched: &mut S,
+    config: &PvnExperimentConfig,
+) {
     for port in &ports {
         println!(
             "Receiving port {} rxq {} txq {}",
@@ -40,7 +56,7 @@ pub fn rdr_proxy_test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>,
     // create a pipeline for each port
     let pipelines: Vec<_> = ports
         .iter()
-        .map(|port| rdr(ReceiveBatch::new(port.clone()), sched).send(port.clone()))
+        .map(|port| rdr(ReceiveBatch::new(port.clone()), sched, config).send(port.clone()))
         .collect();
 
     println!("Running {} pipelines", pipelines.len());
@@ -53,9 +69,10 @@ pub fn rdr_proxy_test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>,
 
 pub fn rdr<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
     parent: T,
-    _sched: &mut S,
+    sched: &mut S,
+    config: &PvnExperimentConfig,
 ) -> CompositionBatch {
-    let param = read_setup_param("/home/jethros/setup".to_string()).unwrap();
+    let param = config.expr_param();
     println!("RDR: {:?}", param);
     let num_of_users = if param.rdr_setup != 0 {
         rdr_retrieve_users(param.rdr_setup).unwrap()
@@ -63,26 +80,7 @@ pub fn rdr<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
         rdr_retrieve_users(param.setup).unwrap()
     };
     let rdr_users = rdr_read_rand_seed(num_of_users, param.iter).unwrap();
-    let usr_data_dir = rdr_read_user_data_dir("/home/jethros/setup".to_string()).unwrap();
-
-    // Measurement code
-    //
-    // NOTE: Store timestamps and calculate the delta to get the processing time for individual
-    // packet is disabled here (TOTAL_MEASURED_PKT removed)
-    let mut metric_exec = true;
-
-    // start timestamps will be a vec protected with arc and mutex.
-    let start_ts = Arc::new(Mutex::new(Vec::<Instant>::with_capacity(EPSILON)));
-    let mut stop_ts_not_matched: HashMap<usize, Instant> = HashMap::with_capacity(EPSILON);
-    let stop_ts_matched = Arc::new(Mutex::new(Vec::<Instant>::with_capacity(EPSILON)));
-
-    let t1_1 = Arc::clone(&start_ts);
-    let t1_2 = Arc::clone(&start_ts);
-    let t2_1 = Arc::clone(&stop_ts_matched);
-    let t2_2 = Arc::clone(&stop_ts_matched);
-
-    // pkt count
-    let mut pkt_count = 0;
+    let usr_data_dir = rdr_read_user_data_dir(config).unwrap();
 
     // States that this NF needs to maintain.
     //
@@ -95,40 +93,44 @@ pub fn rdr<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
     let mut rdr_workload = rdr_load_workload(workload_path.to_string(), num_of_secs, rdr_users.clone()).unwrap();
     println!("Workload is generated",);
 
-    // Browser list.
-    let mut browser_list: HashMap<i64, Browser> = HashMap::new();
-
-    for user in &rdr_users {
-        let browser = browser_create(&usr_data_dir).unwrap();
-        browser_list.insert(*user, browser);
-    }
-    println!("{} browsers are created ", num_of_users);
+    // Browser list, filled once the scheduler runs the pipeline, outside of the packet path.
+    let browser_list = Arc::new(Mutex::new(HashMap::<i64, Browser>::new()));
+    let browsers = Arc::clone(&browser_list);
+    let users = rdr_users.clone();
+    sched
+        .add_hooks(PipelineHooks::new().on_start(move || {
+            let mut browsers = browsers.lock().unwrap();
+            for user in &users {
+                let browser = browser_create(&usr_data_dir).unwrap();
+                browsers.insert(*user, browser);
+            }
+            println!("{} browsers are created ", num_of_users);
+        }))
+        .unwrap();
 
     let _pivot = 1_usize;
 
-    // Metrics for measurement
-    let mut elapsed_time = Vec::new();
-    let mut num_of_ok = 0;
-    let mut num_of_err = 0;
-    let mut num_of_timeout = 0;
-    let mut num_of_closed = 0;
-    let mut num_of_visit = 0;
+    // Metrics for measurement, reported at shutdown
+    let metric = Arc::new(Mutex::new(RdrMetric::default()));
+    let reported = Arc::clone(&metric);
+    add_shutdown_hook(move || {
+        let metric = reported.lock().unwrap();
+        println!(
+            "RDR_Metric: num_of_oks: {:?}, num_of_errs: {:?}, num_of_timeout: {:?}, num_of_closed: {:?}, num_of_visit: {:?}",
+            metric.num_of_ok,
+            metric.num_of_err,
+            metric.num_of_timeout,
+            metric.num_of_closed,
+            metric.num_of_visit,
+        );
+        println!("RDR_Metric: Browsing Time: {:?}\n", metric.elapsed_time);
+    });
 
     let now = Instant::now();
     println!("Timer started");
 
     parent
-        .transform(box move |_| {
-            pkt_count += 1;
-
-            if pkt_count > NUM_TO_IGNORE {
-                let mut w = t1_1.lock().unwrap();
-                let start = Instant::now();
-                if param.inst {
-                    w.push(start);
-                }
-            }
-        })
+        .timestamp_if(param.inst)
         .parse::<MacHeader>()
         .parse::<IpHeader>()
         .metadata(box move |p| {
@@ -171,81 +173,19 @@ pub fn rdr<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
                     let rest_sec = cur_time % 60;
                     if let Some(wd) =  rdr_workload.remove(&cur_time) {
                         println!("{:?} min, {:?} second", min, rest_sec);
-                        if let Some((oks, errs, timeouts, closeds, visits, elapsed)) = rdr_scheduler_ng(&cur_time, &rdr_users, wd, &browser_list) {
-                            num_of_ok += oks;
-                            num_of_err += errs;
-                            num_of_timeout += timeouts;
-                            num_of_closed += closeds;
-                            num_of_visit += visits;
-                            elapsed_time.push(elapsed);
+                        if let Some((oks, errs, timeouts, closeds, visits, elapsed)) = rdr_scheduler_ng(&cur_time, &rdr_users, wd, &browser_list.lock().unwrap()) {
+                            let mut metric = metric.lock().unwrap();
+                            metric.num_of_ok += oks;
+                            metric.num_of_err += errs;
+                            metric.num_of_timeout += timeouts;
+                            metric.num_of_closed += closeds;
+                            metric.num_of_visit += visits;
+                            metric.elapsed_time.push(elapsed);
                         }
                     }
                 }
-
-                // Measurement: instrumentation to collect latency metrics
-                if pkt_count > NUM_TO_IGNORE {
-                    let mut w = t2_1.lock().unwrap();
-                    let end = Instant::now();
-                    if param.inst {
-                        w.push(end);
-                    }
-                }
-            } else if pkt_count > NUM_TO_IGNORE {
-                // Insert the timestamp as
-                let end = Instant::now();
-                if param.inst {
-                    stop_ts_not_matched.insert(pkt_count - NUM_TO_IGNORE, end);
-                }
-            }
-
-            pkt_count += 1;
-
-            if now.elapsed().as_secs() >= param.expr_time && metric_exec {
-                // Measurement: metric for the performance of the RDR proxy
-                println!(
-                    "RDR_Metric: num_of_oks: {:?}, num_of_errs: {:?}, num_of_timeout: {:?}, num_of_closed: {:?}, num_of_visit: {:?}",
-                    num_of_ok, num_of_err, num_of_timeout, num_of_closed, num_of_visit,
-                );
-                println!(
-                    "RDR_Metric: Browsing Time: {:?}\n",
-                    elapsed_time
-                );
-
-                if param.inst {
-                    println!("pkt count {:?}", pkt_count);
-
-                    let w1 = t1_2.lock().unwrap();
-                    let w2 = t2_2.lock().unwrap();
-                    println!(
-                        "# of start ts\n w1 {:#?}, hashmap {:#?}, # of stop ts: {:#?}",
-                        w1.len(),
-                        stop_ts_not_matched.len(),
-                        w2.len(),
-                    );
-                    let actual_stop_ts = merge_ts(pkt_count - 1, w2.clone(), stop_ts_not_matched.clone());
-                    let num = actual_stop_ts.len();
-                    println!(
-                        "stop ts matched len: {:?}, actual_stop_ts len: {:?}",
-                        w2.len(),
-                        actual_stop_ts.len()
-                    );
-                    println!("Latency results start: {:?}", num);
-                    let mut tmp_results = Vec::<u128>::with_capacity(num);
-                    for i in 0..num {
-                        let stop = actual_stop_ts.get(&i).unwrap();
-                        let since_the_epoch = stop.checked_duration_since(w1[i]).unwrap();
-                        tmp_results.push(since_the_epoch.as_nanos());
-                        // print!("{:?}, ", since_the_epoch1); total_time1 = total_time1 +
-                        // since_the_epoch1;
-                    }
-                    compute_stat(tmp_results);
-                    println!("\nLatency results end",);
-
-                }
-
-                metric_exec = false;
-                // println!("avg processing time 1 is {:?}", total_time1 / num as u32);
             }
         })
+        .measure_latency_if(param.inst)
         .compose()
 }
@@ -17,20 +17,25 @@ use e2d2::headers::{IpHeader, MacHeader, NullHeader, TcpHeader};
 use e2d2::interface::*;
 use e2d2::operators::merge;
 use e2d2::operators::{Batch, BatchIterator, CompositionBatch, ReceiveBatch};
+use e2d2::pvn::config::PvnExperimentConfig;
 use e2d2::pvn::measure::*;
-use e2d2::scheduler::Scheduler;
+use e2d2::scheduler::{add_shutdown_hook, Scheduler};
 use e2d2::utils::Flow;
 use rustls::internal::msgs::handshake::HandshakePayload::{ClientHello, ClientKeyExchange, ServerHello};
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::sync::atomic::{self, AtomicUsize};
+use std::sync::Arc;
 use webpki::{DNSName, DNSNameRef};
 
 pub mod utils;
 
 /// Test for the validator network function to schedule pipelines.
-pub fn validator_test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>, sched: &mut S) {
+pub fn validator_test<S: Scheduler + Sized>(
+    ports: Vec<CacheAligned<PortQueue>>,
+    sched: &mut S,
+    config: &PvnExperimentConfig,
+) {
     for port in &ports {
         println!(
             "Receiving port {} rxq {} txq {}",
@@ -43,7 +48,7 @@ pub fn validator_test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>,
     // create a pipeline for each port
     let pipelines: Vec<_> = ports
         .iter()
-        .map(|port| validator(ReceiveBatch::new(port.clone()), sched).send(port.clone()))
+        .map(|port| validator(ReceiveBatch::new(port.clone()), sched, config).send(port.clone()))
         .collect();
     println!("Running {} pipelines", pipelines.len());
 
@@ -53,6 +58,14 @@ pub fn validator_test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>,
     }
 }
 
+/// Count of validated certs, printed when the system shuts down.
+fn cert_counter() -> Arc<AtomicUsize> {
+    let certs = Arc::new(AtomicUsize::new(0));
+    let reported = Arc::clone(&certs);
+    add_shutdown_hook(move || println!("TLSV_Metric: cert count {}", reported.load(atomic::Ordering::Relaxed)));
+    certs
+}
+
 /// Network function that extracts certificates from sequence of packets and validate the
 /// certificates.
 ///
@@ -66,10 +79,10 @@ pub fn validator_test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>,
 pub fn validator<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
     parent: T,
     sched: &mut S,
+    config: &PvnExperimentConfig,
 ) -> CompositionBatch {
-    let param = read_setup_param("/home/jethros/setup".to_string()).unwrap();
+    let param = config.expr_param();
     println!("TLSV: {:?}", param);
-    let mut metric_exec = true;
 
     // New payload cache.
     let mut payload_cache = HashMap::<Flow, Vec<u8>>::with_hasher(Default::default());
@@ -84,42 +97,13 @@ pub fn validator<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
     // DNS name cache.
     let mut name_cache = HashMap::<Flow, DNSName>::with_hasher(Default::default());
 
-    // Cert count
+    // Cert count, reported at shutdown
     let mut cert_count = 0;
-    // pkt count
-    let mut pkt_count = 0;
-
-    // Measurement code
-    //
-    // start timestamps will be a vec protected with arc and mutex.
-    let start_ts_1 = Arc::new(Mutex::new(Vec::<Instant>::with_capacity(TOTAL_MEASURED_PKT + EPSILON)));
-    let stop_ts_non_tcp = Arc::new(Mutex::new(HashMap::<usize, Instant>::with_capacity(
-        TOTAL_MEASURED_PKT + EPSILON,
-    )));
-    let mut stop_ts_tcp = Arc::new(Mutex::new(Vec::with_capacity(TOTAL_MEASURED_PKT + EPSILON)));
-
-    let t1_1 = Arc::clone(&start_ts_1);
-    let t1_2 = Arc::clone(&start_ts_1);
-    let t2_1 = Arc::clone(&stop_ts_non_tcp);
-    let t2_2 = Arc::clone(&stop_ts_non_tcp);
-    let t3_1 = Arc::clone(&stop_ts_tcp);
-    let t3_2 = Arc::clone(&stop_ts_tcp);
-
-    let now = Instant::now();
+    let certs = cert_counter();
 
     // group packets into MAC, TCP and UDP packet.
     let mut groups = parent
-        .transform(box move |_| {
-            pkt_count += 1;
-
-            if pkt_count > NUM_TO_IGNORE {
-                let mut w = t1_1.lock().unwrap();
-                let end = Instant::now();
-                if param.inst {
-                    w.push(end);
-                }
-            }
-        })
+        .timestamp_if(param.inst)
         .parse::<MacHeader>()
         .parse::<IpHeader>()
         .metadata(box move |p| {
@@ -132,63 +116,14 @@ pub fn validator<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
         .group_by(
             2,
             box move |p| {
-                pkt_count += 1;
                 let f = p.read_metadata();
 
-                // 0 means the packet doesn't match RDR or P2P
-                let mut matched = 0;
-                // NOTE: the following ip addr and port are hardcode based on the trace we are
-                // replaying
-
-                // Match TLS packets to group 1 and XCDR packets to group 2, the rest to group 0
+                // Match TLS packets to group 1, the rest to group 0
                 if f.proto == 6 {
-                    matched = 1;
+                    1
                 } else {
-                    if pkt_count > NUM_TO_IGNORE {
-                        let mut w = t2_1.lock().unwrap();
-                        if param.inst {
-                            w.insert(pkt_count - NUM_TO_IGNORE, Instant::now());
-                        }
-                    }
-                }
-
-                if now.elapsed().as_secs() >= param.expr_time && metric_exec {
-                    if param.inst {
-                        println!("pkt count {:?}", pkt_count);
-                        // let mut total_duration = Duration::new(0, 0);
-                        let total_time1 = Duration::new(0, 0);
-                        let w1 = t1_2.lock().unwrap();
-                        let w2 = t2_2.lock().unwrap();
-                        let w3 = t3_2.lock().unwrap();
-                        println!(
-                            "# of start ts\n w1 {:#?}, hashmap {:#?}, # of stop ts: {:#?}",
-                            w1.len(),
-                            w2.len(),
-                            w3.len(),
-                        );
-                        let actual_stop_ts = merge_ts(pkt_count - 1, w3.clone(), w2.clone());
-                        let num = actual_stop_ts.len();
-                        println!(
-                            "stop ts tcp len: {:?}, actual_stop_ts len: {:?}",
-                            w3.len(),
-                            actual_stop_ts.len()
-                        );
-
-                        println!("Latency results start: {:?}", num);
-                        let mut tmp_results = Vec::<u128>::with_capacity(num);
-                        for i in 0..num {
-                            let stop = actual_stop_ts.get(&i).unwrap();
-                            let since_the_epoch = stop.checked_duration_since(w1[i]).unwrap();
-                            tmp_results.push(since_the_epoch.as_nanos());
-                        }
-                        compute_stat(tmp_results);
-                        println!("\nLatency results end",);
-                    }
-
-                    metric_exec = false;
+                    0
                 }
-
-                matched
             },
             sched,
         );
@@ -310,6 +245,7 @@ pub fn validator<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
                                         }
                                         None => {} //eprintln!("We are missing the dns name from the client hello",),
                                     }
+                                    certs.store(cert_count, atomic::Ordering::Relaxed);
                                 }
                                 _ => {} //eprintln!("Other kinds of payload",),
                             }
@@ -326,37 +262,18 @@ pub fn validator<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
                 // let tcph = p.get_mut_header();
                 // tcph.set_rst_flag();
             }
-
-            pkt_count += 1;
-
-            if pkt_count == NUM_TO_IGNORE {
-                println!("\nMeasurement started ",);
-                println!(
-                    "NUM_TO_IGNORE: {:#?}, TOTAL_MEASURED_PKT: {:#?}, pkt_count: {:#?}",
-                    NUM_TO_IGNORE, TOTAL_MEASURED_PKT, pkt_count
-                );
-            }
-
-            if pkt_count > NUM_TO_IGNORE {
-                let mut w = t3_1.lock().unwrap();
-                let end = Instant::now();
-                if param.inst {
-                    w.push(end);
-                }
-            }
         })
         .reset()
         .compose();
 
-    merge(vec![groups.get_group(0).unwrap().compose(), tlsv_pipe]).compose()
+    merge(vec![groups.get_group(0).unwrap().compose(), tlsv_pipe])
+        .measure_latency_if(param.inst)
+        .compose()
 }
 
 pub fn validator_tcp<T: Batch<Header = TcpHeader> + BatchIterator<Metadata = Flow> + 'static>(
     parent: T,
 ) -> CompositionBatch {
-    let param = read_setup_param("/home/jethros/setup".to_string()).unwrap();
-    let mut metric_exec = true;
-
     // New payload cache.
     //
     // Here impl the new data structure for handling reassembling packets in TCP. Note that it is a
@@ -377,28 +294,10 @@ pub fn validator_tcp<T: Batch<Header = TcpHeader> + BatchIterator<Metadata = Flo
     // DNS name cache.
     let mut name_cache = HashMap::<Flow, DNSName>::with_hasher(Default::default());
 
-    // Cert count
+    // Cert count, reported at shutdown
     let mut cert_count = 0;
-    // pkt count
-    let mut pkt_count = 0;
+    let certs = cert_counter();
 
-    // Measurement code
-    //
-    // start timestamps will be a vec protected with arc and mutex.
-    let start_ts_1 = Arc::new(Mutex::new(Vec::<Instant>::with_capacity(TOTAL_MEASURED_PKT + EPSILON)));
-    let stop_ts_non_tcp = Arc::new(Mutex::new(HashMap::<usize, Instant>::with_capacity(
-        TOTAL_MEASURED_PKT + EPSILON,
-    )));
-    let mut stop_ts_tcp: Vec<Instant> = Vec::with_capacity(TOTAL_MEASURED_PKT + EPSILON);
-
-    let t1_1 = Arc::clone(&start_ts_1);
-    let t1_2 = Arc::clone(&start_ts_1);
-    let t2_1 = Arc::clone(&stop_ts_non_tcp);
-    let t2_2 = Arc::clone(&stop_ts_non_tcp);
-
-    let now = Instant::now();
-
-    // group packets into MAC, TCP and UDP packet.
     parent
         .transform(box move |p| {
             let flow = p.read_metadata();
@@ -509,6 +408,7 @@ pub fn validator_tcp<T: Batch<Header = TcpHeader> + BatchIterator<Metadata = Flo
                                         }
                                         None => {} //eprintln!("We are missing the dns name from the client hello",),
                                     }
+                                    certs.store(cert_count, atomic::Ordering::Relaxed);
                                 }
                                 _ => {} //eprintln!("Other kinds of payload",),
                             }
@@ -525,21 +425,6 @@ pub fn validator_tcp<T: Batch<Header = TcpHeader> + BatchIterator<Metadata = Flo
                 // let tcph = p.get_mut_header();
                 // tcph.set_rst_flag();
             }
-
-            pkt_count += 1;
-
-            if pkt_count == NUM_TO_IGNORE {
-                println!("\nMeasurement started ",);
-                println!(
-                    "NUM_TO_IGNORE: {:#?}, TOTAL_MEASURED_PKT: {:#?}, pkt_count: {:#?}",
-                    NUM_TO_IGNORE, TOTAL_MEASURED_PKT, pkt_count
-                );
-            }
-
-            if pkt_count > NUM_TO_IGNORE {
-                let mut w = t1_1.lock().unwrap();
-                let end = Instant::now();
-            }
         })
         .reset()
         .compose()
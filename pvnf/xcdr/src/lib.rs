@@ -19,11 +19,11 @@ use e2d2::headers::{IpHeader, MacHeader, NullHeader, TcpHeader};
 use e2d2::interface::*;
 use e2d2::operators::ReceiveBatch;
 use e2d2::operators::{merge, Batch, CompositionBatch};
+use e2d2::pvn::config::PvnExperimentConfig;
 use e2d2::pvn::measure::*;
-use e2d2::pvn::xcdr::{xcdr_read_setup, xcdr_retrieve_param};
+use e2d2::pvn::xcdr::xcdr_retrieve_param;
 use e2d2::scheduler::Scheduler;
 use faktory::Producer;
-use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
@@ -32,6 +32,7 @@ pub mod utils;
 pub fn transcoder<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
     parent: T,
     sched: &mut S,
+    config: &PvnExperimentConfig,
 ) -> CompositionBatch {
     let mut metric_exec = true;
     let latencyv = Arc::new(Mutex::new(Vec::<u128>::new()));
@@ -42,7 +43,7 @@ pub fn transcoder<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>
     // Specific setup config for this run
 
     // setup for this run
-    let param = xcdr_read_setup("/home/jethros/setup".to_string()).unwrap();
+    let param = config.expr_param();
     let time_span = if param.xcdr_setup != 0 {
         xcdr_retrieve_param(param.xcdr_setup).unwrap()
     } else {
@@ -53,23 +54,6 @@ pub fn transcoder<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>
     // faktory job queue
     let fak_conn = Arc::new(Mutex::new(Producer::connect(None).unwrap()));
 
-    // Measurement code
-    //
-    // NOTE: Store timestamps and calculate the delta to get the processing time for individual
-    // packet is disabled here (TOTAL_MEASURED_PKT removed)
-
-    // start timestamps will be a vec protected with arc and mutex.
-    let start_ts = Arc::new(Mutex::new(Vec::<Instant>::with_capacity(EPSILON)));
-    let mut stop_ts_not_matched: HashMap<usize, Instant> = HashMap::with_capacity(EPSILON);
-    let stop_ts_matched = Arc::new(Mutex::new(Vec::<Instant>::with_capacity(EPSILON)));
-
-    let t1_1 = Arc::clone(&start_ts);
-    let t1_2 = Arc::clone(&start_ts);
-    let t2_1 = Arc::clone(&stop_ts_matched);
-    let t2_2 = Arc::clone(&stop_ts_matched);
-
-    // pkt count
-    let mut pkt_count = 0;
     // job id
     let mut job_id = 0;
 
@@ -86,17 +70,7 @@ pub fn transcoder<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>
     // also need to maintain a content cache for the bulk HTTP request and response pairs.
 
     let mut groups = parent
-        .transform(box move |_p| {
-            pkt_count += 1;
-
-            if pkt_count > NUM_TO_IGNORE {
-                let mut w = t1_1.lock().unwrap();
-                let start = Instant::now();
-                if param.inst {
-                    w.push(start);
-                }
-            }
-        })
+        .timestamp_if(param.inst)
         .parse::<MacHeader>()
         .parse::<IpHeader>()
         .metadata(box move |p| {
@@ -110,7 +84,6 @@ pub fn transcoder<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>
         .group_by(
             2,
             box move |p| {
-                pkt_count += 1;
                 // f Flow { src_ip: 2457012302, dst_ip: 3232235524, src_port: 58111, dst_port: 443, proto: 17 }
                 let f = p.read_metadata();
                 let mut matched = false;
@@ -139,46 +112,9 @@ pub fn transcoder<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>
                     let w = latv_1.lock().unwrap();
                     println!("XCDR_Metric: {:?}", w);
 
-                    if param.inst {
-                        println!("pkt count {:?}", pkt_count);
-                        let w1 = t1_2.lock().unwrap();
-                        let w2 = t2_2.lock().unwrap();
-                        println!(
-                            "# of start ts\n w1 {:#?}, hashmap {:#?}, # of stop ts: {:#?}",
-                            w1.len(),
-                            stop_ts_not_matched.len(),
-                            w2.len(),
-                        );
-                        let actual_stop_ts = merge_ts(pkt_count - 1, w2.clone(), stop_ts_not_matched.clone());
-                        let num = actual_stop_ts.len();
-                        println!(
-                            "stop ts matched len: {:?}, actual_stop_ts len: {:?}",
-                            w2.len(),
-                            actual_stop_ts.len()
-                        );
-                        println!("Latency results start: {:?}", num);
-                        let mut tmp_results = Vec::<u128>::with_capacity(num);
-                        for i in 0..num {
-                            let stop = actual_stop_ts.get(&i).unwrap();
-                            let since_the_epoch = stop.checked_duration_since(w1[i]).unwrap();
-                            tmp_results.push(since_the_epoch.as_nanos());
-                            // print!("{:?}, ", since_the_epoch1);
-                            // total_time1 = total_time1 + since_the_epoch1;
-                        }
-                        compute_stat(tmp_results);
-                        println!("\nLatency results end",);
-                    }
                     metric_exec = false;
                 }
 
-                if pkt_count > NUM_TO_IGNORE && !matched {
-                    let end = Instant::now();
-                    if param.inst {
-                        stop_ts_not_matched.insert(pkt_count - NUM_TO_IGNORE, end);
-                    }
-                }
-                // println!("{:?}", matched);
-
                 if matched {
                     0
                 } else {
@@ -218,25 +154,20 @@ pub fn transcoder<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>
                 pivot += time_span;
                 job_id += 1;
             }
-
-            pkt_count += 1;
-            // println!("pkt count {:?}", pkt_count);
-
-            if pkt_count > NUM_TO_IGNORE {
-                let mut w = t2_1.lock().unwrap();
-                let end = Instant::now();
-                if param.inst {
-                    w.push(end);
-                }
-            }
         })
         .reset()
         .compose();
-    merge(vec![pipe, groups.get_group(1).unwrap().compose()]).compose()
+    merge(vec![pipe, groups.get_group(1).unwrap().compose()])
+        .measure_latency_if(param.inst)
+        .compose()
 }
 
 /// Test for the rdr proxy network function to schedule pipelines.
-pub fn transcoder_test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>, sched: &mut S) {
+pub fn transcoder_test<S: Scheduler + Sized>(
+    ports: Vec<CacheAligned<PortQueue>>,
+    sched: &mut S,
+    config: &PvnExperimentConfig,
+) {
     for port in &ports {
         println!(
             "Receiving port {} rxq {} txq {}",
@@ -249,7 +180,7 @@ pub fn transcoder_test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>
     // create a pipeline for each port
     let pipelines: Vec<_> = ports
         .iter()
-        .map(|port| transcoder(ReceiveBatch::new(port.clone()), sched).send(port.clone()))
+        .map(|port| transcoder(ReceiveBatch::new(port.clone()), sched, config).send(port.clone()))
         .collect();
 
     println!("Running {} pipelines", pipelines.len());
@@ -16,18 +16,20 @@ use e2d2::allocators::CacheAligned;
 use e2d2::config::{basic_opts, read_matches};
 use e2d2::interface::PortQueue;
 use e2d2::operators::{Batch, ReceiveBatch};
+use e2d2::pvn::config::{experiment_config, PvnExperimentConfig};
+use e2d2::pvn::measure::report_latency_at_shutdown;
 use e2d2::scheduler::{initialize_system, Scheduler, StandaloneScheduler};
 use e2d2::utils::Ipv4Prefix;
 use std::env;
+use std::process;
 use std::sync::Arc;
-use std::thread;
 use std::time::{Duration, Instant};
 
 mod nf;
 
 const CONVERSION_FACTOR: f64 = 1000000000.;
 
-fn test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>, sched: &mut S) {
+fn test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>, sched: &mut S, expr_config: &PvnExperimentConfig) {
     for port in &ports {
         println!(
             "Receiving port {} rxq {} txq {}",
@@ -46,7 +48,7 @@ fn test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>, sched: &mut S
     }];
     let pipelines: Vec<_> = ports
         .iter()
-        .map(|port| acl_match(ReceiveBatch::new(port.clone()), acls.clone()).send(port.clone()))
+        .map(|port| acl_match(ReceiveBatch::new(port.clone()), acls.clone(), expr_config).send(port.clone()))
         .collect();
     println!("Running {} pipelines", pipelines.len());
     for pipeline in pipelines {
@@ -66,9 +68,17 @@ fn main() {
     let duration = configuration.duration;
 
     let mut config = initialize_system(&configuration).unwrap();
+    let expr_config = match experiment_config() {
+        Ok(expr_config) => expr_config,
+        Err(ref e) => {
+            println!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+    report_latency_at_shutdown(&expr_config.expr_param());
     config.start_schedulers();
 
-    config.add_pipeline_to_run(Arc::new(move |p, s: &mut StandaloneScheduler| test(p, s)));
+    config.add_pipeline_to_run(Arc::new(move |p, s: &mut StandaloneScheduler| test(p, s, &expr_config)));
     config.execute();
 
     let mut pkts_so_far = (0, 0);
@@ -82,7 +92,10 @@ fn main() {
     let begining = Instant::now();
 
     loop {
-        thread::sleep(sleep_time); // Sleep for a bit
+        if config.wait_for_shutdown(sleep_time) {
+            config.shutdown();
+            break;
+        }
         let now = time::precise_time_ns() as f64 / CONVERSION_FACTOR;
         if now - start > PRINT_DELAY {
             let mut rx = 0;
@@ -1,12 +1,10 @@
 use e2d2::headers::{IpHeader, MacHeader, NullHeader};
 use e2d2::operators::{Batch, CompositionBatch};
-use e2d2::pvn::measure::*;
+use e2d2::pvn::config::PvnExperimentConfig;
 use e2d2::utils::{Flow, Ipv4Prefix};
 use fnv::FnvHasher;
 use std::collections::HashSet;
 use std::hash::BuildHasherDefault;
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
 
 type FnvHash = BuildHasherDefault<FnvHasher>;
 
@@ -40,34 +38,17 @@ impl Acl {
     }
 }
 
-pub fn acl_match<T: 'static + Batch<Header = NullHeader>>(parent: T, acls: Vec<Acl>) -> CompositionBatch {
+pub fn acl_match<T: 'static + Batch<Header = NullHeader>>(
+    parent: T,
+    acls: Vec<Acl>,
+    config: &PvnExperimentConfig,
+) -> CompositionBatch {
     // Measurement code
-    let param = read_setup_param("/home/jethros/setup".to_string()).unwrap();
-
-    // pkt count
-    let mut pkt_count = 0;
-
-    let start_ts = Arc::new(Mutex::new(Vec::<Instant>::with_capacity(TOTAL_MEASURED_PKT + EPSILON)));
-    let start1 = Arc::clone(&start_ts);
-    let start2 = Arc::clone(&start_ts);
-    let mut stop_ts = Vec::<Instant>::with_capacity(TOTAL_MEASURED_PKT + EPSILON);
-
-    let now = Instant::now();
+    let param = config.expr_param();
 
     let mut flow_cache = HashSet::<Flow, FnvHash>::with_hasher(Default::default());
     parent
-        .transform(box move |_| {
-            // first time access start_ts, need to insert timestamp
-            pkt_count += 1;
-            if pkt_count > NUM_TO_IGNORE {
-                let now = Instant::now();
-                let mut w = start1.lock().unwrap();
-                if param.inst {
-                    w.push(now);
-                }
-                // println!("START insert for pkt count {:?}: {:?}", pkt_count, now);
-            }
-        })
+        .timestamp_if(param.inst)
         .parse::<MacHeader>()
         .transform(box move |p| {
             p.get_mut_header().swap_addresses();
@@ -85,45 +66,8 @@ pub fn acl_match<T: 'static + Batch<Header = NullHeader>>(parent: T, acls: Vec<A
                     }
                 }
             }
-
-            pkt_count += 1;
-
-            if now.elapsed().as_secs() == SHORT_MEASURE_TIME && param.inst {
-                // if pkt_count == TOTAL_MEASURED_PKT + NUM_TO_IGNORE {
-                let now = Instant::now();
-                // println!("STOP pkt # {:?}, stop time {:?}", pkt_count, now);
-                stop_ts.push(now);
-
-                println!("\npkt count {:?}", pkt_count);
-                let start = start2.lock().unwrap();
-                println!("# of start ts: {:?}, # of stop ts: {:?}", start.len(), stop_ts.len());
-                // assert_ge!(w.len(), stop_ts.len());
-                let num = stop_ts.len();
-                println!("Latency results start: {:?}", num);
-
-                let mut tmp_results = Vec::<u128>::with_capacity(num);
-                for i in 0..num {
-                    let since_the_epoch = stop_ts[i].duration_since(start[i]);
-                    tmp_results.push(since_the_epoch.as_nanos());
-                    // print!("{:?}", since_the_epoch);
-                }
-                compute_stat(tmp_results);
-                println!("Latency results end",);
-                // println!("start to reset: avg processing time is {:?}", total_time / num as u32);
-            }
-
-            if pkt_count > NUM_TO_IGNORE {
-                if pkt_count == TOTAL_MEASURED_PKT + NUM_TO_IGNORE {
-                } else {
-                    let now = Instant::now();
-                    // println!("STOP pkt # {:?}, stop time {:?}", pkt_count, now);
-                    if param.inst {
-                        stop_ts.push(now);
-                    }
-                }
-            }
-
             result
         })
+        .measure_latency_if(param.inst)
         .compose()
 }
@@ -15,7 +15,6 @@ extern crate time;
 
 use std::env;
 use std::sync::Arc;
-use std::thread;
 use std::time::Duration;
 
 use e2d2::allocators::CacheAligned;
@@ -84,7 +83,10 @@ fn main() {
     let sleep_time = Duration::from_millis(sleep_delay);
     println!("0 OVERALL RX 0.00 TX 0.00 CYCLE_PER_DELAY 0 0 0");
     loop {
-        thread::sleep(sleep_time); // Sleep for a bit
+        if config.wait_for_shutdown(sleep_time) {
+            config.shutdown();
+            break;
+        }
         let now = time::precise_time_ns() as f64 / CONVERSION_FACTOR;
         if now - start > PRINT_DELAY {
             let mut rx = 0;
@@ -17,18 +17,19 @@ extern crate time;
 use e2d2::config::{basic_opts, read_matches};
 use e2d2::interface::{PacketRx, PacketTx};
 use e2d2::operators::{Batch, ReceiveBatch};
+use e2d2::pvn::config::{experiment_config, PvnExperimentConfig};
+use e2d2::pvn::measure::report_latency_at_shutdown;
 use e2d2::scheduler::{initialize_system, Scheduler, StandaloneScheduler};
 use p2p::p2p;
 use std::env;
 use std::fmt::Display;
 use std::process;
 use std::sync::Arc;
-use std::thread;
 use std::time::{Duration, Instant};
 
 const CONVERSION_FACTOR: f64 = 1000000000.;
 
-fn p2p_test<T, S>(ports: Vec<T>, sched: &mut S)
+fn p2p_test<T, S>(ports: Vec<T>, sched: &mut S, expr_config: &PvnExperimentConfig)
 where
     T: PacketRx + PacketTx + Display + Clone + 'static,
     S: Scheduler + Sized,
@@ -37,7 +38,7 @@ where
 
     let pipelines: Vec<_> = ports
         .iter()
-        .map(|port| p2p(ReceiveBatch::new(port.clone()), sched).send(port.clone()))
+        .map(|port| p2p(ReceiveBatch::new(port.clone()), sched, expr_config).send(port.clone()))
         .collect();
     println!("Running {} pipelines", pipelines.len());
 
@@ -58,10 +59,14 @@ fn main() {
     let configuration = read_matches(&matches, &opts);
     let duration = configuration.duration;
 
-    match initialize_system(&configuration) {
-        Ok(mut context) => {
+    let initialized = initialize_system(&configuration).and_then(|context| Ok((context, experiment_config()?)));
+    match initialized {
+        Ok((mut context, expr_config)) => {
+            report_latency_at_shutdown(&expr_config.expr_param());
             context.start_schedulers();
-            context.add_pipeline_to_run(Arc::new(move |p, s: &mut StandaloneScheduler| p2p_test(p, s)));
+            context.add_pipeline_to_run(Arc::new(move |p, s: &mut StandaloneScheduler| {
+                p2p_test(p, s, &expr_config)
+            }));
             context.execute();
 
             let mut pkts_so_far = (0, 0);
@@ -74,7 +79,10 @@ fn main() {
             let begining = Instant::now();
 
             loop {
-                thread::sleep(sleep_time); // Sleep for a bit
+                if context.wait_for_shutdown(sleep_time) {
+                    context.shutdown();
+                    break;
+                }
                 let now = time::precise_time_ns() as f64 / CONVERSION_FACTOR;
                 if now - start > PRINT_INTERVAL {
                     let mut rx = 0;
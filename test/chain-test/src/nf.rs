@@ -12,9 +12,7 @@ pub fn chain_nf<T: 'static + Batch<Header = NullHeader, Metadata = EmptyMetadata
         })
         .parse::<IpHeader>()
         .transform(box |pkt| {
-            let h = pkt.get_mut_header();
-            let ttl = h.ttl();
-            h.set_ttl(ttl - 1);
+            pkt.get_mut_header().decrement_ttl();
         })
         .filter(box |pkt| {
             let h = pkt.get_header();
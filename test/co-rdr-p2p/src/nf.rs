@@ -1,5 +1,6 @@
 use e2d2::headers::{IpHeader, MacHeader, NullHeader, TcpHeader};
 use e2d2::operators::{merge, Batch, CompositionBatch};
+use e2d2::pvn::config::PvnExperimentConfig;
 use e2d2::pvn::measure::*;
 use e2d2::pvn::p2p::*;
 use e2d2::pvn::rdr::*;
@@ -15,9 +16,10 @@ use tokio::runtime::Runtime;
 pub fn rdr_p2p_test<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
     parent: T,
     sched: &mut S,
+    config: &PvnExperimentConfig,
 ) -> CompositionBatch {
     // RDR setup
-    let rdr_param = read_setup_param("/home/jethros/setup".to_string()).unwrap();
+    let rdr_param = config.expr_param();
     println!("RDR: {:?}", rdr_param);
     let num_of_users = if rdr_param.rdr_setup != 0 {
         rdr_retrieve_users(rdr_param.rdr_setup).unwrap()
@@ -25,13 +27,13 @@ pub fn rdr_p2p_test<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Size
         rdr_retrieve_users(rdr_param.setup).unwrap()
     };
     let rdr_users = rdr_read_rand_seed(num_of_users, rdr_param.iter).unwrap();
-    let usr_data_dir = rdr_read_user_data_dir("/home/jethros/setup".to_string()).unwrap();
+    let usr_data_dir = rdr_read_user_data_dir(config).unwrap();
 
     // P2P setup
-    let p2p_param = read_setup_param("/home/jethros/setup".to_string()).unwrap();
+    let p2p_param = config.expr_param();
     println!("P2P: {:?}", p2p_param);
-    let num_of_torrents = p2p_retrieve_param("/home/jethros/setup".to_string()).unwrap();
-    let p2p_type = p2p_read_type("/home/jethros/setup".to_string()).unwrap();
+    let num_of_torrents = p2p_retrieve_param(config).unwrap();
+    let p2p_type = config.p2p_type.clone();
     let torrents_dir = "/home/jethros/dev/pvn/utils/workloads/torrent_files/";
     let mut workload_exec = true;
 
@@ -231,8 +233,6 @@ pub fn rdr_p2p_test<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Size
         .transform(box move |_| {
             if workload_exec {
                 // Workload
-                let fp_workload = p2p_fetch_workload("/home/jethros/setup".to_string()).unwrap();
-
                 println!("p2p type: {}", p2p_type);
                 println!("match p2p controlled before btrun");
                 let p2p_torrents = p2p_read_rand_seed(
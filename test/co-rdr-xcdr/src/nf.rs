@@ -1,5 +1,6 @@
 use e2d2::headers::{IpHeader, MacHeader, NullHeader, TcpHeader};
 use e2d2::operators::{merge, Batch, CompositionBatch};
+use e2d2::pvn::config::PvnExperimentConfig;
 use e2d2::pvn::measure::*;
 use e2d2::pvn::rdr::*;
 use e2d2::pvn::xcdr::*;
@@ -15,9 +16,10 @@ use xcdr::utils::*;
 pub fn rdr_xcdr_test<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
     parent: T,
     sched: &mut S,
+    config: &PvnExperimentConfig,
 ) -> CompositionBatch {
     // RDR setup
-    let rdr_param = read_setup_param("/home/jethros/setup".to_string()).unwrap();
+    let rdr_param = config.expr_param();
     println!("RDR: {:?}", rdr_param);
     let num_of_users = if rdr_param.rdr_setup != 0 {
         rdr_retrieve_users(rdr_param.rdr_setup).unwrap()
@@ -25,14 +27,14 @@ pub fn rdr_xcdr_test<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Siz
         rdr_retrieve_users(rdr_param.setup).unwrap()
     };
     let rdr_users = rdr_read_rand_seed(num_of_users, rdr_param.iter).unwrap();
-    let usr_data_dir = rdr_read_user_data_dir("/home/jethros/setup".to_string()).unwrap();
+    let usr_data_dir = rdr_read_user_data_dir(config).unwrap();
 
     // XCDR setup
     let latencyv = Arc::new(Mutex::new(Vec::<u128>::new()));
     let latv_1 = Arc::clone(&latencyv);
     let latv_2 = Arc::clone(&latencyv);
     println!("Latency vec uses millisecond");
-    let xcdr_param = xcdr_read_setup("/home/jethros/setup".to_string()).unwrap();
+    let xcdr_param = config.expr_param();
     println!("XCDR: {:?}", xcdr_param);
     let time_span = if xcdr_param.xcdr_setup != 0 {
         xcdr_retrieve_param(xcdr_param.xcdr_setup).unwrap()
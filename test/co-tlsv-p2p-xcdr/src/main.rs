@@ -10,10 +10,11 @@ use e2d2::allocators::CacheAligned;
 use e2d2::config::*;
 use e2d2::interface::*;
 use e2d2::operators::*;
+use e2d2::pvn::config::{experiment_config, PvnExperimentConfig};
 use e2d2::scheduler::*;
 use std::env;
+use std::process;
 use std::sync::Arc;
-use std::thread;
 use std::time::{Duration, Instant};
 
 mod nf;
@@ -21,7 +22,7 @@ mod nf;
 const CONVERSION_FACTOR: f64 = 1_000_000_000.;
 
 /// Test for the rdr proxy network function to schedule pipelines.
-fn test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>, sched: &mut S) {
+fn test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>, sched: &mut S, expr_config: &PvnExperimentConfig) {
     for port in &ports {
         println!(
             "Receiving port {} rxq {} txq {}",
@@ -34,7 +35,7 @@ fn test<S: Scheduler + Sized>(ports: Vec<CacheAligned<PortQueue>>, sched: &mut S
     // create a pipeline for each port
     let pipelines: Vec<_> = ports
         .iter()
-        .map(|port| tlsv_p2p_xcdr_test(ReceiveBatch::new(port.clone()), sched).send(port.clone()))
+        .map(|port| tlsv_p2p_xcdr_test(ReceiveBatch::new(port.clone()), sched, expr_config).send(port.clone()))
         .collect();
 
     println!("Running {} pipelines", pipelines.len());
@@ -58,10 +59,17 @@ fn main() {
 
     // configure and start the schedulers
     let mut config = initialize_system(&configuration).unwrap();
+    let expr_config = match experiment_config() {
+        Ok(expr_config) => expr_config,
+        Err(ref e) => {
+            println!("Error: {}", e);
+            process::exit(1);
+        }
+    };
     let duration = configuration.duration;
 
     config.start_schedulers();
-    config.add_pipeline_to_run(Arc::new(move |p, s: &mut StandaloneScheduler| test(p, s)));
+    config.add_pipeline_to_run(Arc::new(move |p, s: &mut StandaloneScheduler| test(p, s, &expr_config)));
     config.execute();
 
     let mut pkts_so_far = (0, 0);
@@ -76,7 +84,10 @@ fn main() {
     let begining = Instant::now();
 
     loop {
-        thread::sleep(sleep_time); // Sleep for a bit
+        if config.wait_for_shutdown(sleep_time) {
+            config.shutdown();
+            break;
+        }
         let now = time::precise_time_ns() as f64 / CONVERSION_FACTOR;
         if now - start > PRINT_DELAY {
             let mut rx = 0;
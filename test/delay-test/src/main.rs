@@ -14,7 +14,6 @@ use std::env;
 use std::fmt::Display;
 use std::process;
 use std::sync::Arc;
-use std::thread;
 use std::time::Duration;
 mod nf;
 
@@ -80,7 +79,10 @@ fn main() {
             let sleep_time = Duration::from_millis(sleep_delay);
             println!("0 OVERALL RX 0.00 TX 0.00 CYCLE_PER_DELAY 0 0 0");
             loop {
-                thread::sleep(sleep_time); // Sleep for a bit
+                if context.wait_for_shutdown(sleep_time) {
+                    context.shutdown();
+                    break;
+                }
                 let now = time::precise_time_ns() as f64 / CONVERSION_FACTOR;
                 if now - start > PRINT_DELAY {
                     let mut rx = 0;
@@ -12,8 +12,9 @@ pub struct DepTask {
 }
 
 impl Executable for DepTask {
-    fn execute(&mut self) {
+    fn execute(&mut self) -> usize {
         println!("Task -- {}", self.id);
+        0
     }
     fn dependencies(&mut self) -> Vec<usize> {
         self.deps.clone()
@@ -0,0 +1,15 @@
+#!/bin/bash
+
+#set -x
+set -euo pipefail
+
+NF_NAME=zcsi-icmp
+
+M_CORE=0
+ADDRESS="10.0.0.1"
+
+PORT_ONE="0000:01:00.0"
+
+../../build.sh run $NF_NAME -n "ICMP echo responder answering for $ADDRESS" \
+    -a $ADDRESS -m $M_CORE \
+    -p $PORT_ONE -c 1
@@ -0,0 +1,80 @@
+//! ICMP responder NF implemented in NetBricks.
+//!
+//! ## Description:
+//! Answers ICMP echo requests (ping) addressed to the given IPv4 address. All other packets have
+//! their TTL decremented as in `chain-test`, but instead of silently dropping packets whose TTL
+//! runs out, the NF sends an ICMP time exceeded message back to the sender (which is what makes
+//! traceroute work).
+
+#![feature(box_syntax)]
+extern crate e2d2;
+
+use self::nf::icmp_nf;
+use e2d2::config::{basic_opts, read_matches};
+use e2d2::interface::{PacketRx, PacketTx};
+use e2d2::operators::{Batch, ReceiveBatch};
+use e2d2::scheduler::{initialize_system, Scheduler, StandaloneScheduler};
+use std::env;
+use std::fmt::Display;
+use std::net::Ipv4Addr;
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
+
+mod nf;
+
+fn test<T, S>(ports: Vec<T>, sched: &mut S, address: u32)
+where
+    T: PacketRx + PacketTx + Display + Clone + 'static,
+    S: Scheduler + Sized,
+{
+    for port in &ports {
+        println!("Receiving port {}", port);
+    }
+
+    let pipelines: Vec<_> = ports
+        .iter()
+        .map(|port| icmp_nf(ReceiveBatch::new(port.clone()), address).send(port.clone()))
+        .collect();
+    println!("Running {} pipelines", pipelines.len());
+    for pipeline in pipelines {
+        sched.add_task(pipeline).unwrap();
+    }
+}
+
+fn main() {
+    let mut opts = basic_opts();
+    opts.optopt("a", "address", "IPv4 address to answer echo requests for", "address");
+
+    let args: Vec<String> = env::args().collect();
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => panic!("{}", f.to_string()),
+    };
+    let configuration = read_matches(&matches, &opts);
+
+    let address: Ipv4Addr = matches
+        .opt_str("a")
+        .unwrap_or_else(|| String::from("10.0.0.1"))
+        .parse()
+        .expect("Could not parse address");
+    let address = u32::from(address);
+
+    match initialize_system(&configuration) {
+        Ok(mut context) => {
+            context.start_schedulers();
+            context.add_pipeline_to_run(Arc::new(move |p, s: &mut StandaloneScheduler| test(p, s, address)));
+            context.execute();
+
+            while !context.wait_for_shutdown(Duration::from_secs(1)) {}
+            context.shutdown();
+        }
+        Err(ref e) => {
+            println!("Error: {}", e);
+            if let Some(backtrace) = e.backtrace() {
+                println!("Backtrace: {:?}", backtrace);
+            }
+            process::exit(1);
+        }
+    }
+}
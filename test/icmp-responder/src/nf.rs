@@ -0,0 +1,39 @@
+use e2d2::common::EmptyMetadata;
+use e2d2::headers::*;
+use e2d2::operators::{Batch, CompositionBatch};
+
+/// Answer echo requests sent to `address`, decrement the TTL of everything else and send back a time exceeded message
+/// when it runs out. Packets go back out the port they came in on, as in `chain-test`.
+#[inline]
+pub fn icmp_nf<T: 'static + Batch<Header = NullHeader, Metadata = EmptyMetadata>>(
+    parent: T,
+    address: u32,
+) -> CompositionBatch {
+    parent
+        .parse::<MacHeader>()
+        .filter(box |pkt| pkt.get_header().etype() == 0x0800)
+        .transform(box |pkt| {
+            pkt.get_mut_header().swap_addresses();
+        })
+        .parse::<IpHeader>()
+        .transform(box move |pkt| {
+            let (dst, ttl) = {
+                let h = pkt.get_header();
+                (h.dst(), h.ttl())
+            };
+            if dst == address {
+                if !pkt.write_echo_reply() {
+                    // Not an echo request, nothing else here to answer; drop it below.
+                    pkt.get_mut_header().set_ttl(0);
+                }
+            } else if ttl <= 1 {
+                if !pkt.write_icmp_error(address, ICMP_TIME_EXCEEDED, 0) {
+                    pkt.get_mut_header().set_ttl(0);
+                }
+            } else {
+                pkt.get_mut_header().decrement_ttl();
+            }
+        })
+        .filter(box |pkt| pkt.get_header().ttl() != 0)
+        .compose()
+}
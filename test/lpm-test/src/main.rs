@@ -21,7 +21,6 @@ use std::env;
 use std::fmt::Display;
 use std::process;
 use std::sync::Arc;
-use std::thread;
 use std::time::{Duration, Instant};
 
 mod nf;
@@ -89,7 +88,10 @@ fn main() {
             let begining = Instant::now();
 
             loop {
-                thread::sleep(sleep_time); // Sleep for a bit
+                if context.wait_for_shutdown(sleep_time) {
+                    context.shutdown();
+                    break;
+                }
                 let now = time::precise_time_ns() as f64 / CONVERSION_FACTOR;
                 if now - start > PRINT_DELAY {
                     let mut rx = 0;
@@ -15,19 +15,20 @@ use self::nf::lpm;
 use e2d2::config::{basic_opts, read_matches};
 use e2d2::interface::{PacketRx, PacketTx};
 use e2d2::operators::{Batch, ReceiveBatch};
+use e2d2::pvn::config::{experiment_config, PvnExperimentConfig};
+use e2d2::pvn::measure::report_latency_at_shutdown;
 use e2d2::scheduler::{initialize_system, Scheduler, StandaloneScheduler};
 use std::env;
 use std::fmt::Display;
 use std::process;
 use std::sync::Arc;
-use std::thread;
 use std::time::{Duration, Instant};
 
 mod nf;
 
 const CONVERSION_FACTOR: f64 = 1000000000.;
 
-fn test<T, S>(ports: Vec<T>, sched: &mut S)
+fn test<T, S>(ports: Vec<T>, sched: &mut S, expr_config: &PvnExperimentConfig)
 where
     T: PacketRx + PacketTx + Display + Clone + 'static,
     S: Scheduler + Sized,
@@ -41,7 +42,7 @@ where
 
     let pipelines: Vec<_> = ports
         .iter()
-        .map(|port| lpm(ReceiveBatch::new(port.clone()), sched).send(port.clone()))
+        .map(|port| lpm(ReceiveBatch::new(port.clone()), sched, expr_config).send(port.clone()))
         .collect();
     println!("Running {} pipelines", pipelines.len());
     for pipeline in pipelines {
@@ -64,16 +65,18 @@ fn main() {
 
     let phy_ports = !matches.opt_present("test");
 
-    match initialize_system(&configuration) {
-        Ok(mut context) => {
+    let initialized = initialize_system(&configuration).and_then(|context| Ok((context, experiment_config()?)));
+    match initialized {
+        Ok((mut context, expr_config)) => {
+            report_latency_at_shutdown(&expr_config.expr_param());
             context.start_schedulers();
 
             if phy_ports {
                 println!("Adding the pipeline b/c we just add that pipeline..");
-                context.add_pipeline_to_run(Arc::new(move |p, s: &mut StandaloneScheduler| test(p, s)));
+                context.add_pipeline_to_run(Arc::new(move |p, s: &mut StandaloneScheduler| test(p, s, &expr_config)));
             } else {
                 println!("Adding only a test pipeline..");
-                context.add_test_pipeline(Arc::new(move |p, s: &mut StandaloneScheduler| test(p, s)));
+                context.add_test_pipeline(Arc::new(move |p, s: &mut StandaloneScheduler| test(p, s, &expr_config)));
             }
             context.execute();
 
@@ -88,7 +91,10 @@ fn main() {
             let begining = Instant::now();
 
             loop {
-                thread::sleep(sleep_time); // Sleep for a bit
+                if context.wait_for_shutdown(sleep_time) {
+                    context.shutdown();
+                    break;
+                }
                 let now = time::precise_time_ns() as f64 / CONVERSION_FACTOR;
                 if now - start > PRINT_DELAY {
                     let mut rx = 0;
@@ -1,14 +1,12 @@
 use e2d2::common::EmptyMetadata;
 use e2d2::headers::{IpHeader, MacHeader, NullHeader};
 use e2d2::operators::{merge, Batch, CompositionBatch};
-use e2d2::pvn::measure::*;
+use e2d2::pvn::config::PvnExperimentConfig;
 use e2d2::scheduler::Scheduler;
 use std::collections::HashMap;
 use std::convert::From;
 use std::hash::BuildHasherDefault;
 use std::net::Ipv4Addr;
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
 
 use fnv::FnvHasher;
 type FnvHash = BuildHasherDefault<FnvHasher>;
@@ -107,16 +105,10 @@ impl IPLookup {
 pub fn lpm<T: 'static + Batch<Header = NullHeader, Metadata = EmptyMetadata>, S: Scheduler + Sized>(
     parent: T,
     s: &mut S,
+    config: &PvnExperimentConfig,
 ) -> CompositionBatch {
     // Measurement code
-    let param = read_setup_param("/home/jethros/setup".to_string()).unwrap();
-    // pkt count
-    let mut pkt_count = 0;
-
-    let start_ts = Arc::new(Mutex::new(Vec::<Instant>::with_capacity(TOTAL_MEASURED_PKT + EPSILON)));
-    let start1 = Arc::clone(&start_ts);
-    let start2 = Arc::clone(&start_ts);
-    let mut stop_ts = Vec::<Instant>::with_capacity(TOTAL_MEASURED_PKT + EPSILON);
+    let param = config.expr_param();
 
     let mut lpm_table = IPLookup::new();
     lpm_table.insert_ipv4(&Ipv4Addr::new(188, 19, 50, 135), 32, 1);
@@ -226,21 +218,8 @@ pub fn lpm<T: 'static + Batch<Header = NullHeader, Metadata = EmptyMetadata>, S:
     lpm_table.insert_ipv4(&Ipv4Addr::new(5, 167, 65, 50), 32, 1);
     lpm_table.construct_table();
 
-    let now = Instant::now();
-
     let mut groups = parent
-        .transform(box move |_| {
-            // first time access start_ts, need to insert timestamp
-            pkt_count += 1;
-            if pkt_count > NUM_TO_IGNORE {
-                let now = Instant::now();
-                let mut w = start1.lock().unwrap();
-                // println!("START insert for pkt count {:?}: {:?}", pkt_count, now);
-                if param.inst {
-                    w.push(now);
-                }
-            }
-        })
+        .timestamp_if(param.inst)
         .parse::<MacHeader>()
         .transform(box |p| p.get_mut_header().swap_addresses())
         .parse::<IpHeader>()
@@ -250,44 +229,6 @@ pub fn lpm<T: 'static + Batch<Header = NullHeader, Metadata = EmptyMetadata>, S:
                 let hdr = pkt.get_header();
                 let result = lpm_table.lookup_entry(hdr.src()) as usize;
 
-                pkt_count += 1;
-
-                if now.elapsed().as_secs() == SHORT_MEASURE_TIME && param.inst {
-                    // if pkt_count == TOTAL_MEASURED_PKT + NUM_TO_IGNORE {
-                    let now = Instant::now();
-                    // println!("STOP pkt # {:?}, stop time {:?}", pkt_count, now);
-                    stop_ts.push(now);
-
-                    println!("\npkt count {:?}", pkt_count);
-                    // let mut total_time = Duration::new(0, 0);
-                    let start = start2.lock().unwrap();
-                    println!("# of start ts: {:?}, # of stop ts: {:?}", start.len(), stop_ts.len());
-                    // assert_ge!(w.len(), stop_ts.len());
-                    let num = stop_ts.len();
-                    println!("Latency results start: {:?}", num);
-
-                    let mut tmp_results = Vec::<u128>::with_capacity(num);
-                    for i in 0..num {
-                        let since_the_epoch = stop_ts[i].duration_since(start[i]);
-                        tmp_results.push(since_the_epoch.as_nanos());
-                        // print!("{:?}, ", since_the_epoch);
-                    }
-                    compute_stat(tmp_results);
-                    println!("Latency results end",);
-                    // println!("start to reset: avg processing time is {:?}", total_time / num as u32);
-                }
-
-                if pkt_count > NUM_TO_IGNORE {
-                    if pkt_count == TOTAL_MEASURED_PKT + NUM_TO_IGNORE {
-                    } else {
-                        let now = Instant::now();
-                        // println!("STOP pkt # {:?}, stop time {:?}", pkt_count, now);
-                        if param.inst {
-                            stop_ts.push(now);
-                        }
-                    }
-                }
-
                 result
             },
             s,
@@ -297,5 +238,6 @@ pub fn lpm<T: 'static + Batch<Header = NullHeader, Metadata = EmptyMetadata>, S:
         groups.get_group(1).unwrap(),
         groups.get_group(2).unwrap(),
     ])
+    .measure_latency_if(param.inst)
     .compose()
 }
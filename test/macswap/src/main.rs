@@ -23,7 +23,6 @@ use std::env;
 use std::fmt::Display;
 use std::process;
 use std::sync::Arc;
-use std::thread;
 use std::time::Duration;
 
 mod nf;
@@ -80,12 +79,11 @@ fn main() {
             context.execute();
 
             if test_duration != 0 {
-                thread::sleep(Duration::from_secs(test_duration));
+                context.wait_for_shutdown(Duration::from_secs(test_duration));
             } else {
-                loop {
-                    thread::sleep(Duration::from_secs(1));
-                }
+                while !context.wait_for_shutdown(Duration::from_secs(1)) {}
             }
+            context.shutdown();
         }
         Err(ref e) => {
             println!("Error: {}", e);
@@ -15,19 +15,20 @@ use self::nf::maglev;
 use e2d2::config::{basic_opts, read_matches};
 use e2d2::interface::{PacketRx, PacketTx};
 use e2d2::operators::{Batch, ReceiveBatch};
+use e2d2::pvn::config::{experiment_config, PvnExperimentConfig};
+use e2d2::pvn::measure::report_latency_at_shutdown;
 use e2d2::scheduler::{initialize_system, Scheduler, StandaloneScheduler};
 use std::env;
 use std::fmt::Display;
 use std::process;
 use std::sync::Arc;
-use std::thread;
 use std::time::{Duration, Instant};
 
 mod nf;
 
 const CONVERSION_FACTOR: f64 = 1000000000.;
 
-fn test<T, S>(ports: Vec<T>, sched: &mut S)
+fn test<T, S>(ports: Vec<T>, sched: &mut S, expr_config: &PvnExperimentConfig)
 where
     T: PacketRx + PacketTx + Display + Clone + 'static,
     S: Scheduler + Sized,
@@ -36,7 +37,15 @@ where
 
     let pipelines: Vec<_> = ports
         .iter()
-        .map(|port| maglev(ReceiveBatch::new(port.clone()), sched, &["Larry", "Curly", "Moe"]).send(port.clone()))
+        .map(|port| {
+            maglev(
+                ReceiveBatch::new(port.clone()),
+                sched,
+                &["Larry", "Curly", "Moe"],
+                expr_config,
+            )
+            .send(port.clone())
+        })
         .collect();
     println!("Running {} pipelines", pipelines.len());
 
@@ -56,10 +65,12 @@ fn main() {
     let configuration = read_matches(&matches, &opts);
     let duration = configuration.duration;
 
-    match initialize_system(&configuration) {
-        Ok(mut context) => {
+    let initialized = initialize_system(&configuration).and_then(|context| Ok((context, experiment_config()?)));
+    match initialized {
+        Ok((mut context, expr_config)) => {
+            report_latency_at_shutdown(&expr_config.expr_param());
             context.start_schedulers();
-            context.add_pipeline_to_run(Arc::new(move |p, s: &mut StandaloneScheduler| test(p, s)));
+            context.add_pipeline_to_run(Arc::new(move |p, s: &mut StandaloneScheduler| test(p, s, &expr_config)));
             context.execute();
 
             let mut pkts_so_far = (0, 0);
@@ -73,7 +84,10 @@ fn main() {
             let begining = Instant::now();
 
             loop {
-                thread::sleep(sleep_time); // Sleep for a bit
+                if context.wait_for_shutdown(sleep_time) {
+                    context.shutdown();
+                    break;
+                }
                 let now = time::precise_time_ns() as f64 / CONVERSION_FACTOR;
                 if now - start > PRINT_DELAY {
                     let mut rx = 0;
@@ -1,14 +1,12 @@
 use e2d2::headers::{MacHeader, NullHeader};
 use e2d2::operators::{merge, Batch, CompositionBatch};
-use e2d2::pvn::measure::*;
+use e2d2::pvn::config::PvnExperimentConfig;
 use e2d2::scheduler::Scheduler;
 use e2d2::utils::ipv4_flow_hash;
 use fnv::FnvHasher;
 use std::collections::HashMap;
 use std::hash::BuildHasherDefault;
 use std::hash::{BuildHasher, Hash, Hasher};
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
 use twox_hash::XxHash;
 
 type FnvHash = BuildHasherDefault<FnvHasher>;
@@ -88,35 +86,16 @@ pub fn maglev<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
     parent: T,
     s: &mut S,
     backends: &[&str],
+    config: &PvnExperimentConfig,
 ) -> CompositionBatch {
     // Measurement code
-    let param = read_setup_param("/home/jethros/setup".to_string()).unwrap();
-    // pkt count
-    let mut pkt_count = 0;
-
-    let start_ts = Arc::new(Mutex::new(Vec::<Instant>::with_capacity(TOTAL_MEASURED_PKT + EPSILON)));
-    let start1 = Arc::clone(&start_ts);
-    let start2 = Arc::clone(&start_ts);
-    let mut stop_ts = Vec::<Instant>::with_capacity(TOTAL_MEASURED_PKT + EPSILON);
-
-    let now = Instant::now();
+    let param = config.expr_param();
 
     let ct = backends.len();
     let lut = Maglev::new(backends, 65537);
     let mut cache = HashMap::<usize, usize, FnvHash>::with_hasher(Default::default());
     let mut groups = parent
-        .transform(box move |_| {
-            // first time access start_ts, need to insert timestamp
-            pkt_count += 1;
-            if pkt_count > NUM_TO_IGNORE {
-                let now = Instant::now();
-                let mut w = start1.lock().unwrap();
-                // println!("START insert for pkt count {:?}: {:?}", pkt_count, now);
-                if param.inst {
-                    w.push(now);
-                }
-            }
-        })
+        .timestamp_if(param.inst)
         .parse::<MacHeader>()
         .transform(box move |pkt| {
             assert!(pkt.refcnt() == 1);
@@ -129,47 +108,10 @@ pub fn maglev<T: 'static + Batch<Header = NullHeader>, S: Scheduler + Sized>(
                 let payload = pkt.get_payload();
                 let hash = ipv4_flow_hash(payload, 0);
                 let out = cache.entry(hash).or_insert_with(|| lut.lookup(hash));
-                pkt_count += 1;
-
-                if now.elapsed().as_secs() == SHORT_MEASURE_TIME && param.inst {
-                    // if pkt_count == TOTAL_MEASURED_PKT + NUM_TO_IGNORE {
-                    let now = Instant::now();
-                    // println!("STOP pkt # {:?}, stop time {:?}", pkt_count, now);
-                    stop_ts.push(now);
-
-                    println!("\npkt count {:?}", pkt_count);
-                    // let mut total_time = Duration::new(0, 0);
-                    let start = start2.lock().unwrap();
-                    println!("# of start ts: {:?}, # of stop ts: {:?}", start.len(), stop_ts.len());
-                    // assert_ge!(w.len(), stop_ts.len());
-                    let num = stop_ts.len();
-                    println!("Latency results start: {:?}", num);
-                    let mut tmp_results = Vec::<u128>::with_capacity(num);
-                    for i in 0..num {
-                        let since_the_epoch = stop_ts[i].duration_since(start[i]);
-                        tmp_results.push(since_the_epoch.as_nanos());
-                        // total_time = total_time + since_the_epoch;
-                        // print!("{:?}, ", since_the_epoch);
-                    }
-                    println!("Latency results end",);
-                    compute_stat(tmp_results);
-                    // println!("start to reset: avg processing time is {:?}", total_time / num as u32);
-                }
-
-                if pkt_count > NUM_TO_IGNORE {
-                    if pkt_count == TOTAL_MEASURED_PKT + NUM_TO_IGNORE {
-                    } else {
-                        let now = Instant::now();
-                        // println!("STOP pkt # {:?}, stop time {:?}", pkt_count, now);
-                        if param.inst {
-                            stop_ts.push(now);
-                        }
-                    }
-                }
                 *out
             },
             s,
         );
     let pipeline = merge((0..ct).map(|i| groups.get_group(i).unwrap()).collect());
-    pipeline.compose()
+    pipeline.measure_latency_if(param.inst).compose()
 }
@@ -15,20 +15,21 @@ use self::nf::nat;
 use e2d2::config::{basic_opts, read_matches};
 use e2d2::interface::{PacketRx, PacketTx};
 use e2d2::operators::{Batch, ReceiveBatch};
+use e2d2::pvn::config::{experiment_config, PvnExperimentConfig};
 use e2d2::scheduler::{initialize_system, Scheduler, StandaloneScheduler};
+use e2d2::utils::{core_latency_snapshots, latency_snapshot};
 use std::env;
 use std::fmt::Display;
 use std::net::Ipv4Addr;
 use std::process;
 use std::sync::Arc;
-use std::thread;
 use std::time::{Duration, Instant};
 
 mod nf;
 
 const CONVERSION_FACTOR: f64 = 1000000000.;
 
-fn test<T, S>(ports: Vec<T>, sched: &mut S)
+fn test<T, S>(ports: Vec<T>, sched: &mut S, expr_config: &PvnExperimentConfig)
 where
     T: PacketRx + PacketTx + Display + Clone + 'static,
     S: Scheduler + Sized,
@@ -37,7 +38,15 @@ where
 
     let pipelines: Vec<_> = ports
         .iter()
-        .map(|port| nat(ReceiveBatch::new(port.clone()), sched, &Ipv4Addr::new(10, 0, 0, 1)).send(port.clone()))
+        .map(|port| {
+            nat(
+                ReceiveBatch::new(port.clone()),
+                sched,
+                &Ipv4Addr::new(10, 0, 0, 1),
+                expr_config,
+            )
+            .send(port.clone())
+        })
         .collect();
     println!("Running {} pipelines", pipelines.len());
 
@@ -58,10 +67,11 @@ fn main() {
     let configuration = read_matches(&matches, &opts);
     let duration = configuration.duration;
 
-    match initialize_system(&configuration) {
-        Ok(mut context) => {
+    let initialized = initialize_system(&configuration).and_then(|context| Ok((context, experiment_config()?)));
+    match initialized {
+        Ok((mut context, expr_config)) => {
             context.start_schedulers();
-            context.add_pipeline_to_run(Arc::new(move |p, s: &mut StandaloneScheduler| test(p, s)));
+            context.add_pipeline_to_run(Arc::new(move |p, s: &mut StandaloneScheduler| test(p, s, &expr_config)));
             context.execute();
 
             let mut pkts_so_far = (0, 0);
@@ -70,7 +80,10 @@ fn main() {
             let begining = Instant::now();
 
             loop {
-                thread::sleep(sleep_time); // Sleep for a bit
+                if context.wait_for_shutdown(sleep_time) {
+                    context.shutdown();
+                    break;
+                }
                 let now = time::precise_time_ns() as f64 / CONVERSION_FACTOR;
                 if now - start > 30.0 {
                     let mut rx = 0;
@@ -89,6 +102,7 @@ fn main() {
                         (pkts.0 - pkts_so_far.0) as f64 / (now - start),
                         (pkts.1 - pkts_so_far.1) as f64 / (now - start)
                     );
+                    println!("LATENCY {}", latency_snapshot());
                     start = now;
                     pkts_so_far = pkts;
                 }
@@ -96,6 +110,9 @@ fn main() {
                     let new_now = Instant::now();
                     if new_now.duration_since(begining) > Duration::new(d as u64, 0) {
                         println!("Have run for {:?}, system shutting down", d);
+                        for (core, latency) in core_latency_snapshots() {
+                            println!("CORE {} LATENCY {}", core, latency);
+                        }
                         context.shutdown();
                         break;
                     }
@@ -1,6 +1,6 @@
 use e2d2::headers::{MacHeader, NullHeader};
 use e2d2::operators::{Batch, CompositionBatch};
-use e2d2::pvn::measure::*;
+use e2d2::pvn::config::PvnExperimentConfig;
 use e2d2::scheduler::Scheduler;
 use e2d2::utils::{ipv4_extract_flow, Flow};
 use fnv::FnvHasher;
@@ -8,8 +8,6 @@ use std::collections::HashMap;
 use std::convert::From;
 use std::hash::BuildHasherDefault;
 use std::net::Ipv4Addr;
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
 
 #[derive(Clone, Default)]
 struct Unit;
@@ -27,18 +25,10 @@ pub fn nat<T: 'static + Batch<Header = NullHeader>>(
     parent: T,
     _s: &mut dyn Scheduler,
     nat_ip: &Ipv4Addr,
+    config: &PvnExperimentConfig,
 ) -> CompositionBatch {
     // Measurement code
-
-    // pkt count
-    let mut pkt_count = 0;
-
-    let param = read_setup_param("/home/jethros/setup".to_string()).unwrap();
-
-    let start_ts = Arc::new(Mutex::new(Vec::<Instant>::with_capacity(TOTAL_MEASURED_PKT + EPSILON)));
-    let start1 = Arc::clone(&start_ts);
-    let start2 = Arc::clone(&start_ts);
-    let mut stop_ts = Vec::<Instant>::with_capacity(TOTAL_MEASURED_PKT + EPSILON);
+    let param = config.expr_param();
 
     let ip = u32::from(*nat_ip);
     let mut port_hash = HashMap::<Flow, Flow, FnvHash>::with_capacity_and_hasher(65536, Default::default());
@@ -46,21 +36,9 @@ pub fn nat<T: 'static + Batch<Header = NullHeader>>(
     let mut next_port = 1024;
     const MIN_PORT: u16 = 1024;
     const MAX_PORT: u16 = 65535;
-    let now = Instant::now();
 
     let pipeline = parent
-        .transform(box move |_| {
-            // first time access start_ts, need to insert timestamp
-            pkt_count += 1;
-            if pkt_count > NUM_TO_IGNORE {
-                let now = Instant::now();
-                let mut w = start1.lock().unwrap();
-                // println!("START insert for pkt count {:?}: {:?}", pkt_count, now);
-                if param.inst {
-                    w.push(now);
-                }
-            }
-        })
+        .timestamp_if(param.inst)
         .parse::<MacHeader>()
         .transform(box move |pkt| {
             // FIXME: this part might crash if the replayed trace satisfies some condition, need to
@@ -96,45 +74,7 @@ pub fn nat<T: 'static + Batch<Header = NullHeader>>(
                 }
             }
             // }
-
-            pkt_count += 1;
-
-            if now.elapsed().as_secs() == SHORT_MEASURE_TIME && param.inst {
-                // if pkt_count == TOTAL_MEASURED_PKT + NUM_TO_IGNORE {
-                let now = Instant::now();
-                // println!("STOP pkt # {:?}, stop time {:?}", pkt_count, now);
-                stop_ts.push(now);
-
-                println!("\npkt count {:?}", pkt_count);
-                // let mut total_time = Duration::new(0, 0);
-                let start = start2.lock().unwrap();
-                println!("# of start ts: {:?}, # of stop ts: {:?}", start.len(), stop_ts.len());
-                // assert_ge!(w.len(), stop_ts.len());
-                let num = stop_ts.len();
-
-                println!("Latency results start: {:?}", num);
-                let mut tmp_results = Vec::<u128>::with_capacity(num);
-                for i in 0..num {
-                    let since_the_epoch = stop_ts[i].duration_since(start[i]);
-                    // total_time = total_time + since_the_epoch;
-                    // print!("{:?}", since_the_epoch);
-                    tmp_results.push(since_the_epoch.as_nanos());
-                }
-                compute_stat(tmp_results);
-                println!("Latency results end",);
-                // println!("start to reset: avg processing time is {:?}", total_time / num as u32);
-            }
-
-            if pkt_count > NUM_TO_IGNORE {
-                if pkt_count == TOTAL_MEASURED_PKT + NUM_TO_IGNORE {
-                } else {
-                    let now = Instant::now();
-                    // println!("STOP pkt # {:?}, stop time {:?}", pkt_count, now);
-                    if param.inst {
-                        stop_ts.push(now);
-                    }
-                }
-            }
-        });
+        })
+        .measure_latency_if(param.inst);
     pipeline.compose()
 }
@@ -48,10 +48,11 @@ impl PacketCreator {
 }
 
 impl Executable for PacketCreator {
-    fn execute(&mut self) {
+    fn execute(&mut self) -> usize {
         for _ in 0..16 {
             self.producer.enqueue_one(self.create_packet());
         }
+        16
     }
     fn dependencies(&mut self) -> Vec<usize> {
         vec![]
@@ -13,7 +13,6 @@ use e2d2::operators::*;
 use e2d2::scheduler::*;
 use std::env;
 use std::sync::Arc;
-use std::thread::sleep;
 use std::time::Duration;
 mod nf;
 
@@ -45,5 +44,6 @@ fn main() {
     println!("BEGIN TEST OUTPUT");
     config.execute();
 
-    sleep(Duration::from_secs(10));
+    config.wait_for_shutdown(Duration::from_secs(10));
+    config.shutdown();
 }
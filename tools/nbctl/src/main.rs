@@ -0,0 +1,79 @@
+//! Command line client for the NetBricks runtime control protocol (see `e2d2::control::protocol`).
+//!
+//! `nbctl -a 127.0.0.1:7800 pipelines` sends one request and prints its result; without a request, requests are read
+//! from standard input, one per line. Start the NF with `--control_address 127.0.0.1:7800` to accept requests.
+use getopts::Options;
+use serde_json::Value;
+use std::env;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::process;
+
+/// Send one request and wait for its response. Returns whether the request succeeded.
+fn request(reader: &mut impl BufRead, writer: &mut impl Write, line: &str) -> io::Result<bool> {
+    writeln!(writer, "{}", line)?;
+    let mut response = String::new();
+    if reader.read_line(&mut response)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed"));
+    }
+    let response: Value = serde_json::from_str(&response)?;
+    if response["ok"] == true {
+        match response["result"] {
+            Value::Null => {}
+            Value::String(ref s) => println!("{}", s),
+            ref result => println!("{}", serde_json::to_string_pretty(result)?),
+        }
+        Ok(true)
+    } else {
+        eprintln!("error: {}", response["error"].as_str().unwrap_or("unknown"));
+        Ok(false)
+    }
+}
+
+fn run(address: &str, args: &[String]) -> io::Result<bool> {
+    let stream = TcpStream::connect(address)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    if !args.is_empty() {
+        return request(&mut reader, &mut writer, &args.join(" "));
+    }
+    let mut ok = true;
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            ok &= request(&mut reader, &mut writer, &line)?;
+        }
+    }
+    Ok(ok)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "print this help menu");
+    opts.optopt("a", "address", "control address of the NF", "address:port");
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            eprintln!("{}", f);
+            process::exit(2);
+        }
+    };
+    let address = match matches.opt_str("a") {
+        Some(address) if !matches.opt_present("h") => address,
+        _ => {
+            let brief = format!("Usage: {} -a address:port [request]", args[0]);
+            print!("{}", opts.usage(&brief));
+            println!("\nRequests: send `help` for the list.");
+            process::exit(if matches.opt_present("h") { 0 } else { 2 });
+        }
+    };
+    match run(&address, &matches.free) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("{}: {}", address, e);
+            process::exit(1);
+        }
+    }
+}